@@ -0,0 +1,189 @@
+//! Consecutive failure tracking for the download pipeline
+//!
+//! Each failure cause keeps its own streak; a successful download resets every
+//! streak. When a streak reaches the configured threshold the caller is told to
+//! alert the bot admins, once per streak. Every failure is also kept in a
+//! short feed for the web dashboard.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Failures kept by `ErrorFeed`
+const ERROR_FEED_SIZE: usize = 50;
+
+/// Stage of the pipeline that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureCause {
+    /// Song detail API request failed
+    SongDetail,
+    /// Download URL API request failed
+    DownloadUrl,
+    /// API returned an empty download URL (VIP/copyright restricted)
+    VipRequired,
+    /// Downloading or processing the audio file failed
+    Download,
+    /// Uploading to Telegram failed
+    Upload,
+    /// The whole job ran past `download.job_timeout_secs`
+    Timeout,
+}
+
+impl FailureCause {
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::SongDetail => "获取歌曲信息失败",
+            Self::DownloadUrl => "获取下载链接失败",
+            Self::VipRequired => "需要VIP权限",
+            Self::Download => "下载失败",
+            Self::Upload => "上传到 Telegram 失败",
+            Self::Timeout => "任务超时",
+        }
+    }
+
+    /// Most likely explanation shown to admins
+    #[must_use]
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::SongDetail => "网易云 API 不可用或被限流，请检查 music.api 配置和网络",
+            Self::DownloadUrl => "下载链接接口异常，可能被风控或 MUSIC_U 已失效",
+            Self::VipRequired => "MUSIC_U 可能已过期或账号 VIP 已到期，可使用 /vipstatus 检查",
+            Self::Download => "CDN 下载或文件处理失败，请检查网络和磁盘空间",
+            Self::Upload => "Telegram 上传失败，请检查 Bot API 服务器和网络",
+            Self::Timeout => "下载或上传卡住，请检查网络、CDN 和 Bot API 服务器的速度",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Streak {
+    count: u32,
+    alerted: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct FailureTracker {
+    streaks: Mutex<HashMap<FailureCause, Streak>>,
+}
+
+impl FailureTracker {
+    /// Record a failure, returning the streak length when an alert should be sent
+    ///
+    /// A `threshold` of 0 disables alerting.
+    pub fn record_failure(&self, cause: FailureCause, threshold: u32) -> Option<u32> {
+        let mut streaks = self.streaks.lock().unwrap();
+        let streak = streaks.entry(cause).or_default();
+        streak.count += 1;
+
+        if threshold == 0 || streak.alerted || streak.count < threshold {
+            return None;
+        }
+
+        streak.alerted = true;
+        Some(streak.count)
+    }
+
+    /// Reset every streak after a successful download
+    pub fn record_success(&self) {
+        self.streaks.lock().unwrap().clear();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    pub at: DateTime<Utc>,
+    pub cause: &'static str,
+    pub error: String,
+}
+
+/// The most recent pipeline failures, newest first
+#[derive(Debug, Default)]
+pub struct ErrorFeed {
+    events: Mutex<VecDeque<ErrorEvent>>,
+}
+
+impl ErrorFeed {
+    pub fn push(&self, cause: FailureCause, error: &str) {
+        let mut events = self.events.lock().unwrap();
+        events.push_front(ErrorEvent {
+            at: Utc::now(),
+            cause: cause.label(),
+            error: error.to_string(),
+        });
+        events.truncate(ERROR_FEED_SIZE);
+    }
+
+    #[must_use]
+    pub fn recent(&self) -> Vec<ErrorEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Diagnostic summary sent to bot admins
+#[must_use]
+pub fn alert_message(cause: FailureCause, count: u32, last_error: &str) -> String {
+    format!(
+        "🚨 下载流程连续失败\n\n原因: {}\n连续次数: {}\n最近错误: {}\n\n💡 {}",
+        cause.label(),
+        count,
+        last_error,
+        cause.hint()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ERROR_FEED_SIZE, ErrorFeed, FailureCause, FailureTracker};
+
+    #[test]
+    fn alerts_once_when_threshold_is_reached() {
+        let tracker = FailureTracker::default();
+        assert_eq!(tracker.record_failure(FailureCause::VipRequired, 3), None);
+        assert_eq!(tracker.record_failure(FailureCause::VipRequired, 3), None);
+        assert_eq!(
+            tracker.record_failure(FailureCause::VipRequired, 3),
+            Some(3)
+        );
+        assert_eq!(tracker.record_failure(FailureCause::VipRequired, 3), None);
+    }
+
+    #[test]
+    fn success_resets_streaks() {
+        let tracker = FailureTracker::default();
+        tracker.record_failure(FailureCause::Upload, 2);
+        tracker.record_success();
+        assert_eq!(tracker.record_failure(FailureCause::Upload, 2), None);
+        assert_eq!(tracker.record_failure(FailureCause::Upload, 2), Some(2));
+    }
+
+    #[test]
+    fn causes_are_tracked_separately() {
+        let tracker = FailureTracker::default();
+        tracker.record_failure(FailureCause::Download, 2);
+        assert_eq!(tracker.record_failure(FailureCause::Upload, 2), None);
+        assert_eq!(tracker.record_failure(FailureCause::Download, 2), Some(2));
+    }
+
+    #[test]
+    fn zero_threshold_disables_alerts() {
+        let tracker = FailureTracker::default();
+        for _ in 0..100 {
+            assert_eq!(tracker.record_failure(FailureCause::SongDetail, 0), None);
+        }
+    }
+
+    #[test]
+    fn error_feed_keeps_newest_events() {
+        let feed = ErrorFeed::default();
+        for i in 0..=ERROR_FEED_SIZE {
+            feed.push(FailureCause::Download, &i.to_string());
+        }
+        let events = feed.recent();
+        assert_eq!(events.len(), ERROR_FEED_SIZE);
+        assert_eq!(events[0].error, ERROR_FEED_SIZE.to_string());
+        assert_eq!(events[0].cause, FailureCause::Download.label());
+    }
+}
@@ -0,0 +1,318 @@
+//! Optional REST API next to the bot (`[api]` config section, `api` feature)
+//!
+//! Exposes song metadata, a proxied audio download and search over HTTP,
+//! backed by the bot's NetEase client and song cache, for operators who want
+//! to put a web UI in front of the same account.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::bot::BotState;
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::music_api::format_artists;
+use crate::utils::clean_filename;
+
+/// Results returned by `/api/search` when `limit` is not given
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+const MAX_SEARCH_LIMIT: u32 = 50;
+
+struct ApiError(StatusCode, String);
+
+impl From<BotError> for ApiError {
+    fn from(e: BotError) -> Self {
+        let status = match e.root() {
+            BotError::MusicApi {
+                kind: MusicApiErrorKind::NotFound,
+                ..
+            } => StatusCode::NOT_FOUND,
+            BotError::MusicApi { .. } | BotError::Network(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug, Serialize)]
+struct CachedFile {
+    file_ext: String,
+    size: i64,
+    bit_rate: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct SongMetadata {
+    id: u64,
+    name: String,
+    artists: String,
+    album: String,
+    cover_url: Option<String>,
+    duration_secs: u64,
+    /// Present when the bot already has the song cached
+    cached: Option<CachedFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchItem {
+    id: u64,
+    name: String,
+    artists: String,
+    album: String,
+    duration_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<u32>,
+}
+
+async fn song_metadata(
+    State(state): State<Arc<BotState>>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<SongMetadata>> {
+    let detail = state.music_api.get_song_detail(id).await?;
+    let cached = state
+        .database
+        .get_song_by_music_id(id as i64)
+        .await?
+        .map(|song| CachedFile {
+            file_ext: song.file_ext,
+            size: song.music_size,
+            bit_rate: song.bit_rate,
+        });
+
+    Ok(Json(SongMetadata {
+        id: detail.id,
+        artists: format_artists(detail.ar.as_deref().unwrap_or(&[])),
+        album: detail
+            .al
+            .as_ref()
+            .map(|al| al.name.clone())
+            .unwrap_or_default(),
+        cover_url: detail.al.and_then(|al| al.pic_url),
+        duration_secs: detail.dt.unwrap_or(0) / 1000,
+        name: detail.name,
+        cached,
+    }))
+}
+
+/// `Content-Disposition` value that keeps non-ASCII file names (RFC 6266 / 5987)
+fn content_disposition(filename: &str) -> String {
+    let ascii: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                char::from(b).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{ascii}\"; filename*=UTF-8''{encoded}")
+}
+
+async fn song_download(
+    State(state): State<Arc<BotState>>,
+    Path(id): Path<u64>,
+) -> ApiResult<Response> {
+    let detail = state.music_api.get_song_detail(id).await?;
+    let song_url = state.music_api.get_best_song_url(id).await?;
+    if song_url.url.is_empty() {
+        return Err(ApiError(
+            StatusCode::FORBIDDEN,
+            "No download URL, the song may require VIP".to_string(),
+        ));
+    }
+
+    let upstream = state.music_api.download_file(&song_url.url).await?;
+    if !upstream.status().is_success() {
+        return Err(ApiError(
+            StatusCode::BAD_GATEWAY,
+            format!("Upstream returned HTTP {}", upstream.status()),
+        ));
+    }
+
+    let file_ext = if song_url.url.contains(".flac") {
+        "flac"
+    } else {
+        "mp3"
+    };
+    let filename = clean_filename(&format!(
+        "{} - {}.{}",
+        format_artists(detail.ar.as_deref().unwrap_or(&[])).replace('/', ","),
+        detail.name,
+        file_ext
+    ));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(if file_ext == "flac" {
+            "audio/flac"
+        } else {
+            "audio/mpeg"
+        }),
+    );
+    if let Some(length) = upstream.content_length() {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    }
+    if let Ok(value) = HeaderValue::from_str(&content_disposition(&filename)) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+
+    Ok((headers, Body::from_stream(upstream.bytes_stream())).into_response())
+}
+
+async fn search(
+    State(state): State<Arc<BotState>>,
+    Query(params): Query<SearchParams>,
+) -> ApiResult<Json<Vec<SearchItem>>> {
+    let keyword = params.q.trim();
+    if keyword.is_empty() {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            "Missing search keyword".to_string(),
+        ));
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let songs = state.music_api.search_songs(keyword, limit).await?;
+    Ok(Json(
+        songs
+            .into_iter()
+            .map(|song| SearchItem {
+                id: song.id,
+                artists: format_artists(&song.artists),
+                album: song.album.name,
+                duration_secs: song.duration / 1000,
+                name: song.name,
+            })
+            .collect(),
+    ))
+}
+
+/// Check `Authorization: Bearer <api.token>` when a token is configured
+///
+/// The token is compared in constant time so response timing does not leak
+/// how much of a guess was right.
+pub(crate) fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    token.is_empty()
+        || headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes())))
+}
+
+async fn require_token(
+    State(state): State<Arc<BotState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_authorized(request.headers(), &state.config.api_token) {
+        next.run(request).await
+    } else {
+        ApiError(StatusCode::UNAUTHORIZED, "Invalid API token".to_string()).into_response()
+    }
+}
+
+fn router(state: Arc<BotState>) -> Router {
+    let mut router = Router::new()
+        .route("/api/song/{id}", get(song_metadata))
+        .route("/api/song/{id}/download", get(song_download))
+        .route("/api/search", get(search))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+    if !state.config.api_admin_token.is_empty() {
+        router = router.merge(crate::dashboard::router(state.clone()));
+    }
+    router.with_state(state)
+}
+
+/// Serve the REST API on `addr` in the background
+///
+/// Refuses to serve a non-loopback address without `api.token`: the API
+/// proxies downloads through the bot's NetEase account.
+pub async fn spawn_api_server(addr: &str, state: Arc<BotState>) -> Result<()> {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to bind REST API listener on {}: {}", addr, e);
+            return Ok(());
+        }
+    };
+    if state.config.api_token.is_empty() && !listener.local_addr()?.ip().is_loopback() {
+        return Err(BotError::Config(format!(
+            "api.listen = {addr} is reachable from other hosts, set api.token or listen on 127.0.0.1"
+        )));
+    }
+    tracing::info!("REST API listening on http://{}/api", addr);
+    if !state.config.api_admin_token.is_empty() {
+        tracing::info!("Admin dashboard available at http://{}/admin", addr);
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router(state)).await {
+            tracing::error!("REST API server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_disposition, is_authorized};
+    use axum::http::{HeaderMap, HeaderValue, header};
+
+    #[test]
+    fn encodes_non_ascii_file_names() {
+        assert_eq!(
+            content_disposition("周杰伦 - 晴天.mp3"),
+            "attachment; filename=\"___ - __.mp3\"; \
+             filename*=UTF-8''%E5%91%A8%E6%9D%B0%E4%BC%A6%20-%20%E6%99%B4%E5%A4%A9.mp3"
+        );
+    }
+
+    #[test]
+    fn checks_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert!(is_authorized(&headers, ""));
+        assert!(!is_authorized(&headers, "secret"));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        assert!(is_authorized(&headers, "secret"));
+        assert!(!is_authorized(&headers, "other"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Health of the NetEase API endpoints (`music.api`, comma separated)
+//!
+//! Every API request records its latency and whether it failed against the
+//! endpoint it went to; requests then go to the endpoint with the best
+//! rolling p95 latency, weighted by its error rate. With several endpoints,
+//! a watchdog pings them all so idle ones keep fresh numbers.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::bot::BotState;
+
+/// Requests remembered per endpoint
+const WINDOW: usize = 50;
+/// Interval of the watchdog pings
+const PING_INTERVAL: Duration = Duration::from_mins(1);
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    latency: Duration,
+    ok: bool,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    samples: VecDeque<Sample>,
+}
+
+/// Rolling numbers of one endpoint, as shown in `/status`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// None until the endpoint answered at least once
+    pub p95: Option<Duration>,
+    pub error_rate: f64,
+    pub samples: usize,
+}
+
+impl EndpointHealth {
+    /// Lower is better; endpoints without numbers come last
+    fn score(&self) -> f64 {
+        match self.p95 {
+            // A failing endpoint costs a timeout, so errors weigh heavily
+            Some(p95) => p95.as_secs_f64() * 1000.0 * (1.0 + 10.0 * self.error_rate),
+            None if self.samples > 0 => f64::MAX / 2.0,
+            None => f64::MAX,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiEndpoints {
+    endpoints: Mutex<Vec<Endpoint>>,
+}
+
+impl ApiEndpoints {
+    /// Endpoints from a comma separated `music.api` value
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        let mut urls = value
+            .split(',')
+            .map(|url| url.trim().trim_end_matches('/'))
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            urls.push("https://music.163.com".to_string());
+        }
+        Self {
+            endpoints: Mutex::new(
+                urls.into_iter()
+                    .map(|url| Endpoint {
+                        url,
+                        samples: VecDeque::with_capacity(WINDOW),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    #[must_use]
+    pub fn urls(&self) -> Vec<String> {
+        let endpoints = self.endpoints.lock().unwrap();
+        endpoints
+            .iter()
+            .map(|endpoint| endpoint.url.clone())
+            .collect()
+    }
+
+    /// Record a request to `request_url` (matched to its endpoint by prefix)
+    pub fn record(&self, request_url: &str, latency: Duration, ok: bool) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if let Some(endpoint) = endpoints.iter_mut().find(|endpoint| {
+            request_url
+                .strip_prefix(&endpoint.url)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+        }) {
+            if endpoint.samples.len() == WINDOW {
+                endpoint.samples.pop_front();
+            }
+            endpoint.samples.push_back(Sample { latency, ok });
+        }
+    }
+
+    /// Endpoints from healthiest to least healthy; ties keep the configured order
+    #[must_use]
+    pub fn ranking(&self) -> Vec<EndpointHealth> {
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut ranking = endpoints.iter().map(health).collect::<Vec<_>>();
+        ranking.sort_by(|a, b| a.score().total_cmp(&b.score()));
+        ranking
+    }
+
+    /// Base URL requests should go to now
+    #[must_use]
+    pub fn best(&self) -> String {
+        let endpoints = self.endpoints.lock().unwrap();
+        if endpoints.len() == 1 {
+            return endpoints[0].url.clone();
+        }
+        endpoints
+            .iter()
+            .map(health)
+            .min_by(|a, b| a.score().total_cmp(&b.score()))
+            .map(|health| health.url)
+            .unwrap_or_default()
+    }
+}
+
+fn health(endpoint: &Endpoint) -> EndpointHealth {
+    let mut latencies = endpoint
+        .samples
+        .iter()
+        .filter(|sample| sample.ok)
+        .map(|sample| sample.latency)
+        .collect::<Vec<_>>();
+    latencies.sort_unstable();
+    let p95 = latencies
+        .len()
+        .checked_sub(1)
+        .map(|last| latencies[last * 95 / 100]);
+    let samples = endpoint.samples.len();
+    let errors = samples - latencies.len();
+    EndpointHealth {
+        url: endpoint.url.clone(),
+        p95,
+        error_rate: if samples == 0 {
+            0.0
+        } else {
+            errors as f64 / samples as f64
+        },
+        samples,
+    }
+}
+
+/// Ping every endpoint each minute, logging when the preferred one changes
+pub fn spawn_watchdog(state: Arc<BotState>) {
+    tokio::spawn(async move {
+        let mut preferred = state.music_api.endpoints().best();
+        loop {
+            tokio::time::sleep(PING_INTERVAL).await;
+            for url in state.music_api.endpoints().urls() {
+                if let Err(e) = state.music_api.ping_endpoint(&url).await {
+                    tracing::debug!("API endpoint {} failed its ping: {}", url, e);
+                }
+            }
+            let best = state.music_api.endpoints().best();
+            if best != preferred {
+                tracing::warn!(
+                    "Switching NetEase API endpoint from {} to {}",
+                    preferred,
+                    best
+                );
+                preferred = best;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ApiEndpoints;
+
+    #[test]
+    fn prefers_fast_healthy_endpoints() {
+        let endpoints = ApiEndpoints::parse("https://a.example, https://b.example/");
+        assert_eq!(endpoints.best(), "https://a.example");
+
+        for _ in 0..10 {
+            endpoints.record("https://a.example/api/x", Duration::from_millis(900), true);
+            endpoints.record("https://b.example/api/x", Duration::from_millis(300), true);
+        }
+        assert_eq!(endpoints.best(), "https://b.example");
+
+        for _ in 0..10 {
+            endpoints.record("https://b.example/api/x", Duration::from_secs(10), false);
+        }
+        let ranking = endpoints.ranking();
+        assert_eq!(ranking[0].url, "https://a.example");
+        assert!((ranking[1].error_rate - 0.5).abs() < f64::EPSILON);
+        assert_eq!(ranking[1].samples, 20);
+    }
+}
@@ -8,6 +8,7 @@
 use anyhow::{Context, Result};
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{LazyLock, Mutex};
 use sysinfo::System;
 use teloxide::types::InputFile;
@@ -21,8 +22,40 @@ static SYSTEM: LazyLock<Mutex<System>> = LazyLock::new(|| {
     Mutex::new(sys)
 });
 
+/// Set while free RAM is below `memory.pressure_floor_mb`; forces disk mode
+static MEMORY_PRESSURE: AtomicBool = AtomicBool::new(false);
+
+/// Force disk storage for new buffers while the system is low on memory
+pub fn set_memory_pressure(active: bool) {
+    MEMORY_PRESSURE.store(active, Ordering::Relaxed);
+}
+
 use crate::config::{Config, StorageMode};
+use crate::error::BotError;
 use crate::music_api::SongDetail;
+#[cfg(feature = "s3")]
+use crate::object_store::S3Store;
+
+/// Object store that receives downloads too large for memory (`[s3]`)
+#[cfg(feature = "s3")]
+static OBJECT_STORE: std::sync::OnceLock<std::sync::Arc<S3Store>> = std::sync::OnceLock::new();
+
+/// Stream downloads that do not fit in memory to `store` instead of the local disk
+#[cfg(feature = "s3")]
+pub fn set_object_store(store: S3Store) {
+    let _ = OBJECT_STORE.set(std::sync::Arc::new(store));
+}
+
+/// Start of an S3 object kept in memory to locate and rewrite its tags
+#[cfg(feature = "s3")]
+const S3_PREFIX_BYTES: usize = 4 * 1024 * 1024;
+
+/// Streaming PUT of an `S3` buffer in progress
+#[cfg(feature = "s3")]
+pub struct S3Upload {
+    chunks: tokio::sync::mpsc::Sender<Vec<u8>>,
+    task: tokio::task::JoinHandle<Result<()>>,
+}
 
 /// Audio file buffer supporting both disk and memory storage
 pub enum AudioBuffer {
@@ -33,13 +66,66 @@ pub enum AudioBuffer {
         filename: String,
     },
     /// Memory-based storage with byte vector
+    ///
+    /// The file content is `header` followed by `data[audio_offset..]`, so tags
+    /// can be replaced without reallocating the (possibly 100 MB) audio data.
     Memory {
         data: Vec<u8>,
+        /// Tag segment written by the tagging methods (empty until tagged)
+        header: Vec<u8>,
+        /// Start of the audio frames kept from `data`
+        audio_offset: usize,
         filename: String,
         capacity: usize,
+        /// Size cap (`memory_max_file_mb`); the buffer spills to disk beyond it
+        max_bytes: u64,
+        /// Directory used when spilling to disk
+        spill_dir: PathBuf,
+    },
+    /// Object storage (`s3` feature); only the first few MB stay in memory
+    ///
+    /// As with `Memory`, the file content is `header` followed by the object
+    /// from `audio_offset` on. `prefix` holds the start of the object so the
+    /// original tags can be located and skipped.
+    #[cfg(feature = "s3")]
+    S3 {
+        store: std::sync::Arc<S3Store>,
+        key: String,
+        filename: String,
+        /// Set until `finish` completes the upload
+        upload: Option<S3Upload>,
+        prefix: Vec<u8>,
+        header: Vec<u8>,
+        audio_offset: usize,
+        /// Bytes written to the object
+        size: u64,
     },
 }
 
+/// How a rewritten FLAC metadata section fits into the space of the old one
+#[derive(Debug, PartialEq, Eq)]
+enum MetadataFit {
+    /// Same size: overwrite in place
+    Exact,
+    /// Smaller: overwrite in place, filling the gap with a PADDING block of this length
+    Padding(u32),
+    /// Larger (or a gap too small for a block header): rewrite the whole file
+    Rewrite,
+}
+
+/// Maximum length of a FLAC metadata block (24-bit length field)
+const MAX_FLAC_BLOCK_LEN: u64 = (1 << 24) - 1;
+
+fn flac_metadata_fit(old_len: u64, new_len: u64) -> MetadataFit {
+    match old_len.checked_sub(new_len) {
+        Some(0) => MetadataFit::Exact,
+        Some(gap) if gap >= 4 && gap - 4 <= MAX_FLAC_BLOCK_LEN => {
+            MetadataFit::Padding((gap - 4) as u32)
+        }
+        _ => MetadataFit::Rewrite,
+    }
+}
+
 /// Thumbnail buffer for album art
 pub enum ThumbnailBuffer {
     /// Disk-based thumbnail
@@ -67,11 +153,19 @@ impl AudioBuffer {
         let use_memory = Self::should_use_memory(config, content_length);
 
         if use_memory {
+            // Size unknown (chunked or compressed response): in hybrid mode keep
+            // the download in memory only until it passes the hybrid threshold
+            let max_mb = if content_length == 0 && config.storage_mode == StorageMode::Hybrid {
+                config.memory_threshold_mb.min(config.memory_max_file_mb)
+            } else {
+                config.memory_max_file_mb
+            };
+            let max_bytes = max_mb * 1024 * 1024;
             let capacity = if content_length > 0 {
                 content_length as usize
             } else {
-                // Default capacity for unknown size
-                10 * 1024 * 1024 // 10MB
+                // Default capacity for unknown size; grows (or spills) as needed
+                (10 * 1024 * 1024).min(max_bytes as usize) // 10MB
             };
 
             tracing::debug!(
@@ -81,10 +175,21 @@ impl AudioBuffer {
 
             Ok(Self::Memory {
                 data: Vec::with_capacity(capacity),
+                header: Vec::new(),
+                audio_offset: 0,
                 filename,
                 capacity,
+                max_bytes,
+                spill_dir: PathBuf::from(cache_dir),
             })
         } else {
+            #[cfg(feature = "s3")]
+            if let Some(store) = OBJECT_STORE.get()
+                && content_length > 0
+            {
+                return Ok(Self::new_s3(store.clone(), content_length, filename));
+            }
+
             let file_path = PathBuf::from(cache_dir).join(&filename);
 
             tracing::debug!(
@@ -124,8 +229,75 @@ impl AudioBuffer {
         })
     }
 
+    /// Start streaming a download of `content_length` bytes to the object store
+    #[cfg(feature = "s3")]
+    fn new_s3(store: std::sync::Arc<S3Store>, content_length: u64, filename: String) -> Self {
+        let key = format!("audio/{}-{filename}", uuid::Uuid::new_v4());
+        tracing::debug!("AudioBuffer: using s3 mode (key: {})", key);
+
+        // A small bound keeps at most a few chunks in flight between download and upload
+        let (chunks, receiver) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
+        let body = reqwest::Body::wrap_stream(futures_util::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                let chunk = receiver.recv().await?;
+                Some((Ok::<_, std::io::Error>(chunk), receiver))
+            },
+        ));
+        let task = {
+            let store = store.clone();
+            let key = key.clone();
+            tokio::spawn(async move { store.put_object(&key, body, content_length).await })
+        };
+
+        Self::S3 {
+            store,
+            key,
+            filename,
+            upload: Some(S3Upload { chunks, task }),
+            prefix: Vec::new(),
+            header: Vec::new(),
+            audio_offset: 0,
+            size: 0,
+        }
+    }
+
+    /// Lazily started download of an object, from byte `offset` on
+    ///
+    /// With `delete_after`, the object is removed once it has been read to the end.
+    #[cfg(feature = "s3")]
+    fn object_reader(
+        store: std::sync::Arc<S3Store>,
+        key: String,
+        offset: u64,
+        delete_after: bool,
+    ) -> impl tokio::io::AsyncRead + Send + Unpin + 'static {
+        use futures_util::{StreamExt, TryStreamExt};
+
+        let body = {
+            let store = store.clone();
+            let key = key.clone();
+            futures_util::stream::once(async move { store.get_object(&key, offset).await })
+                .map_ok(|response| response.bytes_stream().map_err(std::io::Error::other))
+                .map_err(std::io::Error::other)
+                .try_flatten()
+        };
+        let cleanup = futures_util::stream::once(async move {
+            if delete_after && let Err(e) = store.delete_object(&key).await {
+                tracing::warn!("Failed to delete S3 object {}: {}", key, e);
+            }
+        })
+        .filter_map(|()| async { None });
+        tokio_util::io::StreamReader::new(Box::pin(body.chain(cleanup)))
+    }
+
     /// Determine if memory mode should be used based on configuration and system state
     fn should_use_memory(config: &Config, content_length: u64) -> bool {
+        if MEMORY_PRESSURE.load(Ordering::Relaxed) && config.storage_mode != StorageMode::Disk {
+            tracing::debug!("Memory pressure active, using disk");
+            return false;
+        }
+
         match config.storage_mode {
             StorageMode::Disk => false,
             StorageMode::Memory => {
@@ -203,7 +375,7 @@ impl AudioBuffer {
     }
 
     /// Get available system memory in MB (使用缓存的 System 实例)
-    fn get_available_memory_mb() -> u64 {
+    pub fn get_available_memory_mb() -> u64 {
         if let Ok(mut sys) = SYSTEM.lock() {
             sys.refresh_memory();
             sys.available_memory() / (1024 * 1024)
@@ -215,7 +387,50 @@ impl AudioBuffer {
     }
 
     /// Write a chunk of data to the buffer
+    ///
+    /// A memory buffer that would grow past `memory_max_file_mb` (e.g. when the
+    /// server sent no Content-Length) is moved to a disk file first.
     pub async fn write_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+        if let Self::Memory {
+            data,
+            header,
+            audio_offset,
+            filename,
+            max_bytes,
+            spill_dir,
+            ..
+        } = self
+            && (header.len() + data.len() - *audio_offset + chunk.len()) as u64 > *max_bytes
+        {
+            let path = spill_dir.join(&*filename);
+            tracing::info!(
+                "AudioBuffer: memory buffer exceeds {}MB, switching to disk (path: {})",
+                *max_bytes / (1024 * 1024),
+                path.display()
+            );
+
+            let mut file = File::create(&path)
+                .await
+                .with_context(|| format!("Failed to create file: {}", path.display()))?;
+            file.write_all(header)
+                .await
+                .context("Failed to move memory buffer to disk")?;
+            file.write_all(&data[*audio_offset..])
+                .await
+                .context("Failed to move memory buffer to disk")?;
+            file.write_all(chunk)
+                .await
+                .context("Failed to write chunk to disk")?;
+
+            let filename = std::mem::take(filename);
+            *self = Self::Disk {
+                path,
+                file: Some(file),
+                filename,
+            };
+            return Ok(());
+        }
+
         match self {
             Self::Disk { file, .. } => {
                 if let Some(f) = file {
@@ -227,6 +442,28 @@ impl AudioBuffer {
             Self::Memory { data, .. } => {
                 data.extend_from_slice(chunk);
             }
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                upload,
+                prefix,
+                size,
+                ..
+            } => {
+                let room = S3_PREFIX_BYTES.saturating_sub(prefix.len());
+                prefix.extend_from_slice(&chunk[..room.min(chunk.len())]);
+                *size += chunk.len() as u64;
+
+                let Some(active) = upload else {
+                    anyhow::bail!("S3 upload already finished");
+                };
+                if active.chunks.send(chunk.to_vec()).await.is_err() {
+                    // The PUT request ended early; surface its error
+                    if let Some(active) = upload.take() {
+                        active.task.await.context("S3 upload task failed")??;
+                    }
+                    anyhow::bail!("S3 upload ended before the download finished");
+                }
+            }
         }
         Ok(())
     }
@@ -242,6 +479,14 @@ impl AudioBuffer {
             Self::Memory { .. } => {
                 // Nothing to flush for memory buffer
             }
+            #[cfg(feature = "s3")]
+            Self::S3 { upload, .. } => {
+                if let Some(S3Upload { chunks, task }) = upload.take() {
+                    // Closing the channel ends the request body
+                    drop(chunks);
+                    task.await.context("S3 upload task failed")??;
+                }
+            }
         }
         Ok(())
     }
@@ -252,7 +497,19 @@ impl AudioBuffer {
             Self::Disk { path, .. } => {
                 tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0)
             }
-            Self::Memory { data, .. } => data.len() as u64,
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                ..
+            } => (header.len() + data.len() - audio_offset) as u64,
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                header,
+                audio_offset,
+                size,
+                ..
+            } => header.len() as u64 + size.saturating_sub(*audio_offset as u64),
         }
     }
 
@@ -265,6 +522,8 @@ impl AudioBuffer {
     pub fn filename(&self) -> &str {
         match self {
             Self::Disk { filename, .. } | Self::Memory { filename, .. } => filename,
+            #[cfg(feature = "s3")]
+            Self::S3 { filename, .. } => filename,
         }
     }
 
@@ -273,6 +532,8 @@ impl AudioBuffer {
         match self {
             Self::Disk { path, .. } => Some(path),
             Self::Memory { .. } => None,
+            #[cfg(feature = "s3")]
+            Self::S3 { .. } => None,
         }
     }
 
@@ -281,6 +542,15 @@ impl AudioBuffer {
         &mut self,
         song_detail: &SongDetail,
         artwork_data: Option<&[u8]>,
+    ) -> crate::error::Result<()> {
+        self.write_id3_tags(song_detail, artwork_data)
+            .map_err(|e| BotError::Tagging(format!("{e:#}")))
+    }
+
+    fn write_id3_tags(
+        &mut self,
+        song_detail: &SongDetail,
+        artwork_data: Option<&[u8]>,
     ) -> Result<()> {
         use crate::music_api::format_artists;
         use id3::{Tag, TagLike, Version, frame};
@@ -312,62 +582,75 @@ impl AudioBuffer {
                 tag.write_to_path(path, Version::Id3v24)
                     .context("Failed to write ID3 tags to disk file")?;
             }
-            Self::Memory { data, .. } => {
-                // Memory mode: create new tag and prepend to audio data
-                let mut tag = Tag::new();
-
-                tag.set_title(&song_detail.name);
-                let album_name = song_detail
-                    .al
-                    .as_ref()
-                    .map_or("Unknown Album", |al| al.name.as_str());
-                tag.set_album(album_name);
-                tag.set_artist(format_artists(song_detail.ar.as_deref().unwrap_or(&[])));
-                tag.set_duration((song_detail.dt.unwrap_or(0) / 1000) as u32);
-
-                if let Some(artwork) = artwork_data {
-                    let picture = frame::Picture {
-                        mime_type: "image/jpeg".to_string(),
-                        picture_type: frame::PictureType::CoverFront,
-                        description: "Album Cover".to_string(),
-                        data: artwork.to_vec(),
-                    };
-                    tag.add_frame(picture);
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                ..
+            } => {
+                // Memory mode: build the tag as a separate segment in front of the audio
+                let tag_buffer = Self::id3_tag_bytes(song_detail, artwork_data)?;
+
+                // For MP3: ID3v2 tag goes at the beginning. Skip any existing
+                // ID3 tag in the downloaded data instead of copying the audio.
+                let audio = &data[*audio_offset..];
+                if audio.len() >= 3 && &audio[0..3] == b"ID3" {
+                    *audio_offset += Self::find_mp3_audio_start(audio).min(audio.len());
                 }
-
-                // Write tag to buffer
-                let mut tag_buffer = Vec::new();
-                tag.write_to(&mut tag_buffer, Version::Id3v24)
-                    .context("Failed to write ID3 tags to memory")?;
-
-                // For MP3: ID3v2 tag goes at the beginning
-                // Check if data already starts with ID3
-                let has_existing_id3 = data.len() >= 3 && &data[0..3] == b"ID3";
-                if has_existing_id3 {
-                    // Skip existing ID3 tag and replace with new one
-                    let audio_start = Self::find_mp3_audio_start(data);
-                    // Use a single reallocation approach
-                    let mut new_data =
-                        Vec::with_capacity(tag_buffer.len() + data.len() - audio_start);
-                    new_data.extend_from_slice(&tag_buffer);
-                    new_data.extend_from_slice(&data[audio_start..]);
-                    *data = new_data;
-                } else {
-                    // No existing ID3, just prepend - use single allocation
-                    let mut new_data = Vec::with_capacity(tag_buffer.len() + data.len());
-                    new_data.extend_from_slice(&tag_buffer);
-                    new_data.extend_from_slice(data);
-                    *data = new_data;
+                *header = tag_buffer;
+            }
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                prefix,
+                header,
+                audio_offset,
+                ..
+            } => {
+                // The object itself is never rewritten; the new tag is sent in
+                // front of it and the original tag skipped with a range request
+                let tag_buffer = Self::id3_tag_bytes(song_detail, artwork_data)?;
+                if header.is_empty() {
+                    *audio_offset = Self::find_mp3_audio_start(prefix);
                 }
-
-                // Release unused capacity to reduce memory footprint
-                data.shrink_to_fit();
+                *header = tag_buffer;
             }
         }
 
         Ok(())
     }
 
+    /// Serialize an ID3v2.4 tag for a memory segment
+    fn id3_tag_bytes(song_detail: &SongDetail, artwork_data: Option<&[u8]>) -> Result<Vec<u8>> {
+        use crate::music_api::format_artists;
+        use id3::{Tag, TagLike, Version, frame};
+
+        let mut tag = Tag::new();
+
+        tag.set_title(&song_detail.name);
+        let album_name = song_detail
+            .al
+            .as_ref()
+            .map_or("Unknown Album", |al| al.name.as_str());
+        tag.set_album(album_name);
+        tag.set_artist(format_artists(song_detail.ar.as_deref().unwrap_or(&[])));
+        tag.set_duration((song_detail.dt.unwrap_or(0) / 1000) as u32);
+
+        if let Some(artwork) = artwork_data {
+            let picture = frame::Picture {
+                mime_type: "image/jpeg".to_string(),
+                picture_type: frame::PictureType::CoverFront,
+                description: "Album Cover".to_string(),
+                data: artwork.to_vec(),
+            };
+            tag.add_frame(picture);
+        }
+
+        let mut tag_buffer = Vec::new();
+        tag.write_to(&mut tag_buffer, Version::Id3v24)
+            .context("Failed to write ID3 tags to memory")?;
+        Ok(tag_buffer)
+    }
+
     /// Find the start of MP3 audio data (after ID3v2 tag)
     fn find_mp3_audio_start(data: &[u8]) -> usize {
         if data.len() < 10 || &data[0..3] != b"ID3" {
@@ -389,16 +672,49 @@ impl AudioBuffer {
         &mut self,
         song_detail: &SongDetail,
         artwork_data: Option<&[u8]>,
+    ) -> crate::error::Result<()> {
+        self.write_flac_metadata(song_detail, artwork_data)
+            .map_err(|e| BotError::Tagging(format!("{e:#}")))
+    }
+
+    fn write_flac_metadata(
+        &mut self,
+        song_detail: &SongDetail,
+        artwork_data: Option<&[u8]>,
     ) -> Result<()> {
         match self {
             Self::Disk { path, .. } => {
                 // Disk mode: use metaflac directly
                 Self::add_flac_metadata_disk(path, song_detail, artwork_data)
             }
-            Self::Memory { data, .. } => {
-                // Memory mode: parse and rebuild FLAC in memory
-                Self::add_flac_metadata_memory(data, song_detail, artwork_data)
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                ..
+            } => {
+                // Memory mode: rebuild only the metadata segment
+                Self::add_flac_metadata_memory(
+                    data,
+                    header,
+                    audio_offset,
+                    song_detail,
+                    artwork_data,
+                )
             }
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                prefix,
+                header,
+                audio_offset,
+                ..
+            } => Self::add_flac_metadata_memory(
+                prefix,
+                header,
+                audio_offset,
+                song_detail,
+                artwork_data,
+            ),
         }
     }
 
@@ -457,15 +773,96 @@ impl AudioBuffer {
             tag.push_block(metaflac::Block::Picture(pic));
         }
 
-        tag.write_to_path(path)
-            .map_err(|e| anyhow::anyhow!("Failed to write FLAC metadata: {e}"))?;
+        Self::write_flac_metadata_in_place(path, &mut tag)
+    }
+
+    /// Write `tag` over the metadata section of the FLAC file at `path`
+    ///
+    /// When the new blocks fit into the space of the old ones (reusing existing
+    /// PADDING), only the metadata section is overwritten; otherwise metaflac
+    /// rewrites the whole file.
+    fn write_flac_metadata_in_place(path: &Path, tag: &mut metaflac::Tag) -> Result<()> {
+        use metaflac::block::BlockType;
+        use std::io::{Seek, SeekFrom, Write};
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open file: {}", path.display()))?;
+
+        tag.remove_blocks(BlockType::Padding);
+        let mut metadata = Vec::new();
+        tag.write_to(&mut metadata)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize FLAC metadata: {e}"))?;
+
+        // Files with unexpected layouts (e.g. a leading ID3 tag) are left to metaflac
+        let fit = Self::flac_metadata_len(&mut file).map_or(MetadataFit::Rewrite, |old_len| {
+            flac_metadata_fit(old_len, metadata.len() as u64)
+        });
+        match fit {
+            MetadataFit::Exact => {}
+            MetadataFit::Padding(padding) => {
+                tag.push_block(metaflac::Block::Padding(padding));
+                metadata.clear();
+                tag.write_to(&mut metadata)
+                    .map_err(|e| anyhow::anyhow!("Failed to serialize FLAC metadata: {e}"))?;
+            }
+            MetadataFit::Rewrite => {
+                tracing::debug!(
+                    "FLAC metadata ({} bytes) does not fit in place, rewriting file",
+                    metadata.len()
+                );
+                drop(file);
+                return tag
+                    .write_to_path(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to write FLAC metadata: {e}"));
+            }
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&metadata)
+            .context("Failed to write FLAC metadata in place")?;
+        file.flush()?;
 
         Ok(())
     }
 
-    /// Add FLAC metadata in memory by parsing and rebuilding the file
+    /// Size of the FLAC metadata section (magic + all blocks) read from block headers
+    fn flac_metadata_len(file: &mut std::fs::File) -> Result<u64> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut magic = [0u8; 4];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut magic)
+            .context("Failed to read FLAC header")?;
+        if &magic != b"fLaC" {
+            return Err(anyhow::anyhow!("Not a valid FLAC file"));
+        }
+
+        let mut pos = 4u64;
+        loop {
+            let mut header = [0u8; 4];
+            file.read_exact(&mut header)
+                .context("Unexpected end of FLAC metadata")?;
+            let block_len = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+            pos += 4 + u64::from(block_len);
+            file.seek(SeekFrom::Start(pos))?;
+
+            if header[0] & 0x80 != 0 {
+                return Ok(pos);
+            }
+        }
+    }
+
+    /// Add FLAC metadata in memory by rebuilding the metadata blocks into `header`
+    ///
+    /// The audio frames stay in `data`; only `audio_offset` is moved past the
+    /// original metadata.
     fn add_flac_metadata_memory(
-        data: &mut Vec<u8>,
+        data: &[u8],
+        header: &mut Vec<u8>,
+        audio_offset: &mut usize,
         song_detail: &SongDetail,
         artwork_data: Option<&[u8]>,
     ) -> Result<()> {
@@ -473,13 +870,17 @@ impl AudioBuffer {
         use metaflac::Tag;
         use metaflac::block::{Picture, PictureType};
 
-        // 1. Find where audio data starts
-        let audio_start = Self::find_flac_audio_start(data)?;
-        // Clone only the audio portion we need
-        let audio_data = &data[audio_start..];
+        // 1. Find where audio data starts and read existing metadata, either
+        // from the downloaded file or from a previously written header
+        let metadata: &[u8] = if header.is_empty() {
+            *audio_offset = Self::find_flac_audio_start(data)?;
+            &data[..*audio_offset]
+        } else {
+            header
+        };
 
         // 2. Read existing metadata
-        let mut cursor = Cursor::new(&data[..]);
+        let mut cursor = Cursor::new(metadata);
         let mut tag = Tag::read_from(&mut cursor).unwrap_or_else(|_| Tag::new());
 
         // 3. Add Vorbis Comments (text metadata)
@@ -518,13 +919,11 @@ impl AudioBuffer {
             tag.push_block(metaflac::Block::Picture(pic));
         }
 
-        // 5. Build new data with single allocation
-        let mut new_data = Vec::new();
-        tag.write_to(&mut new_data)
+        // 5. Write the metadata segment; the audio frames are not copied
+        let mut new_header = Vec::new();
+        tag.write_to(&mut new_header)
             .map_err(|e| anyhow::anyhow!("Failed to write FLAC metadata to memory: {e}"))?;
-        new_data.extend_from_slice(audio_data);
-        *data = new_data;
-        data.shrink_to_fit();
+        *header = new_header;
 
         Ok(())
     }
@@ -560,21 +959,81 @@ impl AudioBuffer {
         Ok(pos)
     }
 
+    /// Contiguous copy of a memory buffer's segments
+    fn concat_segments(data: &[u8], header: &[u8], audio_offset: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(header.len() + data.len() - audio_offset);
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&data[audio_offset..]);
+        bytes
+    }
+
     /// Convert to InputFile for Telegram upload (borrows)
     pub fn to_input_file(&self) -> InputFile {
         match self {
             Self::Disk { path, .. } => InputFile::file(path),
-            Self::Memory { data, filename, .. } => {
-                InputFile::memory(data.clone()).file_name(filename.clone())
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                filename,
+                ..
+            } => InputFile::memory(Self::concat_segments(data, header, *audio_offset))
+                .file_name(filename.clone()),
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                store,
+                key,
+                filename,
+                header,
+                audio_offset,
+                ..
+            } => {
+                use tokio::io::AsyncReadExt;
+
+                let object =
+                    Self::object_reader(store.clone(), key.clone(), *audio_offset as u64, false);
+                InputFile::read(Cursor::new(header.clone()).chain(object))
+                    .file_name(filename.clone())
             }
         }
     }
 
     /// Convert to InputFile for Telegram upload (consumes self, avoids cloning)
+    ///
+    /// Tagged memory buffers are streamed as tag segment + audio segment, so the
+    /// file is never concatenated in memory.
     pub fn into_input_file(self) -> InputFile {
+        use tokio::io::AsyncReadExt;
+
         match self {
             Self::Disk { path, .. } => InputFile::file(path),
-            Self::Memory { data, filename, .. } => InputFile::memory(data).file_name(filename),
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                filename,
+                ..
+            } => {
+                if header.is_empty() && audio_offset == 0 {
+                    return InputFile::memory(data).file_name(filename);
+                }
+                let mut audio = Cursor::new(data);
+                audio.set_position(audio_offset as u64);
+                InputFile::read(Cursor::new(header).chain(audio)).file_name(filename)
+            }
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                store,
+                key,
+                filename,
+                header,
+                audio_offset,
+                ..
+            } => {
+                // The object is deleted once Telegram has read it to the end
+                let object = Self::object_reader(store, key, audio_offset as u64, true);
+                InputFile::read(Cursor::new(header).chain(object)).file_name(filename)
+            }
         }
     }
 
@@ -584,8 +1043,70 @@ impl AudioBuffer {
             Self::Disk { path, .. } => tokio::fs::read(path)
                 .await
                 .with_context(|| format!("Failed to read file: {}", path.display())),
-            Self::Memory { data, .. } => Ok(data.clone()),
+            Self::Memory {
+                data,
+                header,
+                audio_offset,
+                ..
+            } => Ok(Self::concat_segments(data, header, *audio_offset)),
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                store,
+                key,
+                header,
+                audio_offset,
+                ..
+            } => {
+                let object = store.get_object(key, *audio_offset as u64).await?;
+                let mut bytes = header.clone();
+                bytes.extend_from_slice(&object.bytes().await.context("S3 download failed")?);
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Move the file to `dest`, consuming the buffer
+    ///
+    /// Copies and writes go to `<dest>.part` and are renamed into place, so a
+    /// failure halfway (a full disk) never leaves a truncated file at `dest`.
+    pub async fn save_to(self, dest: &Path) -> Result<()> {
+        if let Self::Disk { path, file, .. } = &self {
+            // Renaming fails across file systems; fall back to copying
+            if let Some(file) = file {
+                file.sync_all().await.ok();
+            }
+            if tokio::fs::rename(path, dest).await.is_ok() {
+                return Ok(());
+            }
         }
+
+        let mut partial = dest.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let written = if let Self::Disk { path, .. } = &self {
+            tokio::fs::copy(path, &partial)
+                .await
+                .map(drop)
+                .with_context(|| format!("Failed to copy file to {}", dest.display()))
+        } else {
+            match self.get_data().await {
+                Ok(data) => tokio::fs::write(&partial, data)
+                    .await
+                    .with_context(|| format!("Failed to write file: {}", dest.display())),
+                Err(e) => Err(e),
+            }
+        };
+        let saved = match written {
+            Ok(()) => tokio::fs::rename(&partial, dest)
+                .await
+                .with_context(|| format!("Failed to move file to {}", dest.display())),
+            Err(e) => Err(e),
+        };
+        if saved.is_err() {
+            tokio::fs::remove_file(&partial).await.ok();
+        }
+        saved?;
+        self.cleanup().await
     }
 
     /// Cleanup resources
@@ -604,6 +1125,15 @@ impl AudioBuffer {
             Self::Memory { .. } => {
                 // Memory is automatically freed when dropped
             }
+            #[cfg(feature = "s3")]
+            Self::S3 {
+                store, key, upload, ..
+            } => {
+                if let Some(upload) = upload {
+                    upload.task.abort();
+                }
+                store.delete_object(&key).await?;
+            }
         }
         Ok(())
     }
@@ -740,4 +1270,150 @@ mod tests {
         let result = AudioBuffer::find_mp3_audio_start(&mp3_data);
         assert_eq!(result, 10); // 10 byte header
     }
+
+    #[tokio::test]
+    async fn save_to_leaves_no_partial_file() {
+        let dir = std::env::temp_dir().join(format!("music163bot-buf-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            storage_mode: StorageMode::Memory,
+            ..Config::default()
+        };
+        let new_buffer = || {
+            AudioBuffer::new(
+                &config,
+                3,
+                "song.mp3".to_string(),
+                "mp3",
+                dir.to_str().unwrap(),
+            )
+        };
+
+        let mut buffer = new_buffer().await.unwrap();
+        buffer.write_chunk(b"abc").await.unwrap();
+        buffer.finish().await.unwrap();
+        let dest = dir.join("saved.mp3");
+        buffer.save_to(&dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abc");
+        assert!(!dir.join("saved.mp3.part").exists());
+
+        // A failed save leaves neither the destination nor the partial file
+        let mut buffer = new_buffer().await.unwrap();
+        buffer.write_chunk(b"abc").await.unwrap();
+        buffer.finish().await.unwrap();
+        let missing = dir.join("missing").join("saved.mp3");
+        assert!(buffer.save_to(&missing).await.is_err());
+        assert!(!missing.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn memory_buffer_spills_to_disk_past_max_size() {
+        let dir = std::env::temp_dir().join(format!("music163bot-buf-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            storage_mode: StorageMode::Memory,
+            memory_max_file_mb: 1,
+            memory_buffer_mb: 0,
+            ..Config::default()
+        };
+
+        let mut buffer = AudioBuffer::new(
+            &config,
+            0,
+            "spill.mp3".to_string(),
+            "mp3",
+            dir.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(buffer.is_memory());
+
+        let chunk = vec![7u8; 512 * 1024];
+        buffer.write_chunk(&chunk).await.unwrap();
+        assert!(buffer.is_memory());
+        buffer.write_chunk(&chunk).await.unwrap();
+        buffer.write_chunk(&chunk).await.unwrap();
+        assert!(!buffer.is_memory());
+
+        buffer.finish().await.unwrap();
+        assert_eq!(buffer.size().await, 3 * 512 * 1024);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn hybrid_unknown_size_spills_past_threshold() {
+        let dir = std::env::temp_dir().join(format!("music163bot-buf-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            storage_mode: StorageMode::Hybrid,
+            memory_threshold_mb: 1,
+            memory_max_file_mb: 100,
+            memory_buffer_mb: 0,
+            ..Config::default()
+        };
+
+        let mut buffer = AudioBuffer::new(
+            &config,
+            0,
+            "chunked.flac".to_string(),
+            "flac",
+            dir.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(buffer.is_memory());
+
+        buffer.write_chunk(&vec![1u8; 768 * 1024]).await.unwrap();
+        assert!(buffer.is_memory());
+        buffer.write_chunk(&vec![2u8; 768 * 1024]).await.unwrap();
+        assert!(!buffer.is_memory());
+
+        buffer.finish().await.unwrap();
+        assert_eq!(buffer.size().await, 2 * 768 * 1024);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn memory_id3_tagging_keeps_audio_segment() {
+        let config = Config {
+            storage_mode: StorageMode::Memory,
+            memory_buffer_mb: 0,
+            ..Config::default()
+        };
+        let mut buffer = AudioBuffer::new(&config, 0, "tag.mp3".to_string(), "mp3", ".")
+            .await
+            .unwrap();
+
+        // Existing empty ID3v2.4 tag followed by audio frames
+        let mut mp3 = b"ID3\x04\x00\x00\x00\x00\x00\x00".to_vec();
+        mp3.extend_from_slice(b"\xFF\xFBAUDIO");
+        buffer.write_chunk(&mp3).await.unwrap();
+
+        let song = SongDetail {
+            id: 1,
+            name: "Song".to_string(),
+            dt: Some(180_000),
+            ar: None,
+            al: None,
+        };
+        buffer.add_id3_tags(&song, None).unwrap();
+
+        let data = buffer.get_data().await.unwrap();
+        assert!(data.starts_with(b"ID3"));
+        assert!(data.ends_with(b"\xFF\xFBAUDIO"));
+        assert_eq!(AudioBuffer::find_mp3_audio_start(&data), data.len() - 7);
+        assert_eq!(buffer.size().await, data.len() as u64);
+    }
+
+    #[test]
+    fn flac_metadata_reuses_padding_when_it_fits() {
+        assert_eq!(flac_metadata_fit(8192, 8192), MetadataFit::Exact);
+        assert_eq!(flac_metadata_fit(8192, 4096), MetadataFit::Padding(4092));
+        assert_eq!(flac_metadata_fit(8192, 8190), MetadataFit::Rewrite);
+        assert_eq!(flac_metadata_fit(4096, 8192), MetadataFit::Rewrite);
+    }
 }
@@ -0,0 +1,114 @@
+//! Scheduled online backups of the song database (`[backup]` config section)
+//!
+//! Each run writes a consistent, compacted copy with `VACUUM INTO` while the
+//! bot keeps serving requests, then prunes the oldest copies beyond
+//! `backup.keep`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::bot::BotState;
+use crate::database::Database;
+use crate::error::Result;
+
+const FILE_PREFIX: &str = "cache-";
+const FILE_SUFFIX: &str = ".db";
+
+/// Timestamped backup file name; names sort chronologically
+#[must_use]
+pub fn backup_file_name(now: DateTime<Local>) -> String {
+    format!("{FILE_PREFIX}{}{FILE_SUFFIX}", now.format("%Y%m%d-%H%M%S"))
+}
+
+/// Delete the oldest backups in `dir` so at most `keep` remain, returning the removed paths
+pub fn prune_backups(dir: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.drain(..excess).collect();
+    for path in &removed {
+        std::fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Write one backup into `dir` and prune old copies, returning the new file
+pub async fn run_backup(database: &Database, dir: &str, keep: usize) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let path = Path::new(dir).join(backup_file_name(Local::now()));
+    database.backup_to(&path).await?;
+
+    let prune_dir = PathBuf::from(dir);
+    let removed = tokio::task::spawn_blocking(move || prune_backups(&prune_dir, keep.max(1)))
+        .await
+        .map_err(|e| anyhow::anyhow!("Backup pruning task failed: {e}"))??;
+    for old in removed {
+        tracing::debug!("Removed old database backup {}", old.display());
+    }
+
+    Ok(path)
+}
+
+/// Back up the database every `backup.interval_hours`, starting one interval after startup
+pub fn spawn_backup_task(state: Arc<BotState>) {
+    tokio::spawn(async move {
+        let interval = Duration::from_hours(u64::from(state.config.backup_interval_hours.max(1)));
+        loop {
+            tokio::time::sleep(interval).await;
+            match run_backup(
+                &state.database,
+                &state.config.backup_dir,
+                state.config.backup_keep,
+            )
+            .await
+            {
+                Ok(path) => tracing::info!("Database backed up to {}", path.display()),
+                Err(e) => tracing::error!("Database backup failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_file_name, prune_backups};
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn backup_names_are_timestamped() {
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 6, 30, 5).unwrap();
+        assert_eq!(backup_file_name(now), "cache-20240515-063005.db");
+    }
+
+    #[test]
+    fn pruning_keeps_newest_backups() {
+        let dir = std::env::temp_dir().join(format!("music163bot-backup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "cache-20240101-000000.db",
+            "cache-20240102-000000.db",
+            "cache-20240103-000000.db",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let removed = prune_backups(&dir, 2).unwrap();
+        assert_eq!(removed, vec![dir.join("cache-20240101-000000.db")]);
+        assert!(dir.join("cache-20240103-000000.db").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
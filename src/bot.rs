@@ -1,24 +1,64 @@
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
-use futures_util::StreamExt;
+use tracing::Instrument;
 use tokio::sync::Mutex;
 use teloxide::RequestError;
 use teloxide::prelude::*;
 use teloxide::sugar::request::RequestLinkPreviewExt;
 use teloxide::types::{
-    CallbackQuery, FileId, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery,
-    InlineQueryResult, InlineQueryResultArticle, InputFile, InputMessageContent,
-    InputMessageContentText, MaybeInaccessibleMessage, Message, MessageKind, ParseMode,
-    ReplyMarkup, ReplyParameters,
+    BotCommandScope, CallbackQuery, FileId, InlineKeyboardButton, InlineKeyboardButtonKind,
+    InlineKeyboardMarkup, InlineQuery, InlineQueryResult, InlineQueryResultArticle,
+    InlineQueryResultAudio, InlineQueryResultCachedAudio, InputFile, InputMedia, InputMediaAudio,
+    InputMessageContent, InputMessageContentText, LabeledPrice, MaybeInaccessibleMessage, Message,
+    MessageKind, ParseMode, PreCheckoutQuery, Recipient, ReplyMarkup, ReplyParameters, Seconds,
+    SuccessfulPayment, User,
 };
-
-use crate::audio_buffer::{AudioBuffer, ThumbnailBuffer};
-use crate::config::{Config, CoverMode};
+use teloxide::utils::html;
+
+use crate::alerting::{self, ErrorFeed, FailureCause, FailureTracker};
+use crate::api_endpoints;
+use crate::backup;
+use crate::cache_dump::CacheDump;
+use crate::card;
+use crate::checkin::{self, CheckinReport};
+use crate::commands::{self, Args, ClearCacheAction, Command};
+use crate::config::{CaptionStyle, Config, CoverMode};
 use crate::database::{Database, SongInfo};
-use crate::error::Result;
-use crate::music_api::{MusicApi, format_artists};
-use crate::utils::{clean_filename, ensure_dir, extract_first_url, parse_music_id, throughput_mbps, update_peak};
+use crate::download_lanes::{self, Lane};
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::health;
+use crate::hot_cache::{HotCache, RedisTarget};
+use crate::http_client::{self, Profile};
+use crate::i18n::{Locale, Text};
+use crate::inline_previews::InlinePreviews;
+use crate::jukebox::{Enqueued, Jukebox, QueueView, QueuedSong};
+use crate::library;
+use crate::lyric;
+use crate::memory::{self, ActivityTracker};
+use crate::music_api::{MusicApi, fee_label, format_artists, unavailable_reason};
+use crate::pipeline::{self, CacheTarget, CoverPolicy, JobFiles, QueueTime, Stage};
+use crate::quiz::{self, Hint, Quiz, Round};
+use crate::scrobble::{self, Scrobbler, Service};
+use crate::selftest;
+use crate::status_tracker::StatusTracker;
+use crate::systemd;
+use crate::telegraph::{LyricPage, Telegraph};
+use crate::throughput::TransferStats;
+use crate::transcode;
+use crate::updater;
+use crate::upgrade;
+use crate::utils::{
+    HASHTAG_EXT_PLACEHOLDER, LOCAL_BOT_API_UPLOAD_LIMIT, StartPayload, TrackSelection,
+    clean_filename, cover_thumbnail_url, ensure_dir, extract_first_url, format_count,
+    format_duration, format_file_size, parse_album_id, parse_artist_id, parse_collection_target,
+    parse_fetch_target, parse_hashtags, parse_music_id, parse_music_id_range, parse_playlist_id,
+    parse_start_payload, parse_timestamp, render_hashtags, smaller_bitrates, split_cover_flag,
+    split_track_selection, update_peak,
+};
+use crate::webdav::WebDav;
+use crate::worker_pool::{self, HandlerLimit};
 
 pub struct BotState {
     pub config: Config,
@@ -26,9 +66,71 @@ pub struct BotState {
     pub music_api: MusicApi,
     pub download_semaphore: Arc<tokio::sync::Semaphore>,
     pub bot_username: String,
+    /// Telegram user ID of this bot; file_ids are scoped to it
+    pub bot_id: i64,
     pub upload_client_state: Arc<Mutex<UploadClientState>>,
     pub maintenance_counters: MaintenanceCounters,
     pub upload_counters: UploadCounters,
+    /// Recent download/upload throughput for /status and /metrics
+    pub transfers: TransferStats,
+    /// Result of the most recent daily check-in run
+    pub last_checkin: Mutex<Option<CheckinReport>>,
+    /// Consecutive pipeline failures per cause, for admin alerts
+    pub failure_tracker: FailureTracker,
+    /// Recent pipeline failures shown on the web dashboard
+    pub error_feed: ErrorFeed,
+    /// Download activity for idle memory release (shared by all bots of the process)
+    pub activity: Arc<ActivityTracker>,
+    /// Redis layer in front of the song cache (`cache.redis_url`)
+    pub hot_cache: Option<Arc<HotCache>>,
+    /// Share `/fetch` uploads to instead of `download.library_dir` (`webdav.url`)
+    pub webdav: Option<Arc<WebDav>>,
+    /// telegra.ph publisher for `/lyric --telegraph` (one account per process)
+    pub telegraph: Arc<Telegraph>,
+    /// Last.fm/ListenBrainz submissions (`scrobble.secret_key`)
+    pub scrobbler: Option<Arc<Scrobbler>>,
+    /// Limit on running message handlers (shared by all bots of the process)
+    pub handlers: Arc<HandlerLimit>,
+    /// Group song queues (`/queue`)
+    pub jukebox: Jukebox,
+    /// Running `/quiz` rounds
+    pub quiz: Quiz,
+    /// Preview URLs of inline search results (shared by all bots of the process)
+    pub inline_previews: Arc<InlinePreviews>,
+    /// `/clearallcache` prompt awaiting its `confirm`
+    pub pending_clear: Mutex<Option<PendingClear>>,
+    /// Bulk `/rmcache` awaiting its `confirm`
+    pub pending_removal: Mutex<Option<PendingRemoval>>,
+    /// Status messages of running downloads, for the stuck status reaper
+    pub status_tracker: StatusTracker,
+}
+
+/// How long a `/clearallcache` or bulk `/rmcache` prompt can be confirmed
+const CONFIRM_WINDOW: std::time::Duration = std::time::Duration::from_secs(30);
+/// Matches listed in a bulk `/rmcache` prompt
+const RMCACHE_PREVIEW: usize = 20;
+/// Largest entries listed by `/cachesize`
+const CACHESIZE_LARGEST: i64 = 10;
+/// Edge length (px) of album covers shown next to inline search results
+const INLINE_THUMBNAIL_SIZE: u32 = 100;
+/// Days of delivery history kept for personalized inline results
+const SEND_LOG_RETENTION_DAYS: u32 = 90;
+/// Songs from each of recent and most-received history shown for an empty inline query
+const INLINE_HISTORY_LIMIT: i64 = 10;
+
+/// A `/clearallcache` request; only the admin who issued it may confirm it
+#[derive(Debug, Clone, Copy)]
+pub struct PendingClear {
+    pub admin_id: i64,
+    pub issued_at: std::time::Instant,
+}
+
+/// Songs matched by a bulk `/rmcache`, deleted once the same admin confirms
+#[derive(Debug, Clone)]
+pub struct PendingRemoval {
+    pub admin_id: i64,
+    pub issued_at: std::time::Instant,
+    pub music_ids: Vec<i64>,
 }
 
 #[derive(Debug)]
@@ -45,14 +147,12 @@ pub struct UploadCounters {
 
 #[derive(Debug)]
 pub struct MaintenanceCounters {
-    pub memory_release_requests: AtomicU32,
     pub db_analyze_requests: AtomicU32,
 }
 
 impl MaintenanceCounters {
     fn new() -> Self {
         Self {
-            memory_release_requests: AtomicU32::new(0),
             db_analyze_requests: AtomicU32::new(0),
         }
     }
@@ -66,26 +166,6 @@ impl MaintenanceCounters {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct CoverPolicy {
-    download_original: bool,
-    download_thumbnail: bool,
-    embed_tags: bool,
-    embed_cover: bool,
-}
-
-fn resolve_cover_policy(cover_mode: CoverMode) -> CoverPolicy {
-    let download_original = matches!(cover_mode, CoverMode::Original | CoverMode::Both);
-    let download_thumbnail = matches!(cover_mode, CoverMode::Thumbnail | CoverMode::Both);
-
-    CoverPolicy {
-        download_original,
-        download_thumbnail,
-        embed_tags: true,
-        embed_cover: download_original,
-    }
-}
-
 pub async fn run(config: Config) -> Result<()> {
     tracing::info!("Starting Telegram bot...");
 
@@ -93,15 +173,255 @@ pub async fn run(config: Config) -> Result<()> {
     ensure_dir(&config.cache_dir)?;
 
     // Initialize database
-    let database = Database::new(&config.database).await?;
+    let database = Database::new(&config).await?;
     tracing::info!("Database initialized");
 
     // Initialize music API
     let music_api = MusicApi::new_with_config(&config);
     tracing::info!("Music API initialized");
 
+    let hot_cache = if config.redis_url.is_empty() {
+        None
+    } else {
+        let target = RedisTarget::parse(&config.redis_url)?;
+        tracing::info!("Redis hot cache enabled at {}", target.addr);
+        Some(Arc::new(HotCache::new(target, config.redis_ttl_secs)))
+    };
+
+    let webdav = if config.webdav_url.is_empty() {
+        None
+    } else {
+        let webdav = WebDav::new(&config)?;
+        tracing::info!("WebDAV library target: {}", config.webdav_url);
+        Some(Arc::new(webdav))
+    };
+
+    #[cfg(feature = "s3")]
+    if !config.s3_endpoint.is_empty() {
+        let store = crate::object_store::S3Store::new(&config)?;
+        crate::audio_buffer::set_object_store(store);
+        tracing::info!("S3 storage enabled: bucket {}", config.s3_bucket);
+    }
+    #[cfg(not(feature = "s3"))]
+    if !config.s3_endpoint.is_empty() {
+        tracing::warn!("s3.endpoint is set but the binary was built without the `s3` feature");
+    }
+
     // Initialize bot with custom API URL support
-    let bot = if !config.bot_api.is_empty() && config.bot_api != "https://api.telegram.org" {
+    let bot = connect_bot(&config).await;
+
+    // Log the API configuration
+    tracing::info!("Music API configured: {}", &config.music_api);
+
+    let me = bot.get_me().await?;
+    let bot_username = me
+        .username
+        .clone()
+        .unwrap_or_else(|| "Music163bot".to_string());
+    tracing::info!("Bot @{} started successfully!", bot_username);
+
+    register_command_menus(&bot, &config.bot_admin).await;
+    systemd::notify_ready();
+
+    // Create bot state (needs bot username)
+    let bot_state = Arc::new(BotState {
+        config: config.clone(),
+        database,
+        music_api,
+        download_semaphore: Arc::new(tokio::sync::Semaphore::new(config.max_concurrent_downloads as usize)),
+        bot_username,
+        bot_id: me.id.0 as i64,
+        upload_client_state: Arc::new(Mutex::new(UploadClientState {
+            bot: None,
+            reuse_count: 0,
+        })),
+        maintenance_counters: MaintenanceCounters::new(),
+        upload_counters: UploadCounters::default(),
+        transfers: TransferStats::default(),
+        last_checkin: Mutex::new(None),
+        pending_clear: Mutex::new(None),
+        pending_removal: Mutex::new(None),
+        failure_tracker: FailureTracker::default(),
+        error_feed: ErrorFeed::default(),
+        activity: Arc::new(ActivityTracker::default()),
+        hot_cache,
+        webdav,
+        telegraph: Arc::new(Telegraph::new(&config)),
+        scrobbler: Scrobbler::from_config(&config).map(Arc::new),
+        handlers: Arc::new(HandlerLimit::new(config.dispatcher_max_handlers)),
+        jukebox: Jukebox::default(),
+        quiz: Quiz::default(),
+        inline_previews: Arc::new(InlinePreviews::default()),
+        status_tracker: StatusTracker::default(),
+    });
+
+    match bot_state
+        .database
+        .claim_legacy_file_ids(bot_state.bot_id)
+        .await
+    {
+        Ok(0) => {}
+        Ok(claimed) => tracing::info!(
+            "Assigned {} cached file_ids to @{}",
+            claimed,
+            bot_state.bot_username
+        ),
+        Err(e) => tracing::warn!("Failed to migrate cached file_ids: {}", e),
+    }
+
+    let report = selftest::run(&bot, &bot_state).await;
+    if report.passed() {
+        tracing::info!("Self-test passed:\n{}", report.summary());
+    } else {
+        tracing::error!("Self-test failed:\n{}", report.summary());
+    }
+
+    let mut extra_bots = Vec::new();
+    for token in &config.extra_bot_tokens {
+        match start_extra_bot(token, &bot_state).await {
+            Ok(extra) => extra_bots.push(extra),
+            Err(e) => tracing::error!("Failed to start extra bot: {}", e),
+        }
+    }
+
+    if config.checkin_enabled {
+        if config.music_u.is_some() {
+            checkin::spawn_daily_checkin(bot_state.clone());
+        } else {
+            tracing::warn!("checkin.enabled is set but MUSIC_U is not configured, skipping");
+        }
+    }
+
+    if config.quality_upgrade_enabled {
+        if config.music_u.is_none() {
+            tracing::warn!("cache.upgrade_enabled is set but MUSIC_U is not configured, skipping");
+        } else if config.bot_admin.is_empty() {
+            tracing::warn!("cache.upgrade_enabled is set but bot_admin is empty, skipping");
+        } else {
+            upgrade::spawn_quality_upgrade(bot.clone(), bot_state.clone());
+        }
+    }
+
+    if config.auto_update {
+        updater::spawn_update_checker(bot.clone(), bot_state.clone());
+    }
+
+    if !config.backup_dir.is_empty() {
+        backup::spawn_backup_task(bot_state.clone());
+    }
+
+    if config.purge_deleted_after_days > 0 {
+        spawn_deleted_song_purge(bot_state.clone());
+    }
+
+    if bot_state.music_api.endpoints().urls().len() > 1 {
+        api_endpoints::spawn_watchdog(bot_state.clone());
+    }
+
+    systemd::spawn_watchdog(bot_state.clone());
+    memory::spawn_idle_release(bot_state.clone());
+    memory::spawn_pressure_watchdog(bot_state.clone());
+
+    if !config.health_listen.is_empty() {
+        health::spawn_health_server(&config.health_listen, bot.clone(), bot_state.clone()).await;
+    }
+
+    #[cfg(feature = "api")]
+    if !config.api_listen.is_empty() {
+        crate::api::spawn_api_server(&config.api_listen, bot_state.clone()).await?;
+    }
+    #[cfg(not(feature = "api"))]
+    if !config.api_listen.is_empty() {
+        tracing::warn!("api.listen is set but the binary was built without the `api` feature");
+    }
+
+    let extra_dispatchers = futures_util::future::join_all(
+        extra_bots
+            .into_iter()
+            .map(|(bot, state)| dispatch(bot, state)),
+    );
+    tokio::join!(dispatch(bot, bot_state), extra_dispatchers);
+
+    systemd::notify_stopping();
+    Ok(())
+}
+
+/// Start an additional bot that shares the database, downloads and memory
+/// tracking of the primary bot but keeps its own file_ids and upload client
+async fn start_extra_bot(token: &str, primary: &BotState) -> Result<(Bot, Arc<BotState>)> {
+    let mut config = primary.config.clone();
+    config.bot_token = token.to_string();
+
+    let bot = connect_bot(&config).await;
+    let me = bot.get_me().await?;
+    let bot_username = me
+        .username
+        .clone()
+        .unwrap_or_else(|| "Music163bot".to_string());
+    tracing::info!("Extra bot @{} started successfully!", bot_username);
+
+    register_command_menus(&bot, &config.bot_admin).await;
+
+    let state = Arc::new(BotState {
+        config,
+        database: primary.database.clone(),
+        music_api: primary.music_api.clone(),
+        download_semaphore: primary.download_semaphore.clone(),
+        bot_username,
+        bot_id: me.id.0 as i64,
+        upload_client_state: Arc::new(Mutex::new(UploadClientState {
+            bot: None,
+            reuse_count: 0,
+        })),
+        maintenance_counters: MaintenanceCounters::new(),
+        upload_counters: UploadCounters::default(),
+        transfers: TransferStats::default(),
+        last_checkin: Mutex::new(None),
+        pending_clear: Mutex::new(None),
+        pending_removal: Mutex::new(None),
+        failure_tracker: FailureTracker::default(),
+        error_feed: ErrorFeed::default(),
+        activity: primary.activity.clone(),
+        hot_cache: primary.hot_cache.clone(),
+        webdav: primary.webdav.clone(),
+        telegraph: primary.telegraph.clone(),
+        scrobbler: primary.scrobbler.clone(),
+        handlers: primary.handlers.clone(),
+        jukebox: Jukebox::default(),
+        quiz: Quiz::default(),
+        inline_previews: primary.inline_previews.clone(),
+        status_tracker: StatusTracker::default(),
+    });
+    Ok((bot, state))
+}
+
+/// Run the update dispatcher of one bot until Ctrl-C
+async fn dispatch(bot: Bot, state: Arc<BotState>) {
+    if state.config.stuck_status_minutes > 0 {
+        spawn_status_reaper(bot.clone(), state.clone());
+    }
+
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(handle_message))
+        .branch(Update::filter_callback_query().endpoint(handle_callback))
+        .branch(Update::filter_inline_query().endpoint(handle_inline_query))
+        .branch(Update::filter_pre_checkout_query().endpoint(handle_pre_checkout_query));
+
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![state])
+        .default_handler(|upd| async move {
+            tracing::debug!("Unhandled update: {:?}", upd);
+        })
+        .enable_ctrlc_handler()
+        .build()
+        .dispatch()
+        .await;
+}
+
+/// Build the bot client for `config.bot_token`, falling back to the official API
+/// when a custom `bot.api` is unreachable
+async fn connect_bot(config: &Config) -> Bot {
+    if !config.bot_api.is_empty() && config.bot_api != "https://api.telegram.org" {
         // 使用自定义API URL
         // API URL must be base URL without "/bot" suffix - teloxide appends "bot<TOKEN>/" automatically
         let api_url_str = format!("{}/", config.bot_api.trim_end_matches("/bot"));
@@ -110,18 +430,8 @@ pub async fn run(config: Config) -> Result<()> {
             Ok(api_url) => {
                 tracing::info!("Using custom Telegram API URL: {}", api_url);
 
-                // Create a custom HTTP client tuned for Cloudflare compatibility (mimic Go http client)
-                // pool_max_idle_per_host(2) keeps reasonable connection pool for API efficiency
-                let client = reqwest::Client::builder()
-                    .use_rustls_tls()
-                    .user_agent("Go-http-client/2.0")
-                    .pool_max_idle_per_host(2)
-                    .pool_idle_timeout(std::time::Duration::from_secs(60))
-                    .danger_accept_invalid_certs(false)
-                    .timeout(std::time::Duration::from_secs(30))
-                    .no_gzip()
-                    .build()
-                    .unwrap();
+                // The api profile is tuned for Cloudflare compatibility (mimics the Go http client)
+                let client = http_client::build(config, Profile::Api).unwrap();
 
                 // Create bot with custom client and API URL
                 let bot = Bot::with_client(&config.bot_token, client).set_api_url(api_url.clone());
@@ -174,142 +484,215 @@ pub async fn run(config: Config) -> Result<()> {
     } else {
         // 使用默认API URL，但配置连接池以提高效率
         tracing::info!("Using default Telegram API URL: https://api.telegram.org");
-        let client = reqwest::Client::builder()
-            .use_rustls_tls()
-            .pool_max_idle_per_host(2)
-            .pool_idle_timeout(std::time::Duration::from_secs(60))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap();
+        let client = http_client::build(config, Profile::Api).unwrap();
         Bot::with_client(&config.bot_token, client)
-    };
-
-    // Log the API configuration
-    tracing::info!("Music API configured: {}", &config.music_api);
-
-    let me = bot.get_me().await?;
-    let bot_username = me
-        .username
-        .clone()
-        .unwrap_or_else(|| "Music163bot".to_string());
-    tracing::info!("Bot @{} started successfully!", bot_username);
+    }
+}
 
-    // Create bot state (needs bot username)
-    let bot_state = Arc::new(BotState {
-        config: config.clone(),
-        database,
-        music_api,
-        download_semaphore: Arc::new(tokio::sync::Semaphore::new(config.max_concurrent_downloads as usize)),
-        bot_username,
-        upload_client_state: Arc::new(Mutex::new(UploadClientState {
-            bot: None,
-            reuse_count: 0,
-        })),
-        maintenance_counters: MaintenanceCounters::new(),
-        upload_counters: UploadCounters::default(),
-    });
+/// Register command menus with Telegram: user commands by default, the group
+/// subset for group chats and the full list (including admin commands) for admins
+async fn register_command_menus(bot: &Bot, admins: &[i64]) {
+    let scopes = [
+        (
+            BotCommandScope::Default,
+            commands::menu_commands(false, false),
+        ),
+        (
+            BotCommandScope::AllGroupChats,
+            commands::menu_commands(false, true),
+        ),
+    ];
+
+    for (scope, menu) in scopes {
+        if let Err(e) = bot.set_my_commands(menu).scope(scope.clone()).await {
+            tracing::warn!("Failed to register commands for scope {:?}: {}", scope, e);
+        }
+    }
 
-    // Create dispatcher
-    let handler = dptree::entry()
-        .branch(Update::filter_message().endpoint(handle_message))
-        .branch(Update::filter_callback_query().endpoint(handle_callback))
-        .branch(Update::filter_inline_query().endpoint(handle_inline_query));
+    for &admin in admins {
+        let scope = BotCommandScope::Chat {
+            chat_id: Recipient::Id(ChatId(admin)),
+        };
+        if let Err(e) = bot
+            .set_my_commands(commands::menu_commands(true, false))
+            .scope(scope)
+            .await
+        {
+            // Fails if the admin has never started a chat with the bot
+            tracing::warn!("Failed to register admin commands for {}: {}", admin, e);
+        }
+    }
 
-    Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![bot_state])
-        .default_handler(|upd| async move {
-            tracing::debug!("Unhandled update: {:?}", upd);
-        })
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
-    Ok(())
+    tracing::info!("Command menus registered");
 }
 
 async fn handle_message(bot: Bot, msg: Message, state: Arc<BotState>) -> ResponseResult<()> {
+    if let Some(payment) = msg.successful_payment() {
+        return handle_successful_payment(&bot, &msg, &state, payment).await;
+    }
+
     if let MessageKind::Common(common) = &msg.kind
         && let teloxide::types::MediaKind::Text(text_content) = &common.media_kind
     {
         let text = text_content.text.clone();
-        let bot = bot.clone();
-        let msg = msg.clone();
-        let state = state.clone();
+        let chat_id = msg.chat.id;
+        let message_id = msg.id;
+
+        // Short ID attached to every log line of this update, so concurrent
+        // downloads can be told apart
+        let span = tracing::info_span!(
+            "update",
+            request_id = %new_request_id(),
+            chat_id = chat_id.0
+        );
 
-        // Spawn a new task to handle the message concurrently
-        // This allows multiple messages to be processed in parallel
-        tokio::spawn(async move {
-            // Handle commands
-            if text.starts_with('/') {
-                if let Err(e) = handle_command(&bot, &msg, &state, &text).await {
-                    tracing::error!("Error handling command: {}", e);
+        // Spawn the handler if a slot is free; otherwise drop the message so a
+        // flood of messages cannot pile up unbounded work
+        let handlers = state.handlers.clone();
+        let reply_bot = bot.clone();
+        let reply_state = state.clone();
+        let language_code = msg
+            .from
+            .as_ref()
+            .and_then(|user| user.language_code.clone());
+        let accepted = handlers.try_spawn(
+            async move {
+                // Handle commands
+                if text.starts_with('/') {
+                    if let Err(e) = handle_command(&bot, &msg, &state, &text).await {
+                        tracing::error!("Error handling command: {}", e);
+                    }
+                }
+                // Answers to a running /quiz round
+                else if let Some(round) = state.quiz.guess(chat_id.0, &text).await {
+                    if let Err(e) = finish_quiz_round(&bot, &msg, &state, &round).await {
+                        tracing::error!("Error finishing quiz round: {}", e);
+                    }
+                }
+                // Handle music URLs
+                else if (text.contains("music.163.com")
+                    || text.contains("163cn.tv")
+                    || text.contains("163cn.link"))
+                    && let Err(e) = handle_music_url(&bot, &msg, &state, &text).await
+                {
+                    tracing::error!("Error handling music URL: {}", e);
                 }
             }
-            // Handle music URLs
-            else if (text.contains("music.163.com")
-                || text.contains("163cn.tv")
-                || text.contains("163cn.link"))
-                && let Err(e) = handle_music_url(&bot, &msg, &state, &text).await
-            {
-                tracing::error!("Error handling music URL: {}", e);
+            .instrument(span),
+        );
+        if !accepted {
+            tracing::warn!(
+                "Too many messages in progress ({}), dropping message from chat {}",
+                handlers.running(),
+                chat_id
+            );
+            if !handlers.should_reply_busy(chat_id.0) {
+                return Ok(());
             }
-        });
+            reply_bot
+                .send_message(
+                    chat_id,
+                    Text::Busy
+                        .get(chat_locale(&reply_state, chat_id, language_code.as_deref()).await),
+                )
+                .reply_parameters(ReplyParameters::new(message_id))
+                .await
+                .ok();
+        }
     }
     Ok(())
 }
 
+/// Generate a short random ID for log correlation
+fn new_request_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
 async fn handle_command(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
     text: &str,
 ) -> ResponseResult<()> {
-    let parts: Vec<&str> = text.split_whitespace().collect();
-    let mut command = parts[0].trim_start_matches('/');
-
-    // Remove bot username if present (e.g., "/start@BotName" -> "start")
-    if let Some(at_pos) = command.find('@') {
-        command = &command[..at_pos];
+    // Unknown commands and commands addressed to other bots: don't respond
+    let Some(parsed) = Command::parse_text(text, &state.bot_username) else {
+        return Ok(());
+    };
+    // Only log music/search commands and admin commands
+    if parsed.is_logged() {
+        tracing::info!(
+            chat_id = msg.chat.id.0,
+            "Command: /{} from chat {}",
+            parsed.name(),
+            msg.chat.id
+        );
     }
 
-    let args = if parts.len() > 1 {
-        Some(parts[1..].join(" "))
-    } else {
-        None
-    };
+    if parsed.is_admin()
+        && let Some(user) = &msg.from
+        && state.config.bot_admin.contains(&(user.id.0 as i64))
+    {
+        record_admin_action(state, user, msg.chat.id, parsed.name(), &parsed.args()).await;
+    }
 
-    // Only log music/search commands and admin commands
-    match command {
-        "music" | "netease" | "search" | "rmcache" | "clearallcache" => {
-            tracing::info!("Command: /{} from chat {}", command, msg.chat.id);
-        }
-        _ => {} // Don't log about/start/status commands
-    }
-
-    match command {
-        "start" => handle_start_command(bot, msg, state, args).await,
-        "help" => handle_help_command(bot, msg, state).await,
-        "music" | "netease" => handle_music_command(bot, msg, state, args).await,
-        "search" => handle_search_command(bot, msg, state, args).await,
-        "about" => handle_about_command(bot, msg, state).await,
-        "lyric" => handle_lyric_command(bot, msg, state, args).await,
-        "status" => handle_status_command(bot, msg, state).await,
-        "rmcache" => handle_rmcache_command(bot, msg, state, args).await,
-        "clearallcache" => {
-            // Check if this is a confirmation
-            if let Some(ref arg) = args {
-                if arg.trim() == "confirm" {
-                    handle_clearallcache_confirm_command(bot, msg, state).await
-                } else {
-                    handle_clearallcache_command(bot, msg, state).await
-                }
-            } else {
-                handle_clearallcache_command(bot, msg, state).await
-            }
+    match parsed {
+        Command::Start(Args(args)) => handle_start_command(bot, msg, state, args).await,
+        Command::Help => handle_help_command(bot, msg, state).await,
+        Command::Music(Args(args)) | Command::Netease(Args(args)) => {
+            handle_music_command(bot, msg, state, args).await
         }
-        _ => {
-            // Unknown commands: don't respond (as requested)
-            Ok(())
+        Command::Search(Args(args)) => handle_search_command(bot, msg, state, args).await,
+        Command::About => handle_about_command(bot, msg, state).await,
+        Command::Album(Args(args)) => {
+            handle_collection_command(bot, msg, state, CollectionKind::Album, args).await
+        }
+        Command::Heartbeat(Args(args)) => handle_heartbeat_command(bot, msg, state, args).await,
+        Command::Playlist(Args(args)) => {
+            handle_collection_command(bot, msg, state, CollectionKind::Playlist, args).await
+        }
+        Command::Lyric(Args(args)) => handle_lyric_command(bot, msg, state, args).await,
+        Command::Voice(Args(args)) => handle_voice_command(bot, msg, state, args).await,
+        Command::Clip(Args(args)) => handle_clip_command(bot, msg, state, args).await,
+        Command::Card(Args(args)) => handle_card_command(bot, msg, state, args).await,
+        Command::Comments(Args(args)) => handle_comments_command(bot, msg, state, args).await,
+        Command::Info(Args(args)) => handle_info_command(bot, msg, state, args).await,
+        Command::Status => handle_status_command(bot, msg, state).await,
+        Command::Premium => handle_premium_command(bot, msg, state).await,
+        Command::DeleteMyData(Args(args)) => {
+            handle_deletemydata_command(bot, msg, state, args).await
+        }
+        Command::Silent(Args(args)) => handle_silent_command(bot, msg, state, args).await,
+        Command::Lang(Args(args)) => handle_lang_command(bot, msg, state, args).await,
+        Command::Quiz(Args(args)) => handle_quiz_command(bot, msg, state, args).await,
+        Command::Jukebox(Args(args)) => handle_jukebox_command(bot, msg, state, args).await,
+        Command::Queue(Args(args)) => handle_queue_command(bot, msg, state, args).await,
+        Command::NowPlaying => handle_nowplaying_command(bot, msg, state).await,
+        Command::Skip => handle_skip_command(bot, msg, state).await,
+        Command::Scrobble(Args(args)) => handle_scrobble_command(bot, msg, state, args).await,
+        Command::Caption(Args(args)) => handle_caption_command(bot, msg, state, args).await,
+        Command::Hashtags(Args(args)) => handle_hashtags_command(bot, msg, state, args).await,
+        Command::Cover(Args(args)) => handle_cover_command(bot, msg, state, args).await,
+        Command::RmCache(Args(args)) => handle_rmcache_command(bot, msg, state, args).await,
+        Command::Undelete(Args(args)) => handle_undelete_command(bot, msg, state, args).await,
+        Command::Refresh(Args(args)) => handle_refresh_command(bot, msg, state, args).await,
+        Command::AuditLog(Args(args)) => handle_auditlog_command(bot, msg, state, args).await,
+        Command::CacheSize => handle_cachesize_command(bot, msg, state).await,
+        Command::VipStatus => handle_vipstatus_command(bot, msg, state).await,
+        Command::SelfTest => handle_selftest_command(bot, msg, state).await,
+        Command::MyPlaylists => handle_myplaylists_command(bot, msg, state).await,
+        Command::Cloud => handle_cloud_command(bot, msg, state).await,
+        Command::MemStats(Args(args)) => handle_memstats_command(bot, msg, state, args).await,
+        Command::Precache(Args(args)) => handle_precache_command(bot, msg, state, args).await,
+        Command::Discography(Args(args)) => handle_discography_command(bot, msg, state, args).await,
+        Command::Fetch(Args(args)) => handle_fetch_command(bot, msg, state, args).await,
+        Command::ExportCache => handle_exportcache_command(bot, msg, state).await,
+        Command::ImportCache => handle_importcache_command(bot, msg, state).await,
+        Command::Report(Args(args)) => handle_report_command(bot, msg, state, args).await,
+        Command::ClearAllCache(ClearCacheAction::Prompt) => {
+            handle_clearallcache_command(bot, msg, state).await
+        }
+        Command::ClearAllCache(ClearCacheAction::Confirm { delete_files }) => {
+            handle_clearallcache_confirm_command(bot, msg, state, delete_files).await
         }
     }
 }
@@ -320,22 +703,41 @@ async fn handle_start_command(
     state: &Arc<BotState>,
     args: Option<String>,
 ) -> ResponseResult<()> {
-    if let Some(arg) = args
-        && let Ok(music_id) = arg.parse::<u64>()
-    {
+    let payload = args.as_deref().and_then(parse_start_payload);
+    match payload {
+        Some(StartPayload::Album(album_id)) => {
+            return handle_collection(
+                bot,
+                msg,
+                state,
+                CollectionKind::Album,
+                album_id,
+                TrackSelection::default(),
+                None,
+            )
+            .await;
+        }
+        Some(StartPayload::Playlist(playlist_id)) => {
+            return handle_collection(
+                bot,
+                msg,
+                state,
+                CollectionKind::Playlist,
+                playlist_id,
+                TrackSelection::default(),
+                None,
+            )
+            .await;
+        }
+        Some(StartPayload::Song(_)) | None => {}
+    }
+
+    if let Some(StartPayload::Song(music_id)) = payload {
         // Check if we already have this in database
-        if let Ok(Some(song_info)) = state.database.get_song_by_music_id(music_id as i64).await
-            && let Some(file_id) = song_info.file_id
+        if let Ok(Some(song_info)) = lookup_song(state, music_id as i64).await
+            && let Some(file_id) = song_info.file_id.clone()
         {
-            let caption = build_caption(
-                &song_info.song_name,
-                &song_info.song_artists,
-                &song_info.song_album,
-                &song_info.file_ext,
-                song_info.music_size,
-                song_info.bit_rate,
-                &state.bot_username,
-            );
+            let caption = chat_caption(state, msg, &song_info, song_info.bit_rate).await;
             let keyboard = create_music_keyboard(
                 song_info.music_id as u64,
                 &song_info.song_name,
@@ -346,7 +748,8 @@ async fn handle_start_command(
                 .send_audio(msg.chat.id, InputFile::file_id(FileId(file_id)))
                 .caption(caption)
                 .reply_markup(ReplyMarkup::InlineKeyboard(keyboard))
-                .reply_parameters(ReplyParameters::new(msg.id));
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .disable_notification(is_silent_chat(state, msg.chat.id).await);
 
             if let Some(thumb_id) = song_info.thumb_file_id {
                 send_audio = send_audio.thumbnail(InputFile::file_id(FileId(thumb_id)));
@@ -362,10 +765,7 @@ async fn handle_start_command(
                             music_id,
                             e
                         );
-                        let _ = state
-                            .database
-                            .delete_song_by_music_id(music_id as i64)
-                            .await;
+                        let _ = forget_song(state, music_id as i64).await;
                     } else {
                         return Err(e);
                     }
@@ -409,22 +809,21 @@ async fn handle_help_command(
     msg: &Message,
     state: &Arc<BotState>,
 ) -> ResponseResult<()> {
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+    let is_admin = state.config.bot_admin.contains(&user_id);
+
     let help_text = format!(
         "📖 <b>使用帮助</b>\n\n\
-        1️⃣ <b>直接解析</b>\n\
+        🔗 <b>直接解析</b>\n\
         发送网易云音乐链接给机器人，例如：\n\
         <code>https://music.163.com/song?id=12345</code>\n\n\
-        2️⃣ <b>搜索音乐</b>\n\
-        使用 <code>/search &lt;关键词&gt;</code> 在私聊中搜索。\n\n\
-        3️⃣ <b>Inline 搜索</b>\n\
+        🔍 <b>Inline 搜索</b>\n\
         在任何对话框输入 <code>@{} &lt;关键词&gt;</code> 即可快速搜索并分享音乐。\n\n\
-        4️⃣ <b>获取歌词</b>\n\
-        使用 <code>/lyric &lt;关键词或ID&gt;</code> 获取歌词。\n\n\
-        5️⃣ <b>更多命令</b>\n\
-        • <code>/status</code> - 查看系统状态\n\
-        • <code>/about</code> - 关于机器人\n\n\
+        📋 <b>命令列表</b>\n\
+        {}\n\n\
         💬 <b>项目主页：</b> <a href=\"https://github.com/Lemonawa/music163bot-rust\">GitHub</a>",
-        state.bot_username
+        state.bot_username,
+        commands::help_command_list(is_admin)
     );
 
     bot.send_message(msg.chat.id, help_text)
@@ -442,7 +841,20 @@ async fn handle_music_command(
     state: &Arc<BotState>,
     args: Option<String>,
 ) -> ResponseResult<()> {
-    let args = args.unwrap_or_default();
+    let (args, cover_flag) = split_cover_flag(&args.unwrap_or_default());
+    let cover = match cover_flag.map(|mode| mode.parse::<CoverMode>()) {
+        None => None,
+        Some(Ok(mode)) => Some(mode),
+        Some(Err(_)) => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ 无效的封面模式，可选: original|thumbnail|both|none",
+            )
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+            return Ok(());
+        }
+    };
 
     if args.is_empty() {
         bot.send_message(msg.chat.id, "请输入歌曲ID或歌曲关键词")
@@ -453,14 +865,22 @@ async fn handle_music_command(
 
     // Try to parse as music ID first
     if let Some(music_id) = parse_music_id(&args) {
-        return process_music(bot, msg, state, music_id).await;
+        let overrides = DownloadOverrides {
+            cover,
+            ..DownloadOverrides::default()
+        };
+        return process_music_into(bot, msg, state, music_id, None, overrides).await;
     }
 
     // If not a number, search for the song
     match state.music_api.search_songs(&args, 1).await {
         Ok(songs) => {
             if let Some(song) = songs.first() {
-                process_music(bot, msg, state, song.id).await
+                let overrides = DownloadOverrides {
+                    cover,
+                    ..DownloadOverrides::default()
+                };
+                process_music_into(bot, msg, state, song.id, None, overrides).await
             } else {
                 bot.send_message(msg.chat.id, "未找到相关歌曲")
                     .reply_parameters(ReplyParameters::new(msg.id))
@@ -482,15 +902,97 @@ async fn process_music(
     msg: &Message,
     state: &Arc<BotState>,
     music_id: u64,
+) -> ResponseResult<()> {
+    process_music_into(
+        bot,
+        msg,
+        state,
+        music_id,
+        None,
+        DownloadOverrides::default(),
+    )
+    .await
+}
+
+/// Per-request changes to how a song is fetched
+#[derive(Debug, Clone, Copy, Default)]
+struct DownloadOverrides<'a> {
+    /// Cover handling instead of the chat/config default
+    cover: Option<CoverMode>,
+    /// Fetch at most this bitrate (from `/info`); such downloads are not cached
+    /// so they never replace the best-quality copy
+    bitrate: Option<u64>,
+    /// Download even when cached, replacing the cached copy (`/refresh`)
+    refresh: bool,
+    /// Who asked when `msg` is the bot's own message (button presses); quota,
+    /// premium, stats and scrobbling go to them instead of `msg.from`
+    requester: Option<&'a User>,
+}
+
+/// Audio ready to go out as part of an album/playlist media group
+struct PreparedAudio {
+    media: InputMediaAudio,
+    /// Freshly downloaded track, saved once Telegram returns its file_id
+    song_info: Option<SongInfo>,
+    cache: CacheTarget,
+}
+
+/// Album/playlist tracks collected for the next `send_media_group` call
+struct MediaBatch {
+    /// Collection status message, reused for per-track progress and errors
+    status_msg: Message,
+    tracks: Vec<PreparedAudio>,
+}
+
+/// Fetch a song and send it, or queue it in `batch` when delivering a collection
+#[tracing::instrument(skip_all, fields(music_id = music_id, chat_id = msg.chat.id.0))]
+async fn process_music_into(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    music_id: u64,
+    mut batch: Option<&mut MediaBatch>,
+    mut overrides: DownloadOverrides<'_>,
 ) -> ResponseResult<()> {
     let music_id_i64 = music_id as i64;
+    let requester = overrides.requester.or(msg.from.as_ref());
+
+    // With `premium.lossless_only`, other users get (and are served from
+    // cache) at most the best lossy quality
+    let lossy_only = state.config.premium_lossless_only
+        && overrides
+            .bitrate
+            .is_none_or(|bitrate| bitrate > LOSSY_MAX_BITRATE)
+        && !is_premium_user(state, requester.map_or(0, |u| u.id.0 as i64)).await;
+    if lossy_only {
+        overrides.bitrate = Some(LOSSY_MAX_BITRATE);
+    }
 
-    // Check if song is cached
-    if let Ok(Some(cached_song)) = state.database.get_song_by_music_id(music_id_i64).await {
+    // Check if song is cached (and not above the requested bitrate)
+    if !overrides.refresh
+        && let Ok(Some(cached_song)) = lookup_song_for(state, music_id_i64, lossy_only).await
+        && overrides
+            .bitrate
+            .is_none_or(|bitrate| cached_song.bit_rate <= bitrate as i64)
+        && !(batch.is_none()
+            && overrides.bitrate.is_none()
+            && state.config.verify_audio_hash
+            && forget_if_audio_replaced(state, &cached_song).await)
+    {
         // Validate cached file: must have file_id AND valid size (>1KB)
         if let Some(file_id) = &cached_song.file_id {
-            if cached_song.music_size > 1024 {
-                // Must be larger than 1KB
+            if let Some(batch) = batch.as_deref_mut()
+                && cached_song.music_size > 1024
+            {
+                batch.tracks.push(PreparedAudio {
+                    media: InputMediaAudio::new(InputFile::file_id(FileId(file_id.clone()))),
+                    song_info: None,
+                    cache: CacheTarget::None,
+                });
+                return Ok(());
+            }
+            if cached_song.music_size > 1024 {
+                // Must be larger than 1KB
                 // bitrate fallback if missing
                 let bitrate = if cached_song.bit_rate > 0 {
                     cached_song.bit_rate
@@ -502,15 +1004,7 @@ async fn process_music(
                     }) as f64;
                     (8.0 * cached_song.music_size as f64 / dur) as i64
                 };
-                let caption = build_caption(
-                    &cached_song.song_name,
-                    &cached_song.song_artists,
-                    &cached_song.song_album,
-                    &cached_song.file_ext,
-                    cached_song.music_size,
-                    bitrate,
-                    &state.bot_username,
-                );
+                let caption = chat_caption(state, msg, &cached_song, bitrate).await;
 
                 let keyboard = create_music_keyboard(
                     music_id,
@@ -523,9 +1017,23 @@ async fn process_music(
                     .caption(caption)
                     .reply_markup(keyboard)
                     .reply_parameters(ReplyParameters::new(msg.id))
+                    .disable_notification(is_silent_chat(state, msg.chat.id).await)
                     .await
                 {
-                    Ok(_) => return Ok(()),
+                    Ok(_) => {
+                        log_delivery(state, msg.chat.id, requester, music_id);
+                        scrobble_delivery(
+                            state,
+                            requester,
+                            scrobble::Track {
+                                artist: cached_song.song_artists.clone(),
+                                title: cached_song.song_name.clone(),
+                                album: cached_song.song_album.clone(),
+                                duration_secs: cached_song.duration.max(0) as u64,
+                            },
+                        );
+                        return Ok(());
+                    }
                     Err(e) => {
                         let err_str = format!("{e}");
                         if err_str.contains("invalid remote file identifier") {
@@ -534,7 +1042,7 @@ async fn process_music(
                                 music_id,
                                 e
                             );
-                            let _ = state.database.delete_song_by_music_id(music_id_i64).await;
+                            let _ = forget_song(state, music_id_i64).await;
                             // Continue to download flow below
                         } else {
                             return Err(e);
@@ -548,725 +1056,906 @@ async fn process_music(
                 music_id,
                 cached_song.music_size
             );
-            let _ = state.database.delete_song_by_music_id(music_id_i64).await;
+            let _ = forget_song(state, music_id_i64).await;
         }
     }
 
-    // Send initial message
-    let status_msg = bot
-        .send_message(msg.chat.id, "🔄 正在获取歌曲信息...")
-        .reply_parameters(ReplyParameters::new(msg.id))
-        .await?;
+    // Enforce daily quota for new downloads (cache hits above are free)
+    let quota = match reserve_quota(state, msg.chat.id, requester).await {
+        Ok(reservation) => reservation,
+        Err(quota) => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "⛔ 今日下载额度已用完\n\n{}\n额度将于午夜重置，已缓存的歌曲仍可正常获取。",
+                    quota.describe()
+                ),
+            )
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+            return Ok(());
+        }
+    };
 
-    // Get song details
-    let song_detail = match state.music_api.get_song_detail(music_id).await {
-        Ok(detail) => detail,
-        Err(e) => {
+    // Send initial message (collections report progress on their own status message)
+    let locale = message_locale(state, msg).await;
+    let status_msg = match batch.as_deref() {
+        Some(batch) => batch.status_msg.clone(),
+        None => {
+            bot.send_message(msg.chat.id, Text::FetchingSong.get(locale))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?
+        }
+    };
+    // Left registered only if this job panics; collections track their own
+    let _status = batch
+        .is_none()
+        .then(|| state.status_tracker.track(msg.chat.id.0, status_msg.id.0));
+
+    // Everything after the status message shares one deadline, so a stalled
+    // API call, CDN or upload cannot leave the job hanging forever; waiting
+    // for a download permit or another instance's lock does not count
+    let files = JobFiles::default();
+    let queue = QueueTime::default();
+    let work = async {
+        // Get song details
+        let song_detail = match state.music_api.get_song_detail(music_id).await {
+            Ok(detail) => detail,
+            Err(e) => {
+                record_pipeline_failure(bot, state, FailureCause::SongDetail, &e.to_string()).await;
+                bot.edit_message_text(
+                    msg.chat.id,
+                    status_msg.id,
+                    format!("{}: {e}", Text::FetchSongFailed.get(locale)),
+                )
+                .reply_markup(report_keyboard(music_id))
+                .await?;
+                return Ok(());
+            }
+        };
+
+        // Get download URL - try FLAC first if MUSIC_U is available, then fall back to MP3
+        let song_url = match overrides.bitrate {
+            Some(bitrate) => state.music_api.get_song_url(music_id, bitrate).await,
+            None => state.music_api.get_best_song_url(music_id).await,
+        };
+
+        // Paid songs may only have a trial clip; it is sent (uncached) as a labeled
+        // preview when enabled, and otherwise never mistaken for the full song
+        let full_available = song_url
+            .as_ref()
+            .is_ok_and(|url| !url.url.is_empty() && url.free_trial_info.is_none());
+        let preview_url = if !full_available && state.config.preview_fallback && batch.is_none() {
+            match state.music_api.get_preview_url(music_id).await {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    tracing::info!("No preview available for music_id {}: {}", music_id, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let preview = preview_url.is_some();
+        let song_url = match (preview_url, song_url) {
+            (Some(url), _) => Ok(url),
+            (None, Ok(url)) if url.free_trial_info.is_some() => Ok(crate::music_api::SongUrl {
+                url: String::new(),
+                ..url
+            }),
+            (None, song_url) => song_url,
+        };
+        let song_url = match song_url {
+            Ok(url) => url,
+            Err(e) => {
+                record_pipeline_failure(bot, state, FailureCause::DownloadUrl, &e.to_string())
+                    .await;
+                bot.edit_message_text(
+                    msg.chat.id,
+                    status_msg.id,
+                    format!(
+                        "❌ 获取下载链接失败: {e}\n{}",
+                        unavailable_song_reason(state, music_id).await
+                    ),
+                )
+                .reply_markup(report_keyboard(music_id))
+                .await?;
+                return Ok(());
+            }
+        };
+
+        if song_url.url.is_empty() {
+            record_pipeline_failure(
+                bot,
+                state,
+                FailureCause::VipRequired,
+                &format!("music_id {music_id} 返回空下载链接"),
+            )
+            .await;
             bot.edit_message_text(
                 msg.chat.id,
                 status_msg.id,
-                format!("❌ 获取歌曲信息失败: {e}"),
+                format!("❌ {}", unavailable_song_reason(state, music_id).await),
             )
+            .reply_markup(report_keyboard(music_id))
             .await?;
             return Ok(());
         }
-    };
 
-    // Get download URL - try FLAC first if MUSIC_U is available, then fall back to MP3
-    let song_url = if state.music_api.music_u.is_some() {
-        // Try FLAC quality first for VIP users
-        match state.music_api.get_song_url(music_id, 999_000).await {
-            Ok(url) if !url.url.is_empty() => {
-                tracing::info!("Using FLAC quality for music_id {}", music_id);
-                url
-            }
-            _ => {
-                // Fallback to high quality MP3
-                tracing::info!(
-                    "FLAC not available, falling back to MP3 for music_id {}",
-                    music_id
-                );
-                match state.music_api.get_song_url(music_id, 320_000).await {
-                    Ok(url) => url,
-                    Err(e) => {
-                        bot.edit_message_text(
-                            msg.chat.id,
-                            status_msg.id,
-                            format!("❌ 获取下载链接失败: {e}"),
-                        )
-                        .await?;
-                        return Ok(());
+        // Update status
+        let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+        let status_text = match quota.remaining() {
+            Some(remaining) => format!(
+                "{}: {} - {}\n{}: {}",
+                Text::Downloading.get(locale),
+                song_detail.name,
+                artists,
+                Text::RemainingQuota.get(locale),
+                remaining.saturating_sub(1)
+            ),
+            None => format!(
+                "{}: {} - {}",
+                Text::Downloading.get(locale),
+                song_detail.name,
+                artists
+            ),
+        };
+        bot.edit_message_text(msg.chat.id, status_msg.id, status_text)
+            .await?;
+
+        // Another instance sharing Redis may be fetching the same song already
+        let cache = CacheTarget::for_request(preview, overrides.bitrate, lossy_only);
+        let lock_token = if cache == CacheTarget::Song {
+            match queue.wait(acquire_download_lock(state, music_id_i64)).await {
+                // The copy the other instance saved is the one being replaced
+                DownloadLock::CachedElsewhere if overrides.refresh => None,
+                DownloadLock::CachedElsewhere => {
+                    if batch.is_none() {
+                        bot.delete_message(msg.chat.id, status_msg.id).await.ok();
                     }
+                    return Box::pin(process_music_into(
+                        bot, msg, state, music_id, batch, overrides,
+                    ))
+                    .await;
                 }
+                DownloadLock::Held(token) => Some(token),
+                DownloadLock::Unlocked => None,
             }
+        } else {
+            None
+        };
+
+        // Download and process the song
+        let queued = batch.is_some();
+        let tracks = batch.map(|batch| &mut batch.tracks);
+        // Boxed: the download pipeline future is large and would bloat every command handler
+        let cover_mode = match overrides.cover {
+            Some(mode) => mode,
+            None => chat_cover_mode(state, msg.chat.id).await,
+        };
+        let job = DownloadJob {
+            song_detail: &song_detail,
+            song_url: &song_url,
+            cover_mode,
+            cache,
+            preview,
+            files: &files,
+            queue: &queue,
+            requester,
+        };
+        let result = Box::pin(download_and_send_music(bot, msg, state, job, tracks)).await;
+        if let (Some(token), Some(hot_cache)) = (lock_token, &state.hot_cache)
+            && let Err(e) = hot_cache.unlock(music_id_i64, &token).await
+        {
+            tracing::warn!("Failed to release download lock of {}: {}", music_id, e);
         }
-    } else {
-        // Get best available MP3 quality
-        match state.music_api.get_song_url(music_id, 320_000).await {
-            Ok(url) => url,
-            Err(_) => {
-                // Try lower quality as fallback
-                match state.music_api.get_song_url(music_id, 128_000).await {
-                    Ok(url) => url,
-                    Err(e) => {
-                        bot.edit_message_text(
-                            msg.chat.id,
-                            status_msg.id,
-                            format!("❌ 获取下载链接失败: {e}"),
-                        )
+        match result {
+            Ok(()) => {
+                state.failure_tracker.record_success();
+                quota.keep();
+                // Delete status message
+                if !queued {
+                    bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+                    log_delivery(state, msg.chat.id, requester, music_id);
+                    scrobble_delivery(
+                        state,
+                        requester,
+                        scrobble::Track {
+                            artist: artists,
+                            title: song_detail.name.clone(),
+                            album: song_detail
+                                .al
+                                .as_ref()
+                                .map(|al| al.name.clone())
+                                .unwrap_or_default(),
+                            duration_secs: song_detail.dt.unwrap_or(0) / 1000,
+                        },
+                    );
+                }
+            }
+            Err(e) => {
+                // Not a failure of the bot: tell the user how to get a smaller file
+                if let Some((size, limit)) = e.file_too_large() {
+                    tracing::warn!(
+                        music_id,
+                        bytes = size,
+                        limit,
+                        "music_id {} is too large for Telegram",
+                        music_id
+                    );
+                    let (text, keyboard) =
+                        file_too_large_reply(locale, music_id, &song_url, size, limit);
+                    bot.edit_message_text(msg.chat.id, status_msg.id, text)
+                        .reply_markup(keyboard)
                         .await?;
-                        return Ok(());
-                    }
+                    return Ok(());
                 }
+                let e = e.with_music_id(music_id).with_chat_id(msg.chat.id.0);
+                let cause = if matches!(e.root(), BotError::Telegram(_)) {
+                    FailureCause::Upload
+                } else {
+                    FailureCause::Download
+                };
+                record_pipeline_failure(bot, state, cause, &e.to_string()).await;
+                let text = format!("{}: {}", Text::ProcessingFailed.get(locale), e.root());
+                bot.edit_message_text(msg.chat.id, status_msg.id, text)
+                    .reply_markup(report_keyboard(music_id))
+                    .await?;
             }
         }
+        ResponseResult::Ok(())
     };
-
-    if song_url.url.is_empty() {
-        bot.edit_message_text(
-            msg.chat.id,
-            status_msg.id,
-            "❌ 无法获取下载链接，可能需要VIP权限",
-        )
-        .await?;
-        return Ok(());
+    if state.config.job_timeout_secs == 0 {
+        return work.await;
+    }
+    let budget = std::time::Duration::from_secs(state.config.job_timeout_secs);
+    if let Some(result) = pipeline::run_with_budget(budget, &queue, work).await {
+        return result;
     }
 
-    // Update status
-    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
-    bot.edit_message_text(
-        msg.chat.id,
-        status_msg.id,
-        format!("📥 正在下载: {} - {}", song_detail.name, artists),
+    // The job future is dropped by now; the download lock expires on its own
+    files.remove_all().await;
+    record_pipeline_failure(
+        bot,
+        state,
+        FailureCause::Timeout,
+        &format!("music_id {music_id} 超过 {}s", budget.as_secs()),
     )
-    .await?;
+    .await;
+    bot.edit_message_text(msg.chat.id, status_msg.id, Text::JobTimedOut.get(locale))
+        .reply_markup(report_keyboard(music_id))
+        .await?;
 
-    // Download and process the song
-    match download_and_send_music(bot, msg, state, &song_detail, &song_url, &status_msg).await {
-        Ok(()) => {
-            // Delete status message
-            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+    Ok(())
+}
+
+/// Result of taking the cross-instance download lock of a song
+enum DownloadLock {
+    /// Taken; release with the token once the upload is saved
+    Held(String),
+    /// No Redis, Redis errors or waited too long: download without a lock
+    Unlocked,
+    /// Another instance finished the song while we waited
+    CachedElsewhere,
+}
+
+/// Take the Redis download lock of `music_id`, waiting (up to
+/// `cache.download_lock_secs`) while another instance holds it
+async fn acquire_download_lock(state: &BotState, music_id: i64) -> DownloadLock {
+    let Some(hot_cache) = &state.hot_cache else {
+        return DownloadLock::Unlocked;
+    };
+    let lock_secs = state.config.download_lock_secs;
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(lock_secs);
+    let mut logged = false;
+    loop {
+        match hot_cache.try_lock(music_id, lock_secs).await {
+            Ok(Some(token)) => return DownloadLock::Held(token),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("Download lock unavailable for {}: {}", music_id, e);
+                return DownloadLock::Unlocked;
+            }
+        }
+        if !logged {
+            tracing::info!(
+                "music_id {} is being downloaded by another instance, waiting",
+                music_id
+            );
+            logged = true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return DownloadLock::Unlocked;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        if let Ok(Some(song)) = lookup_song(state, music_id).await
+            && song.file_id.is_some()
+        {
+            return DownloadLock::CachedElsewhere;
         }
+    }
+}
+
+/// Why a song has no download URL, from its fee and privilege fields
+async fn unavailable_song_reason(state: &BotState, music_id: u64) -> String {
+    match state.music_api.get_song_overview(music_id).await {
+        Ok((song, privilege)) => unavailable_reason(
+            song.fee,
+            privilege.as_ref(),
+            state.music_api.music_u.is_some(),
+        ),
         Err(e) => {
-            bot.edit_message_text(msg.chat.id, status_msg.id, format!("❌ 处理失败: {e}"))
-                .await?;
+            tracing::warn!("Failed to fetch privilege of {}: {}", music_id, e);
+            "无法获取下载链接，可能需要VIP权限".to_string()
         }
     }
+}
 
-    Ok(())
+/// Song to download, with the cover handling chosen for this request
+struct DownloadJob<'a> {
+    song_detail: &'a crate::music_api::SongDetail,
+    song_url: &'a crate::music_api::SongUrl,
+    cover_mode: CoverMode,
+    /// Where the upload is cached
+    cache: CacheTarget,
+    /// Trial clip of a paid song (`download.preview_fallback`)
+    preview: bool,
+    /// Cleaned up when the job runs out of time
+    files: &'a JobFiles,
+    /// Waiting for a download permit is not part of the job's time limit
+    queue: &'a QueueTime,
+    /// Recorded as the song's uploader
+    requester: Option<&'a User>,
 }
 
+#[tracing::instrument(skip_all, fields(music_id = job.song_detail.id, chat_id = msg.chat.id.0))]
 async fn download_and_send_music(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
-    song_detail: &crate::music_api::SongDetail,
-    song_url: &crate::music_api::SongUrl,
-    status_msg: &Message,
+    job: DownloadJob<'_>,
+    batch: Option<&mut Vec<PreparedAudio>>,
 ) -> Result<()> {
-    let _permit = state.download_semaphore.acquire().await.unwrap();
+    let DownloadJob {
+        song_detail,
+        song_url,
+        cover_mode,
+        cache,
+        preview,
+        files,
+        queue,
+        requester,
+    } = job;
+    let lane = Lane::for_request(state.config.download_priority_lane, batch.is_some());
+    let _permit = queue
+        .wait(download_lanes::acquire(&state.download_semaphore, lane))
+        .await;
+    state.activity.touch();
+    ensure_dir(&state.config.cache_dir)?;
 
-    // Determine file extension
-    let file_ext = if song_url.url.contains(".flac") {
-        "flac"
-    } else {
-        "mp3"
+    let music_id = song_detail.id;
+    let source = pipeline::Source {
+        song_detail,
+        song_url,
+        preview,
     };
+    let prepared =
+        pipeline::prepare(state, &source, CoverPolicy::for_mode(cover_mode), files).await?;
+    let pipeline::Prepared {
+        tagged,
+        thumbnail,
+        md5,
+    } = prepared;
+
+    let mut song_info = pipeline::describe(&source, &tagged, md5);
+    song_info.from_user_id = requester.map_or(0, |u| u.id.0 as i64);
+    song_info.from_user_name = requester
+        .and_then(|u| u.username.clone())
+        .unwrap_or_default();
+    song_info.from_chat_id = msg.chat.id.0;
+    song_info.from_chat_name = msg.chat.username().unwrap_or("").to_string();
+
+    let mut caption = chat_caption(state, msg, &song_info, song_info.bit_rate).await;
+    if preview {
+        let locale = message_locale(state, msg).await;
+        caption.insert_str(0, &format!("{}\n\n", Text::PreviewNotice.get(locale)));
+        song_info.song_name = format!(
+            "{} ({})",
+            song_info.song_name,
+            Text::PreviewTitle.get(locale)
+        );
+    }
 
-    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
-    let filename = clean_filename(&format!(
-        "{} - {}.{}",
-        artists.replace('/', ","),
-        song_detail.name,
-        file_ext
-    ));
+    // Collection tracks are uploaded together by `send_media_batch`
+    if let Some(batch) = batch {
+        let mut media = InputMediaAudio::new(tagged.audio.into_input_file())
+            .title(&song_info.song_name)
+            .performer(&song_info.song_artists);
+        media.duration = u16::try_from(song_info.duration).ok();
+        if let Some(thumbnail) = thumbnail {
+            media = media.thumbnail(thumbnail.into_input_file());
+        }
+        batch.push(PreparedAudio {
+            media,
+            song_info: Some(song_info),
+            cache,
+        });
+        return Ok(());
+    }
 
-    // Ensure cache directory exists
-    ensure_dir(&state.config.cache_dir)?;
+    let upload_bot = upload_bot(state).await;
+    let message = pipeline::AudioMessage {
+        chat_id: msg.chat.id,
+        reply_to: Some(msg.id),
+        caption,
+        keyboard: Some(create_music_keyboard(
+            music_id,
+            &song_info.song_name,
+            &song_info.song_artists,
+        )),
+        title: song_info.song_name.clone(),
+        performer: song_info.song_artists.clone(),
+        duration_secs: song_info.duration as u32,
+        silent: is_silent_chat(state, msg.chat.id).await,
+    };
+    let uploaded = pipeline::timed(
+        &state.config,
+        Stage::Upload,
+        music_id,
+        pipeline::upload(state, &upload_bot, tagged, thumbnail, message),
+    )
+    .await?;
+    song_info.file_id = uploaded.file_id;
 
-    let cover_mode = state.config.cover_mode;
-    let cover_policy = resolve_cover_policy(cover_mode);
-    let download_original = cover_policy.download_original;
-    let download_thumbnail = cover_policy.download_thumbnail;
-
-    // Start parallel downloads: audio file and album art
-    let artwork_future = async {
-        if let Some(ref al) = song_detail.al {
-            tracing::debug!("Album info found: id={}, name={}", al.id, al.name);
-            if let Some(ref pic_url) = al.pic_url {
-                if pic_url.is_empty() {
-                    tracing::warn!("Album art URL is empty for music_id {}", song_detail.id);
-                    (None, None)
-                } else {
-                    tracing::info!(
-                        "Starting album art download for music_id {} (mode: {:?}), pic_url: {}",
-                        song_detail.id,
-                        cover_mode,
-                        pic_url
-                    );
+    if cache != CacheTarget::None {
+        pipeline::timed(
+            &state.config,
+            Stage::Persist,
+            music_id,
+            pipeline::cache_upload(state, bot, &song_info, &uploaded.message, cache),
+        )
+        .await?;
+    }
+    run_request_maintenance(state).await;
+    Ok(())
+}
 
-                    if download_original && download_thumbnail {
-                        // Download both versions in parallel: original (for embedding) and resized (for Telegram thumbnail)
-                        let original_future =
-                            state.music_api.download_album_art_original(pic_url);
-                        let thumbnail_future = state.music_api.download_album_art_data(pic_url);
-
-                        let (original_result, thumbnail_result) =
-                            tokio::join!(original_future, thumbnail_future);
-
-                        // Process original high-res image for embedding
-                        let original_data = match original_result {
-                            Ok(data) => {
-                                tracing::info!(
-                                    "Downloaded original album art for music_id {} ({} bytes)",
-                                    song_detail.id,
-                                    data.len()
-                                );
-                                Some(data)
-                            }
-                            Err(e) => {
-                                tracing::warn!(
-                                    "Failed to download original album art for music_id {}: {}",
-                                    song_detail.id,
-                                    e
-                                );
-                                None
-                            }
-                        };
-
-                        // Process 320x320 thumbnail for Telegram display
-                        let thumbnail_buffer = match thumbnail_result {
-                            Ok(data) => {
-                                tracing::info!(
-                                    "Downloaded thumbnail for music_id {} ({} bytes)",
-                                    song_detail.id,
-                                    data.len()
-                                );
-                                let thumb_filename = format!(
-                                    "thumb_{}_{}.jpg",
-                                    song_detail.id,
-                                    chrono::Utc::now().timestamp()
-                                );
-                                ThumbnailBuffer::new(
-                                    &state.config,
-                                    data,
-                                    &state.config.cache_dir,
-                                    &thumb_filename,
-                                )
-                                .await
-                                .ok()
-                            }
-                            Err(e) => {
-                                tracing::warn!(
-                                    "Failed to download thumbnail for music_id {}: {}",
-                                    song_detail.id,
-                                    e
-                                );
-                                None
-                            }
-                        };
-
-                        (original_data, thumbnail_buffer)
-                    } else {
-                        let original_data = if download_original {
-                            match state.music_api.download_album_art_original(pic_url).await {
-                                Ok(data) => {
-                                    tracing::info!(
-                                        "Downloaded original album art for music_id {} ({} bytes)",
-                                        song_detail.id,
-                                        data.len()
-                                    );
-                                    Some(data)
-                                }
-                                Err(e) => {
-                                    tracing::warn!(
-                                        "Failed to download original album art for music_id {}: {}",
-                                        song_detail.id,
-                                        e
-                                    );
-                                    None
-                                }
-                            }
-                        } else {
-                            None
-                        };
-
-                        let thumbnail_buffer = if download_thumbnail {
-                            match state.music_api.download_album_art_data(pic_url).await {
-                                Ok(data) => {
-                                    tracing::info!(
-                                        "Downloaded thumbnail for music_id {} ({} bytes)",
-                                        song_detail.id,
-                                        data.len()
-                                    );
-                                    let thumb_filename = format!(
-                                        "thumb_{}_{}.jpg",
-                                        song_detail.id,
-                                        chrono::Utc::now().timestamp()
-                                    );
-                                    ThumbnailBuffer::new(
-                                        &state.config,
-                                        data,
-                                        &state.config.cache_dir,
-                                        &thumb_filename,
-                                    )
-                                    .await
-                                    .ok()
-                                }
-                                Err(e) => {
-                                    tracing::warn!(
-                                        "Failed to download thumbnail for music_id {}: {}",
-                                        song_detail.id,
-                                        e
-                                    );
-                                    None
-                                }
-                            }
-                        } else {
-                            None
-                        };
+/// Periodic database housekeeping, every few downloads
+async fn run_request_maintenance(state: &BotState) {
+    let analyze_interval = state.config.db_analyze_interval_requests;
+    if MaintenanceCounters::should_run(
+        &state.maintenance_counters.db_analyze_requests,
+        analyze_interval,
+    ) {
+        state.database.analyze().await.ok(); // Non-critical, ignore errors
+        state.database.purge_usage_before(&usage_day()).await.ok();
+        state
+            .database
+            .purge_send_log(SEND_LOG_RETENTION_DAYS)
+            .await
+            .ok();
+    }
+}
 
-                        (original_data, thumbnail_buffer)
-                    }
-                }
-            } else {
-                tracing::warn!("No pic_url found in album for music_id {}", song_detail.id);
-                (None, None)
-            }
+/// Dedicated upload bot with an HTTP client tuned for large multipart uploads
+///
+/// The client is reused for a bounded number of requests.
+pub(crate) async fn upload_bot(state: &BotState) -> Bot {
+    let mut upload_state = state.upload_client_state.lock().await;
+    if upload_state.bot.is_none()
+        || upload_state.reuse_count >= state.config.upload_client_reuse_requests
+    {
+        // API URL must match teloxide's internal format: base URL without "/bot" suffix
+        // teloxide automatically appends "bot<TOKEN>/" to the path
+        let api_url_str = if !state.config.bot_api.is_empty()
+            && state.config.bot_api != "https://api.telegram.org"
+        {
+            // Custom API: strip "/bot" suffix if present to match teloxide's expected format
+            let base = state.config.bot_api.trim_end_matches("/bot");
+            format!("{base}/")
         } else {
-            tracing::warn!("No album info found for music_id {}", song_detail.id);
-            (None, None)
-        }
-    };
+            // Default API: use base URL without "/bot" (matches Bot::new() behavior)
+            "https://api.telegram.org/".to_string()
+        };
 
-    // Download audio file using smart storage
-    let audio_future = async {
-        let download_start = std::time::Instant::now();
-        let response = state.music_api.download_file(&song_url.url).await?;
+        let api_url = reqwest::Url::parse(&api_url_str)
+            .unwrap_or_else(|_| reqwest::Url::parse("https://api.telegram.org/").unwrap());
 
-        // Check response status
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("HTTP {}", response.status()));
+        if api_url_str != "https://api.telegram.org/" {
+            tracing::info!("Using custom API for upload: {}", api_url);
         }
 
-        // Check content length
-        let content_length = response.content_length().unwrap_or(0);
-        if content_length == 0 {
-            return Err(anyhow::anyhow!("Empty file or unable to get file size"));
-        }
+        // The upload profile is optimized for multipart uploads of large files
+        let client = http_client::builder(&state.config, Profile::Upload)
+            .default_headers(reqwest::header::HeaderMap::new())
+            .build()
+            .unwrap();
 
-        // Create audio buffer based on storage mode configuration
-        let mut audio_buffer = AudioBuffer::new(
-            &state.config,
-            content_length,
-            filename.clone(),
-            file_ext,
-            &state.config.cache_dir,
-        )
-        .await?;
+        upload_state.bot =
+            Some(Bot::with_client(&state.config.bot_token, client).set_api_url(api_url));
+        upload_state.reuse_count = 0;
+    }
 
-        let mut stream = response.bytes_stream();
-        let mut downloaded = 0u64;
-        let chunk_size = state.config.download_chunk_size_kb * 1024;
-        let mut buffer = Vec::with_capacity(chunk_size);
+    upload_state.reuse_count = upload_state.reuse_count.saturating_add(1);
+    upload_state.bot.clone().unwrap()
+}
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            downloaded += chunk.len() as u64;
+const USAGE_SCOPE_CHAT: &str = "chat";
+const USAGE_SCOPE_USER: &str = "user";
+/// Reports filed per user (`limits.daily_reports`)
+const USAGE_SCOPE_REPORT: &str = "report";
 
-            if buffer.len() + chunk.len() > chunk_size {
-                if !buffer.is_empty() {
-                    audio_buffer.write_chunk(&buffer).await?;
-                    buffer.clear();
-                }
-                if chunk.len() >= chunk_size {
-                    audio_buffer.write_chunk(&chunk).await?;
-                } else {
-                    buffer.extend_from_slice(&chunk);
-                }
-            } else {
-                buffer.extend_from_slice(&chunk);
-            }
-        }
-        if !buffer.is_empty() {
-            audio_buffer.write_chunk(&buffer).await?;
-        }
-        audio_buffer.finish().await?;
-        let download_duration = download_start.elapsed();
-        let download_mbps = throughput_mbps(downloaded, download_duration);
-        tracing::info!(
-            "Audio download completed in {:.2}s ({:.2} MB/s)",
-            download_duration.as_secs_f64(),
-            download_mbps
-        );
+/// Remaining daily download quota for a chat and user (`None` = unlimited)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QuotaStatus {
+    chat_remaining: Option<u32>,
+    user_remaining: Option<u32>,
+}
 
-        Ok::<(AudioBuffer, u64), anyhow::Error>((audio_buffer, downloaded))
+impl QuotaStatus {
+    const UNLIMITED: Self = Self {
+        chat_remaining: None,
+        user_remaining: None,
     };
 
-    // Execute both downloads in parallel
-    let (downloaded_result, (original_artwork_data, thumbnail_buffer)) =
-        tokio::join!(audio_future, artwork_future);
-    let (mut audio_buffer, downloaded) = downloaded_result?;
+    fn is_exhausted(&self) -> bool {
+        self.chat_remaining == Some(0) || self.user_remaining == Some(0)
+    }
 
-    tracing::info!(
-        "Audio download completed: {} bytes (mode: {})",
-        downloaded,
-        if audio_buffer.is_memory() {
-            "memory"
-        } else {
-            "disk"
+    /// The tighter of the two remaining quotas
+    fn remaining(&self) -> Option<u32> {
+        match (self.chat_remaining, self.user_remaining) {
+            (Some(chat), Some(user)) => Some(chat.min(user)),
+            (chat, user) => chat.or(user),
         }
-    );
-    let original_status = if download_original {
-        if original_artwork_data.is_some() {
-            "Available"
+    }
+
+    fn describe(&self) -> String {
+        let format_remaining = |remaining: Option<u32>| {
+            remaining.map_or_else(|| "不限".to_string(), |r| r.to_string())
+        };
+        format!(
+            "本对话剩余: {}\n当前用户剩余: {}",
+            format_remaining(self.chat_remaining),
+            format_remaining(self.user_remaining)
+        )
+    }
+}
+
+/// Day key for usage counters (resets at local midnight)
+fn usage_day() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn remaining_quota(limit: u32, used: i64) -> Option<u32> {
+    if limit == 0 {
+        return None;
+    }
+    Some(limit.saturating_sub(u32::try_from(used.max(0)).unwrap_or(u32::MAX)))
+}
+
+async fn get_quota_status(
+    state: &Arc<BotState>,
+    chat_id: ChatId,
+    requester: Option<&User>,
+) -> QuotaStatus {
+    let user_id = requester.map_or(0, |u| u.id.0 as i64);
+    let chat_limit = state.config.daily_limit_per_chat;
+    let user_limit = state.config.daily_limit_per_user;
+
+    // Admins are never limited
+    if (chat_limit == 0 && user_limit == 0) || state.config.bot_admin.contains(&user_id) {
+        return QuotaStatus::UNLIMITED;
+    }
+
+    let day = usage_day();
+    if is_premium_user(state, user_id).await {
+        let limit = state.config.premium_daily_limit;
+        let used = if limit > 0 {
+            state
+                .database
+                .get_usage(USAGE_SCOPE_USER, user_id, &day)
+                .await
+                .unwrap_or(0)
         } else {
-            "None"
-        }
+            0
+        };
+        return QuotaStatus {
+            chat_remaining: None,
+            user_remaining: remaining_quota(limit, used),
+        };
+    }
+
+    let chat_used = if chat_limit > 0 {
+        state
+            .database
+            .get_usage(USAGE_SCOPE_CHAT, chat_id.0, &day)
+            .await
+            .unwrap_or(0)
     } else {
-        "Skipped"
+        0
     };
-    let thumbnail_status = if download_thumbnail {
-        if thumbnail_buffer.is_some() {
-            "Available"
-        } else {
-            "None"
-        }
+    let user_used = if user_limit > 0 && user_id != 0 {
+        state
+            .database
+            .get_usage(USAGE_SCOPE_USER, user_id, &day)
+            .await
+            .unwrap_or(0)
     } else {
-        "Skipped"
+        0
     };
-    tracing::info!(
-        "Cover download result - Original: {}, Thumbnail: {}",
-        original_status,
-        thumbnail_status
-    );
 
-    // Validate file size (async to avoid blocking I/O)
-    let actual_size = audio_buffer.size().await;
+    QuotaStatus {
+        chat_remaining: remaining_quota(chat_limit, chat_used),
+        user_remaining: if user_id == 0 {
+            None
+        } else {
+            remaining_quota(user_limit, user_used)
+        },
+    }
+}
+
+/// Daily quota taken for a download, given back when it is dropped without
+/// `keep` (the download failed, timed out or was abandoned)
+struct QuotaReservation {
+    state: Arc<BotState>,
+    day: String,
+    /// Counters incremented for this download: (scope, subject)
+    counted: Vec<(&'static str, i64)>,
+    /// Quota left before this download
+    status: QuotaStatus,
+    kept: bool,
+}
 
-    if actual_size == 0 {
-        audio_buffer.cleanup().await.ok();
-        bot.edit_message_text(msg.chat.id, status_msg.id, "下载失败: 文件为空")
-            .await?;
-        return Ok(());
+impl QuotaReservation {
+    fn remaining(&self) -> Option<u32> {
+        self.status.remaining()
     }
 
-    if actual_size < 1024 {
-        audio_buffer.cleanup().await.ok();
-        bot.edit_message_text(
-            msg.chat.id,
-            status_msg.id,
-            format!("下载失败: 文件太小({actual_size} bytes)"),
-        )
-        .await?;
-        return Ok(());
+    /// The song was delivered: the quota stays used
+    fn keep(mut self) {
+        self.kept = true;
     }
+}
 
-    tracing::info!("File validation passed: {} bytes", actual_size);
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        if self.kept || self.counted.is_empty() {
+            return;
+        }
+        let state = self.state.clone();
+        let day = std::mem::take(&mut self.day);
+        let counted = std::mem::take(&mut self.counted);
+        tokio::spawn(async move {
+            for (scope, subject_id) in counted {
+                if let Err(e) = state
+                    .database
+                    .decrement_usage(scope, subject_id, &day)
+                    .await
+                {
+                    tracing::warn!("Failed to refund {} usage of {}: {}", scope, subject_id, e);
+                }
+            }
+        });
+    }
+}
 
-    // 封面处理：使用原始高分辨率图片嵌入文件，缩略图用于Telegram显示
-    tracing::info!("Processing tags for {} format", file_ext);
-    let embed_artwork = if cover_policy.embed_cover {
-        original_artwork_data.as_deref()
-    } else {
-        None
+/// Take one download from the chat's and the user's daily quota
+///
+/// Counters are incremented first and the new counts checked against the
+/// limits, so concurrent requests cannot all pass a check made before any of
+/// them is counted. Returns the exhausted quota when a limit is exceeded.
+async fn reserve_quota(
+    state: &Arc<BotState>,
+    chat_id: ChatId,
+    requester: Option<&User>,
+) -> std::result::Result<QuotaReservation, QuotaStatus> {
+    let mut reservation = QuotaReservation {
+        state: state.clone(),
+        day: usage_day(),
+        counted: Vec::new(),
+        status: QuotaStatus::UNLIMITED,
+        kept: false,
     };
+    let user_id = requester.map_or(0, |u| u.id.0 as i64);
+    let chat_limit = state.config.daily_limit_per_chat;
+    let user_limit = state.config.daily_limit_per_user;
+    // Admins are never limited
+    if (chat_limit == 0 && user_limit == 0) || state.config.bot_admin.contains(&user_id) {
+        return Ok(reservation);
+    }
 
-    // 根据文件格式嵌入封面（使用原始高分辨率图片）
-    match file_ext {
-        "mp3" => {
-            let cover_label = if cover_policy.embed_cover {
-                "original"
-            } else {
-                "none"
-            };
-            tracing::info!("Adding ID3 tags to MP3 (cover: {})", cover_label);
-            match audio_buffer.add_id3_tags(song_detail, embed_artwork) {
-                Ok(()) => tracing::info!("MP3 tags added successfully"),
-                Err(e) => tracing::warn!("Failed to add MP3 tags: {}", e),
-            }
-        }
-        "flac" => {
-            let cover_label = if cover_policy.embed_cover {
-                "original"
-            } else {
-                "none"
-            };
-            tracing::info!("Adding FLAC metadata (cover: {})", cover_label);
-            match audio_buffer.add_flac_metadata(song_detail, embed_artwork) {
-                Ok(()) => tracing::info!("FLAC metadata added successfully"),
-                Err(e) => tracing::warn!("Failed to add FLAC metadata: {}", e),
+    let mut subjects = vec![(USAGE_SCOPE_CHAT, chat_id.0)];
+    if user_id != 0 {
+        subjects.push((USAGE_SCOPE_USER, user_id));
+    }
+    let mut used = [0, 0];
+    for (slot, (scope, subject_id)) in subjects.into_iter().enumerate() {
+        match state
+            .database
+            .increment_usage(scope, subject_id, &reservation.day)
+            .await
+        {
+            Ok(count) => {
+                reservation.counted.push((scope, subject_id));
+                // Uses before this one
+                used[slot] = count - 1;
             }
-        }
-        _ => {
-            tracing::info!("Unknown format {}, skipping tag embedding", file_ext);
+            Err(e) => tracing::warn!("Failed to record {} usage of {}: {}", scope, subject_id, e),
         }
     }
 
-    // Get file size for database (async to avoid blocking)
-    let audio_file_size = audio_buffer.size().await as i64;
-    let duration_sec = (song_detail.dt.unwrap_or(0) / 1000) as i64;
-
-    // Calculate actual bitrate from file size and duration
-    // API's song_url.br is often theoretical (e.g., 1411kbps for FLAC) but
-    // actual file may be compressed (e.g., 960kbps). Use real calculated value.
-    let actual_bitrate_bps = if duration_sec > 0 {
-        (8 * audio_file_size) / duration_sec
+    let quota = if is_premium_user(state, user_id).await {
+        QuotaStatus {
+            chat_remaining: None,
+            user_remaining: remaining_quota(state.config.premium_daily_limit, used[1]),
+        }
     } else {
-        // Fallback to API value if duration is missing
-        song_url.br as i64
+        QuotaStatus {
+            chat_remaining: remaining_quota(chat_limit, used[0]),
+            user_remaining: if user_id == 0 {
+                None
+            } else {
+                remaining_quota(user_limit, used[1])
+            },
+        }
     };
+    if quota.is_exhausted() {
+        // Dropping the reservation gives the counts back
+        return Err(quota);
+    }
+    reservation.status = quota;
+    Ok(reservation)
+}
 
-    tracing::info!(
-        "Bitrate - API: {} bps, Calculated from file: {} bps (duration: {}s)",
-        song_url.br,
-        actual_bitrate_bps,
-        duration_sec
-    );
-
-    // Create song info for database
-    let mut song_info = SongInfo {
-        music_id: song_detail.id as i64,
-        song_name: song_detail.name.clone(),
-        song_artists: artists,
-        song_album: song_detail
-            .al
-            .as_ref()
-            .map_or_else(|| "Unknown Album".to_string(), |al| al.name.clone()),
-        file_ext: file_ext.to_string(),
-        music_size: audio_file_size,
-        pic_size: 0,
-        emb_pic_size: 0,
-        bit_rate: actual_bitrate_bps,
-        duration: duration_sec,
-        file_id: None,
-        thumb_file_id: None,
-        from_user_id: msg.from.as_ref().map_or(0, |u| u.id.0 as i64),
-        from_user_name: msg
-            .from
-            .as_ref()
-            .and_then(|u| u.username.clone())
-            .unwrap_or_default(),
-        from_chat_id: msg.chat.id.0,
-        from_chat_name: msg.chat.username().unwrap_or("").to_string(),
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-        ..Default::default()
-    };
-
-    // Log final thumbnail status
-    tracing::info!("Final thumbnail status: {}", thumbnail_status);
-
-    // Send the audio file
-    let caption = build_caption(
-        &song_info.song_name,
-        &song_info.song_artists,
-        &song_info.song_album,
-        &song_info.file_ext,
-        song_info.music_size,
-        song_info.bit_rate,
-        &state.bot_username,
-    );
+fn create_music_keyboard(music_id: u64, song_name: &str, artists: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![
+        vec![InlineKeyboardButton::url(
+            format!("{song_name} - {artists}"),
+            reqwest::Url::parse(&format!("https://music.163.com/song?id={music_id}")).unwrap(),
+        )],
+        vec![
+            InlineKeyboardButton::switch_inline_query(
+                "分享给朋友",
+                format!("https://music.163.com/song?id={music_id}"),
+            ),
+            InlineKeyboardButton::callback("🎧 相似歌曲", format!("simi {music_id}")),
+        ],
+    ])
+}
 
-    let keyboard = create_music_keyboard(
-        song_detail.id,
-        &song_info.song_name,
-        &song_info.song_artists,
-    );
+/// Similar songs listed by the "相似歌曲" button
+const SIMILAR_SONGS_SHOWN: usize = 5;
 
-    // Get file size for logging (async to avoid blocking)
-    let file_size = audio_buffer.size().await;
-    if file_size == 0 {
-        audio_buffer.cleanup().await.ok();
-        if let Some(thumb_buf) = thumbnail_buffer {
-            thumb_buf.cleanup().await.ok();
-        }
-        return Err(anyhow::anyhow!("Audio file is empty after processing").into());
-    }
+/// List songs similar to `music_id` under the audio message, with a download
+/// button for each
+async fn handle_simi_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    state: &Arc<BotState>,
+    music_id: u64,
+) -> ResponseResult<()> {
+    let Some(MaybeInaccessibleMessage::Regular(msg)) = &query.message else {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 消息已失效")
+            .await?;
+        return Ok(());
+    };
 
-    tracing::info!(
-        "Prepared audio: {} ({:.2} MB, mode: {})",
-        audio_buffer.filename(),
-        file_size as f64 / 1024.0 / 1024.0,
-        if audio_buffer.is_memory() {
-            "memory"
-        } else {
-            "disk"
+    let songs = match state.music_api.get_simi_songs(music_id).await {
+        Ok(songs) if !songs.is_empty() => songs,
+        Ok(_) => {
+            bot.answer_callback_query(query.id.clone())
+                .text("暂无相似歌曲")
+                .await?;
+            return Ok(());
         }
-    );
-
-    // Build a dedicated upload bot with optimized HTTP client for large file uploads.
-    // Reuse the upload client for a bounded number of requests.
-    let upload_bot = {
-        let mut upload_state = state.upload_client_state.lock().await;
-        if upload_state.bot.is_none()
-            || upload_state.reuse_count >= state.config.upload_client_reuse_requests
-        {
-            // API URL must match teloxide's internal format: base URL without "/bot" suffix
-            // teloxide automatically appends "bot<TOKEN>/" to the path
-            let api_url_str = if !state.config.bot_api.is_empty()
-                && state.config.bot_api != "https://api.telegram.org"
-            {
-                // Custom API: strip "/bot" suffix if present to match teloxide's expected format
-                let base = state.config.bot_api.trim_end_matches("/bot");
-                format!("{base}/")
-            } else {
-                // Default API: use base URL without "/bot" (matches Bot::new() behavior)
-                "https://api.telegram.org/".to_string()
-            };
-
-            let api_url = reqwest::Url::parse(&api_url_str)
-                .unwrap_or_else(|_| reqwest::Url::parse("https://api.telegram.org/").unwrap());
-
-            if api_url_str != "https://api.telegram.org/" {
-                tracing::info!("Using custom API for upload: {}", api_url);
-            }
-
-            // Create a client optimized for multipart uploads
-            // - longer timeout for large files
-            // - pool_max_idle_per_host(0) prevents stale connection issues after long uploads
-            // - no_gzip avoids gzip interference on multipart boundaries
-            let client = reqwest::Client::builder()
-                .use_rustls_tls()
-                .timeout(std::time::Duration::from_secs(state.config.upload_timeout_secs))
-                .pool_max_idle_per_host(0)
-                .no_gzip()
-                .user_agent("Go-http-client/2.0")
-                .default_headers(reqwest::header::HeaderMap::new())
-                .build()
-                .unwrap();
-
-            upload_state.bot = Some(Bot::with_client(&state.config.bot_token, client).set_api_url(api_url));
-            upload_state.reuse_count = 0;
+        Err(e) => {
+            bot.answer_callback_query(query.id.clone())
+                .text(format!("❌ 获取相似歌曲失败: {e}"))
+                .await?;
+            return Ok(());
         }
-
-        upload_state.reuse_count = upload_state.reuse_count.saturating_add(1);
-        upload_state.bot.clone().unwrap()
     };
 
-    // Send audio file with enhanced error handling and proper MIME type
-    tracing::info!(
-        "Sending audio file: {} ({:.2} MB)",
-        audio_buffer.filename(),
-        file_size as f64 / 1024.0 / 1024.0
-    );
-
-    // Simple approach: try sending as audio first, fallback to document if needed
-    let is_flac = file_ext == "flac";
-
-    tracing::info!("File format: {}", if is_flac { "FLAC" } else { "MP3" });
-
-    // Try sending as audio with basic metadata
-    // Use into_input_file to consume audio_buffer and avoid cloning memory
-    let in_flight = state.upload_counters.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
-    let peak_in_flight = update_peak(&state.upload_counters.peak_in_flight, in_flight);
-    let upload_start = std::time::Instant::now();
-    let mut audio_req = upload_bot
-        .send_audio(msg.chat.id, audio_buffer.into_input_file())
-        .caption(&caption)
-        .title(&song_info.song_name)
-        .performer(&song_info.song_artists)
-        .duration(song_info.duration as u32)
-        .reply_markup(keyboard.clone())
-        .reply_parameters(ReplyParameters::new(msg.id));
-
-    // Attach thumbnail if available
-    if let Some(thumb_buf) = thumbnail_buffer {
-        let thumb_input = thumb_buf.into_input_file();
-        audio_req = audio_req.thumbnail(thumb_input);
-    }
-
-    // Thumbnail will be embedded into tags for MP3 and FLAC (when possible)
-    let audio_result = audio_req.await;
-    let upload_duration = upload_start.elapsed();
-    let in_flight_after = state.upload_counters.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
-
-    match audio_result {
-        Ok(sent_msg) => {
-            let upload_mbps = throughput_mbps(file_size, upload_duration);
-            tracing::info!(
-                "Upload completed in {:.2}s ({:.2} MB/s, inflight: {}, peak: {})",
-                upload_duration.as_secs_f64(),
-                upload_mbps,
-                in_flight_after,
-                peak_in_flight
-            );
-            tracing::info!(
-                "Successfully sent as audio: {}",
-                if is_flac { "FLAC" } else { "MP3" }
-            );
+    let mut text = String::from("🎧 相似歌曲\n\n");
+    let mut buttons = Vec::new();
+    for (i, song) in songs.iter().take(SIMILAR_SONGS_SHOWN).enumerate() {
+        let artists = format_artists(song.ar.as_deref().unwrap_or(&[]));
+        std::fmt::write(
+            &mut text,
+            format_args!("{}.「{}」 - {}\n", i + 1, song.name, artists),
+        )
+        .unwrap();
+        buttons.push(InlineKeyboardButton::callback(
+            format!("{}", i + 1),
+            format!("music {}", song.id),
+        ));
+    }
 
-            // Extract file_id from sent message
-            if let MessageKind::Common(common) = &sent_msg.kind
-                && let teloxide::types::MediaKind::Audio(audio) = &common.media_kind
-            {
-                song_info.file_id = Some(audio.audio.file.id.to_string());
-            }
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .reply_markup(InlineKeyboardMarkup::new(vec![buttons]))
+        .await?;
+    bot.answer_callback_query(query.id.clone()).await?;
+    Ok(())
+}
 
-            // No cleanup needed - both audio_buffer and thumbnail_buffer were consumed
-        }
-        Err(e) => {
-            let upload_mbps = throughput_mbps(file_size, upload_duration);
-            tracing::warn!(
-                "Upload failed after {:.2}s ({:.2} MB/s, inflight: {}, peak: {})",
-                upload_duration.as_secs_f64(),
-                upload_mbps,
-                in_flight_after,
-                peak_in_flight
-            );
-            tracing::warn!("Audio send failed: {}, trying document fallback", e);
+/// Count a pipeline failure and DM the bot admins once the streak for this
+/// cause reaches `alerts.failure_threshold`
+async fn record_pipeline_failure(
+    bot: &Bot,
+    state: &Arc<BotState>,
+    cause: FailureCause,
+    error: &str,
+) {
+    state.error_feed.push(cause, error);
+    let Some(count) = state
+        .failure_tracker
+        .record_failure(cause, state.config.alert_failure_threshold)
+    else {
+        return;
+    };
 
-            // Note: audio_buffer was consumed above, we need to check if we can retry
-            // Since the buffer was moved, we cannot retry - this is a limitation
-            // For fallback, we would need to re-download or keep a backup
-            // For now, just clean up and return error
+    tracing::warn!(
+        "{} consecutive failures ({:?}), alerting admins: {}",
+        count,
+        cause,
+        error
+    );
 
-            bot.edit_message_text(msg.chat.id, status_msg.id, format!("发送失败: {e}"))
-                .await
-                .ok();
-            return Err(e.into());
+    let text = alerting::alert_message(cause, count, error);
+    for &admin in &state.config.bot_admin {
+        if let Err(e) = bot.send_message(ChatId(admin), text.clone()).await {
+            tracing::warn!("Failed to send alert to admin {}: {}", admin, e);
         }
     }
+}
 
-    // Save to database and update query statistics
-    state.database.save_song_info(&song_info).await?;
-    let analyze_interval = state.config.db_analyze_interval_requests;
-    if MaintenanceCounters::should_run(
-        &state.maintenance_counters.db_analyze_requests,
-        analyze_interval,
-    ) {
-        state.database.analyze().await.ok(); // Non-critical, ignore errors
+/// Size, limit and ways to still get a song Telegram refused as too large:
+/// lower qualities that fit, and the local Bot API server for admins
+fn file_too_large_reply(
+    locale: Locale,
+    music_id: u64,
+    song_url: &crate::music_api::SongUrl,
+    size: u64,
+    limit: u64,
+) -> (String, InlineKeyboardMarkup) {
+    let offers = smaller_bitrates(song_url.br, size, limit);
+    let mut text = format!(
+        "{}\n📦 {} > {}",
+        Text::FileTooLarge.get(locale),
+        format_file_size(size),
+        format_file_size(limit)
+    );
+    if !offers.is_empty() {
+        text.push('\n');
+        text.push_str(Text::FileTooLargeHint.get(locale));
     }
-
-    // Delete status message
-    bot.delete_message(msg.chat.id, status_msg.id).await.ok();
-
-    // Force memory release after download completes
-    let release_interval = state.config.memory_release_interval_requests;
-    if MaintenanceCounters::should_run(
-        &state.maintenance_counters.memory_release_requests,
-        release_interval,
-    ) {
-        // Give tokio time to clean up spawned tasks before forcing memory release
-        tokio::task::yield_now().await;
-        crate::memory::force_memory_release();
-        crate::memory::log_memory_stats();
+    if limit < LOCAL_BOT_API_UPLOAD_LIMIT {
+        text.push_str("\n💡 ");
+        text.push_str(Text::LocalBotApiHint.get(locale));
     }
 
-    Ok(())
+    let mut rows = offers
+        .into_iter()
+        .map(|(br, estimate)| {
+            vec![InlineKeyboardButton::callback(
+                format!("🎚 {}kbps (~{})", br / 1000, format_file_size(estimate)),
+                format!("music {music_id} {br}"),
+            )]
+        })
+        .collect::<Vec<_>>();
+    rows.extend(report_keyboard(music_id).inline_keyboard);
+    (text, InlineKeyboardMarkup::new(rows))
 }
 
-fn create_music_keyboard(music_id: u64, song_name: &str, artists: &str) -> InlineKeyboardMarkup {
-    InlineKeyboardMarkup::new(vec![
-        vec![InlineKeyboardButton::url(
-            format!("{song_name} - {artists}"),
-            reqwest::Url::parse(&format!("https://music.163.com/song?id={music_id}")).unwrap(),
-        )],
-        vec![InlineKeyboardButton::switch_inline_query(
-            "分享给朋友",
-            format!("https://music.163.com/song?id={music_id}"),
-        )],
-    ])
+/// Keyboard attached to failed downloads so users can report the song
+fn report_keyboard(music_id: u64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "⚠️ 报告问题",
+        format!("report new {music_id}"),
+    )]])
 }
 
 async fn handle_music_url(
@@ -1308,402 +1997,4847 @@ async fn handle_music_url(
     }
 }
 
-async fn handle_search_command(
+/// Multi-track collections that can be sent in one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CollectionKind {
+    Album,
+    Playlist,
+    /// Heartbeat mode (心动模式) mix seeded by a song ID
+    Heartbeat,
+}
+
+impl CollectionKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Album => "专辑",
+            Self::Playlist => "歌单",
+            Self::Heartbeat => "心动模式",
+        }
+    }
+}
+
+/// Fetch the title and track IDs of an album or playlist
+async fn fetch_collection(
+    state: &Arc<BotState>,
+    kind: CollectionKind,
+    id: u64,
+) -> Result<(String, Vec<u64>)> {
+    match kind {
+        CollectionKind::Album => {
+            let (album, songs) = state.music_api.get_album(id).await?;
+            Ok((album.name, songs.into_iter().map(|song| song.id).collect()))
+        }
+        CollectionKind::Playlist => {
+            let playlist = state.music_api.get_playlist(id).await?;
+            Ok((
+                playlist.name,
+                playlist
+                    .track_ids
+                    .into_iter()
+                    .map(|track| track.id)
+                    .collect(),
+            ))
+        }
+        CollectionKind::Heartbeat => {
+            let (seed, track_ids) = tokio::try_join!(
+                state.music_api.get_song_detail(id),
+                state.music_api.get_intelligence_list(id)
+            )?;
+            Ok((seed.name, track_ids))
+        }
+    }
+}
+
+async fn handle_heartbeat_command(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
     args: Option<String>,
 ) -> ResponseResult<()> {
-    let keyword = match args {
-        Some(kw) if !kw.is_empty() => kw,
-        _ => {
-            bot.send_message(msg.chat.id, "请输入搜索关键词")
-                .reply_parameters(ReplyParameters::new(msg.id))
-                .await?;
-            return Ok(());
-        }
+    if state.music_api.music_u.is_none() {
+        bot.send_message(msg.chat.id, "⚠️ 未配置 MUSIC_U，无法使用心动模式")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
     };
+    handle_collection(
+        bot,
+        msg,
+        state,
+        CollectionKind::Heartbeat,
+        music_id,
+        TrackSelection::default(),
+        None,
+    )
+    .await
+}
 
-    let search_msg = bot
-        .send_message(msg.chat.id, "🔍 搜索中...")
-        .reply_parameters(ReplyParameters::new(msg.id))
-        .await?;
-
-    match state.music_api.search_songs(&keyword, 10).await {
-        Ok(songs) => {
-            if songs.is_empty() {
-                bot.edit_message_text(msg.chat.id, search_msg.id, "未找到相关歌曲")
-                    .await?;
-                return Ok(());
-            }
+async fn handle_collection_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    kind: CollectionKind,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let (args, selection) = match kind {
+        CollectionKind::Album | CollectionKind::Playlist => {
+            split_track_selection(&args.unwrap_or_default())
+        }
+        CollectionKind::Heartbeat => (args.unwrap_or_default(), TrackSelection::default()),
+    };
+    let id = match kind {
+        CollectionKind::Album => parse_album_id(&args),
+        CollectionKind::Playlist => parse_playlist_id(&args),
+        CollectionKind::Heartbeat => parse_music_id(&args),
+    };
 
-            let mut results = String::new();
-            let mut buttons = Vec::new();
+    let Some(id) = id else {
+        bot.send_message(msg.chat.id, format!("请输入{}ID或链接", kind.label()))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
 
-            for (i, song) in songs.iter().take(8).enumerate() {
-                let artists = format_artists(&song.artists);
-                std::fmt::write(
-                    &mut results,
-                    format_args!("{}.「{}」 - {}\n", i + 1, song.name, artists),
-                )
-                .unwrap();
-                buttons.push(InlineKeyboardButton::callback(
-                    format!("{}", i + 1),
-                    format!("music {}", song.id),
-                ));
-            }
+    handle_collection(bot, msg, state, kind, id, selection, None).await
+}
 
-            let keyboard = InlineKeyboardMarkup::new(vec![buttons]);
+/// Send every track of an album or playlist (sequentially, respecting the download semaphore)
+async fn handle_collection(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    kind: CollectionKind,
+    id: u64,
+    selection: TrackSelection,
+    requester: Option<&User>,
+) -> ResponseResult<()> {
+    let label = kind.label();
+    let status_msg = bot
+        .send_message(msg.chat.id, format!("🔄 正在获取{label}信息..."))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    let _status = state.status_tracker.track(msg.chat.id.0, status_msg.id.0);
 
-            bot.edit_message_text(msg.chat.id, search_msg.id, results)
-                .reply_markup(keyboard)
-                .await?;
-        }
+    let (title, mut track_ids) = match fetch_collection(state, kind, id).await {
+        Ok(collection) => collection,
         Err(e) => {
-            bot.edit_message_text(msg.chat.id, search_msg.id, format!("搜索失败: {e}"))
-                .await?;
+            bot.edit_message_text(
+                msg.chat.id,
+                status_msg.id,
+                format!("❌ 获取{label}信息失败: {e}"),
+            )
+            .await?;
+            return Ok(());
         }
+    };
+
+    if track_ids.is_empty() {
+        bot.edit_message_text(
+            msg.chat.id,
+            status_msg.id,
+            format!("{label}「{title}」中没有歌曲"),
+        )
+        .await?;
+        return Ok(());
     }
 
-    Ok(())
-}
+    let collection_size = track_ids.len();
+    selection.apply(&mut track_ids, quiz::random_below);
+    if track_ids.is_empty() {
+        bot.edit_message_text(
+            msg.chat.id,
+            status_msg.id,
+            format!("❌ {label}「{title}」只有 {collection_size} 首，所选范围内没有歌曲"),
+        )
+        .await?;
+        return Ok(());
+    }
 
-async fn handle_about_command(
-    bot: &Bot,
-    msg: &Message,
-    _state: &Arc<BotState>,
-) -> ResponseResult<()> {
-    let about_text = format!(
-        r"🎵 Music163bot-Rust v{}
+    let total = track_ids.len();
+    let limit = state.config.max_collection_tracks.max(1);
+    track_ids.truncate(limit);
+    let truncated_note = [
+        selection
+            .describe()
+            .map(|selected| format!("（{selected}）")),
+        (total > limit).then(|| format!("（共 {total} 首，仅发送前 {limit} 首）")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<String>();
 
-一个用来下载/分享/搜索网易云歌曲的 Telegram Bot
+    tracing::info!(
+        "Sending {} {} ({} of {} tracks) to chat {}",
+        label,
+        id,
+        track_ids.len(),
+        total,
+        msg.chat.id
+    );
 
-特性：
-• 🔗 分享链接嗅探
-• 🎵 歌曲搜索与下载
-• 💾 智能缓存系统
-• 🚀 智能存储 (v1.1.0+)
-• 🎤 歌词获取
-• 📊 使用统计
+    bot.edit_message_text(
+        msg.chat.id,
+        status_msg.id,
+        format!(
+            "💿 {label}「{title}」开始发送 {} 首{truncated_note}",
+            track_ids.len()
+        ),
+    )
+    .await?;
 
-技术栈：
-• 🦀 Rust + Teloxide
-• 🔧 高并发处理
-• 📦 轻量级部署
+    // Deliver in media groups so a whole album does not produce one notification per track
+    let mut batch = MediaBatch {
+        status_msg: status_msg.clone(),
+        tracks: Vec::new(),
+    };
+    let silent = is_silent_chat(state, msg.chat.id).await;
+    let mut sent = 0usize;
+    for (index, chunk) in track_ids.chunks(MEDIA_GROUP_MAX).enumerate() {
+        for music_id in chunk {
+            if let Err(e) = process_music_into(
+                bot,
+                msg,
+                state,
+                *music_id,
+                Some(&mut batch),
+                DownloadOverrides {
+                    requester,
+                    ..DownloadOverrides::default()
+                },
+            )
+            .await
+            {
+                tracing::warn!(
+                    "Failed to prepare track {} of {} {}: {}",
+                    music_id,
+                    label,
+                    id,
+                    e
+                );
+            }
+        }
 
-源码：GitHub | 原版：Music163bot-Go",
-        env!("CARGO_PKG_VERSION")
-    );
+        let first = index * MEDIA_GROUP_MAX + 1;
+        let caption = collection_caption(
+            label,
+            &title,
+            first,
+            first + chunk.len() - 1,
+            track_ids.len(),
+            &state.bot_username,
+        );
+        let tracks = std::mem::take(&mut batch.tracks);
+        match send_media_batch(msg, state, tracks, caption, silent).await {
+            Ok(messages) => sent += messages.len(),
+            Err(e) => {
+                tracing::warn!("Failed to send media group of {} {}: {}", label, id, e);
+                record_pipeline_failure(bot, state, FailureCause::Upload, &e.to_string()).await;
+            }
+        }
+    }
 
-    bot.send_message(msg.chat.id, about_text)
-        .reply_parameters(ReplyParameters::new(msg.id))
-        .disable_link_preview(true)
-        .await?;
+    bot.edit_message_text(
+        msg.chat.id,
+        status_msg.id,
+        format!(
+            "✅ {label}「{title}」处理完成 ({sent}/{}){truncated_note}",
+            track_ids.len()
+        ),
+    )
+    .await?;
 
     Ok(())
 }
 
-async fn handle_lyric_command(
+/// Admin-only: download and cache every track of an album or playlist in the background
+async fn handle_precache_command(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
     args: Option<String>,
 ) -> ResponseResult<()> {
-    let args = args.unwrap_or_default();
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
 
-    if args.is_empty() {
-        bot.send_message(msg.chat.id, "请输入歌曲ID或关键词")
+    let (kind, id) = match args.as_deref().and_then(parse_collection_target) {
+        Some(StartPayload::Album(id)) => (CollectionKind::Album, id),
+        Some(StartPayload::Playlist(id)) => (CollectionKind::Playlist, id),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "用法: /precache <歌单ID|歌单链接|专辑链接|al_专辑ID>",
+            )
             .reply_parameters(ReplyParameters::new(msg.id))
             .await?;
-        return Ok(());
-    }
+            return Ok(());
+        }
+    };
 
-    let music_id = if let Some(id) = parse_music_id(&args) {
-        id
-    } else {
-        match state.music_api.search_songs(&args, 1).await {
-            Ok(songs) => {
-                if let Some(song) = songs.first() {
-                    song.id
-                } else {
-                    bot.send_message(msg.chat.id, "未找到相关歌曲")
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    return Ok(());
-                }
-            }
-            Err(e) => {
-                bot.send_message(msg.chat.id, format!("搜索失败: {e}"))
-                    .reply_parameters(ReplyParameters::new(msg.id))
-                    .await?;
-                return Ok(());
-            }
+    let label = kind.label();
+    let status_msg = bot
+        .send_message(msg.chat.id, format!("🔄 正在获取{label}信息..."))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let (title, track_ids) = match fetch_collection(state, kind, id).await {
+        Ok(collection) => collection,
+        Err(e) => {
+            bot.edit_message_text(
+                msg.chat.id,
+                status_msg.id,
+                format!("❌ 获取{label}信息失败: {e}"),
+            )
+            .await?;
+            return Ok(());
         }
     };
 
+    tracing::info!(
+        "Precaching {} {} ({} tracks) for admin in chat {}",
+        label,
+        id,
+        track_ids.len(),
+        msg.chat.id
+    );
+
+    let job = PrecacheJob {
+        label,
+        title,
+        track_ids,
+        low_priority: false,
+    };
+    worker_pool::spawn_job(
+        "Precache",
+        run_precache(bot.clone(), msg.clone(), state.clone(), job, status_msg),
+    );
+    Ok(())
+}
+
+/// Admin-only: precache every album of an artist at low priority
+async fn handle_discography_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+    let Some(artist_id) = args.as_deref().and_then(parse_artist_id) else {
+        bot.send_message(msg.chat.id, "用法: /discography <歌手ID|歌手链接>")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
     let status_msg = bot
-        .send_message(msg.chat.id, "🎵 正在获取歌词...")
+        .send_message(msg.chat.id, "🔄 正在获取歌手的专辑列表...")
         .reply_parameters(ReplyParameters::new(msg.id))
         .await?;
+    let (artist, albums, track_ids) = match fetch_discography(state, artist_id).await {
+        Ok(discography) => discography,
+        Err(e) => {
+            bot.edit_message_text(
+                msg.chat.id,
+                status_msg.id,
+                format!("❌ 获取歌手作品失败: {e}"),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
 
-    match state.music_api.get_song_lyric(music_id).await {
-        Ok(lyric) => {
-            if lyric.trim().is_empty() || lyric == "No lyrics available" {
-                bot.edit_message_text(msg.chat.id, status_msg.id, "该歌曲暂无歌词")
-                    .await?;
-                return Ok(());
+    tracing::info!(
+        "Precaching discography of artist {} ({} albums, {} tracks) for admin in chat {}",
+        artist_id,
+        albums,
+        track_ids.len(),
+        msg.chat.id
+    );
+    bot.edit_message_text(
+        msg.chat.id,
+        status_msg.id,
+        format!(
+            "🎤 歌手「{artist}」共 {albums} 张专辑、{} 首歌曲，开始低优先级预缓存",
+            track_ids.len()
+        ),
+    )
+    .await?;
+
+    let job = PrecacheJob {
+        label: "歌手",
+        title: artist,
+        track_ids,
+        low_priority: true,
+    };
+    worker_pool::spawn_job(
+        "Discography",
+        run_precache(bot.clone(), msg.clone(), state.clone(), job, status_msg),
+    );
+    Ok(())
+}
+
+/// Artist name, album count and the distinct tracks of all its albums
+async fn fetch_discography(state: &BotState, artist_id: u64) -> Result<(String, usize, Vec<u64>)> {
+    const PAGE_SIZE: u32 = 100;
+    let mut albums = Vec::new();
+    let mut offset = 0;
+    let artist = loop {
+        let (artist, page, more) = state
+            .music_api
+            .get_artist_albums(artist_id, PAGE_SIZE, offset)
+            .await?;
+        albums.extend(page);
+        offset += PAGE_SIZE;
+        if !more {
+            break artist;
+        }
+    };
+
+    // The same song shows up on singles, the album and compilations
+    let mut seen = std::collections::HashSet::new();
+    let mut track_ids = Vec::new();
+    for album in &albums {
+        match state.music_api.get_album(album.id).await {
+            Ok((_, songs)) => track_ids.extend(
+                songs
+                    .into_iter()
+                    .map(|song| song.id)
+                    .filter(|id| seen.insert(*id)),
+            ),
+            Err(e) => tracing::warn!(
+                "Failed to fetch album {} of artist {}: {}",
+                album.id,
+                artist_id,
+                e
+            ),
+        }
+    }
+    Ok((artist.name, albums.len(), track_ids))
+}
+
+/// Tracks for `run_precache`, reported as `{label}「{title}」`
+struct PrecacheJob {
+    label: &'static str,
+    title: String,
+    track_ids: Vec<u64>,
+    /// Leave a download permit free for interactive requests
+    low_priority: bool,
+}
+
+/// Wait until a download permit beyond the one kept for interactive
+/// requests is free (with a single permit, until it is free)
+async fn wait_for_spare_download(state: &BotState) {
+    let reserved = usize::from(state.config.max_concurrent_downloads > 1);
+    while state.download_semaphore.available_permits() <= reserved {
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    }
+}
+
+/// Download and upload every uncached track, keeping only the file_ids
+///
+/// Tracks go to the admin's chat as silent media groups that are deleted
+/// right after sending; Telegram keeps the uploaded files.
+async fn run_precache(
+    bot: Bot,
+    msg: Message,
+    state: Arc<BotState>,
+    job: PrecacheJob,
+    status_msg: Message,
+) {
+    let _status = state.status_tracker.track(msg.chat.id.0, status_msg.id.0);
+    let PrecacheJob {
+        label,
+        title,
+        track_ids,
+        low_priority,
+    } = job;
+    let total = track_ids.len();
+    let mut batch = MediaBatch {
+        status_msg: status_msg.clone(),
+        tracks: Vec::new(),
+    };
+    let (mut fresh, mut known, mut failed) = (0usize, 0usize, 0usize);
+
+    for (index, chunk) in track_ids.chunks(MEDIA_GROUP_MAX).enumerate() {
+        for music_id in chunk {
+            if low_priority {
+                wait_for_spare_download(&state).await;
+            }
+            if let Err(e) = process_music_into(
+                &bot,
+                &msg,
+                &state,
+                *music_id,
+                Some(&mut batch),
+                DownloadOverrides::default(),
+            )
+            .await
+            {
+                tracing::warn!("Failed to precache track {}: {}", music_id, e);
             }
+        }
 
-            // Get song detail for filename
-            let song_detail = match state.music_api.get_song_detail(music_id).await {
-                Ok(detail) => detail,
-                Err(e) => {
-                    bot.edit_message_text(
-                        msg.chat.id,
-                        status_msg.id,
-                        format!("获取歌曲信息失败: {e}"),
-                    )
-                    .await?;
-                    return Ok(());
+        let (downloaded, cached): (Vec<_>, Vec<_>) = std::mem::take(&mut batch.tracks)
+            .into_iter()
+            .partition(|track| track.song_info.is_some());
+        known += cached.len();
+        let downloaded_count = downloaded.len();
+        failed += chunk.len() - downloaded_count - cached.len();
+
+        let first = index * MEDIA_GROUP_MAX + 1;
+        let caption = collection_caption(
+            label,
+            &title,
+            first,
+            first + chunk.len() - 1,
+            total,
+            &state.bot_username,
+        );
+        match send_media_batch(&msg, &state, downloaded, caption, true).await {
+            Ok(messages) => {
+                fresh += messages.len();
+                for sent_msg in messages {
+                    bot.delete_message(msg.chat.id, sent_msg.id).await.ok();
                 }
-            };
+            }
+            Err(e) => {
+                failed += downloaded_count;
+                tracing::warn!("Failed to upload precache batch: {}", e);
+                record_pipeline_failure(&bot, &state, FailureCause::Upload, &e.to_string()).await;
+            }
+        }
 
-            let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
-            let lrc_filename = clean_filename(&format!("{} - {}.lrc", artists, song_detail.name));
-            let lrc_path = format!("{}/{}", state.config.cache_dir, lrc_filename);
+        let done = first + chunk.len() - 1;
+        bot.edit_message_text(
+            msg.chat.id,
+            status_msg.id,
+            format!(
+                "🔥 正在预缓存{label}「{title}」: {done}/{total}\n新缓存 {fresh} · 已缓存 {known} · 失败 {failed}"
+            ),
+        )
+        .await
+        .ok();
+    }
 
-            tokio::fs::write(&lrc_path, &lyric)
-                .await
-                .map_err(|e| RequestError::Io(Arc::new(e)))?;
+    tracing::info!(
+        "Precache of {} finished: {} new, {} already cached, {} failed",
+        title,
+        fresh,
+        known,
+        failed
+    );
+    bot.edit_message_text(
+        msg.chat.id,
+        status_msg.id,
+        format!(
+            "✅ {label}「{title}」预缓存完成 ({total} 首)\n新缓存 {fresh} · 已缓存 {known} · 失败 {failed}"
+        ),
+    )
+    .await
+    .ok();
+}
 
-            bot.send_document(
+/// Admin-only: download a song, album or playlist into `download.library_dir`
+/// (or the `[webdav]` share)
+/// without sending anything
+async fn handle_fetch_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+    if state.config.library_dir.is_empty() && state.webdav.is_none() {
+        bot.send_message(
+            msg.chat.id,
+            "⚠️ 未配置 download.library_dir 或 webdav.url，无法使用 /fetch",
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    let (kind, id) = match args.as_deref().and_then(parse_fetch_target) {
+        Some(StartPayload::Song(id)) => (None, id),
+        Some(StartPayload::Album(id)) => (Some(CollectionKind::Album), id),
+        Some(StartPayload::Playlist(id)) => (Some(CollectionKind::Playlist), id),
+        None => {
+            bot.send_message(
                 msg.chat.id,
-                InputFile::file(std::path::Path::new(&lrc_path)),
+                "用法: /fetch <音乐ID|歌曲链接|歌单链接|专辑链接|pl_歌单ID|al_专辑ID>",
             )
             .reply_parameters(ReplyParameters::new(msg.id))
             .await?;
+            return Ok(());
+        }
+    };
 
-            tokio::fs::remove_file(&lrc_path).await.ok();
-            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+    let status_msg = bot
+        .send_message(msg.chat.id, "🔄 正在获取歌曲信息...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    let (title, track_ids) = match kind {
+        None => (format!("歌曲 {id}"), vec![id]),
+        Some(kind) => match fetch_collection(state, kind, id).await {
+            Ok((title, track_ids)) => (format!("{}「{title}」", kind.label()), track_ids),
+            Err(e) => {
+                bot.edit_message_text(
+                    msg.chat.id,
+                    status_msg.id,
+                    format!("❌ 获取{}信息失败: {e}", kind.label()),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    tracing::info!(
+        "Fetching {} ({} tracks) into the library",
+        title,
+        track_ids.len()
+    );
+    worker_pool::spawn_job(
+        "Fetch",
+        run_fetch(bot.clone(), state.clone(), title, track_ids, status_msg),
+    );
+    Ok(())
+}
+
+/// Save every track to the library, reporting progress in `status_msg`
+async fn run_fetch(
+    bot: Bot,
+    state: Arc<BotState>,
+    title: String,
+    track_ids: Vec<u64>,
+    status_msg: Message,
+) {
+    let _status = state
+        .status_tracker
+        .track(status_msg.chat.id.0, status_msg.id.0);
+    let total = track_ids.len();
+    let (mut saved, mut existing, mut failed) = (0usize, 0usize, 0usize);
+
+    for (index, music_id) in track_ids.into_iter().enumerate() {
+        match library::fetch_song(&state, music_id).await {
+            Ok(library::Fetched::Saved(_)) => saved += 1,
+            Ok(library::Fetched::Exists(_)) => existing += 1,
+            Err(e) => {
+                failed += 1;
+                tracing::warn!(
+                    "Failed to fetch music_id {} into the library: {}",
+                    music_id,
+                    e
+                );
+            }
+        }
+        // Progress every few tracks to stay clear of edit rate limits
+        let done = index + 1;
+        if done % MEDIA_GROUP_MAX == 0 && done < total {
+            bot.edit_message_text(
+                status_msg.chat.id,
+                status_msg.id,
+                format!(
+                    "💾 正在保存{title}: {done}/{total}\n新保存 {saved} · 已存在 {existing} · 失败 {failed}"
+                ),
+            )
+            .await
+            .ok();
+        }
+    }
+
+    tracing::info!(
+        "Fetch of {} finished: {} saved, {} already in the library, {} failed",
+        title,
+        saved,
+        existing,
+        failed
+    );
+    bot.edit_message_text(
+        status_msg.chat.id,
+        status_msg.id,
+        format!(
+            "✅ {title}已保存到曲库 ({total} 首)\n新保存 {saved} · 已存在 {existing} · 失败 {failed}"
+        ),
+    )
+    .await
+    .ok();
+}
+
+/// Cached song with this bot's file_ids, answered from Redis when configured
+async fn lookup_song(state: &BotState, music_id: i64) -> Result<Option<SongInfo>> {
+    if let Some(hot_cache) = &state.hot_cache {
+        match hot_cache.get(music_id, state.bot_id).await {
+            Ok(Some(song_info)) => return Ok(Some(song_info)),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Redis lookup failed for music_id {}: {}", music_id, e),
         }
+    }
+
+    let song_info = state
+        .database
+        .get_song_for_bot(music_id, state.bot_id)
+        .await?;
+    if let Some(hot_cache) = &state.hot_cache
+        && let Some(song_info) = song_info.as_ref().filter(|song| song.file_id.is_some())
+        && let Err(e) = hot_cache.put(song_info, state.bot_id).await
+    {
+        tracing::warn!("Failed to cache music_id {} in redis: {}", music_id, e);
+    }
+    Ok(song_info)
+}
+
+/// Cached copy to serve; users limited to lossy quality fall back to the
+/// song's lossy copy when the cached one is lossless
+async fn lookup_song_for(
+    state: &BotState,
+    music_id: i64,
+    lossy_only: bool,
+) -> Result<Option<SongInfo>> {
+    let song_info = lookup_song(state, music_id).await?;
+    if !lossy_only
+        || song_info
+            .as_ref()
+            .is_some_and(|song| song.bit_rate <= LOSSY_MAX_BITRATE as i64)
+    {
+        return Ok(song_info);
+    }
+    state.database.get_lossy_copy(music_id, state.bot_id).await
+}
+
+/// Remove a song from the database and the Redis layer
+pub(crate) async fn forget_song(state: &BotState, music_id: i64) -> Result<bool> {
+    if let Some(hot_cache) = &state.hot_cache
+        && let Err(e) = hot_cache.remove(music_id).await
+    {
+        tracing::warn!("Failed to remove music_id {} from redis: {}", music_id, e);
+    }
+    state.database.delete_song_by_music_id(music_id).await
+}
+
+/// Drop the cached copy when NetEase now serves different audio for the song
+/// (remaster or re-upload); lookup failures keep the cache
+async fn forget_if_audio_replaced(state: &BotState, cached_song: &SongInfo) -> bool {
+    let Some(cached_md5) = cached_song.audio_md5.as_deref() else {
+        return false;
+    };
+    let song_url = match state
+        .music_api
+        .get_best_song_url(cached_song.music_id as u64)
+        .await
+    {
+        Ok(url) => url,
         Err(e) => {
-            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取歌词失败: {e}"))
-                .await?;
+            tracing::debug!(
+                "Skipping audio hash check for music_id {}: {}",
+                cached_song.music_id,
+                e
+            );
+            return false;
         }
+    };
+    // A different format or a trial clip means the account lost access to the
+    // cached quality, not that the audio changed
+    if song_url.url.is_empty()
+        || song_url.md5.is_empty()
+        || song_url.free_trial_info.is_some()
+        || !song_url.format.eq_ignore_ascii_case(&cached_song.file_ext)
+        || song_url.md5.eq_ignore_ascii_case(cached_md5)
+    {
+        return false;
+    }
+    tracing::info!(
+        "Audio of music_id {} changed upstream ({} -> {}), invalidating cached file_id",
+        cached_song.music_id,
+        cached_md5,
+        song_url.md5
+    );
+    if let Err(e) = forget_song(state, cached_song.music_id).await {
+        tracing::warn!(
+            "Failed to drop replaced song {}: {}",
+            cached_song.music_id,
+            e
+        );
+    }
+    true
+}
+
+/// Soft-delete every cached song and flush the Redis layer
+pub(crate) async fn clear_song_cache(state: &BotState) -> Result<u64> {
+    let count = state.database.clear_all_songs().await?;
+    if let Some(hot_cache) = &state.hot_cache
+        && let Err(e) = hot_cache.clear().await
+    {
+        tracing::warn!("Failed to clear redis cache: {}", e);
     }
+    Ok(count)
+}
+
+/// Every minute, replace status messages left behind by panicked jobs
+/// (older than `download.stuck_status_minutes`) with an error
+fn spawn_status_reaper(bot: Bot, state: Arc<BotState>) {
+    let max_age = std::time::Duration::from_secs(state.config.stuck_status_minutes * 60);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_mins(1)).await;
+            for (chat_id, message_id) in state.status_tracker.reap(max_age) {
+                tracing::warn!(
+                    "Job of status message {} in chat {} died, marking it as aborted",
+                    message_id,
+                    chat_id
+                );
+                let locale = chat_locale(&state, ChatId(chat_id), None).await;
+                if let Err(e) = bot
+                    .edit_message_text(
+                        ChatId(chat_id),
+                        teloxide::types::MessageId(message_id),
+                        Text::JobAborted.get(locale),
+                    )
+                    .await
+                {
+                    tracing::warn!("Failed to mark status message {}: {}", message_id, e);
+                }
+            }
+        }
+    });
+}
+
+/// Permanently remove soft-deleted songs past `cache.purge_deleted_after_days`, daily
+fn spawn_deleted_song_purge(state: Arc<BotState>) {
+    tokio::spawn(async move {
+        loop {
+            match state
+                .database
+                .purge_deleted_songs(state.config.purge_deleted_after_days)
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::info!("Purged {} deleted songs", count);
+                    if let Err(e) = state.database.optimize().await {
+                        tracing::warn!("Database optimization failed after purge: {}", e);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to purge deleted songs: {}", e),
+            }
+            tokio::time::sleep(std::time::Duration::from_hours(24)).await;
+        }
+    });
+}
+
+/// Telegram accepts at most 10 items per media group
+const MEDIA_GROUP_MAX: usize = 10;
+
+/// Shared caption for one media group of an album/playlist (`first`..=`last` of `total`)
+fn collection_caption(
+    label: &str,
+    title: &str,
+    first: usize,
+    last: usize,
+    total: usize,
+    bot_username: &str,
+) -> String {
+    let range = if first == last {
+        format!("{first}/{total}")
+    } else {
+        format!("{first}-{last}/{total}")
+    };
+    format!("💿 {label}「{title}」 {range}\nvia @{bot_username}")
+}
+
+/// Upload queued collection tracks as one media group, returning the sent messages
+///
+/// A single leftover track is sent with `send_audio`, since media groups need two items.
+async fn send_media_batch(
+    msg: &Message,
+    state: &Arc<BotState>,
+    tracks: Vec<PreparedAudio>,
+    caption: String,
+    silent: bool,
+) -> Result<Vec<Message>> {
+    if tracks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (mut media, song_infos): (Vec<_>, Vec<_>) = tracks
+        .into_iter()
+        .map(|track| (track.media, (track.song_info, track.cache)))
+        .unzip();
+    media[0].caption = Some(caption);
+
+    let upload_bot = upload_bot(state).await;
+    let in_flight = state
+        .upload_counters
+        .in_flight
+        .fetch_add(1, Ordering::Relaxed)
+        + 1;
+    update_peak(&state.upload_counters.peak_in_flight, in_flight);
+    let upload_start = std::time::Instant::now();
+
+    let result = if media.len() == 1 {
+        let audio = media.remove(0);
+        let mut request = upload_bot
+            .send_audio(msg.chat.id, audio.media)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .disable_notification(silent);
+        if let Some(caption) = audio.caption {
+            request = request.caption(caption);
+        }
+        if let Some(title) = audio.title {
+            request = request.title(title);
+        }
+        if let Some(performer) = audio.performer {
+            request = request.performer(performer);
+        }
+        if let Some(duration) = audio.duration {
+            request = request.duration(u32::from(duration));
+        }
+        if let Some(thumbnail) = audio.thumbnail {
+            request = request.thumbnail(thumbnail);
+        }
+        request.await.map(|sent_msg| vec![sent_msg])
+    } else {
+        upload_bot
+            .send_media_group(msg.chat.id, media.into_iter().map(InputMedia::Audio))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .disable_notification(silent)
+            .await
+    };
+
+    let in_flight_after = state
+        .upload_counters
+        .in_flight
+        .fetch_sub(1, Ordering::Relaxed)
+        - 1;
+    if in_flight_after == 0 {
+        state.activity.uploads_drained();
+    }
+    let messages = result?;
+    let bytes = song_infos
+        .iter()
+        .filter_map(|(song_info, _)| song_info.as_ref())
+        .map(|song_info| song_info.music_size.max(0) as u64)
+        .sum();
+    state
+        .transfers
+        .uploads
+        .record(bytes, upload_start.elapsed());
+    tracing::info!(
+        duration_ms = upload_start.elapsed().as_millis() as u64,
+        "Sent media group of {} tracks in {:.2}s",
+        messages.len(),
+        upload_start.elapsed().as_secs_f64()
+    );
+
+    for (sent_msg, (song_info, cache)) in messages.iter().zip(song_infos) {
+        let Some(mut song_info) = song_info else {
+            continue;
+        };
+        song_info.file_id = pipeline::audio_file_id(sent_msg);
+        pipeline::cache_upload(state, &upload_bot, &song_info, sent_msg, cache).await?;
+    }
+
+    Ok(messages)
+}
+
+async fn handle_search_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let keyword = match args {
+        Some(kw) if !kw.is_empty() => kw,
+        _ => {
+            bot.send_message(msg.chat.id, "请输入搜索关键词")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let search_msg = bot
+        .send_message(msg.chat.id, "🔍 搜索中...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    match state.music_api.search_songs(&keyword, 10).await {
+        Ok(songs) => {
+            if songs.is_empty() {
+                bot.edit_message_text(msg.chat.id, search_msg.id, "未找到相关歌曲")
+                    .await?;
+                return Ok(());
+            }
+
+            let mut results = String::new();
+            let mut buttons = Vec::new();
+
+            for (i, song) in songs.iter().take(8).enumerate() {
+                let artists = format_artists(&song.artists);
+                std::fmt::write(
+                    &mut results,
+                    format_args!("{}.「{}」 - {}\n", i + 1, song.name, artists),
+                )
+                .unwrap();
+                buttons.push(InlineKeyboardButton::callback(
+                    format!("{}", i + 1),
+                    format!("music {}", song.id),
+                ));
+            }
+
+            let keyboard = InlineKeyboardMarkup::new(vec![buttons]);
+
+            bot.edit_message_text(msg.chat.id, search_msg.id, results)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, search_msg.id, format!("搜索失败: {e}"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_about_command(
+    bot: &Bot,
+    msg: &Message,
+    _state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    let about_text = format!(
+        r"🎵 Music163bot-Rust v{}
+
+一个用来下载/分享/搜索网易云歌曲的 Telegram Bot
+
+特性：
+• 🔗 分享链接嗅探
+• 🎵 歌曲搜索与下载
+• 💾 智能缓存系统
+• 🚀 智能存储 (v1.1.0+)
+• 🎤 歌词获取
+• 📊 使用统计
+
+技术栈：
+• 🦀 Rust + Teloxide
+• 🔧 高并发处理
+• 📦 轻量级部署
+
+源码：GitHub | 原版：Music163bot-Go",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    bot.send_message(msg.chat.id, about_text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .disable_link_preview(true)
+        .await?;
+
+    Ok(())
+}
+
+/// Resolve a song from an ID/link, or the first search result for a keyword
+///
+/// Replies to the user and returns `None` when no song could be resolved.
+/// Music ID of an audio message the bot delivered: looked up by its file_id,
+/// falling back to the song link in its inline keyboard
+async fn music_id_of_audio(state: &Arc<BotState>, message: &Message) -> Option<u64> {
+    let audio = message.audio()?;
+    match state
+        .database
+        .find_music_id_by_file_id(&audio.file.id.0)
+        .await
+    {
+        Ok(Some(music_id)) => return Some(music_id as u64),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to look up audio file_id: {}", e),
+    }
+
+    message
+        .reply_markup()?
+        .inline_keyboard
+        .iter()
+        .flatten()
+        .find_map(|button| match &button.kind {
+            InlineKeyboardButtonKind::Url(url) => parse_music_id(url.as_str()),
+            _ => None,
+        })
+}
+
+/// Music ID from a command's arguments (ID, link or search keywords), or from
+/// the bot's audio message the command replies to when no arguments are given
+async fn resolve_music_id(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<&str>,
+) -> ResponseResult<Option<u64>> {
+    let args = args.unwrap_or_default().trim();
+
+    if args.is_empty()
+        && let Some(replied) = msg.reply_to_message()
+        && let Some(music_id) = music_id_of_audio(state, replied).await
+    {
+        return Ok(Some(music_id));
+    }
+
+    if args.is_empty() {
+        bot.send_message(msg.chat.id, "请输入歌曲ID或关键词，或回复机器人发送的音频")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(None);
+    }
+
+    if let Some(id) = parse_music_id(args) {
+        return Ok(Some(id));
+    }
+
+    match state.music_api.search_songs(args, 1).await {
+        Ok(songs) => {
+            if let Some(song) = songs.first() {
+                Ok(Some(song.id))
+            } else {
+                bot.send_message(msg.chat.id, "未找到相关歌曲")
+                    .reply_parameters(ReplyParameters::new(msg.id))
+                    .await?;
+                Ok(None)
+            }
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("搜索失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Source bitrate for voice notes (Opus output is far below it anyway)
+const VOICE_SOURCE_BITRATE: u64 = 128_000;
+/// Source bitrate for clips
+const CLIP_SOURCE_BITRATE: u64 = 320_000;
+
+async fn handle_voice_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !state.config.ffmpeg_enabled {
+        bot.send_message(msg.chat.id, "⚠️ 语音模式未启用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🎙️ 正在生成语音...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let song_detail = match state.music_api.get_song_detail(music_id).await {
+        Ok(detail) => detail,
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取歌曲信息失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let voice_path = {
+        let _permit = state.download_semaphore.acquire().await.unwrap();
+        state.activity.touch();
+        create_voice_file(state, music_id).await
+    };
+
+    let voice_path = match voice_path {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("Failed to create voice note for {}: {}", music_id, e);
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("生成语音失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+    let silent = is_silent_chat(state, msg.chat.id).await;
+    let result = bot
+        .send_voice(msg.chat.id, InputFile::file(&voice_path))
+        .caption(format!("🎵 {} - {}", song_detail.name, artists))
+        .duration((song_detail.dt.unwrap_or(0) / 1000) as u32)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .disable_notification(silent)
+        .await;
+    tokio::fs::remove_file(&voice_path).await.ok();
+
+    match result {
+        Ok(_) => {
+            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("发送失败: {e}"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Download a song and transcode it to an OGG/Opus voice note in the cache directory
+async fn create_voice_file(state: &Arc<BotState>, music_id: u64) -> Result<std::path::PathBuf> {
+    ensure_dir(&state.config.cache_dir)?;
+    let source = transcode::download_source(
+        &state.music_api,
+        music_id,
+        VOICE_SOURCE_BITRATE,
+        &state.config.cache_dir,
+    )
+    .await?;
+    let output = source.with_extension("ogg");
+
+    let result = transcode::to_voice(&state.config, &source, &output).await;
+    tokio::fs::remove_file(&source).await.ok();
+    if let Err(e) = result {
+        tokio::fs::remove_file(&output).await.ok();
+        return Err(e);
+    }
+
+    Ok(output)
+}
+
+async fn handle_clip_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    const USAGE: &str = "用法: /clip <音乐ID> <开始> <结束>\n时间格式: 秒数、分:秒 或 时:分:秒，例如 /clip 1234567 1:05 1:35";
+
+    if !state.config.ffmpeg_enabled {
+        bot.send_message(msg.chat.id, "⚠️ 片段截取未启用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let args = args.unwrap_or_default();
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let parsed = match parts.as_slice() {
+        [id, start, end] => parse_music_id(id)
+            .zip(parse_timestamp(start))
+            .zip(parse_timestamp(end))
+            .map(|((id, start), end)| (id, start, end)),
+        _ => None,
+    };
+    let Some((music_id, start, end)) = parsed else {
+        bot.send_message(msg.chat.id, USAGE)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
+    let max_secs = state.config.clip_max_secs;
+    let error = if end <= start {
+        Some("❌ 结束时间必须晚于开始时间".to_string())
+    } else if end - start > max_secs {
+        Some(format!("❌ 片段最长 {max_secs} 秒"))
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        bot.send_message(msg.chat.id, error)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "✂️ 正在截取片段...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let song_detail = match state.music_api.get_song_detail(music_id).await {
+        Ok(detail) => detail,
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取歌曲信息失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let song_secs = song_detail.dt.unwrap_or(0) / 1000;
+    if song_secs > 0 && start >= song_secs {
+        bot.edit_message_text(
+            msg.chat.id,
+            status_msg.id,
+            format!("❌ 开始时间超出歌曲长度 ({})", format_duration(song_secs)),
+        )
+        .await?;
+        return Ok(());
+    }
+    let end = if song_secs > 0 {
+        end.min(song_secs)
+    } else {
+        end
+    };
+
+    let clip_path = {
+        let _permit = state.download_semaphore.acquire().await.unwrap();
+        state.activity.touch();
+        create_clip_file(state, music_id, start, end - start).await
+    };
+
+    let clip_path = match clip_path {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("Failed to create clip for {}: {}", music_id, e);
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("截取片段失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+    let range = format!("{}-{}", format_duration(start), format_duration(end));
+    let filename = clean_filename(&format!(
+        "{} - {} ({}).mp3",
+        artists, song_detail.name, range
+    ));
+    let silent = is_silent_chat(state, msg.chat.id).await;
+    let result = bot
+        .send_audio(msg.chat.id, InputFile::file(&clip_path).file_name(filename))
+        .caption(format!("✂️ {} - {} [{}]", song_detail.name, artists, range))
+        .title(format!("{} ({})", song_detail.name, range))
+        .performer(&artists)
+        .duration((end - start) as u32)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .disable_notification(silent)
+        .await;
+    tokio::fs::remove_file(&clip_path).await.ok();
+
+    match result {
+        Ok(_) => {
+            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("发送失败: {e}"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Download a song and cut `duration` seconds from `start` into an MP3 in the cache directory
+async fn create_clip_file(
+    state: &Arc<BotState>,
+    music_id: u64,
+    start: u64,
+    duration: u64,
+) -> Result<std::path::PathBuf> {
+    ensure_dir(&state.config.cache_dir)?;
+    let source = transcode::download_source(
+        &state.music_api,
+        music_id,
+        CLIP_SOURCE_BITRATE,
+        &state.config.cache_dir,
+    )
+    .await?;
+    let output = source.with_file_name(format!("clip_{music_id}_{}.mp3", uuid::Uuid::new_v4()));
+
+    let result = transcode::to_clip(&state.config, &source, &output, start, duration).await;
+    tokio::fs::remove_file(&source).await.ok();
+    if let Err(e) = result {
+        tokio::fs::remove_file(&output).await.ok();
+        return Err(e);
+    }
+
+    Ok(output)
+}
+
+async fn handle_card_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !state.config.ffmpeg_enabled || state.config.card_font.is_empty() {
+        bot.send_message(msg.chat.id, "⚠️ 歌曲卡片未启用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🪪 正在生成卡片...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let song_detail = match state.music_api.get_song_detail(music_id).await {
+        Ok(detail) => detail,
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取歌曲信息失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+    let card_path = match create_card_file(state, &song_detail, &artists).await {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("Failed to render card for {}: {}", music_id, e);
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("生成卡片失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let result = bot
+        .send_photo(msg.chat.id, InputFile::file(&card_path))
+        .caption(format!(
+            "🎵 {} - {}\nhttps://music.163.com/song?id={}",
+            song_detail.name, artists, music_id
+        ))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await;
+    tokio::fs::remove_file(&card_path).await.ok();
+
+    match result {
+        Ok(_) => {
+            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("发送失败: {e}"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Download the cover and render a share card into the cache directory
+async fn create_card_file(
+    state: &Arc<BotState>,
+    song_detail: &crate::music_api::SongDetail,
+    artists: &str,
+) -> Result<std::path::PathBuf> {
+    let pic_url = song_detail
+        .al
+        .as_ref()
+        .and_then(|al| al.pic_url.as_deref())
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "Song has no cover art"))?;
+    let cover = state.music_api.download_album_art_original(pic_url).await?;
+
+    ensure_dir(&state.config.cache_dir)?;
+    let output = std::path::Path::new(&state.config.cache_dir).join(format!(
+        "card_{}_{}.jpg",
+        song_detail.id,
+        uuid::Uuid::new_v4()
+    ));
+    card::render_card(
+        &state.config,
+        cover,
+        &song_detail.name,
+        artists,
+        &format!("@{}", state.bot_username),
+        &output,
+    )
+    .await?;
+
+    Ok(output)
+}
+
+async fn handle_info_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let (song, privilege) = match state.music_api.get_song_overview(music_id).await {
+        Ok(overview) => overview,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("获取歌曲信息失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let detail = &song.detail;
+    let artists = format_artists(detail.ar.as_deref().unwrap_or(&[]));
+    let mut text = format!(
+        "🎵 <b>{}</b>\n👤 {}\n",
+        html::escape(&detail.name),
+        html::escape(&artists)
+    );
+    if let Some(album) = &detail.al {
+        std::fmt::write(
+            &mut text,
+            format_args!("💿 {}\n", html::escape(&album.name)),
+        )
+        .unwrap();
+    }
+    if let Some(date) =
+        chrono::DateTime::from_timestamp_millis(song.publish_time).filter(|_| song.publish_time > 0)
+    {
+        std::fmt::write(
+            &mut text,
+            format_args!("📅 发行日期: {}\n", date.format("%Y-%m-%d")),
+        )
+        .unwrap();
+    }
+    if let Some(duration) = detail.dt {
+        std::fmt::write(
+            &mut text,
+            format_args!("⏱ 时长: {}\n", format_duration(duration / 1000)),
+        )
+        .unwrap();
+    }
+    std::fmt::write(
+        &mut text,
+        format_args!("🔥 热度: {:.0}/100\n💰 {}\n", song.pop, fee_label(song.fee)),
+    )
+    .unwrap();
+
+    // Qualities above what this account may play cannot be downloaded
+    let playable = privilege
+        .as_ref()
+        .map_or(u64::MAX, |privilege| privilege.pl);
+    let mut buttons = Vec::new();
+    text.push_str("\n🎚 <b>可用音质</b>\n");
+    for (label, quality) in song.qualities() {
+        let locked = quality.br > playable;
+        std::fmt::write(
+            &mut text,
+            format_args!(
+                "• {label} {}kbps · {}{}\n",
+                quality.br / 1000,
+                format_file_size(quality.size),
+                if locked { " 🔒" } else { "" }
+            ),
+        )
+        .unwrap();
+        if !locked {
+            buttons.push(vec![InlineKeyboardButton::callback(
+                format!("⬇️ {label} {}kbps", quality.br / 1000),
+                format!("music {music_id} {}", quality.br),
+            )]);
+        }
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .disable_link_preview(true)
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Hot comments shown by `/comments`
+const HOT_COMMENTS_SHOWN: usize = 5;
+/// Longer comments are cut to keep the reply within one message
+const COMMENT_MAX_CHARS: usize = 300;
+
+async fn handle_comments_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "💬 正在获取热门评论...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let (song_detail, comments) = match tokio::try_join!(
+        state.music_api.get_song_detail(music_id),
+        state.music_api.get_hot_comments(music_id)
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取评论失败: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if comments.is_empty() {
+        bot.edit_message_text(msg.chat.id, status_msg.id, "该歌曲暂无热门评论")
+            .await?;
+        return Ok(());
+    }
+
+    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+    let mut text = format!("💬 {} - {} 的热门评论\n", song_detail.name, artists);
+    for (index, comment) in comments.iter().take(HOT_COMMENTS_SHOWN).enumerate() {
+        let mut content: String = comment.content.chars().take(COMMENT_MAX_CHARS).collect();
+        if content.len() < comment.content.len() {
+            content.push('…');
+        }
+        let heading = format!(
+            "\n{}. {} (👍 {})\n",
+            index + 1,
+            comment.user.nickname,
+            format_count(comment.liked_count)
+        );
+        text.push_str(&heading);
+        text.push_str(&content);
+        text.push('\n');
+    }
+
+    bot.edit_message_text(msg.chat.id, status_msg.id, text)
+        .await?;
+    Ok(())
+}
+
+async fn handle_lyric_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let (args, options) = match lyric::split_lyric_flags(&args.unwrap_or_default()) {
+        Ok(split) => split,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+    let Some(music_id) = resolve_music_id(bot, msg, state, Some(&args)).await? else {
+        return Ok(());
+    };
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🎵 正在获取歌词...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    match state.music_api.get_song_lyric(music_id).await {
+        Ok(lyric) => {
+            if lyric.trim().is_empty() || lyric == "No lyrics available" {
+                bot.edit_message_text(msg.chat.id, status_msg.id, "该歌曲暂无歌词")
+                    .await?;
+                return Ok(());
+            }
+
+            // Get song detail for filename
+            let song_detail = match state.music_api.get_song_detail(music_id).await {
+                Ok(detail) => detail,
+                Err(e) => {
+                    bot.edit_message_text(
+                        msg.chat.id,
+                        status_msg.id,
+                        format!("获取歌曲信息失败: {e}"),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+
+            let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+            if options.telegraph {
+                let text = lyric::render(
+                    &lyric,
+                    &lyric::LyricOptions {
+                        plain: true,
+                        ..options
+                    },
+                );
+                let page = LyricPage {
+                    title: &song_detail.name,
+                    artists: &artists,
+                    album: song_detail.al.as_ref().map_or("", |al| al.name.as_str()),
+                    cover_url: song_detail.al.as_ref().and_then(|al| al.pic_url.as_deref()),
+                    lyrics: &text,
+                };
+                let reply = match state
+                    .telegraph
+                    .publish_lyrics(&page, &state.bot_username)
+                    .await
+                {
+                    Ok(url) => format!("🎤 {} - {}\n{url}", song_detail.name, artists),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to publish lyrics of {} to Telegraph: {}",
+                            music_id,
+                            e
+                        );
+                        format!("发布歌词到 Telegraph 失败: {e}")
+                    }
+                };
+                bot.edit_message_text(msg.chat.id, status_msg.id, reply)
+                    .await?;
+                return Ok(());
+            }
+            let lrc_filename = clean_filename(&format!(
+                "{} - {}.{}",
+                artists,
+                song_detail.name,
+                options.format.extension()
+            ));
+            let lrc_path = format!("{}/{}", state.config.cache_dir, lrc_filename);
+
+            // Karaoke output needs an LRC file with timestamps; songs without
+            // word-level lyrics fall back to regular LRC
+            let karaoke =
+                if options.karaoke && !options.plain && options.format == lyric::LyricFormat::Lrc {
+                    match state.music_api.get_song_yrc(music_id).await {
+                        Ok(yrc) => yrc,
+                        Err(e) => {
+                            tracing::warn!("Failed to fetch yrc lyrics of {}: {}", music_id, e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+            let content = match karaoke {
+                Some(yrc) => lyric::yrc_to_enhanced_lrc(&yrc, options.offset_ms),
+                None => lyric::render(&lyric, &options),
+            };
+            tokio::fs::write(&lrc_path, content)
+                .await
+                .map_err(|e| RequestError::Io(Arc::new(e)))?;
+
+            bot.send_document(
+                msg.chat.id,
+                InputFile::file(std::path::Path::new(&lrc_path)),
+            )
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+
+            tokio::fs::remove_file(&lrc_path).await.ok();
+            bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("获取歌词失败: {e}"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_status_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+    let chat_id = msg.chat.id.0;
+
+    let total_count = state.database.count_total_songs().await.unwrap_or(0);
+    let user_count = state
+        .database
+        .count_songs_from_user(user_id)
+        .await
+        .unwrap_or(0);
+    let chat_count = state
+        .database
+        .count_songs_from_chat(chat_id)
+        .await
+        .unwrap_or(0);
+
+    let quota_line = match get_quota_status(state, msg.chat.id, msg.from.as_ref())
+        .await
+        .remaining()
+    {
+        Some(remaining) => format!("📅 今日剩余下载额度: {remaining}\n"),
+        None => String::new(),
+    };
+
+    let checkin_line = match state.last_checkin.lock().await.as_ref() {
+        Some(report) => format!(
+            "✅ 每日签到: {}\n",
+            teloxide::utils::markdown::escape(&report.summary())
+        ),
+        None => String::new(),
+    };
+
+    let endpoints_line = endpoint_ranking(state);
+    let throughput_lines = [
+        ("⬇️ 下载", &state.transfers.downloads),
+        ("⬆️ 上传", &state.transfers.uploads),
+    ]
+    .into_iter()
+    .filter_map(|(label, history)| {
+        history.throughput().map(|t| {
+            teloxide::utils::markdown::escape(&format!(
+                "{label}速度 p50 {:.2} MB/s · p95 {:.2} MB/s (近 {} 次)\n",
+                t.p50_mbps, t.p95_mbps, t.samples
+            ))
+        })
+    })
+    .collect::<String>();
+
+    let status_text = format!(
+        r"📊 *统计信息*
+
+🎵 数据库中总缓存歌曲数量: {total_count}
+👤 当前用户缓存歌曲数量: {user_count}
+💬 当前对话缓存歌曲数量: {chat_count}
+{quota_line}{checkin_line}{throughput_lines}{endpoints_line}
+🤖 Bot 运行状态: 正常
+🦀 语言: Rust
+⚡ 框架: Teloxide
+"
+    );
+
+    bot.send_message(msg.chat.id, status_text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// Ranking of the `music.api` endpoints for /status (MarkdownV2), when there are several
+fn endpoint_ranking(state: &BotState) -> String {
+    let ranking = state.music_api.endpoints().ranking();
+    if ranking.len() < 2 {
+        return String::new();
+    }
+    let lines = ranking
+        .iter()
+        .enumerate()
+        .map(|(index, health)| {
+            let p95 = health
+                .p95
+                .map_or_else(|| "-".to_string(), |p95| format!("{}ms", p95.as_millis()));
+            teloxide::utils::markdown::escape(&format!(
+                "{}. {} p95 {} · 错误率 {:.0}% ({} 次)",
+                index + 1,
+                health.url,
+                p95,
+                health.error_rate * 100.0,
+                health.samples
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("🌐 API 节点排名:\n{lines}\n")
+}
+
+/// Chat setting key for silent audio delivery
+const SILENT_SETTING_KEY: &str = "silent";
+
+/// Resolve whether audio sent to this chat should skip notifications
+/// (per-chat setting first, then the global `bot.silent` default)
+async fn is_silent_chat(state: &Arc<BotState>, chat_id: ChatId) -> bool {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, SILENT_SETTING_KEY)
+        .await
+    {
+        Ok(Some(value)) => value == "on",
+        Ok(None) => state.config.silent,
+        Err(e) => {
+            tracing::warn!("Failed to read silent setting for chat {}: {}", chat_id, e);
+            state.config.silent
+        }
+    }
+}
+
+/// Check whether the sender may change settings of the current chat
+/// (anyone in private chats; group admins or bot admins in groups)
+async fn can_manage_chat_settings(bot: &Bot, msg: &Message, state: &Arc<BotState>) -> bool {
+    if msg.chat.is_private() {
+        return true;
+    }
+
+    let Some(user) = msg.from.as_ref() else {
+        return false;
+    };
+
+    if state.config.bot_admin.contains(&(user.id.0 as i64)) {
+        return true;
+    }
+
+    match bot.get_chat_member(msg.chat.id, user.id).await {
+        Ok(member) => member.is_privileged(),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to get chat member {} in {}: {}",
+                user.id,
+                msg.chat.id,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Remember a song delivered to `user` in `chat_id` (for personalized inline results)
+fn log_delivery(state: &Arc<BotState>, chat_id: ChatId, user: Option<&User>, music_id: u64) {
+    let Some(user) = user.filter(|user| !user.is_bot) else {
+        return;
+    };
+    let user_id = user.id.0 as i64;
+    let chat_id = chat_id.0;
+    let database = state.database.clone();
+    tokio::spawn(async move {
+        if let Err(e) = database.log_send(user_id, chat_id, music_id as i64).await {
+            tracing::warn!("Failed to log delivery of {}: {}", music_id, e);
+        }
+    });
+}
+
+/// Scrobble a song delivered to `user` to their enabled accounts, in the
+/// background (album/playlist tracks are not scrobbled)
+fn scrobble_delivery(state: &Arc<BotState>, user: Option<&User>, track: scrobble::Track) {
+    let Some(scrobbler) = state.scrobbler.clone() else {
+        return;
+    };
+    let Some(user) = user.filter(|user| !user.is_bot) else {
+        return;
+    };
+    let user_id = user.id.0 as i64;
+    let database = state.database.clone();
+    let listened_at = chrono::Utc::now().timestamp();
+
+    tokio::spawn(async move {
+        let accounts = match database.get_scrobble_accounts(user_id).await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                tracing::warn!("Failed to load scrobble accounts of {}: {}", user_id, e);
+                return;
+            }
+        };
+        for account in accounts.into_iter().filter(|account| account.enabled) {
+            let Ok(service) = account.service.parse::<Service>() else {
+                continue;
+            };
+            let result = match scrobbler.cipher.decrypt(&account.credential) {
+                Ok(credential) => {
+                    scrobbler
+                        .submit(service, &credential, &track, listened_at)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::warn!(
+                    "Failed to scrobble to {} for user {}: {}",
+                    service.label(),
+                    user_id,
+                    e
+                );
+            }
+        }
+    });
+}
+
+async fn handle_scrobble_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let Some(scrobbler) = state.scrobbler.as_ref() else {
+        bot.send_message(msg.chat.id, "⚠️ 机器人未启用 Scrobble 功能")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let user_id = user.id.0 as i64;
+    let args = args.unwrap_or_default();
+    let words: Vec<&str> = args.split_whitespace().collect();
+
+    let reply = match words.as_slice() {
+        [] => match state.database.get_scrobble_accounts(user_id).await {
+            Ok(accounts) if accounts.is_empty() => "尚未绑定账号\n\n\
+                用法 (请在私聊中发送):\n\
+                /scrobble listenbrainz <用户令牌>\n\
+                /scrobble lastfm <用户名> <密码>\n\
+                /scrobble on|off 开启/暂停\n\
+                /scrobble unlink 解除绑定"
+                .to_string(),
+            Ok(accounts) => {
+                let mut text = String::from("🎧 已绑定的 Scrobble 账号:\n");
+                for account in &accounts {
+                    let Ok(service) = account.service.parse::<Service>() else {
+                        continue;
+                    };
+                    let status = if account.enabled {
+                        "开启"
+                    } else {
+                        "已暂停"
+                    };
+                    std::fmt::write(&mut text, format_args!("• {}: {status}\n", service.label()))
+                        .unwrap();
+                }
+                text
+            }
+            Err(e) => format!("❌ 查询失败: {e}"),
+        },
+        [toggle @ ("on" | "off")] => {
+            match state
+                .database
+                .set_scrobble_enabled(user_id, *toggle == "on")
+                .await
+            {
+                Ok(0) => "尚未绑定账号".to_string(),
+                Ok(_) if *toggle == "on" => "✅ 已开启 Scrobble".to_string(),
+                Ok(_) => "⏸ 已暂停 Scrobble".to_string(),
+                Err(e) => format!("❌ 设置失败: {e}"),
+            }
+        }
+        ["unlink"] => match state.database.delete_scrobble_accounts(user_id).await {
+            Ok(0) => "尚未绑定账号".to_string(),
+            Ok(_) => "✅ 已解除绑定并删除保存的凭据".to_string(),
+            Err(e) => format!("❌ 解除绑定失败: {e}"),
+        },
+        [service, credentials @ ..] if service.parse::<Service>().is_ok() => {
+            // Credentials must not linger in groups or the chat history
+            if !msg.chat.is_private() {
+                bot.delete_message(msg.chat.id, msg.id).await.ok();
+                bot.send_message(msg.chat.id, "⚠️ 请在私聊中绑定账号，刚才的消息已删除")
+                    .await?;
+                return Ok(());
+            }
+            bot.delete_message(msg.chat.id, msg.id).await.ok();
+            let service = service.parse::<Service>().unwrap_or(Service::ListenBrainz);
+            let credential = match (service, credentials) {
+                (Service::ListenBrainz, [token]) => Ok((*token).to_string()),
+                (Service::LastFm, [username, password]) if scrobbler.lastfm_available() => {
+                    scrobbler.lastfm_session(username, password).await
+                }
+                (Service::LastFm, [_, _]) => Err(BotError::Config(
+                    "机器人未配置 Last.fm API 账号".to_string(),
+                )),
+                (Service::ListenBrainz, _) => Err(BotError::Parse(
+                    "用法: /scrobble listenbrainz <用户令牌>".to_string(),
+                )),
+                (Service::LastFm, _) => Err(BotError::Parse(
+                    "用法: /scrobble lastfm <用户名> <密码>".to_string(),
+                )),
+            };
+            let stored = match credential {
+                Ok(credential) => match scrobbler.cipher.encrypt(&credential) {
+                    Ok(encrypted) => {
+                        state
+                            .database
+                            .set_scrobble_account(user_id, service.as_str(), &encrypted)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            match stored {
+                Ok(()) => format!(
+                    "✅ 已绑定 {}，之后通过机器人获取的歌曲会同步记录",
+                    service.label()
+                ),
+                Err(e) => format!("❌ 绑定 {} 失败: {e}", service.label()),
+            }
+        }
+        _ => {
+            "用法: /scrobble [on|off|unlink|listenbrainz <令牌>|lastfm <用户名> <密码>]".to_string()
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn handle_deletemydata_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let user_id = user.id.0 as i64;
+
+    if !args.is_some_and(|args| args.eq_ignore_ascii_case("confirm")) {
+        let mut text = String::from(
+            "🗑 将删除机器人保存的你的个人数据:\n\
+             • 获取歌曲记录 (个性化 Inline 结果)\n\
+             • Last.fm/ListenBrainz 绑定\n\
+             • 猜歌积分与往日下载计数 (今日计数次日自动失效)\n\
+             • 与机器人私聊的对话设置\n\
+             你获取并缓存的歌曲和提交的反馈会保留，但不再关联你的 ID 和名字。\n",
+        );
+        if matches!(state.database.premium_expiry(user_id).await, Ok(Some(_))) {
+            text.push_str("\n⚠️ 你的高级会员记录也会删除，剩余时长将作废。\n");
+        }
+        text.push_str("\n此操作无法撤销，确认请发送 /deletemydata confirm");
+        bot.send_message(msg.chat.id, text)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let reply = match state.database.delete_user_data(user_id, &usage_day()).await {
+        Ok((changed, music_ids)) => {
+            tracing::info!(
+                "Deleted personal data of user {} ({} rows)",
+                user_id,
+                changed
+            );
+            // Redis still holds copies of the songs with the user's name
+            if let Some(hot_cache) = &state.hot_cache {
+                for music_id in music_ids {
+                    if let Err(e) = hot_cache.remove(music_id).await {
+                        tracing::warn!("Failed to drop {} from redis: {}", music_id, e);
+                    }
+                }
+            }
+            "✅ 已删除你的所有个人数据".to_string()
+        }
+        Err(e) => {
+            tracing::error!("Failed to delete data of user {}: {}", user_id, e);
+            format!("❌ 删除失败: {e}")
+        }
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_silent_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_lowercase();
+
+    if args.is_empty() {
+        let silent = is_silent_chat(state, msg.chat.id).await;
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "🔕 静默发送: {}\n\n用法: /silent on|off|default",
+                if silent { "开启" } else { "关闭" }
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, "❌ 只有群组管理员可以修改此设置")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = match args.as_str() {
+        "on" | "off" => {
+            state
+                .database
+                .set_chat_setting(msg.chat.id.0, SILENT_SETTING_KEY, &args)
+                .await
+        }
+        "default" => state
+            .database
+            .delete_chat_setting(msg.chat.id.0, SILENT_SETTING_KEY)
+            .await
+            .map(|_| ()),
+        _ => {
+            bot.send_message(msg.chat.id, "用法: /silent on|off|default")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match result {
+        Ok(()) => {
+            let silent = is_silent_chat(state, msg.chat.id).await;
+            format!("✅ 静默发送已{}", if silent { "开启" } else { "关闭" })
+        }
+        Err(e) => format!("❌ 保存设置失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// Chat setting key for the caption style of audio messages
+const CAPTION_STYLE_SETTING_KEY: &str = "caption_style";
+
+/// Resolve the caption style for this chat (per-chat setting first, then
+/// the global `bot.caption_style` default)
+async fn chat_caption_style(state: &Arc<BotState>, chat_id: ChatId) -> CaptionStyle {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, CAPTION_STYLE_SETTING_KEY)
+        .await
+    {
+        Ok(Some(value)) => value.parse().unwrap_or(state.config.caption_style),
+        Ok(None) => state.config.caption_style,
+        Err(e) => {
+            tracing::warn!("Failed to read caption style for chat {}: {}", chat_id, e);
+            state.config.caption_style
+        }
+    }
+}
+
+/// Chat setting key for caption hashtags (space separated, empty = none)
+const HASHTAGS_SETTING_KEY: &str = "hashtags";
+
+/// Resolve the caption hashtags for this chat (per-chat setting first, then
+/// the global `bot.hashtags` default)
+async fn chat_hashtags(state: &Arc<BotState>, chat_id: ChatId) -> Vec<String> {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, HASHTAGS_SETTING_KEY)
+        .await
+    {
+        Ok(Some(value)) => parse_hashtags(&value),
+        Ok(None) => state.config.hashtags.clone(),
+        Err(e) => {
+            tracing::warn!("Failed to read hashtags for chat {}: {}", chat_id, e);
+            state.config.hashtags.clone()
+        }
+    }
+}
+
+/// Caption for `song` using this chat's caption style and hashtags
+async fn chat_caption(
+    state: &Arc<BotState>,
+    msg: &Message,
+    song: &SongInfo,
+    bitrate_bps: i64,
+) -> String {
+    let style = chat_caption_style(state, msg.chat.id).await;
+    let hashtags = if style == CaptionStyle::Full {
+        chat_hashtags(state, msg.chat.id).await
+    } else {
+        Vec::new()
+    };
+    let locale = message_locale(state, msg).await;
+    build_caption(
+        style,
+        song,
+        bitrate_bps,
+        &hashtags,
+        &state.bot_username,
+        locale,
+    )
+}
+
+/// Chat setting key for the pinned reply language
+const LANGUAGE_SETTING_KEY: &str = "language";
+
+/// Resolve the reply language (per-chat `/lang` setting first, then the
+/// sender's Telegram client language)
+async fn chat_locale(state: &BotState, chat_id: ChatId, language_code: Option<&str>) -> Locale {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, LANGUAGE_SETTING_KEY)
+        .await
+    {
+        Ok(Some(value)) => value
+            .parse()
+            .unwrap_or_else(|_| Locale::from_language_code(language_code)),
+        Ok(None) => Locale::from_language_code(language_code),
+        Err(e) => {
+            tracing::warn!("Failed to read language for chat {}: {}", chat_id, e);
+            Locale::from_language_code(language_code)
+        }
+    }
+}
+
+/// Reply language for `msg`
+async fn message_locale(state: &BotState, msg: &Message) -> Locale {
+    let language_code = msg
+        .from
+        .as_ref()
+        .and_then(|user| user.language_code.as_deref());
+    chat_locale(state, msg.chat.id, language_code).await
+}
+
+async fn handle_lang_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_lowercase();
+    let locale = message_locale(state, msg).await;
+
+    if args.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "{}\n{}\n\n{}",
+                Text::LanguageCurrent.get(locale),
+                Text::LanguageScope.get(locale),
+                Text::LanguageUsage.get(locale)
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, Text::AdminOnly.get(locale))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = if args == "default" {
+        state
+            .database
+            .delete_chat_setting(msg.chat.id.0, LANGUAGE_SETTING_KEY)
+            .await
+            .map(|_| ())
+    } else if let Ok(locale) = args.parse::<Locale>() {
+        state
+            .database
+            .set_chat_setting(msg.chat.id.0, LANGUAGE_SETTING_KEY, locale.as_str())
+            .await
+    } else {
+        bot.send_message(msg.chat.id, Text::LanguageUsage.get(locale))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
+    let reply = match result {
+        Ok(()) => Text::LanguageSet
+            .get(message_locale(state, msg).await)
+            .to_string(),
+        Err(e) => format!("{}: {e}", Text::SaveSettingFailed.get(locale)),
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// Seconds of audio in a `/quiz` clip
+const QUIZ_CLIP_SECS: u64 = 10;
+/// Players listed by `/quiz top`
+const QUIZ_LEADERBOARD: i64 = 10;
+
+async fn handle_quiz_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    const USAGE: &str = "用法: /quiz [clip|cover|top]";
+
+    if msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "❌ 猜歌游戏仅在群组中可用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let args = args.unwrap_or_default().trim().to_lowercase();
+    if args == "top" {
+        return handle_quiz_leaderboard(bot, msg, state).await;
+    }
+    let hint = if args.is_empty() {
+        if state.config.ffmpeg_enabled {
+            Hint::Clip
+        } else {
+            Hint::Cover
+        }
+    } else if let Ok(hint) = args.parse::<Hint>() {
+        hint
+    } else {
+        bot.send_message(msg.chat.id, USAGE)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+    if hint == Hint::Clip && !state.config.ffmpeg_enabled {
+        bot.send_message(msg.chat.id, "⚠️ 片段截取未启用，可使用 /quiz cover")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let song = match state.database.random_song().await {
+        Ok(Some(song)) => song,
+        Ok(None) => {
+            bot.send_message(msg.chat.id, "❌ 缓存中还没有歌曲")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 出题失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let round = Round::new(song.music_id, &song.song_name, &song.song_artists);
+    let round_id = round.id;
+    if !state.quiz.start(msg.chat.id.0, round).await {
+        bot.send_message(msg.chat.id, "❓ 本群已有一轮猜歌正在进行")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🎲 正在出题...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    let prompt = format!(
+        "❓ 猜猜这是哪首歌？{} 秒内直接发送歌名作答",
+        state.config.quiz_round_secs
+    );
+    let sent = match hint {
+        Hint::Clip => send_quiz_clip(bot, msg, state, &song, &prompt).await,
+        Hint::Cover => send_quiz_cover(bot, msg, state, &song, &prompt).await,
+    };
+    if let Err(e) = sent {
+        tracing::warn!("Failed to post quiz for {}: {}", song.music_id, e);
+        state.quiz.cancel(msg.chat.id.0).await;
+        bot.edit_message_text(msg.chat.id, status_msg.id, format!("❌ 出题失败: {e}"))
+            .await?;
+        return Ok(());
+    }
+    bot.delete_message(msg.chat.id, status_msg.id).await.ok();
+
+    let bot = bot.clone();
+    let state = state.clone();
+    let chat_id = msg.chat.id;
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(state.config.quiz_round_secs)).await;
+        if let Some(round) = state.quiz.expire(chat_id.0, round_id).await {
+            bot.send_message(
+                chat_id,
+                format!("⏰ 时间到！答案是: {} - {}", round.title, round.artists),
+            )
+            .await
+            .ok();
+        }
+    });
+
+    Ok(())
+}
+
+/// Post a metadata-free clip from a random point of the song
+async fn send_quiz_clip(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    song: &SongInfo,
+    prompt: &str,
+) -> Result<()> {
+    let duration = u64::try_from(song.duration).unwrap_or(0);
+    // Start somewhere in the middle of the song, away from intros and fade-outs
+    let start = if duration > QUIZ_CLIP_SECS * 3 {
+        duration / 5 + quiz::random_below(duration * 3 / 5 - QUIZ_CLIP_SECS)
+    } else {
+        0
+    };
+    let clip_path = {
+        let _permit = state.download_semaphore.acquire().await.unwrap();
+        state.activity.touch();
+        create_clip_file(state, song.music_id as u64, start, QUIZ_CLIP_SECS).await?
+    };
+    let result = bot
+        .send_audio(
+            msg.chat.id,
+            InputFile::file(&clip_path).file_name("quiz.mp3"),
+        )
+        .caption(prompt)
+        .title("???")
+        .performer("???")
+        .duration(QUIZ_CLIP_SECS as u32)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await;
+    tokio::fs::remove_file(&clip_path).await.ok();
+    result?;
+    Ok(())
+}
+
+/// Post the song's cover, blurred
+async fn send_quiz_cover(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    song: &SongInfo,
+    prompt: &str,
+) -> Result<()> {
+    let detail = state
+        .music_api
+        .get_song_detail(song.music_id as u64)
+        .await?;
+    let pic_url = detail
+        .al
+        .as_ref()
+        .and_then(|al| al.pic_url.as_deref())
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "Song has no cover art"))?;
+    let cover = state.music_api.download_album_art_original(pic_url).await?;
+    let blurred = tokio::task::spawn_blocking(move || quiz::blur_cover(&cover))
+        .await
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Cover blurring task failed: {e}")))??;
+    bot.send_photo(
+        msg.chat.id,
+        InputFile::memory(blurred).file_name("quiz.jpg"),
+    )
+    .caption(prompt)
+    .reply_parameters(ReplyParameters::new(msg.id))
+    .await?;
+    Ok(())
+}
+
+/// Credit the sender of the correct answer
+async fn finish_quiz_round(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    round: &Round,
+) -> ResponseResult<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let name = user_display_name(user);
+    let score = match state
+        .database
+        .add_quiz_point(msg.chat.id.0, user.id.0 as i64, &name)
+        .await
+    {
+        Ok(score) => format!(" (累计 {score} 分)"),
+        Err(e) => {
+            tracing::warn!("Failed to save quiz score: {}", e);
+            String::new()
+        }
+    };
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🎉 {name} 答对了{score}！\n答案: {} - {}\n\n发送 /netease {} 获取这首歌",
+            round.title, round.artists, round.music_id
+        ),
+    )
+    .reply_parameters(ReplyParameters::new(msg.id))
+    .await?;
+    Ok(())
+}
+
+async fn handle_quiz_leaderboard(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    let reply = match state
+        .database
+        .quiz_leaderboard(msg.chat.id.0, QUIZ_LEADERBOARD)
+        .await
+    {
+        Ok(scores) if scores.is_empty() => "🏆 本群还没有人得分，发送 /quiz 开始猜歌".to_string(),
+        Ok(scores) => {
+            let mut text = String::from("🏆 猜歌排行榜\n\n");
+            for (i, (name, score)) in scores.iter().enumerate() {
+                std::fmt::write(
+                    &mut text,
+                    format_args!("{}. {} - {} 分\n", i + 1, name, score),
+                )
+                .unwrap();
+            }
+            text
+        }
+        Err(e) => format!("❌ 读取排行榜失败: {e}"),
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Invoice payload of premium purchases
+const PREMIUM_PAYLOAD: &str = "premium";
+/// Telegram Stars
+const PREMIUM_CURRENCY: &str = "XTR";
+/// Highest bitrate for users without premium under `premium.lossless_only`
+const LOSSY_MAX_BITRATE: u64 = 320_000;
+
+/// Whether a user has bought premium that has not expired (admins always have it)
+async fn is_premium_user(state: &Arc<BotState>, user_id: i64) -> bool {
+    if state.config.bot_admin.contains(&user_id) {
+        return true;
+    }
+    if !state.config.premium_enabled || user_id == 0 {
+        return false;
+    }
+    match state.database.premium_expiry(user_id).await {
+        Ok(expiry) => expiry.is_some(),
+        Err(e) => {
+            tracing::warn!("Failed to read premium status of {}: {}", user_id, e);
+            false
+        }
+    }
+}
+
+async fn handle_premium_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !state.config.premium_enabled {
+        bot.send_message(msg.chat.id, "⚠️ 本机器人未开启高级会员")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+    if !msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "请在与机器人的私聊中发送 /premium")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let mut text = match state.database.premium_expiry(user.id.0 as i64).await {
+        Ok(Some(expiry)) => format!("⭐ 高级会员有效期至 {expiry} (UTC)，续费将顺延\n\n"),
+        _ => "⭐ 你还不是高级会员\n\n".to_string(),
+    };
+    text.push_str("会员权益:\n");
+    if state.config.premium_daily_limit == 0 {
+        text.push_str("• 每日下载不限量\n");
+    } else {
+        std::fmt::write(
+            &mut text,
+            format_args!(
+                "• 每日可下载 {} 首新歌曲\n",
+                state.config.premium_daily_limit
+            ),
+        )
+        .unwrap();
+    }
+    if state.config.premium_lossless_only {
+        text.push_str("• 无损音质下载\n");
+    }
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    let days = state.config.premium_days;
+    bot.send_invoice(
+        msg.chat.id,
+        "高级会员",
+        format!("{days} 天高级会员，付款后立即生效"),
+        PREMIUM_PAYLOAD,
+        PREMIUM_CURRENCY,
+        vec![LabeledPrice::new(
+            format!("{days} 天高级会员"),
+            state.config.premium_price_stars,
+        )],
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Approve checkouts of the current premium offer only (the price may have
+/// changed since the invoice was sent)
+async fn handle_pre_checkout_query(
+    bot: Bot,
+    query: PreCheckoutQuery,
+    state: Arc<BotState>,
+) -> ResponseResult<()> {
+    let valid = state.config.premium_enabled
+        && query.invoice_payload == PREMIUM_PAYLOAD
+        && query.currency == PREMIUM_CURRENCY
+        && query.total_amount == state.config.premium_price_stars;
+    let answer = bot.answer_pre_checkout_query(query.id, valid);
+    if valid {
+        answer.await?;
+    } else {
+        tracing::warn!(
+            "Rejected checkout of {} {} ({}) from {}",
+            query.total_amount,
+            query.currency,
+            query.invoice_payload,
+            query.from.id
+        );
+        answer
+            .error_message("价格已变更或已停止销售，请重新发送 /premium")
+            .await?;
+    }
+    Ok(())
+}
+
+async fn handle_successful_payment(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    payment: &SuccessfulPayment,
+) -> ResponseResult<()> {
+    if payment.invoice_payload != PREMIUM_PAYLOAD {
+        return Ok(());
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let charge_id = &payment.telegram_payment_charge_id.0;
+    tracing::info!(
+        "Premium payment of {} {} from {} ({})",
+        payment.total_amount,
+        payment.currency,
+        user.id,
+        charge_id
+    );
+
+    let reply = match state
+        .database
+        .extend_premium(user.id.0 as i64, state.config.premium_days, charge_id)
+        .await
+    {
+        Ok(expiry) => format!("🎉 付款成功！高级会员有效期至 {expiry} (UTC)"),
+        Err(e) => {
+            tracing::error!(
+                "Failed to record premium payment {} of {}: {}",
+                charge_id,
+                user.id,
+                e
+            );
+            format!("❌ 已收到付款但记录失败，请联系管理员并提供付款编号: {charge_id}")
+        }
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+/// Chat setting key for the group jukebox (`/jukebox on|off`)
+const JUKEBOX_SETTING_KEY: &str = "jukebox";
+/// Upcoming songs listed by `/nowplaying`
+const JUKEBOX_PREVIEW: usize = 10;
+
+async fn is_jukebox_chat(state: &Arc<BotState>, chat_id: ChatId) -> bool {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, JUKEBOX_SETTING_KEY)
+        .await
+    {
+        Ok(value) => value.as_deref() == Some("on"),
+        Err(e) => {
+            tracing::warn!("Failed to read jukebox setting for chat {}: {}", chat_id, e);
+            false
+        }
+    }
+}
+
+/// Reply why the jukebox can't be used here, or return `true` when it can
+async fn ensure_jukebox_chat(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<bool> {
+    let reply = if msg.chat.is_private() {
+        "❌ 点歌台仅在群组中可用"
+    } else if !is_jukebox_chat(state, msg.chat.id).await {
+        "📻 本群未开启点歌台，群组管理员可使用 /jukebox on 开启"
+    } else {
+        return Ok(true);
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(false)
+}
+
+async fn handle_jukebox_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_lowercase();
+
+    if msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "❌ 点歌台仅在群组中可用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    if args.is_empty() {
+        let enabled = is_jukebox_chat(state, msg.chat.id).await;
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "📻 点歌台: {}\n\n用法: /jukebox on|off",
+                if enabled { "开启" } else { "关闭" }
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, "❌ 只有群组管理员可以修改此设置")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = match args.as_str() {
+        "on" => {
+            state
+                .database
+                .set_chat_setting(msg.chat.id.0, JUKEBOX_SETTING_KEY, "on")
+                .await
+        }
+        "off" => {
+            state.jukebox.clear(msg.chat.id.0).await;
+            state
+                .database
+                .delete_chat_setting(msg.chat.id.0, JUKEBOX_SETTING_KEY)
+                .await
+                .map(|_| ())
+        }
+        _ => {
+            bot.send_message(msg.chat.id, "用法: /jukebox on|off")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match result {
+        Ok(()) if args == "on" => format!(
+            "✅ 点歌台已开启，使用 /queue <关键词或ID> 点歌，每 {} 秒发送一首",
+            state.config.jukebox_interval_secs
+        ),
+        Ok(()) => "✅ 点歌台已关闭，队列已清空".to_string(),
+        Err(e) => format!("❌ 保存设置失败: {e}"),
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// `/queue` without arguments lists the queue; otherwise it requests a song
+async fn handle_queue_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_jukebox_chat(bot, msg, state).await? {
+        return Ok(());
+    }
+    if args.is_none() && msg.reply_to_message().is_none() {
+        return handle_nowplaying_command(bot, msg, state).await;
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let Some(music_id) = resolve_music_id(bot, msg, state, args.as_deref()).await? else {
+        return Ok(());
+    };
+    let title = match state.music_api.get_song_detail(music_id).await {
+        Ok(detail) => format!(
+            "{} - {}",
+            detail.name,
+            format_artists(detail.ar.as_deref().unwrap_or(&[]))
+        ),
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 获取歌曲信息失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let song = QueuedSong {
+        music_id,
+        title: title.clone(),
+        requested_by: user_display_name(user),
+        requester_id: user.id.0 as i64,
+        request: msg.clone(),
+    };
+    let reply = match state.jukebox.enqueue(msg.chat.id.0, song).await {
+        Enqueued::Queued {
+            position,
+            start_player,
+        } => {
+            if start_player {
+                worker_pool::spawn_job(
+                    "Jukebox player",
+                    run_jukebox(bot.clone(), state.clone(), msg.chat.id),
+                );
+            }
+            format!("📻 已加入点歌队列 #{position}: {title}")
+        }
+        Enqueued::Full => format!(
+            "❌ 点歌队列已满 ({} 首)，请稍后再点",
+            crate::jukebox::MAX_QUEUE
+        ),
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// Play a chat's queue until it runs empty
+async fn run_jukebox(bot: Bot, state: Arc<BotState>, chat_id: ChatId) {
+    let skip = state.jukebox.skip_signal(chat_id.0).await;
+    let interval = std::time::Duration::from_secs(state.config.jukebox_interval_secs);
+    while let Some(song) = state.jukebox.advance(chat_id.0).await {
+        if let Err(e) = Box::pin(process_music_into(
+            &bot,
+            &song.request,
+            &state,
+            song.music_id,
+            None,
+            DownloadOverrides::default(),
+        ))
+        .await
+        {
+            tracing::warn!(
+                "Jukebox failed to play {} in chat {}: {}",
+                song.music_id,
+                chat_id,
+                e
+            );
+        }
+        tokio::select! {
+            () = skip.notified() => {}
+            () = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+fn format_queue(view: &QueueView) -> String {
+    let mut text = String::new();
+    match &view.now_playing {
+        Some(song) => {
+            std::fmt::write(
+                &mut text,
+                format_args!(
+                    "▶️ 正在播放: {} (点歌人: {})\n",
+                    song.title, song.requested_by
+                ),
+            )
+            .unwrap();
+        }
+        None => text.push_str("⏹ 当前没有正在播放的歌曲\n"),
+    }
+    if view.pending.is_empty() {
+        text.push_str("\n队列为空，使用 /queue <关键词或ID> 点歌");
+        return text;
+    }
+    std::fmt::write(
+        &mut text,
+        format_args!("\n📻 队列 ({} 首):\n", view.pending.len()),
+    )
+    .unwrap();
+    for (i, song) in view.pending.iter().take(JUKEBOX_PREVIEW).enumerate() {
+        std::fmt::write(
+            &mut text,
+            format_args!("{}. {} ({})\n", i + 1, song.title, song.requested_by),
+        )
+        .unwrap();
+    }
+    if view.pending.len() > JUKEBOX_PREVIEW {
+        std::fmt::write(
+            &mut text,
+            format_args!("... 还有 {} 首", view.pending.len() - JUKEBOX_PREVIEW),
+        )
+        .unwrap();
+    }
+    text
+}
+
+async fn handle_nowplaying_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_jukebox_chat(bot, msg, state).await? {
+        return Ok(());
+    }
+    let view = state.jukebox.view(msg.chat.id.0).await;
+    bot.send_message(msg.chat.id, format_queue(&view))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Skip the current song (its requester or group admins)
+async fn handle_skip_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_jukebox_chat(bot, msg, state).await? {
+        return Ok(());
+    }
+    let view = state.jukebox.view(msg.chat.id.0).await;
+    let reply = match view.now_playing {
+        None => "⏹ 当前没有正在播放的歌曲".to_string(),
+        Some(current)
+            if msg.from.as_ref().map(|user| user.id.0 as i64) != Some(current.requester_id)
+                && !can_manage_chat_settings(bot, msg, state).await =>
+        {
+            "❌ 只有点歌人或群组管理员可以跳过".to_string()
+        }
+        Some(_) => match state.jukebox.skip(msg.chat.id.0).await {
+            Some(skipped) => format!("⏭ 已跳过: {}", skipped.title),
+            None => "⏹ 当前没有正在播放的歌曲".to_string(),
+        },
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Chat setting key for the cover mode of new downloads
+const COVER_MODE_SETTING_KEY: &str = "cover_mode";
+
+/// Resolve the cover mode for downloads requested in this chat (per-chat
+/// setting first, then the global `download.cover_mode` default)
+async fn chat_cover_mode(state: &Arc<BotState>, chat_id: ChatId) -> CoverMode {
+    match state
+        .database
+        .get_chat_setting(chat_id.0, COVER_MODE_SETTING_KEY)
+        .await
+    {
+        Ok(Some(value)) => value.parse().unwrap_or(state.config.cover_mode),
+        Ok(None) => state.config.cover_mode,
+        Err(e) => {
+            tracing::warn!("Failed to read cover mode for chat {}: {}", chat_id, e);
+            state.config.cover_mode
+        }
+    }
+}
+
+fn cover_mode_label(mode: CoverMode) -> &'static str {
+    match mode {
+        CoverMode::Thumbnail => "缩略图 (thumbnail)",
+        CoverMode::Original => "嵌入原图 (original)",
+        CoverMode::Both => "缩略图 + 嵌入原图 (both)",
+        CoverMode::None => "无封面 (none)",
+    }
+}
+
+async fn handle_cover_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_lowercase();
+    let usage = "用法: /cover original|thumbnail|both|none|default\n单次下载也可使用 /music <ID> --cover=original";
+
+    if args.is_empty() {
+        let mode = chat_cover_mode(state, msg.chat.id).await;
+        bot.send_message(
+            msg.chat.id,
+            format!("🖼️ 封面模式: {}\n\n{usage}", cover_mode_label(mode)),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, "❌ 只有群组管理员可以修改此设置")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = if args == "default" {
+        state
+            .database
+            .delete_chat_setting(msg.chat.id.0, COVER_MODE_SETTING_KEY)
+            .await
+            .map(|_| ())
+    } else if args.parse::<CoverMode>().is_ok() {
+        state
+            .database
+            .set_chat_setting(msg.chat.id.0, COVER_MODE_SETTING_KEY, &args)
+            .await
+    } else {
+        bot.send_message(msg.chat.id, usage)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
+    let reply = match result {
+        Ok(()) => {
+            let mode = chat_cover_mode(state, msg.chat.id).await;
+            format!(
+                "✅ 封面模式已设为: {}\n仅对之后新下载的歌曲生效，已缓存的歌曲不变",
+                cover_mode_label(mode)
+            )
+        }
+        Err(e) => format!("❌ 保存设置失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+fn caption_style_label(style: CaptionStyle) -> &'static str {
+    match style {
+        CaptionStyle::Full => "完整",
+        CaptionStyle::Compact => "精简 (仅歌名 - 歌手)",
+        CaptionStyle::None => "无",
+    }
+}
+
+async fn handle_caption_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_lowercase();
+
+    if args.is_empty() {
+        let style = chat_caption_style(state, msg.chat.id).await;
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "📝 说明文字样式: {}\n\n用法: /caption full|compact|none|default",
+                caption_style_label(style)
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, "❌ 只有群组管理员可以修改此设置")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = if args == "default" {
+        state
+            .database
+            .delete_chat_setting(msg.chat.id.0, CAPTION_STYLE_SETTING_KEY)
+            .await
+            .map(|_| ())
+    } else if args.parse::<CaptionStyle>().is_ok() {
+        state
+            .database
+            .set_chat_setting(msg.chat.id.0, CAPTION_STYLE_SETTING_KEY, &args)
+            .await
+    } else {
+        bot.send_message(msg.chat.id, "用法: /caption full|compact|none|default")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
+    let reply = match result {
+        Ok(()) => {
+            let style = chat_caption_style(state, msg.chat.id).await;
+            format!("✅ 说明文字样式已设为: {}", caption_style_label(style))
+        }
+        Err(e) => format!("❌ 保存设置失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_hashtags_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    let args = args.unwrap_or_default().trim().to_string();
+    let usage =
+        "用法: /hashtags <标签...>|none|default\n{ext} 会替换为文件格式，如 /hashtags 音乐 {ext}";
+
+    if args.is_empty() {
+        let tags = chat_hashtags(state, msg.chat.id).await;
+        let current = if tags.is_empty() {
+            "无".to_string()
+        } else {
+            render_hashtags(&tags, HASHTAG_EXT_PLACEHOLDER)
+        };
+        bot.send_message(msg.chat.id, format!("🏷️ 话题标签: {current}\n\n{usage}"))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    if !can_manage_chat_settings(bot, msg, state).await {
+        bot.send_message(msg.chat.id, "❌ 只有群组管理员可以修改此设置")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let result = match args.to_lowercase().as_str() {
+        "default" => state
+            .database
+            .delete_chat_setting(msg.chat.id.0, HASHTAGS_SETTING_KEY)
+            .await
+            .map(|_| ()),
+        "none" | "off" => {
+            state
+                .database
+                .set_chat_setting(msg.chat.id.0, HASHTAGS_SETTING_KEY, "")
+                .await
+        }
+        _ => {
+            state
+                .database
+                .set_chat_setting(
+                    msg.chat.id.0,
+                    HASHTAGS_SETTING_KEY,
+                    &parse_hashtags(&args).join(" "),
+                )
+                .await
+        }
+    };
+
+    let reply = match result {
+        Ok(()) => {
+            let tags = chat_hashtags(state, msg.chat.id).await;
+            if tags.is_empty() {
+                "✅ 已关闭话题标签".to_string()
+            } else {
+                format!(
+                    "✅ 话题标签已设为: {}",
+                    render_hashtags(&tags, HASHTAG_EXT_PLACEHOLDER)
+                )
+            }
+        }
+        Err(e) => format!("❌ 保存设置失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_rmcache_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    // Check if user is admin
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+
+    tracing::info!(
+        "rmcache command from user_id: {}, configured admins: {:?}",
+        user_id,
+        state.config.bot_admin
+    );
+
+    if !state.config.bot_admin.contains(&user_id) {
+        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let args = args.unwrap_or_default();
+
+    if args.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "请输入要删除缓存的歌曲ID\n\n用法: `/rmcache <音乐ID>`\n`/rmcache <起始ID>-<结束ID>` 删除ID范围\n`/rmcache <关键词>` 按歌名/歌手/专辑匹配",
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    if args.trim() == "confirm" {
+        return handle_rmcache_confirm(bot, msg, state, user_id).await;
+    }
+
+    if let Some(music_id) = parse_music_id(&args) {
+        let music_id_i64 = music_id as i64;
+
+        // Get song info before deletion
+        if let Ok(Some(song_info)) = state.database.get_song_by_music_id(music_id_i64).await {
+            match forget_song(state, music_id_i64).await {
+                Ok(deleted) => {
+                    if deleted {
+                        bot.send_message(
+                            msg.chat.id,
+                            format!(
+                                "✅ 已删除歌曲缓存: {}\n\n误删可使用 /undelete {} 恢复",
+                                song_info.song_name, music_id
+                            ),
+                        )
+                        .reply_parameters(ReplyParameters::new(msg.id))
+                        .await?;
+                    } else {
+                        bot.send_message(msg.chat.id, "歌曲未缓存")
+                            .reply_parameters(ReplyParameters::new(msg.id))
+                            .await?;
+                    }
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("删除缓存失败: {e}"))
+                        .reply_parameters(ReplyParameters::new(msg.id))
+                        .await?;
+                }
+            }
+        } else {
+            bot.send_message(msg.chat.id, "歌曲未缓存")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+    } else {
+        // Ranges and keywords may match many songs: list them and ask for confirmation
+        let matches = match parse_music_id_range(&args) {
+            Some((start, end)) => {
+                state
+                    .database
+                    .cached_songs_in_range(start as i64, end as i64)
+                    .await
+            }
+            None => state.database.find_cached_songs(args.trim()).await,
+        };
+        let songs = match matches {
+            Ok(songs) => songs,
+            Err(e) => {
+                bot.send_message(msg.chat.id, format!("查询缓存失败: {e}"))
+                    .reply_parameters(ReplyParameters::new(msg.id))
+                    .await?;
+                return Ok(());
+            }
+        };
+        if songs.is_empty() {
+            bot.send_message(msg.chat.id, "没有匹配的缓存")
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+
+        let mut lines: Vec<String> = songs
+            .iter()
+            .take(RMCACHE_PREVIEW)
+            .map(|song| {
+                format!(
+                    "• {} - {} ({})",
+                    song.song_artists, song.song_name, song.music_id
+                )
+            })
+            .collect();
+        if songs.len() > RMCACHE_PREVIEW {
+            lines.push(format!("… 以及另外 {} 首", songs.len() - RMCACHE_PREVIEW));
+        }
+        let text = format!(
+            "⚠️ 匹配到 {} 首缓存歌曲：\n\n{}\n\n请在30秒内发送 /rmcache confirm 确认删除",
+            songs.len(),
+            lines.join("\n")
+        );
+
+        *state.pending_removal.lock().await = Some(PendingRemoval {
+            admin_id: user_id,
+            issued_at: std::time::Instant::now(),
+            music_ids: songs.iter().map(|song| song.music_id).collect(),
+        });
+        bot.send_message(msg.chat.id, text)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Delete the songs listed by the last bulk `/rmcache` of this admin
+async fn handle_rmcache_confirm(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    user_id: i64,
+) -> ResponseResult<()> {
+    let music_ids = {
+        let mut pending = state.pending_removal.lock().await;
+        match pending.take() {
+            Some(request)
+                if request.admin_id == user_id && request.issued_at.elapsed() <= CONFIRM_WINDOW =>
+            {
+                Some(request.music_ids)
+            }
+            // Keep another admin's prompt confirmable by them
+            Some(request) if request.admin_id != user_id => {
+                *pending = Some(request);
+                None
+            }
+            _ => None,
+        }
+    };
+    let Some(music_ids) = music_ids else {
+        bot.send_message(
+            msg.chat.id,
+            "❌ 没有待确认的删除请求或已超时，请重新发送 /rmcache",
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    };
+
+    let text = match state.database.delete_songs(&music_ids).await {
+        Ok(count) => {
+            if let Some(hot_cache) = &state.hot_cache {
+                for &music_id in &music_ids {
+                    if let Err(e) = hot_cache.remove(music_id).await {
+                        tracing::warn!("Failed to remove music_id {} from redis: {}", music_id, e);
+                    }
+                }
+            }
+            tracing::info!("Admin {} removed {} cached songs", user_id, count);
+            format!("✅ 已删除 {count} 条缓存\n\n误删可使用 /undelete last 恢复")
+        }
+        Err(e) => format!("删除缓存失败: {e}"),
+    };
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Send the song cache as a JSON dump (with this bot's file_ids)
+async fn handle_exportcache_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let songs = match state.database.list_songs_for_bot(state.bot_id).await {
+        Ok(songs) => songs,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 读取缓存失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+    let total = songs.len();
+    let data = match serde_json::to_vec(&CacheDump::new(state.bot_id, songs)) {
+        Ok(data) => data,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 导出失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let filename = format!(
+        "music163bot-cache-{}.json",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    bot.send_document(msg.chat.id, InputFile::memory(data).file_name(filename))
+        .caption(format!(
+            "共导出 {total} 首歌曲\n回复此文件发送 /importcache 即可导入"
+        ))
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Merge a dump from `/exportcache` that the admin replied to
+async fn handle_importcache_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    use teloxide::net::Download;
+
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let Some(document) = msg.reply_to_message().and_then(Message::document) else {
+        bot.send_message(
+            msg.chat.id,
+            "用法: 回复 /exportcache 导出的 JSON 文件发送 /importcache",
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    };
+    // The Bot API only serves files up to 20MB
+    if document.file.size > 20 * 1024 * 1024 {
+        bot.send_message(msg.chat.id, "❌ 文件超过 20MB，无法通过 Bot API 下载")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut data = Vec::with_capacity(file.size as usize);
+    if let Err(e) = bot.download_file(&file.path, &mut data).await {
+        bot.send_message(msg.chat.id, format!("❌ 下载文件失败: {e}"))
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let dump = match CacheDump::parse(&data) {
+        Ok(dump) => dump,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 无法解析缓存文件: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+    let foreign = dump.bot_id != state.bot_id;
+    let songs = dump.into_songs_for(state.bot_id);
+
+    let text = match state
+        .database
+        .import_songs(&songs, Some(state.bot_id))
+        .await
+    {
+        Ok((added_songs, added_file_ids)) => {
+            tracing::info!(
+                "Imported cache dump: {} songs, {} new, {} file_ids",
+                songs.len(),
+                added_songs,
+                added_file_ids
+            );
+            let mut text = format!(
+                "✅ 导入完成\n文件内歌曲: {}\n新增歌曲: {added_songs}\n新增 file_id: {added_file_ids}",
+                songs.len()
+            );
+            if foreign {
+                text.push_str("\n\n该文件由其他机器人导出，file_id 不可用，仅导入了歌曲信息");
+            }
+            text
+        }
+        Err(e) => format!("❌ 导入失败: {e}"),
+    };
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Restore songs removed by /rmcache or /clearallcache before they are purged
+async fn handle_undelete_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let arg = args.unwrap_or_default();
+    let text = if arg.trim() == "last" {
+        match state.database.restore_last_deleted().await {
+            Ok(0) => "没有可恢复的记录".to_string(),
+            Ok(count) => format!("✅ 已恢复最近一次删除的 {count} 条记录"),
+            Err(e) => format!("❌ 恢复失败: {e}"),
+        }
+    } else if let Some(music_id) = parse_music_id(&arg) {
+        match state.database.restore_song(music_id as i64).await {
+            Ok(true) => format!("✅ 已恢复歌曲缓存: {music_id}"),
+            Ok(false) => "该歌曲没有可恢复的缓存".to_string(),
+            Err(e) => format!("❌ 恢复失败: {e}"),
+        }
+    } else {
+        "用法: /undelete <音乐ID>\n或 /undelete last 撤销最近一次 /rmcache 或 /clearallcache"
+            .to_string()
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Admin-only: download a song again even though it is cached, replacing
+/// the cached copy (after a quality upgrade, or over a low-bitrate fallback)
+async fn handle_refresh_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+    let Some(music_id) = args.as_deref().and_then(parse_music_id) else {
+        bot.send_message(msg.chat.id, "用法: /refresh <音乐ID或链接>")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    };
+
+    let before = cached_file_id(state, music_id).await;
+    let overrides = DownloadOverrides {
+        refresh: true,
+        ..DownloadOverrides::default()
+    };
+    Box::pin(process_music_into(
+        bot, msg, state, music_id, None, overrides,
+    ))
+    .await?;
+
+    let after = cached_file_id(state, music_id).await;
+    if after.is_some() && after != before {
+        drop_replaced_uploads(state, music_id).await;
+    }
+    Ok(())
+}
+
+/// Forget the uploads other bots and the hot cache hold for a song whose
+/// cached file was just replaced, so they don't keep sending the old one
+pub(crate) async fn drop_replaced_uploads(state: &BotState, music_id: u64) {
+    match state
+        .database
+        .clear_other_bot_file_ids(music_id as i64, state.bot_id)
+        .await
+    {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(
+            "Dropped {} file_ids of other bots for replaced music_id {}",
+            count,
+            music_id
+        ),
+        Err(e) => tracing::warn!("Failed to drop other bots' file_ids of {}: {}", music_id, e),
+    }
+    if let Some(hot_cache) = &state.hot_cache
+        && let Err(e) = hot_cache.remove(music_id as i64).await
+    {
+        tracing::warn!("Failed to remove music_id {} from redis: {}", music_id, e);
+    }
+}
+
+/// file_id of the stored upload of a song, if any
+async fn cached_file_id(state: &BotState, music_id: u64) -> Option<String> {
+    match state.database.get_song_by_music_id(music_id as i64).await {
+        Ok(song) => song.and_then(|song| song.file_id),
+        Err(e) => {
+            tracing::warn!("Failed to look up music_id {}: {}", music_id, e);
+            None
+        }
+    }
+}
+
+async fn handle_clearallcache_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    // Check if user is admin
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+
+    tracing::info!(
+        "clearallcache command from user_id: {}, configured admins: {:?}",
+        user_id,
+        state.config.bot_admin
+    );
+
+    if !state.config.bot_admin.contains(&user_id) {
+        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    *state.pending_clear.lock().await = Some(PendingClear {
+        admin_id: user_id,
+        issued_at: std::time::Instant::now(),
+    });
+
+    // Send confirmation message
+    bot
+        .send_message(msg.chat.id, "⚠️ 确认要清除所有缓存吗？\n\n这将删除数据库中的所有歌曲缓存记录（可在自动清理前使用 /undelete last 恢复）。\n\n请在30秒内再次发送 `/clearallcache confirm` 确认操作，发送 `/clearallcache confirm files` 同时删除缓存目录中的残留文件。")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_clearallcache_confirm_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    remove_files: bool,
+) -> ResponseResult<()> {
+    // Check if user is admin
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+
+    if !state.config.bot_admin.contains(&user_id) {
+        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    // The prompt is consumed only by the admin who issued it, within the window
+    let rejection = {
+        let mut pending = state.pending_clear.lock().await;
+        match *pending {
+            Some(request) if request.issued_at.elapsed() > CONFIRM_WINDOW => {
+                *pending = None;
+                Some("❌ 确认已超时，请重新发送 /clearallcache")
+            }
+            Some(request) if request.admin_id != user_id => {
+                Some("❌ 该清除请求由其他管理员发起，请自行发送 /clearallcache")
+            }
+            Some(_) => {
+                *pending = None;
+                None
+            }
+            None => Some("❌ 没有待确认的清除请求，请先发送 /clearallcache"),
+        }
+    };
+    if let Some(rejection) = rejection {
+        bot.send_message(msg.chat.id, rejection)
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🗑️ 正在清除所有缓存...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    match clear_song_cache(state).await {
+        Ok(count) => {
+            let files_note = if !remove_files {
+                String::new()
+            } else if downloads_running(state) {
+                "\n\n⚠️ 有正在进行的下载，已跳过删除缓存文件".to_string()
+            } else {
+                match remove_cache_files(&state.config).await {
+                    Ok((files, bytes)) => format!(
+                        "\n已删除缓存目录中的 {files} 个文件 ({})",
+                        format_file_size(bytes)
+                    ),
+                    Err(e) => format!("\n\n⚠️ 删除缓存文件失败: {e}"),
+                }
+            };
+            let text = format!(
+                "✅ 成功清除所有缓存！\n\n删除了 {count} 条记录\n误操作可使用 /undelete last 恢复{files_note}"
+            );
+
+            bot.edit_message_text(msg.chat.id, status_msg.id, text)
+                .await?;
+
+            tracing::info!(
+                "Admin {} cleared all cache, {} records deleted",
+                user_id,
+                count
+            );
+        }
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status_msg.id, format!("❌ 清除缓存失败: {e}"))
+                .await?;
+
+            tracing::error!("Failed to clear all cache: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Write an admin action to the audit log; failures are only logged
+async fn record_admin_action(
+    state: &BotState,
+    user: &teloxide::types::User,
+    chat_id: ChatId,
+    action: &str,
+    args: &str,
+) {
+    if let Err(e) = state
+        .database
+        .add_audit_entry(
+            user.id.0 as i64,
+            &user_display_name(user),
+            chat_id.0,
+            action,
+            args,
+        )
+        .await
+    {
+        tracing::warn!("Failed to record admin action {}: {}", action, e);
+    }
+}
+
+/// Show the most recent admin actions
+async fn handle_auditlog_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    args: Option<String>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let limit = args
+        .as_deref()
+        .and_then(|arg| arg.trim().parse::<i64>().ok())
+        .unwrap_or(20)
+        .clamp(1, 100);
+    let text = match state.database.recent_audit_entries(limit).await {
+        Ok(entries) if entries.is_empty() => "暂无管理员操作记录".to_string(),
+        Ok(entries) => {
+            let lines: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let time = chrono::NaiveDateTime::parse_from_str(
+                        &entry.created_at,
+                        "%Y-%m-%d %H:%M:%S",
+                    )
+                    .map_or_else(
+                        |_| entry.created_at.clone(),
+                        |time| {
+                            time.and_utc()
+                                .with_timezone(&chrono::Local)
+                                .format("%m-%d %H:%M")
+                                .to_string()
+                        },
+                    );
+                    format!(
+                        "{time} {} ({}) /{} {}",
+                        entry.actor_name, entry.actor_id, entry.action, entry.args
+                    )
+                    .trim_end()
+                    .to_string()
+                })
+                .collect();
+            format!(
+                "📜 最近 {} 条管理员操作\n\n{}",
+                lines.len(),
+                lines.join("\n")
+            )
+        }
+        Err(e) => format!("❌ 读取操作记录失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Report disk usage of the cache directory and database
+async fn handle_cachesize_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let cache_dir = match directory_size(std::path::Path::new(&state.config.cache_dir)).await {
+        Ok((files, bytes)) => format!("{} ({files} 个文件)", format_file_size(bytes)),
+        Err(e) => format!("读取失败: {e}"),
+    };
+    let database_size = database_file_size(&state.config.database).await;
+
+    let by_ext = match state.database.count_songs_by_ext().await {
+        Ok(counts) if counts.is_empty() => "暂无缓存".to_string(),
+        Ok(counts) => counts
+            .iter()
+            .map(|(ext, count)| {
+                let ext = if ext.is_empty() {
+                    "未知"
+                } else {
+                    ext.as_str()
+                };
+                format!("  {ext}: {count}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => format!("  读取失败: {e}"),
+    };
+    let largest = match state.database.largest_songs(CACHESIZE_LARGEST).await {
+        Ok(songs) if songs.is_empty() => "  -".to_string(),
+        Ok(songs) => songs
+            .iter()
+            .map(|song| {
+                format!(
+                    "  {} - {} ({}, {})",
+                    song.song_name,
+                    song.song_artists,
+                    song.music_id,
+                    format_file_size(song.music_size.max(0) as u64)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => format!("  读取失败: {e}"),
+    };
+
+    let text = format!(
+        "💾 缓存占用\n\n缓存目录: {cache_dir}\n数据库: {}\n\n按格式统计:\n{by_ext}\n\n最大的缓存:\n{largest}",
+        format_file_size(database_size)
+    );
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(())
+}
+
+/// Total size of all files below `root`, returning (files, bytes)
+async fn directory_size(root: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    let (mut files, mut bytes) = (0, 0);
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                files += 1;
+                bytes += metadata.len();
+            }
+        }
+    }
+    Ok((files, bytes))
+}
+
+/// Size of the SQLite database including its WAL and shared-memory files
+pub(crate) async fn database_file_size(database: &str) -> u64 {
+    let mut bytes = 0;
+    for suffix in ["", "-wal", "-shm"] {
+        if let Ok(metadata) = tokio::fs::metadata(format!("{database}{suffix}")).await {
+            bytes += metadata.len();
+        }
+    }
+    bytes
+}
+
+/// Whether a job may be writing into the cache directory: a download permit
+/// is held (fetching or tagging) or an upload is in flight
+fn downloads_running(state: &BotState) -> bool {
+    state.download_semaphore.available_permits() < state.config.max_concurrent_downloads as usize
+        || state.upload_counters.in_flight.load(Ordering::Relaxed) > 0
+}
+
+/// Delete leftover files in the cache directory, returning (files, bytes)
+///
+/// The database file and its WAL/SHM companions are kept even if they live there.
+async fn remove_cache_files(config: &Config) -> std::io::Result<(u64, u64)> {
+    let database = std::path::Path::new(&config.database);
+    let database_name = database
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let database_dir = database
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .canonicalize()
+        .ok();
+    let cache_dir = std::path::Path::new(&config.cache_dir).canonicalize()?;
+    let keep_database = database_dir.as_deref() == Some(cache_dir.as_path());
+
+    let (mut files, mut bytes) = (0, 0);
+    let mut entries = tokio::fs::read_dir(&cache_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if keep_database
+            && database_name
+                .as_deref()
+                .is_some_and(|db| name.starts_with(db))
+        {
+            continue;
+        }
+        tokio::fs::remove_file(entry.path()).await?;
+        files += 1;
+        bytes += metadata.len();
+    }
+    Ok((files, bytes))
+}
+
+/// Check that the sender is a bot admin, replying with a rejection otherwise
+async fn ensure_admin(bot: &Bot, msg: &Message, state: &Arc<BotState>) -> ResponseResult<bool> {
+    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+
+    if state.config.bot_admin.contains(&user_id) {
+        return Ok(true);
+    }
+
+    bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    Ok(false)
+}
+
+/// Playlists per `/myplaylists` page
+const MY_PLAYLISTS_PAGE: u32 = 8;
+/// Tracks per playlist page in `/myplaylists`
+const PLAYLIST_TRACKS_PAGE: usize = 10;
+
+/// Previous/next buttons of a paged `/myplaylists` view (`callback` gets the page)
+fn page_buttons(
+    page: usize,
+    has_next: bool,
+    callback: impl Fn(usize) -> String,
+) -> Vec<InlineKeyboardButton> {
+    let mut buttons = Vec::new();
+    if page > 0 {
+        buttons.push(InlineKeyboardButton::callback(
+            "⬅️ 上一页",
+            callback(page - 1),
+        ));
+    }
+    if has_next {
+        buttons.push(InlineKeyboardButton::callback(
+            "下一页 ➡️",
+            callback(page + 1),
+        ));
+    }
+    buttons
+}
+
+/// A page of the MUSIC_U account's playlists
+async fn my_playlists_page(
+    state: &BotState,
+    page: usize,
+) -> Result<(String, InlineKeyboardMarkup)> {
+    let user_id = state
+        .music_api
+        .get_account_info()
+        .await?
+        .profile
+        .map(|profile| profile.user_id)
+        .ok_or_else(|| BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U 未登录"))?;
+    let offset = u32::try_from(page)
+        .unwrap_or(u32::MAX)
+        .saturating_mul(MY_PLAYLISTS_PAGE);
+    let (playlists, more) = state
+        .music_api
+        .get_user_playlists(user_id, MY_PLAYLISTS_PAGE, offset)
+        .await?;
+
+    let mut text = format!("📚 我的歌单 (第 {} 页)\n\n", page + 1);
+    let mut keyboard = Vec::new();
+    if playlists.is_empty() {
+        text.push_str("没有歌单");
+    }
+    for (i, playlist) in playlists.iter().enumerate() {
+        let mark = if playlist.subscribed { "⭐ " } else { "" };
+        std::fmt::write(
+            &mut text,
+            format_args!(
+                "{}. {mark}{} ({} 首)\n",
+                offset as usize + i + 1,
+                playlist.name,
+                playlist.track_count
+            ),
+        )
+        .unwrap();
+        keyboard.push(vec![InlineKeyboardButton::callback(
+            format!("{mark}{}", playlist.name),
+            format!("mypl open {} 0", playlist.id),
+        )]);
+    }
+    keyboard.push(page_buttons(page, more, |page| format!("mypl list {page}")));
+    Ok((text, InlineKeyboardMarkup::new(keyboard)))
+}
+
+/// A page of one playlist's tracks, with a download button per track
+async fn playlist_tracks_page(
+    state: &BotState,
+    playlist_id: u64,
+    page: usize,
+) -> Result<(String, InlineKeyboardMarkup)> {
+    let playlist = state.music_api.get_playlist(playlist_id).await?;
+    let start = page * PLAYLIST_TRACKS_PAGE;
+    let tracks: Vec<&crate::music_api::SongDetail> = playlist
+        .tracks
+        .iter()
+        .skip(start)
+        .take(PLAYLIST_TRACKS_PAGE)
+        .collect();
+
+    let mut text = format!(
+        "💿 {} ({} 首，第 {} 页)\n\n",
+        playlist.name,
+        playlist.track_count,
+        page + 1
+    );
+    let mut keyboard = Vec::new();
+    let mut track_buttons = Vec::new();
+    for (i, song) in tracks.iter().enumerate() {
+        let artists = format_artists(song.ar.as_deref().unwrap_or(&[]));
+        std::fmt::write(
+            &mut text,
+            format_args!("{}.「{}」 - {}\n", start + i + 1, song.name, artists),
+        )
+        .unwrap();
+        track_buttons.push(InlineKeyboardButton::callback(
+            format!("{}", start + i + 1),
+            format!("music {}", song.id),
+        ));
+    }
+    for row in track_buttons.chunks(5) {
+        keyboard.push(row.to_vec());
+    }
+    let has_next = playlist.tracks.len() > start + PLAYLIST_TRACKS_PAGE;
+    keyboard.push(page_buttons(page, has_next, |page| {
+        format!("mypl open {playlist_id} {page}")
+    }));
+    keyboard.push(vec![
+        InlineKeyboardButton::callback("⬇️ 全部下载", format!("mypl all {playlist_id}")),
+        InlineKeyboardButton::callback("🔙 返回歌单列表", "mypl list 0"),
+    ]);
+    Ok((text, InlineKeyboardMarkup::new(keyboard)))
+}
+
+async fn handle_myplaylists_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    if state.music_api.music_u.is_none() {
+        bot.send_message(msg.chat.id, "⚠️ 未配置 MUSIC_U，无法获取歌单")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    match my_playlists_page(state, 0).await {
+        Ok((text, keyboard)) => {
+            bot.send_message(msg.chat.id, text)
+                .reply_markup(keyboard)
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 获取歌单失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// `mypl list <page>`, `mypl open <playlist> <page>` and `mypl all <playlist>`
+async fn handle_myplaylists_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    state: &Arc<BotState>,
+    args: &[&str],
+) -> ResponseResult<()> {
+    let Some(MaybeInaccessibleMessage::Regular(msg)) = &query.message else {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 消息已失效")
+            .await?;
+        return Ok(());
+    };
+    if !state.config.bot_admin.contains(&(query.from.id.0 as i64)) {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 仅限管理员操作")
+            .await?;
+        return Ok(());
+    }
+
+    let number = |index: usize| args.get(index).and_then(|arg| arg.parse::<u64>().ok());
+    let page = match (args[0], number(1)) {
+        ("list", page) => my_playlists_page(state, page.unwrap_or(0) as usize).await,
+        ("open", Some(playlist_id)) => {
+            playlist_tracks_page(state, playlist_id, number(2).unwrap_or(0) as usize).await
+        }
+        ("all", Some(playlist_id)) => {
+            bot.answer_callback_query(query.id.clone())
+                .text("✅ 开始下载")
+                .await?;
+            return handle_collection(
+                bot,
+                msg,
+                state,
+                CollectionKind::Playlist,
+                playlist_id,
+                TrackSelection::default(),
+                Some(&query.from),
+            )
+            .await;
+        }
+        _ => {
+            bot.answer_callback_query(query.id.clone())
+                .text("❌ 无效的操作")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match page {
+        Ok((text, keyboard)) => {
+            bot.edit_message_text(msg.chat.id, msg.id, text)
+                .reply_markup(keyboard)
+                .await?;
+            bot.answer_callback_query(query.id.clone()).await?;
+        }
+        Err(e) => {
+            bot.answer_callback_query(query.id.clone())
+                .text(format!("❌ 获取歌单失败: {e}"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Songs per `/cloud` page
+const CLOUD_PAGE: u32 = 10;
+
+/// A page of the MUSIC_U account's cloud disk, with a download button per song
+async fn cloud_page(state: &BotState, page: usize) -> Result<(String, InlineKeyboardMarkup)> {
+    let offset = u32::try_from(page)
+        .unwrap_or(u32::MAX)
+        .saturating_mul(CLOUD_PAGE);
+    let (songs, more) = state.music_api.get_cloud_songs(CLOUD_PAGE, offset).await?;
+
+    let mut text = format!("☁️ 我的云盘 (第 {} 页)\n\n", page + 1);
+    if songs.is_empty() {
+        text.push_str("云盘中没有歌曲");
+    }
+    let mut song_buttons = Vec::new();
+    for (i, song) in songs.iter().enumerate() {
+        let index = offset as usize + i + 1;
+        std::fmt::write(
+            &mut text,
+            format_args!(
+                "{index}.「{}」 - {} ({})\n",
+                song.song_name,
+                song.artist,
+                format_file_size(song.file_size)
+            ),
+        )
+        .unwrap();
+        song_buttons.push(InlineKeyboardButton::callback(
+            format!("{index}"),
+            format!("music {}", song.song_id),
+        ));
+    }
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> =
+        song_buttons.chunks(5).map(<[_]>::to_vec).collect();
+    keyboard.push(page_buttons(page, more, |page| format!("cloud {page}")));
+    Ok((text, InlineKeyboardMarkup::new(keyboard)))
+}
+
+async fn handle_cloud_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    if state.music_api.music_u.is_none() {
+        bot.send_message(msg.chat.id, "⚠️ 未配置 MUSIC_U，无法访问云盘")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    match cloud_page(state, 0).await {
+        Ok((text, keyboard)) => {
+            bot.send_message(msg.chat.id, text)
+                .reply_markup(keyboard)
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 获取云盘失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_cloud_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    state: &Arc<BotState>,
+    page: usize,
+) -> ResponseResult<()> {
+    let Some(MaybeInaccessibleMessage::Regular(msg)) = &query.message else {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 消息已失效")
+            .await?;
+        return Ok(());
+    };
+    if !state.config.bot_admin.contains(&(query.from.id.0 as i64)) {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 仅限管理员操作")
+            .await?;
+        return Ok(());
+    }
+
+    match cloud_page(state, page).await {
+        Ok((text, keyboard)) => {
+            bot.edit_message_text(msg.chat.id, msg.id, text)
+                .reply_markup(keyboard)
+                .await?;
+            bot.answer_callback_query(query.id.clone()).await?;
+        }
+        Err(e) => {
+            bot.answer_callback_query(query.id.clone())
+                .text(format!("❌ 获取云盘失败: {e}"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Format a millisecond timestamp as a local date
+fn format_timestamp_ms(timestamp_ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(timestamp_ms).map_or_else(
+        || "-".to_string(),
+        |dt| {
+            dt.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    )
+}
+
+async fn handle_selftest_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    let status_msg = bot
+        .send_message(msg.chat.id, "🩺 正在自检...")
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    let report = selftest::run(bot, state).await;
+    bot.edit_message_text(msg.chat.id, status_msg.id, report.summary())
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_vipstatus_command(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+) -> ResponseResult<()> {
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
+
+    if state.music_api.music_u.is_none() {
+        bot.send_message(msg.chat.id, "⚠️ 未配置 MUSIC_U，无法查询账号状态")
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+        return Ok(());
+    }
+
+    let (account, vip) = tokio::join!(
+        state.music_api.get_account_info(),
+        state.music_api.get_vip_info()
+    );
+
+    let account = match account {
+        Ok(account) => account,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ 获取账号信息失败: {e}"))
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let nickname = account
+        .profile
+        .as_ref()
+        .map_or("未知", |profile| profile.nickname.as_str());
+    let user_id = account.account.as_ref().map_or(0, |a| a.id);
+    let vip_type = account.account.as_ref().map_or(0, |a| a.vip_type);
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let describe_package = |package: Option<&crate::music_api::VipPackage>| match package {
+        Some(package) if package.expire_time > 0 => format!(
+            "{} (到期: {})",
+            if package.is_active_at(now_ms) {
+                "✅ 有效"
+            } else {
+                "❌ 已过期"
+            },
+            format_timestamp_ms(package.expire_time)
+        ),
+        _ => "未开通".to_string(),
+    };
+
+    let vip_text = match vip {
+        Ok(vip) => {
+            let lossless = vip
+                .associator
+                .as_ref()
+                .is_some_and(|p| p.is_active_at(now_ms));
+            format!(
+                "黑胶VIP: {}\n音乐包: {}\nVIP等级: {}\n\n{}",
+                describe_package(vip.associator.as_ref()),
+                describe_package(vip.music_package.as_ref()),
+                vip.red_vip_level,
+                if lossless {
+                    "🎧 当前账号可获取无损音质"
+                } else {
+                    "⚠️ 当前账号可能无法获取无损/VIP歌曲"
+                }
+            )
+        }
+        Err(e) => format!("获取VIP信息失败: {e}"),
+    };
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "👤 网易云账号状态\n\n昵称: {nickname}\n用户ID: {user_id}\nvipType: {vip_type}\n{vip_text}"
+        ),
+    )
+    .reply_parameters(ReplyParameters::new(msg.id))
+    .await?;
 
     Ok(())
 }
 
-async fn handle_status_command(
+async fn handle_memstats_command(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
+    args: Option<String>,
 ) -> ResponseResult<()> {
-    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
-    let chat_id = msg.chat.id.0;
-
-    let total_count = state.database.count_total_songs().await.unwrap_or(0);
-    let user_count = state
-        .database
-        .count_songs_from_user(user_id)
-        .await
-        .unwrap_or(0);
-    let chat_count = state
-        .database
-        .count_songs_from_chat(chat_id)
-        .await
-        .unwrap_or(0);
-
-    let status_text = format!(
-        r"📊 *统计信息*
-
-🎵 数据库中总缓存歌曲数量: {total_count}
-👤 当前用户缓存歌曲数量: {user_count}
-💬 当前对话缓存歌曲数量: {chat_count}
+    if !ensure_admin(bot, msg, state).await? {
+        return Ok(());
+    }
 
-🤖 Bot 运行状态: 正常
-🦀 语言: Rust
-⚡ 框架: Teloxide
-"
-    );
+    let action = args.unwrap_or_default().trim().to_lowercase();
+    let text = match action.as_str() {
+        "" => {
+            let stats_text = crate::memory::memory_stats().map_or_else(
+                || "当前平台不支持 jemalloc 统计".to_string(),
+                |stats| {
+                    format!(
+                        "已分配: {}\n活跃: {}\n常驻: {}\n映射: {}\n保留: {}",
+                        format_file_size(stats.allocated as u64),
+                        format_file_size(stats.active as u64),
+                        format_file_size(stats.resident as u64),
+                        format_file_size(stats.mapped as u64),
+                        format_file_size(stats.retained as u64)
+                    )
+                },
+            );
+            let profiling = if !crate::memory::profiling_available() {
+                "不可用 (需以 _RJEM_MALLOC_CONF=prof:true 启动)"
+            } else if crate::memory::profiling_active() {
+                "采样中"
+            } else {
+                "已暂停"
+            };
+            format!(
+                "🧠 内存统计 (jemalloc)\n\n{stats_text}\n\n堆分析: {profiling}\n用法: /memstats [prof on|prof off|dump]"
+            )
+        }
+        "prof on" | "prof off" => {
+            let active = action == "prof on";
+            match crate::memory::set_profiling_active(active) {
+                Ok(()) => {
+                    tracing::info!(
+                        "Heap profiling {}",
+                        if active { "enabled" } else { "disabled" }
+                    );
+                    format!("✅ 堆分析已{}", if active { "开启" } else { "暂停" })
+                }
+                Err(e) => {
+                    format!("❌ 切换堆分析失败: {e}\n请以 _RJEM_MALLOC_CONF=prof:true 启动后重试")
+                }
+            }
+        }
+        "dump" => match crate::memory::dump_heap_profile(&state.config.cache_dir) {
+            Ok(path) => {
+                tracing::info!("Heap profile written to {}", path.display());
+                format!("✅ 堆分析快照已写入 {}", path.display())
+            }
+            Err(e) => {
+                format!("❌ 写入堆分析快照失败: {e}\n请以 _RJEM_MALLOC_CONF=prof:true 启动后重试")
+            }
+        },
+        _ => "用法: /memstats [prof on|prof off|dump]".to_string(),
+    };
 
-    bot.send_message(msg.chat.id, status_text)
-        .parse_mode(ParseMode::MarkdownV2)
+    bot.send_message(msg.chat.id, text)
         .reply_parameters(ReplyParameters::new(msg.id))
         .await?;
 
     Ok(())
 }
 
-async fn handle_rmcache_command(
+/// Display name of a Telegram user for reports
+fn user_display_name(user: &teloxide::types::User) -> String {
+    user.username
+        .as_ref()
+        .map_or_else(|| user.full_name(), |username| format!("@{username}"))
+}
+
+async fn handle_report_command(
     bot: &Bot,
     msg: &Message,
     state: &Arc<BotState>,
     args: Option<String>,
 ) -> ResponseResult<()> {
-    // Check if user is admin
-    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
-
-    tracing::info!(
-        "rmcache command from user_id: {}, configured admins: {:?}",
-        user_id,
-        state.config.bot_admin
-    );
-
-    if !state.config.bot_admin.contains(&user_id) {
-        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
-            .reply_parameters(ReplyParameters::new(msg.id))
-            .await?;
-        return Ok(());
-    }
-
     let args = args.unwrap_or_default();
+    let (target, reason) = args
+        .split_once(char::is_whitespace)
+        .map_or((args.as_str(), ""), |(target, reason)| {
+            (target, reason.trim())
+        });
 
-    if args.is_empty() {
+    let Some(music_id) = parse_music_id(target) else {
         bot.send_message(
             msg.chat.id,
-            "请输入要删除缓存的歌曲ID\n\n用法: `/rmcache <音乐ID>`",
+            "请输入要反馈的歌曲ID和原因\n\n用法: /report <音乐ID> <原因>",
         )
         .reply_parameters(ReplyParameters::new(msg.id))
         .await?;
         return Ok(());
-    }
-
-    if let Some(music_id) = parse_music_id(&args) {
-        let music_id_i64 = music_id as i64;
+    };
 
-        // Get song info before deletion
-        if let Ok(Some(song_info)) = state.database.get_song_by_music_id(music_id_i64).await {
-            match state.database.delete_song_by_music_id(music_id_i64).await {
-                Ok(deleted) => {
-                    if deleted {
-                        bot.send_message(
-                            msg.chat.id,
-                            format!("✅ 已删除歌曲缓存: {}", song_info.song_name),
-                        )
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                    } else {
-                        bot.send_message(msg.chat.id, "歌曲未缓存")
-                            .reply_parameters(ReplyParameters::new(msg.id))
-                            .await?;
-                    }
-                }
-                Err(e) => {
-                    bot.send_message(msg.chat.id, format!("删除缓存失败: {e}"))
-                        .reply_parameters(ReplyParameters::new(msg.id))
-                        .await?;
-                }
-            }
-        } else {
-            bot.send_message(msg.chat.id, "歌曲未缓存")
-                .reply_parameters(ReplyParameters::new(msg.id))
-                .await?;
-        }
+    let reason = if reason.is_empty() {
+        "未说明"
     } else {
-        bot.send_message(msg.chat.id, "无效的歌曲ID")
-            .reply_parameters(ReplyParameters::new(msg.id))
-            .await?;
-    }
+        reason
+    };
+    let text = match file_report(bot, state, music_id, msg.chat.id, msg.from.as_ref(), reason).await
+    {
+        Ok(Some(report_id)) => format!("✅ 已提交反馈 #{report_id}，管理员会尽快处理"),
+        Ok(None) => REPORT_LIMIT_REACHED.to_string(),
+        Err(e) => format!("❌ 提交反馈失败: {e}"),
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
 
     Ok(())
 }
 
-async fn handle_clearallcache_command(
+/// Reply when a user has used up `limits.daily_reports`
+const REPORT_LIMIT_REACHED: &str = "⏳ 今日反馈次数已达上限，请明天再试";
+
+/// Store a song report and forward it to every bot admin with quick actions,
+/// or return `None` when the user has used up `limits.daily_reports`
+async fn file_report(
     bot: &Bot,
-    msg: &Message,
     state: &Arc<BotState>,
-) -> ResponseResult<()> {
-    // Check if user is admin
-    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
+    music_id: u64,
+    chat_id: ChatId,
+    user: Option<&teloxide::types::User>,
+    reason: &str,
+) -> Result<Option<i64>> {
+    let user_id = user.map_or(0, |u| u.id.0 as i64);
+    let limit = state.config.daily_report_limit;
+    if limit > 0 && !state.config.bot_admin.contains(&user_id) {
+        let filed = state
+            .database
+            .increment_usage(USAGE_SCOPE_REPORT, user_id, &usage_day())
+            .await?;
+        if filed > i64::from(limit) {
+            return Ok(None);
+        }
+    }
+    let user_name = user.map(user_display_name).unwrap_or_default();
+    let report_id = state
+        .database
+        .add_report(music_id as i64, chat_id.0, user_id, &user_name, reason)
+        .await?;
 
     tracing::info!(
-        "clearallcache command from user_id: {}, configured admins: {:?}",
+        "Report #{} for music_id {} from user {} in chat {}",
+        report_id,
+        music_id,
         user_id,
-        state.config.bot_admin
+        chat_id
     );
 
-    if !state.config.bot_admin.contains(&user_id) {
-        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
-            .reply_parameters(ReplyParameters::new(msg.id))
-            .await?;
-        return Ok(());
+    let song_name = match state.database.get_song_by_music_id(music_id as i64).await {
+        Ok(Some(song)) => format!("{} - {} (已缓存)", song.song_name, song.song_artists),
+        _ => "未缓存".to_string(),
+    };
+    let text = format!(
+        "🚩 新的歌曲反馈 #{report_id}\n\n歌曲ID: {music_id}\n歌曲: {song_name}\n反馈用户: {user_name} ({user_id})\n对话: {chat_id}\n原因: {reason}"
+    );
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🗑 清除缓存", format!("report rmcache {report_id}")),
+        InlineKeyboardButton::callback("🔁 重试", format!("report retry {report_id}")),
+        InlineKeyboardButton::callback("🙈 忽略", format!("report ignore {report_id}")),
+    ]]);
+
+    for &admin in &state.config.bot_admin {
+        if let Err(e) = bot
+            .send_message(ChatId(admin), text.clone())
+            .reply_markup(keyboard.clone())
+            .await
+        {
+            tracing::warn!(
+                "Failed to forward report #{} to admin {}: {}",
+                report_id,
+                admin,
+                e
+            );
+        }
     }
 
-    // Send confirmation message
-    bot
-        .send_message(msg.chat.id, "⚠️ 确认要清除所有缓存吗？\n\n这将删除数据库中的所有歌曲缓存记录。\n\n请在30秒内再次发送 `/clearallcache confirm` 确认操作。")
-        .reply_parameters(ReplyParameters::new(msg.id))
-        .await?;
-
-    Ok(())
+    Ok(Some(report_id))
 }
 
-async fn handle_clearallcache_confirm_command(
+/// Handle `report <action> <id>` callbacks from failure messages and admin notifications
+async fn handle_report_callback(
     bot: &Bot,
-    msg: &Message,
+    query: &CallbackQuery,
     state: &Arc<BotState>,
+    action: &str,
+    id: u64,
 ) -> ResponseResult<()> {
-    // Check if user is admin
-    let user_id = msg.from.as_ref().map_or(0, |u| u.id.0 as i64);
-
-    if !state.config.bot_admin.contains(&user_id) {
-        bot.send_message(msg.chat.id, "❌ 该命令仅限管理员使用")
-            .reply_parameters(ReplyParameters::new(msg.id))
+    let Some(MaybeInaccessibleMessage::Regular(msg)) = &query.message else {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 消息已失效")
             .await?;
         return Ok(());
-    }
-
-    let status_msg = bot
-        .send_message(msg.chat.id, "🗑️ 正在清除所有缓存...")
-        .reply_parameters(ReplyParameters::new(msg.id))
-        .await?;
+    };
 
-    match state.database.clear_all_songs().await {
-        Ok(count) => {
-            // Optimize database after bulk deletion
-            if let Err(e) = state.database.optimize().await {
-                tracing::warn!("Database optimization failed after clear: {}", e);
+    // Users reporting a failed download
+    if action == "new" {
+        let reason = msg.text().unwrap_or("下载失败");
+        let text = match file_report(bot, state, id, msg.chat.id, Some(&query.from), reason).await {
+            Ok(None) => REPORT_LIMIT_REACHED.to_string(),
+            Ok(Some(report_id)) => {
+                // Drop the button so the same failure is not reported twice
+                bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                    .await
+                    .ok();
+                format!("✅ 已提交反馈 #{report_id}")
             }
+            Err(e) => format!("❌ 提交反馈失败: {e}"),
+        };
+        bot.answer_callback_query(query.id.clone())
+            .text(text)
+            .await?;
+        return Ok(());
+    }
 
-            bot.edit_message_text(
-                msg.chat.id,
-                status_msg.id,
-                format!("✅ 成功清除所有缓存！\n\n删除了 {count} 条记录"),
-            )
+    if !state.config.bot_admin.contains(&(query.from.id.0 as i64)) {
+        bot.answer_callback_query(query.id.clone())
+            .text("❌ 该操作仅限管理员使用")
             .await?;
+        return Ok(());
+    }
 
-            tracing::info!(
-                "Admin {} cleared all cache, {} records deleted",
-                user_id,
-                count
-            );
+    record_admin_action(
+        state,
+        &query.from,
+        msg.chat.id,
+        &format!("report:{action}"),
+        &format!("#{id}"),
+    )
+    .await;
+
+    let report = match state.database.get_report(id as i64).await {
+        Ok(Some(report)) => report,
+        Ok(None) => {
+            bot.answer_callback_query(query.id.clone())
+                .text("❌ 反馈不存在")
+                .await?;
+            return Ok(());
         }
         Err(e) => {
-            bot.edit_message_text(msg.chat.id, status_msg.id, format!("❌ 清除缓存失败: {e}"))
+            bot.answer_callback_query(query.id.clone())
+                .text(format!("❌ 读取反馈失败: {e}"))
                 .await?;
+            return Ok(());
+        }
+    };
 
-            tracing::error!("Failed to clear all cache: {}", e);
+    let (status, result) = match action {
+        "rmcache" => match forget_song(state, report.music_id).await {
+            Ok(true) => ("resolved", "已清除缓存".to_string()),
+            Ok(false) => ("resolved", "歌曲未缓存".to_string()),
+            Err(e) => {
+                bot.answer_callback_query(query.id.clone())
+                    .text(format!("❌ 删除缓存失败: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        },
+        "retry" => {
+            // Answered now: the download can outlive the callback query
+            bot.answer_callback_query(query.id.clone())
+                .text("🔁 正在重试")
+                .await?;
+            // Re-run the pipeline in the admin chat to see the current result
+            let overrides = DownloadOverrides {
+                requester: Some(&query.from),
+                ..DownloadOverrides::default()
+            };
+            if let Err(e) =
+                process_music_into(bot, msg, state, report.music_id as u64, None, overrides).await
+            {
+                tracing::warn!("Retry of report #{} failed: {}", report.id, e);
+            }
+            ("retried", "已重试".to_string())
+        }
+        "ignore" => ("ignored", "已忽略".to_string()),
+        _ => {
+            bot.answer_callback_query(query.id.clone())
+                .text("❌ 无效的操作")
+                .await?;
+            return Ok(());
         }
+    };
+
+    if let Err(e) = state.database.set_report_status(report.id, status).await {
+        tracing::warn!("Failed to update report #{}: {}", report.id, e);
+    }
+
+    let handled_by = user_display_name(&query.from);
+    let text = format!(
+        "{}\n\n✔️ {result} ({handled_by})",
+        msg.text().unwrap_or_default()
+    );
+    bot.edit_message_text(msg.chat.id, msg.id, text).await.ok();
+    if action != "retry" {
+        bot.answer_callback_query(query.id.clone())
+            .text(format!("✅ {result}"))
+            .await?;
     }
 
     Ok(())
 }
 
+#[tracing::instrument(name = "update", skip_all, fields(request_id = %new_request_id()))]
 async fn handle_callback(
     bot: Bot,
     query: CallbackQuery,
     state: Arc<BotState>,
 ) -> ResponseResult<()> {
-    if let Some(data) = query.data {
+    if let Some(data) = query.data.clone() {
         let parts: Vec<&str> = data.split_whitespace().collect();
+        if parts.len() >= 3
+            && parts[0] == "report"
+            && let Ok(id) = parts[2].parse::<u64>()
+        {
+            return handle_report_callback(&bot, &query, &state, parts[1], id).await;
+        }
+
+        if parts.len() >= 2
+            && parts[0] == "cloud"
+            && let Ok(page) = parts[1].parse::<usize>()
+        {
+            return handle_cloud_callback(&bot, &query, &state, page).await;
+        }
+
+        if parts.len() >= 2 && parts[0] == "mypl" {
+            return handle_myplaylists_callback(&bot, &query, &state, &parts[1..]).await;
+        }
+
+        if parts.len() >= 2
+            && parts[0] == "simi"
+            && let Ok(music_id) = parts[1].parse::<u64>()
+        {
+            return handle_simi_callback(&bot, &query, &state, music_id).await;
+        }
+
         if parts.len() >= 2
             && parts[0] == "music"
             && let Ok(music_id) = parts[1].parse::<u64>()
             && let Some(MaybeInaccessibleMessage::Regular(msg)) = &query.message
         {
-            match process_music(&bot, msg, &state, music_id).await {
+            // `/info` quality buttons carry the bitrate as a third part
+            let overrides = DownloadOverrides {
+                bitrate: parts.get(2).and_then(|bitrate| bitrate.parse().ok()),
+                requester: Some(&query.from),
+                ..DownloadOverrides::default()
+            };
+            match process_music_into(&bot, msg, &state, music_id, None, overrides).await {
                 Ok(()) => {
                     bot.answer_callback_query(query.id)
                         .text("✅ 开始下载")
@@ -1759,6 +6893,16 @@ async fn handle_inline_query(
             bot.answer_inline_query(query.id, vec![InlineQueryResult::Article(help_article)])
                 .await?;
         } else {
+            // With no typing, offer the songs this user gets most
+            let history = inline_history_results(&state, &query).await;
+            if !history.is_empty() {
+                bot.answer_inline_query(query.id, history)
+                    .is_personal(true)
+                    .cache_time(30)
+                    .await?;
+                return Ok(());
+            }
+
             let help_article = InlineQueryResultArticle::new(
                 "usage_help",
                 "如何使用此机器人？",
@@ -1777,19 +6921,74 @@ async fn handle_inline_query(
     match state.music_api.search_songs(search_keyword, 10).await {
         Ok(songs) => {
             let mut results = Vec::new();
+            let previews = inline_preview_urls(&state, query.from.id.0, &songs).await;
+            let locale = chat_locale(
+                &state,
+                ChatId(query.from.id.0 as i64),
+                query.from.language_code.as_deref(),
+            )
+            .await;
 
             for (i, song) in songs.iter().take(10).enumerate() {
                 let artists = format_artists(&song.artists);
+                let id = format!("{}_{}", song.id, i);
 
-                let article = InlineQueryResultArticle::new(
-                    format!("{}_{}", song.id, i),
-                    &song.name,
-                    InputMessageContent::Text(InputMessageContentText::new(format!(
-                        "/netease {}",
-                        song.id
-                    ))),
-                )
-                .description(artists);
+                // Cached songs are sent straight from Telegram's copy
+                if let Ok(Some(cached)) = lookup_song(&state, song.id as i64).await
+                    && let Some(file_id) = cached.file_id.clone()
+                {
+                    let caption = build_caption(
+                        state.config.caption_style,
+                        &cached,
+                        cached.bit_rate,
+                        &state.config.hashtags,
+                        &state.bot_username,
+                        locale,
+                    );
+                    let audio = InlineQueryResultCachedAudio::new(id, FileId(file_id))
+                        .caption(caption)
+                        .reply_markup(create_music_keyboard(
+                            song.id,
+                            &cached.song_name,
+                            &cached.song_artists,
+                        ));
+                    results.push(InlineQueryResult::CachedAudio(audio));
+                    continue;
+                }
+
+                // Others can be played from the popup; choosing one still sends
+                // the download command
+                let command = InputMessageContent::Text(InputMessageContentText::new(format!(
+                    "/netease {}",
+                    song.id
+                )));
+                if let Some(url) = previews.get(&song.id) {
+                    let audio = InlineQueryResultAudio::new(id, url.clone(), &song.name)
+                        .performer(artists)
+                        .audio_duration(Seconds::from_seconds((song.duration / 1000) as u32))
+                        .input_message_content(command);
+                    results.push(InlineQueryResult::Audio(audio));
+                    continue;
+                }
+
+                let mut article =
+                    InlineQueryResultArticle::new(id, &song.name, command).description(artists);
+
+                // Album cover next to the result, resized so the dropdown stays light
+                if let Some(thumbnail) = song
+                    .album
+                    .pic_url
+                    .as_deref()
+                    .filter(|url| !url.is_empty())
+                    .and_then(|url| {
+                        reqwest::Url::parse(&cover_thumbnail_url(url, INLINE_THUMBNAIL_SIZE)).ok()
+                    })
+                {
+                    article = article
+                        .thumbnail_url(thumbnail)
+                        .thumbnail_width(INLINE_THUMBNAIL_SIZE)
+                        .thumbnail_height(INLINE_THUMBNAIL_SIZE);
+                }
 
                 results.push(InlineQueryResult::Article(article));
             }
@@ -1815,25 +7014,119 @@ async fn handle_inline_query(
     Ok(())
 }
 
-/// Build caption with exact format:
+/// Cached-audio results for the user's recent, then most received songs
+async fn inline_history_results(state: &BotState, query: &InlineQuery) -> Vec<InlineQueryResult> {
+    let user_id = query.from.id.0 as i64;
+    let (recent, top) = tokio::join!(
+        state.database.recent_sends(user_id, INLINE_HISTORY_LIMIT),
+        state.database.top_sends(user_id, INLINE_HISTORY_LIMIT)
+    );
+    let mut music_ids = recent.unwrap_or_default();
+    for music_id in top.unwrap_or_default() {
+        if !music_ids.contains(&music_id) {
+            music_ids.push(music_id);
+        }
+    }
+
+    let locale = chat_locale(state, ChatId(user_id), query.from.language_code.as_deref()).await;
+    let mut results = Vec::new();
+    for music_id in music_ids {
+        let Ok(Some(song)) = lookup_song(state, music_id).await else {
+            continue;
+        };
+        let Some(file_id) = song.file_id.clone() else {
+            continue;
+        };
+        let caption = build_caption(
+            state.config.caption_style,
+            &song,
+            song.bit_rate,
+            &state.config.hashtags,
+            &state.bot_username,
+            locale,
+        );
+        let audio =
+            InlineQueryResultCachedAudio::new(format!("history_{music_id}"), FileId(file_id))
+                .caption(caption)
+                .reply_markup(create_music_keyboard(
+                    music_id as u64,
+                    &song.song_name,
+                    &song.song_artists,
+                ));
+        results.push(InlineQueryResult::CachedAudio(audio));
+    }
+    results
+}
+
+/// Playable MP3 URLs of search results that need no VIP, for inline previews
+/// (see `inline_previews` for caching and throttling)
+async fn inline_preview_urls(
+    state: &BotState,
+    user_id: u64,
+    songs: &[crate::music_api::SearchSong],
+) -> HashMap<u64, reqwest::Url> {
+    let ids: Vec<u64> = songs.iter().map(|song| song.id).collect();
+    let mut previews = state.inline_previews.cached(&ids);
+    let missing: Vec<u64> = ids
+        .into_iter()
+        .filter(|id| !previews.contains_key(id))
+        .collect();
+    if missing.is_empty() || !state.inline_previews.may_fetch(user_id) {
+        return previews;
+    }
+    match state
+        .music_api
+        .get_public_song_urls(&missing, 128_000)
+        .await
+    {
+        Ok(urls) => {
+            let fetched: HashMap<u64, reqwest::Url> = urls
+                .into_iter()
+                .filter(|url| {
+                    !url.url.is_empty()
+                        && url.free_trial_info.is_none()
+                        && url.format.eq_ignore_ascii_case("mp3")
+                })
+                .filter_map(|url| Some((url.id, reqwest::Url::parse(&url.url).ok()?)))
+                .collect();
+            state.inline_previews.store(&fetched);
+            previews.extend(fetched);
+        }
+        Err(e) => tracing::debug!("No inline previews: {}", e),
+    }
+    previews
+}
+
+/// Build caption; the full style has the exact format:
 /// 「Title」- Artists
 /// 专辑: Album
-/// #网易云音乐 #ext {sizeMB}MB {kbps}kbps
+/// #网易云音乐 #ext {sizeMB}MB {kbps}kbps (hashtags from `bot.hashtags` or /hashtags)
 /// via @`BotName`
 fn build_caption(
-    title: &str,
-    artists: &str,
-    album: &str,
-    file_ext: &str,
-    size_bytes: i64,
+    style: CaptionStyle,
+    song: &SongInfo,
     bitrate_bps: i64,
+    hashtags: &[String],
     bot_username: &str,
+    locale: Locale,
 ) -> String {
-    let size_mb = (size_bytes as f64) / 1024.0 / 1024.0;
+    let title = &song.song_name;
+    let artists = &song.song_artists;
+    match style {
+        CaptionStyle::Full => {}
+        CaptionStyle::Compact => return format!("{title} - {artists}"),
+        CaptionStyle::None => return String::new(),
+    }
+    let album = &song.song_album;
+    let size_mb = (song.music_size as f64) / 1024.0 / 1024.0;
     // bitrate_bps may already be bps, convert to kbps with 2 decimals
     let kbps = (bitrate_bps as f64) / 1000.0;
-    let ext = file_ext.to_lowercase();
+    let mut tags = render_hashtags(hashtags, &song.file_ext);
+    if !tags.is_empty() {
+        tags.push(' ');
+    }
     format!(
-        "「{title}」- {artists}\n专辑: {album}\n#网易云音乐 #{ext} {size_mb:.2}MB {kbps:.2}kbps\nvia @{bot_username}",
+        "「{title}」- {artists}\n{}: {album}\n{tags}{size_mb:.2}MB {kbps:.2}kbps\nvia @{bot_username}",
+        Text::CaptionAlbum.get(locale),
     )
 }
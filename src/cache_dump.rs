@@ -0,0 +1,109 @@
+//! Portable JSON dumps of the song cache (`/exportcache`, `/importcache`)
+//!
+//! A dump records which bot its file_ids belong to; file_ids only work for
+//! the bot that uploaded the file, so importing into a different bot keeps
+//! the song metadata but drops the file_ids.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::database::SongInfo;
+use crate::error::{BotError, Result};
+
+pub const DUMP_FORMAT: &str = "music163bot-cache";
+pub const DUMP_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheDump {
+    pub format: String,
+    pub version: u32,
+    /// Bot the included file_ids were uploaded by
+    pub bot_id: i64,
+    pub exported_at: DateTime<Utc>,
+    pub songs: Vec<SongInfo>,
+}
+
+impl CacheDump {
+    #[must_use]
+    pub fn new(bot_id: i64, songs: Vec<SongInfo>) -> Self {
+        Self {
+            format: DUMP_FORMAT.to_string(),
+            version: DUMP_VERSION,
+            bot_id,
+            exported_at: Utc::now(),
+            songs,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let dump: Self = serde_json::from_slice(data)?;
+        if dump.format != DUMP_FORMAT {
+            return Err(BotError::Parse(format!(
+                "Not a cache dump (format: {})",
+                dump.format
+            )));
+        }
+        if dump.version > DUMP_VERSION {
+            return Err(BotError::Parse(format!(
+                "Unsupported cache dump version {}",
+                dump.version
+            )));
+        }
+        Ok(dump)
+    }
+
+    /// Songs to import into `bot_id`, without file_ids uploaded by other bots
+    #[must_use]
+    pub fn into_songs_for(self, bot_id: i64) -> Vec<SongInfo> {
+        let same_bot = self.bot_id == bot_id;
+        self.songs
+            .into_iter()
+            .map(|mut song| {
+                if !same_bot {
+                    song.file_id = None;
+                    song.thumb_file_id = None;
+                }
+                song
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheDump;
+    use crate::database::SongInfo;
+
+    fn cached_song() -> SongInfo {
+        SongInfo {
+            music_id: 1,
+            song_name: "晴天".to_string(),
+            file_id: Some("file".to_string()),
+            thumb_file_id: Some("thumb".to_string()),
+            ..SongInfo::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_and_keeps_own_file_ids() {
+        let data = serde_json::to_vec(&CacheDump::new(42, vec![cached_song()])).unwrap();
+        let songs = CacheDump::parse(&data).unwrap().into_songs_for(42);
+        assert_eq!(songs[0].song_name, "晴天");
+        assert_eq!(songs[0].file_id.as_deref(), Some("file"));
+    }
+
+    #[test]
+    fn drops_file_ids_from_other_bots() {
+        let songs = CacheDump::new(42, vec![cached_song()]).into_songs_for(7);
+        assert_eq!(songs[0].file_id, None);
+        assert_eq!(songs[0].thumb_file_id, None);
+    }
+
+    #[test]
+    fn rejects_foreign_json() {
+        assert!(CacheDump::parse(br#"{"songs": []}"#).is_err());
+        let mut dump = CacheDump::new(1, Vec::new());
+        dump.format = "other".to_string();
+        assert!(CacheDump::parse(&serde_json::to_vec(&dump).unwrap()).is_err());
+    }
+}
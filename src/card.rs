@@ -0,0 +1,169 @@
+//! Rendered "song card" share images (`/card`)
+//!
+//! The layout (blurred cover backdrop + cover art) is composed with the `image`
+//! crate; title, artist and the bot watermark are drawn by ffmpeg's `drawtext`
+//! filter with the configured font, since CJK text needs a full font.
+
+use std::path::{Path, PathBuf};
+
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+use crate::config::Config;
+use crate::error::{BotError, Result};
+use crate::transcode;
+
+pub const CARD_WIDTH: u32 = 1200;
+pub const CARD_HEIGHT: u32 = 630;
+const COVER_SIZE: u32 = 480;
+const MARGIN: u32 = 75;
+/// Longest title/artist line drawn before truncating with an ellipsis
+const MAX_LINE_CHARS: usize = 18;
+
+/// Compose the card background: blurred, darkened cover with the cover art on the left
+pub fn compose_background(cover: &[u8]) -> Result<RgbImage> {
+    let cover = image::load_from_memory(cover)
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Failed to decode cover: {e}")))?;
+
+    // Blur a small copy and scale it up; blurring at full size is needlessly slow
+    let backdrop = cover
+        .resize_to_fill(CARD_WIDTH / 10, CARD_HEIGHT / 10, FilterType::Triangle)
+        .blur(3.0)
+        .resize_exact(CARD_WIDTH, CARD_HEIGHT, FilterType::Triangle);
+    let mut canvas = backdrop.to_rgb8();
+    for pixel in canvas.pixels_mut() {
+        let Rgb([r, g, b]) = *pixel;
+        *pixel = Rgb([r / 5 * 2, g / 5 * 2, b / 5 * 2]);
+    }
+
+    let art = cover
+        .resize_to_fill(COVER_SIZE, COVER_SIZE, FilterType::Lanczos3)
+        .to_rgb8();
+    imageops::overlay(
+        &mut canvas,
+        &art,
+        i64::from(MARGIN),
+        i64::from((CARD_HEIGHT - COVER_SIZE) / 2),
+    );
+
+    Ok(canvas)
+}
+
+/// Shorten `text` to `max_chars` characters, appending an ellipsis when cut
+#[must_use]
+pub fn truncate_line(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    format!("{truncated}…")
+}
+
+/// Escape a path for use as a `drawtext` option value
+fn escape_filter_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace(':', "\\:")
+        .replace('\'', "\\'")
+}
+
+/// `drawtext` filters for the title, artist and watermark text files
+///
+/// Text is passed via text files so titles never need filter escaping, and
+/// `expansion=none` keeps `%` and `\` in titles (such as "100%") literal.
+fn text_filter(font: &Path, text_paths: &[PathBuf; 3]) -> String {
+    let font = escape_filter_path(font);
+    let text_x = MARGIN * 2 + COVER_SIZE;
+    format!(
+        "drawtext=fontfile='{font}':textfile='{}':expansion=none:fontsize=60:fontcolor=white:x={text_x}:y=220,\
+         drawtext=fontfile='{font}':textfile='{}':expansion=none:fontsize=38:fontcolor=white@0.8:x={text_x}:y=320,\
+         drawtext=fontfile='{font}':textfile='{}':expansion=none:fontsize=26:fontcolor=white@0.6:x=w-tw-40:y=h-th-30",
+        escape_filter_path(&text_paths[0]),
+        escape_filter_path(&text_paths[1]),
+        escape_filter_path(&text_paths[2]),
+    )
+}
+
+/// Render the full card to `output` (JPEG)
+pub async fn render_card(
+    config: &Config,
+    cover: Vec<u8>,
+    title: &str,
+    artist: &str,
+    watermark: &str,
+    output: &Path,
+) -> Result<()> {
+    let stem = output.with_extension("");
+    let background_path = stem.with_extension("bg.png");
+    let text_paths = [
+        stem.with_extension("title.txt"),
+        stem.with_extension("artist.txt"),
+        stem.with_extension("mark.txt"),
+    ];
+
+    let background = tokio::task::spawn_blocking(move || compose_background(&cover))
+        .await
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Card composition task failed: {e}")))??;
+    let save_path = background_path.clone();
+    tokio::task::spawn_blocking(move || background.save(&save_path))
+        .await
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Card composition task failed: {e}")))?
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Failed to save card background: {e}")))?;
+
+    let texts = [
+        truncate_line(title, MAX_LINE_CHARS),
+        truncate_line(artist, MAX_LINE_CHARS + 6),
+        watermark.to_string(),
+    ];
+    for (path, text) in text_paths.iter().zip(&texts) {
+        tokio::fs::write(path, text).await?;
+    }
+
+    let filter = text_filter(Path::new(&config.card_font), &text_paths);
+
+    let result = transcode::render_image(config, &background_path, &filter, output).await;
+
+    tokio::fs::remove_file(&background_path).await.ok();
+    for path in &text_paths {
+        tokio::fs::remove_file(path).await.ok();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{CARD_HEIGHT, CARD_WIDTH, compose_background, text_filter, truncate_line};
+
+    #[test]
+    fn background_has_card_dimensions() {
+        let cover = image::RgbImage::from_pixel(64, 64, image::Rgb([200, 40, 40]));
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageRgb8(cover)
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+
+        let card = compose_background(&jpeg).unwrap();
+        assert_eq!(card.dimensions(), (CARD_WIDTH, CARD_HEIGHT));
+    }
+
+    #[test]
+    fn truncates_long_lines() {
+        assert_eq!(truncate_line("短标题", 18), "短标题");
+        assert_eq!(truncate_line("一二三四五六", 4), "一二三…");
+    }
+
+    #[test]
+    fn keeps_percent_signs_in_titles_literal() {
+        assert_eq!(truncate_line("100% 纯音乐", 18), "100% 纯音乐");
+
+        let paths = ["title.txt", "artist.txt", "mark.txt"].map(PathBuf::from);
+        let filter = text_filter(Path::new("/fonts/a.ttf"), &paths);
+        assert_eq!(filter.matches("drawtext=").count(), 3);
+        assert_eq!(filter.matches(":expansion=none:").count(), 3);
+    }
+}
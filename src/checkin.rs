@@ -0,0 +1,124 @@
+//! Scheduled NetEase daily sign-in for the account bound to MUSIC_U
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+
+use crate::bot::BotState;
+use crate::music_api::{MusicApi, SigninKind, SigninOutcome};
+
+/// Result of one check-in run (mobile + web)
+#[derive(Debug, Clone)]
+pub struct CheckinReport {
+    pub finished_at: DateTime<Local>,
+    pub mobile: Result<SigninOutcome, String>,
+    pub web: Result<SigninOutcome, String>,
+    /// Point balance after signing in (None if it could not be fetched)
+    pub points: Option<i64>,
+}
+
+impl CheckinReport {
+    /// One-line summary used in logs and /status
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let points = self
+            .points
+            .map_or_else(|| "未知".to_string(), |points| points.to_string());
+        format!(
+            "{} 移动端: {}, 网页端: {}, 积分: {}",
+            self.finished_at.format("%Y-%m-%d %H:%M"),
+            describe_outcome(&self.mobile),
+            describe_outcome(&self.web),
+            points
+        )
+    }
+}
+
+fn describe_outcome(outcome: &Result<SigninOutcome, String>) -> String {
+    match outcome {
+        Ok(SigninOutcome::Signed(point)) => format!("成功 +{point}"),
+        Ok(SigninOutcome::AlreadySigned) => "今日已签到".to_string(),
+        Err(e) => format!("失败 ({e})"),
+    }
+}
+
+/// Sign in as both mobile and web client, then read the point balance
+pub async fn run_checkin(api: &MusicApi) -> CheckinReport {
+    let mobile = api
+        .daily_signin(SigninKind::Mobile)
+        .await
+        .map_err(|e| e.to_string());
+    let web = api
+        .daily_signin(SigninKind::Web)
+        .await
+        .map_err(|e| e.to_string());
+    let points = match api.get_user_points().await {
+        Ok(points) => Some(points),
+        Err(e) => {
+            tracing::warn!("Failed to fetch account points: {}", e);
+            None
+        }
+    };
+
+    CheckinReport {
+        finished_at: Local::now(),
+        mobile,
+        web,
+        points,
+    }
+}
+
+/// Time to wait from `now` until the next occurrence of `hour:00` local time
+#[must_use]
+pub fn next_run_delay(now: DateTime<Local>, hour: u32) -> Duration {
+    let at = NaiveTime::from_hms_opt(hour.min(23), 0, 0).unwrap_or_default();
+    let mut date = now.date_naive();
+    if now.time() >= at {
+        date = date.succ_opt().unwrap_or(date);
+    }
+
+    // Ambiguous/skipped local times (DST) fall back to the earliest valid instant
+    let next = Local
+        .from_local_datetime(&date.and_time(at))
+        .earliest()
+        .unwrap_or_else(|| now + chrono::Duration::days(1));
+
+    (next - now).to_std().unwrap_or(Duration::from_mins(1))
+}
+
+/// Run the check-in once at startup and then every day at `checkin.hour`
+pub fn spawn_daily_checkin(state: Arc<BotState>) {
+    tokio::spawn(async move {
+        loop {
+            let report = run_checkin(&state.music_api).await;
+            tracing::info!("Daily check-in: {}", report.summary());
+            *state.last_checkin.lock().await = Some(report);
+
+            let delay = next_run_delay(Local::now(), state.config.checkin_hour);
+            tracing::debug!("Next daily check-in in {}s", delay.as_secs());
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::next_run_delay;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn next_run_is_later_today_before_hour() {
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 6, 30, 0).unwrap();
+        let delay = next_run_delay(now, 8);
+        assert_eq!(delay.as_secs(), 90 * 60);
+    }
+
+    #[test]
+    fn next_run_is_tomorrow_after_hour() {
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 9, 0, 0).unwrap();
+        let delay = next_run_delay(now, 8);
+        assert!(delay.as_secs() > 20 * 3600);
+        assert!(delay.as_secs() <= 24 * 3600);
+    }
+}
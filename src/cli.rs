@@ -0,0 +1,231 @@
+//! Headless subcommands (`download`, `search`, `check-config`, `healthcheck`)
+//!
+//! These reuse the bot's NetEase client and tagging code without connecting
+//! to Telegram, so the binary can be used from scripts and CI.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, StorageMode, lint_values};
+use crate::database::Database;
+use crate::download::download_song;
+use crate::error::{BotError, Result};
+use crate::music_api::{MusicApi, format_artists};
+use crate::utils::{ensure_dir, format_duration, format_file_size};
+
+/// Download one song into `output` using the configured API and MD5 setting
+pub async fn download(config: &Config, music_id: u64, output: &Path) -> Result<PathBuf> {
+    let api = MusicApi::new_with_config(config);
+    download_song(&api, music_id, output, config.check_md5)
+        .await
+        .map_err(|e| e.with_music_id(music_id))
+}
+
+/// Print the top search results as `id<TAB>artists - name (album, duration)`
+pub async fn search(config: &Config, keyword: &str, limit: u32) -> Result<()> {
+    let api = MusicApi::new_with_config(config);
+    let songs = api.search_songs(keyword, limit).await?;
+    for song in songs {
+        println!(
+            "{}\t{} - {} ({}, {})",
+            song.id,
+            format_artists(&song.artists),
+            song.name,
+            song.album.name,
+            format_duration(song.duration / 1000)
+        );
+    }
+    Ok(())
+}
+
+/// Problems that would stop the bot from starting
+#[must_use]
+pub fn config_errors(config: &Config) -> Vec<String> {
+    let mut errors = Vec::new();
+    let token_valid = config
+        .bot_token
+        .split_once(':')
+        .is_some_and(|(id, secret)| id.parse::<u64>().is_ok() && !secret.is_empty());
+    if config.bot_token.is_empty() {
+        errors.push("bot.token is not set".to_string());
+    } else if !token_valid {
+        errors.push("bot.token is not a valid Telegram bot token".to_string());
+    }
+    if config.max_concurrent_downloads == 0 {
+        errors.push("max_concurrent_downloads must be at least 1".to_string());
+    }
+    if !config.s3_endpoint.is_empty() && config.s3_bucket.is_empty() {
+        errors.push("s3.endpoint is set but s3.bucket is empty".to_string());
+    }
+
+    let mut timeouts = vec![
+        ("downloadtimeout", config.download_timeout),
+        (
+            "download.connect_timeout_secs",
+            config.download_connect_timeout_secs,
+        ),
+        ("upload.timeout_secs", config.upload_timeout_secs),
+    ];
+    if config.ffmpeg_enabled {
+        timeouts.push(("ffmpeg.timeout_secs", config.ffmpeg_timeout_secs));
+    }
+    for (key, secs) in timeouts {
+        if secs == 0 {
+            errors.push(format!("{key} is 0, every request would time out at once"));
+        }
+    }
+
+    if config.storage_mode != StorageMode::Disk && config.memory_max_file_mb == 0 {
+        errors.push(format!(
+            "download.storage_mode = {} but download.memory_max_file_mb is 0, nothing fits in memory",
+            config.storage_mode
+        ));
+    }
+    if config.storage_mode == StorageMode::Hybrid && config.memory_threshold_mb == 0 {
+        errors.push(
+            "download.storage_mode = hybrid but download.memory_threshold is 0, use disk instead"
+                .to_string(),
+        );
+    }
+    errors
+}
+
+/// Settings that work but are probably not what the operator intended
+#[must_use]
+pub fn config_warnings(config: &Config) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if config.bot_admin.is_empty() {
+        warnings.push("bot.botadmin is empty, admin commands are disabled".to_string());
+    }
+    if config.music_u.as_deref().unwrap_or("").is_empty() {
+        warnings.push("music.music_u is not set, only standard quality is available".to_string());
+    }
+    if cfg!(not(feature = "s3")) && !config.s3_endpoint.is_empty() {
+        warnings.push("s3.endpoint is set but the binary was built without `s3`".to_string());
+    }
+    if config.storage_mode == StorageMode::Hybrid
+        && config.memory_threshold_mb > config.memory_max_file_mb
+    {
+        warnings.push(format!(
+            "download.memory_threshold ({}) is above download.memory_max_file_mb ({}), the cap wins",
+            config.memory_threshold_mb, config.memory_max_file_mb
+        ));
+    }
+    if config.memory_pressure_floor_mb > 0
+        && config.memory_pressure_concurrency >= config.max_concurrent_downloads
+    {
+        warnings.push(
+            "memory.pressure_concurrency is not below download.max_concurrent, pressure throttling has no effect"
+                .to_string(),
+        );
+    }
+    if config.premium_lossless_only && !config.premium_enabled {
+        warnings.push("premium.lossless_only is set but premium.enabled is false".to_string());
+    }
+    warnings
+}
+
+/// Validate every key and value of the config file and command line
+/// overrides, check the cache directory and database can be opened, and
+/// report all problems together
+pub async fn check_config(config_path: &str, overrides: &[(String, String)]) -> Result<()> {
+    if !Path::new(config_path).exists() {
+        return Err(BotError::Config(format!(
+            "Config file {config_path} not found"
+        )));
+    }
+
+    let values =
+        Config::read_values(config_path, overrides).map_err(|e| BotError::Config(e.to_string()))?;
+    let config = &Config::from_values(&values);
+    let mut errors = lint_values(&values);
+    errors.extend(config_errors(config));
+    if let Err(e) = ensure_dir(&config.cache_dir) {
+        errors.push(format!("cache dir {}: {e}", config.cache_dir));
+    }
+    match Database::new(config).await {
+        Ok(database) => {
+            let songs = database.count_total_songs().await?;
+            println!("database {}: {songs} cached songs", config.database);
+        }
+        Err(e) => errors.push(format!("database {}: {e}", config.database)),
+    }
+    if let Ok(metadata) = tokio::fs::metadata(&config.database).await {
+        println!("database size: {}", format_file_size(metadata.len()));
+    }
+
+    for warning in config_warnings(config) {
+        println!("warning: {warning}");
+    }
+    for error in &errors {
+        println!("error: {error}");
+    }
+
+    if errors.is_empty() {
+        println!("{config_path}: OK");
+        Ok(())
+    } else {
+        Err(BotError::Config(format!(
+            "{config_path}: {} problem(s) found",
+            errors.len()
+        )))
+    }
+}
+
+/// Print the health report as JSON, returning whether every dependency is reachable
+pub async fn healthcheck(config: &Config) -> Result<bool> {
+    let report = crate::health::check_config_health(config).await;
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(report.is_healthy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_errors, config_warnings};
+    use crate::config::{Config, StorageMode};
+
+    #[test]
+    fn rejects_missing_or_malformed_token() {
+        let mut config = Config::default();
+        assert_eq!(config_errors(&config), vec!["bot.token is not set"]);
+
+        config.bot_token = "not-a-token".to_string();
+        assert_eq!(
+            config_errors(&config),
+            vec!["bot.token is not a valid Telegram bot token"]
+        );
+
+        config.bot_token = "123456:ABC-DEF".to_string();
+        assert!(config_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn rejects_zero_timeouts_and_unusable_memory_mode() {
+        let mut config = Config {
+            bot_token: "123456:ABC-DEF".to_string(),
+            ..Config::default()
+        };
+        config.download_timeout = 0;
+        config.storage_mode = StorageMode::Memory;
+        config.memory_max_file_mb = 0;
+        let errors = config_errors(&config);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("downloadtimeout is 0"));
+        assert!(errors[1].starts_with("download.storage_mode = memory"));
+    }
+
+    #[test]
+    fn warns_without_admins() {
+        let mut config = Config::default();
+        assert!(
+            config_warnings(&config)
+                .iter()
+                .any(|w| w.starts_with("bot.botadmin"))
+        );
+        config.bot_admin = vec![1];
+        assert!(
+            !config_warnings(&config)
+                .iter()
+                .any(|w| w.starts_with("bot.botadmin"))
+        );
+    }
+}
@@ -0,0 +1,749 @@
+//! Command registry shared by the dispatcher, Telegram command menus and /help
+//!
+//! Every user-visible command is declared once here so that the menus registered
+//! via `set_my_commands` and the generated /help text never drift apart. The
+//! `Command` enum (teloxide `BotCommands` derive) parses incoming commands and
+//! carries the descriptions; `COMMANDS` adds who sees each one and its usage.
+
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use teloxide::types::BotCommand;
+use teloxide::utils::command::BotCommands;
+
+/// Who may see (and use) a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandAudience {
+    /// Available to every user
+    Everyone,
+    /// Restricted to `bot_admin` users
+    Admin,
+}
+
+/// Static description of a bot command
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    /// Command name without the leading slash
+    pub name: &'static str,
+    /// Argument hint shown in /help (empty if the command takes no arguments)
+    pub usage: &'static str,
+    pub audience: CommandAudience,
+    /// Whether the command is offered in the group chat menu
+    pub in_groups: bool,
+}
+
+/// Parsed bot command; descriptions double as the Telegram menu and /help text
+#[derive(BotCommands, Debug, Clone, PartialEq, Eq)]
+#[command(rename_rule = "lowercase")]
+pub enum Command {
+    #[command(description = "下载/分享网易云音乐 (支持搜索关键词或 ID)")]
+    Music(Args),
+    #[command(description = "下载/分享网易云音乐 (等同于 /music)")]
+    Netease(Args),
+    #[command(description = "搜索网易云音乐")]
+    Search(Args),
+    #[command(description = "发送整张专辑")]
+    Album(Args),
+    #[command(description = "发送歌单中的歌曲")]
+    Playlist(Args),
+    #[command(description = "以一首歌为种子发送心动模式推荐歌曲")]
+    Heartbeat(Args),
+    #[command(description = "获取歌曲歌词 (也可回复机器人发送的音频)")]
+    Lyric(Args),
+    #[command(description = "以语音消息发送歌曲")]
+    Voice(Args),
+    #[command(description = "截取歌曲片段")]
+    Clip(Args),
+    #[command(description = "查看歌曲详情与可用音质，可选择音质下载")]
+    Info(Args),
+    #[command(description = "查看歌曲热门评论 (也可回复机器人发送的音频)")]
+    Comments(Args),
+    #[command(description = "生成歌曲分享卡片")]
+    Card(Args),
+    #[command(description = "反馈无法播放或错误的歌曲")]
+    Report(Args),
+    #[command(description = "绑定 Last.fm/ListenBrainz，同步记录获取的歌曲")]
+    Scrobble(Args),
+    #[command(description = "删除机器人保存的你的所有个人数据")]
+    DeleteMyData(Args),
+    #[command(description = "设置当前对话静默发送音频")]
+    Silent(Args),
+    #[command(description = "设置当前对话下载消息与音频说明的语言 (中文/English)")]
+    Lang(Args),
+    #[command(description = "猜歌游戏，/quiz top 查看排行榜")]
+    Quiz(Args),
+    #[command(description = "开启/关闭群组点歌台")]
+    Jukebox(Args),
+    #[command(description = "点歌台点歌，不带参数查看队列")]
+    Queue(Args),
+    #[command(description = "查看点歌台正在播放的歌曲")]
+    NowPlaying,
+    #[command(description = "跳过点歌台当前歌曲")]
+    Skip,
+    #[command(description = "设置当前对话音频的说明文字样式")]
+    Caption(Args),
+    #[command(description = "设置当前对话音频说明中的话题标签")]
+    Hashtags(Args),
+    #[command(description = "设置当前对话新下载歌曲的封面模式")]
+    Cover(Args),
+    #[command(description = "查看机器人运行状态和缓存信息")]
+    Status,
+    #[command(description = "使用 Telegram Stars 购买高级会员")]
+    Premium,
+    #[command(description = "开始使用机器人或解析歌曲 ID")]
+    Start(Args),
+    #[command(description = "关于机器人")]
+    About,
+    #[command(description = "显示详细使用帮助")]
+    Help,
+    #[command(description = "[管理员] 清理指定音乐的缓存")]
+    RmCache(Args),
+    #[command(description = "[管理员] 恢复被删除的缓存")]
+    Undelete(Args),
+    #[command(description = "[管理员] 忽略缓存重新下载歌曲并替换缓存")]
+    Refresh(Args),
+    #[command(description = "[管理员] 浏览 MUSIC_U 账号创建和收藏的歌单")]
+    MyPlaylists,
+    #[command(description = "[管理员] 浏览并下载 MUSIC_U 账号云盘中的歌曲")]
+    Cloud,
+    #[command(description = "[管理员] 查看 MUSIC_U 账号与 VIP 状态")]
+    VipStatus,
+    #[command(description = "[管理员] 检查 API、MUSIC_U、缓存目录与数据库")]
+    SelfTest,
+    #[command(description = "[管理员] 查看内存统计 / 切换堆分析")]
+    MemStats(Args),
+    #[command(description = "[管理员] 后台预缓存歌单或专辑")]
+    Precache(Args),
+    #[command(description = "[管理员] 后台低优先级预缓存歌手的全部专辑")]
+    Discography(Args),
+    #[command(description = "[管理员] 下载歌曲、歌单或专辑到本地曲库 (不发送)")]
+    Fetch(Args),
+    #[command(description = "[管理员] 导出缓存数据库为 JSON 文件")]
+    ExportCache,
+    #[command(description = "[管理员] 回复导出文件以合并导入缓存")]
+    ImportCache,
+    #[command(description = "[管理员] 查看缓存目录与数据库占用")]
+    CacheSize,
+    #[command(description = "[管理员] 查看管理员操作记录")]
+    AuditLog(Args),
+    #[command(description = "[管理员] 清除所有缓存 (需确认)")]
+    ClearAllCache(ClearCacheAction),
+}
+
+/// Free-form arguments, whitespace-normalized (`None` when absent)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args(pub Option<String>);
+
+impl FromStr for Args {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        Ok(Self((!args.is_empty()).then_some(args)))
+    }
+}
+
+/// `/clearallcache` step: prompt first, then `confirm` (optionally `files`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearCacheAction {
+    Prompt,
+    Confirm { delete_files: bool },
+}
+
+impl FromStr for ClearCacheAction {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        Ok(match args.as_slice() {
+            ["confirm"] => Self::Confirm {
+                delete_files: false,
+            },
+            ["confirm", "files"] => Self::Confirm { delete_files: true },
+            _ => Self::Prompt,
+        })
+    }
+}
+
+impl Command {
+    /// Parse a message text, ignoring commands addressed to other bots
+    #[must_use]
+    pub fn parse_text(text: &str, bot_username: &str) -> Option<Self> {
+        // Commands may be followed by a newline instead of a space
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        <Self as BotCommands>::parse(&text, bot_username).ok()
+    }
+
+    /// Command name without the leading slash
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Music(_) => "music",
+            Self::Netease(_) => "netease",
+            Self::Search(_) => "search",
+            Self::Album(_) => "album",
+            Self::Playlist(_) => "playlist",
+            Self::Heartbeat(_) => "heartbeat",
+            Self::Lyric(_) => "lyric",
+            Self::Voice(_) => "voice",
+            Self::Clip(_) => "clip",
+            Self::Info(_) => "info",
+            Self::Comments(_) => "comments",
+            Self::Card(_) => "card",
+            Self::Report(_) => "report",
+            Self::Scrobble(_) => "scrobble",
+            Self::DeleteMyData(_) => "deletemydata",
+            Self::Silent(_) => "silent",
+            Self::Lang(_) => "lang",
+            Self::Quiz(_) => "quiz",
+            Self::Jukebox(_) => "jukebox",
+            Self::Queue(_) => "queue",
+            Self::NowPlaying => "nowplaying",
+            Self::Skip => "skip",
+            Self::Caption(_) => "caption",
+            Self::Hashtags(_) => "hashtags",
+            Self::Cover(_) => "cover",
+            Self::Status => "status",
+            Self::Premium => "premium",
+            Self::Start(_) => "start",
+            Self::About => "about",
+            Self::Help => "help",
+            Self::RmCache(_) => "rmcache",
+            Self::Undelete(_) => "undelete",
+            Self::Refresh(_) => "refresh",
+            Self::MyPlaylists => "myplaylists",
+            Self::Cloud => "cloud",
+            Self::VipStatus => "vipstatus",
+            Self::SelfTest => "selftest",
+            Self::MemStats(_) => "memstats",
+            Self::Precache(_) => "precache",
+            Self::Discography(_) => "discography",
+            Self::Fetch(_) => "fetch",
+            Self::ExportCache => "exportcache",
+            Self::ImportCache => "importcache",
+            Self::CacheSize => "cachesize",
+            Self::AuditLog(_) => "auditlog",
+            Self::ClearAllCache(_) => "clearallcache",
+        }
+    }
+
+    /// Arguments as recorded in the audit log (empty if none)
+    #[must_use]
+    pub fn args(&self) -> String {
+        match self {
+            Self::ClearAllCache(ClearCacheAction::Confirm { delete_files }) => if *delete_files {
+                "confirm files"
+            } else {
+                "confirm"
+            }
+            .to_string(),
+            Self::Music(Args(args))
+            | Self::Netease(Args(args))
+            | Self::Search(Args(args))
+            | Self::Album(Args(args))
+            | Self::Playlist(Args(args))
+            | Self::Heartbeat(Args(args))
+            | Self::Lyric(Args(args))
+            | Self::Voice(Args(args))
+            | Self::Clip(Args(args))
+            | Self::Info(Args(args))
+            | Self::Comments(Args(args))
+            | Self::Card(Args(args))
+            | Self::Report(Args(args))
+            | Self::Scrobble(Args(args))
+            | Self::DeleteMyData(Args(args))
+            | Self::Silent(Args(args))
+            | Self::Lang(Args(args))
+            | Self::Quiz(Args(args))
+            | Self::Jukebox(Args(args))
+            | Self::Queue(Args(args))
+            | Self::Caption(Args(args))
+            | Self::Hashtags(Args(args))
+            | Self::Cover(Args(args))
+            | Self::Start(Args(args))
+            | Self::RmCache(Args(args))
+            | Self::Undelete(Args(args))
+            | Self::Refresh(Args(args))
+            | Self::MemStats(Args(args))
+            | Self::Precache(Args(args))
+            | Self::Discography(Args(args))
+            | Self::Fetch(Args(args))
+            | Self::AuditLog(Args(args)) => args.clone().unwrap_or_default(),
+            Self::NowPlaying
+            | Self::Skip
+            | Self::Status
+            | Self::Premium
+            | Self::About
+            | Self::Help
+            | Self::MyPlaylists
+            | Self::Cloud
+            | Self::VipStatus
+            | Self::SelfTest
+            | Self::ExportCache
+            | Self::ImportCache
+            | Self::CacheSize
+            | Self::ClearAllCache(ClearCacheAction::Prompt) => String::new(),
+        }
+    }
+
+    /// Whether the command is restricted to `bot_admin` users
+    #[must_use]
+    pub fn is_admin(&self) -> bool {
+        COMMANDS
+            .iter()
+            .any(|spec| spec.name == self.name() && spec.audience == CommandAudience::Admin)
+    }
+
+    /// Whether the command is worth an info log line: downloads, searches and
+    /// admin commands, not help or settings
+    #[must_use]
+    pub fn is_logged(&self) -> bool {
+        self.is_admin()
+            || matches!(
+                self,
+                Self::Music(_)
+                    | Self::Netease(_)
+                    | Self::Search(_)
+                    | Self::Album(_)
+                    | Self::Playlist(_)
+                    | Self::Heartbeat(_)
+                    | Self::Voice(_)
+                    | Self::Clip(_)
+                    | Self::Card(_)
+                    | Self::Comments(_)
+                    | Self::Queue(_)
+                    | Self::Report(_)
+            )
+    }
+}
+
+impl CommandSpec {
+    /// Menu description, declared on the matching `Command` variant
+    #[must_use]
+    pub fn description(&self) -> String {
+        Command::bot_commands()
+            .into_iter()
+            .find(|command| command.command.trim_start_matches('/') == self.name)
+            .map(|command| command.description)
+            .unwrap_or_default()
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "music",
+        usage: "<关键词或ID> [--cover=original|thumbnail|both|none]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "netease",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "search",
+        usage: "<关键词>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "album",
+        usage: "<专辑ID或链接> [起-止] [--shuffle]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "playlist",
+        usage: "<歌单ID或链接> [起-止] [--shuffle]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "heartbeat",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "lyric",
+        usage: "<关键词或ID> [--plain] [--offset=±毫秒] [--lrc|--txt] [--karaoke] [--telegraph]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "voice",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "clip",
+        usage: "<音乐ID> <开始> <结束>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "info",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "comments",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "card",
+        usage: "<关键词或ID>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "report",
+        usage: "<音乐ID> <原因>",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "scrobble",
+        usage: "[on|off|unlink|listenbrainz <令牌>|lastfm <用户名> <密码>]",
+        audience: CommandAudience::Everyone,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "deletemydata",
+        usage: "[confirm]",
+        audience: CommandAudience::Everyone,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "silent",
+        usage: "on|off|default",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "lang",
+        usage: "zh|en|default",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "quiz",
+        usage: "[clip|cover|top]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "jukebox",
+        usage: "on|off",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "queue",
+        usage: "[关键词或ID]",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "nowplaying",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "skip",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "caption",
+        usage: "full|compact|none|default",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "hashtags",
+        usage: "<标签...>|none|default",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "cover",
+        usage: "original|thumbnail|both|none|default",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "status",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "premium",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "start",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "about",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "help",
+        usage: "",
+        audience: CommandAudience::Everyone,
+        in_groups: true,
+    },
+    CommandSpec {
+        name: "rmcache",
+        usage: "<音乐ID>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "undelete",
+        usage: "<音乐ID|last>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "refresh",
+        usage: "<音乐ID>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "myplaylists",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "cloud",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "vipstatus",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "selftest",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "memstats",
+        usage: "[prof on|prof off|dump]",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "precache",
+        usage: "<歌单ID|链接>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "discography",
+        usage: "<歌手ID|链接>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "fetch",
+        usage: "<音乐ID|歌单链接|专辑链接>",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "exportcache",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "importcache",
+        usage: "(回复 JSON 文件)",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "cachesize",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "auditlog",
+        usage: "[条数]",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+    CommandSpec {
+        name: "clearallcache",
+        usage: "",
+        audience: CommandAudience::Admin,
+        in_groups: false,
+    },
+];
+
+/// Build the command menu for a scope
+///
+/// * `include_admin` - include admin-only commands (admin private chats)
+/// * `groups_only` - only commands offered in group chats
+#[must_use]
+pub fn menu_commands(include_admin: bool, groups_only: bool) -> Vec<BotCommand> {
+    COMMANDS
+        .iter()
+        .filter(|spec| include_admin || spec.audience == CommandAudience::Everyone)
+        .filter(|spec| !groups_only || spec.in_groups)
+        .map(|spec| BotCommand::new(spec.name, spec.description()))
+        .collect()
+}
+
+/// Render the command list section of /help as HTML
+#[must_use]
+pub fn help_command_list(include_admin: bool) -> String {
+    COMMANDS
+        .iter()
+        .filter(|spec| include_admin || spec.audience == CommandAudience::Everyone)
+        .map(|spec| {
+            let usage = if spec.usage.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", html_escape(spec.usage))
+            };
+            format!(
+                "• <code>/{}{}</code> - {}",
+                spec.name,
+                usage,
+                html_escape(&spec.description())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Args, COMMANDS, ClearCacheAction, Command, CommandAudience, help_command_list,
+        menu_commands,
+    };
+    use teloxide::utils::command::BotCommands;
+
+    #[test]
+    fn menus_hide_admin_commands_from_users() {
+        let user_menu = menu_commands(false, false);
+        assert!(user_menu.iter().all(|cmd| cmd.command != "rmcache"));
+
+        let admin_menu = menu_commands(true, false);
+        assert_eq!(admin_menu.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn every_command_has_a_description() {
+        for spec in COMMANDS {
+            assert!(
+                !spec.description().is_empty(),
+                "/{} is undescribed",
+                spec.name
+            );
+        }
+    }
+
+    #[test]
+    fn parses_commands_with_arguments() {
+        assert_eq!(
+            Command::parse_text("/music@MyBot  晴天\n周杰伦", "MyBot"),
+            Some(Command::Music(Args(Some("晴天 周杰伦".to_string()))))
+        );
+        assert_eq!(
+            Command::parse_text("/lyric\n123", "MyBot"),
+            Some(Command::Lyric(Args(Some("123".to_string()))))
+        );
+        assert_eq!(
+            Command::parse_text("/music", "MyBot"),
+            Some(Command::Music(Args(None)))
+        );
+        assert_eq!(
+            Command::parse_text("/clearallcache confirm files", "MyBot"),
+            Some(Command::ClearAllCache(ClearCacheAction::Confirm {
+                delete_files: true
+            }))
+        );
+        assert_eq!(Command::parse_text("/music@OtherBot 晴天", "MyBot"), None);
+        assert_eq!(Command::parse_text("/unknown", "MyBot"), None);
+    }
+
+    #[test]
+    fn recognizes_admin_commands() {
+        let parse = |text| Command::parse_text(text, "MyBot").unwrap();
+        assert!(parse("/rmcache 123").is_admin());
+        assert!(parse("/refresh 123").is_admin());
+        assert!(parse("/fetch 123").is_admin());
+        assert!(parse("/discography 6452").is_admin());
+        assert!(parse("/auditlog").is_admin());
+        assert!(parse("/clearallcache").is_admin());
+        assert!(!parse("/music 晴天").is_admin());
+        assert_eq!(
+            parse("/clearallcache confirm  files").args(),
+            "confirm files"
+        );
+        assert_eq!(parse("/rmcache\n123").args(), "123");
+    }
+
+    #[test]
+    fn every_command_name_is_registered() {
+        for command in Command::bot_commands() {
+            let text = command.command.clone();
+            let parsed = Command::parse_text(&text, "MyBot").unwrap();
+            assert_eq!(format!("/{}", parsed.name()), text);
+            assert!(COMMANDS.iter().any(|spec| spec.name == parsed.name()));
+        }
+    }
+
+    #[test]
+    fn group_menu_only_lists_group_commands() {
+        let group_menu = menu_commands(false, true);
+        assert!(group_menu.iter().any(|cmd| cmd.command == "music"));
+        assert!(group_menu.iter().all(|cmd| cmd.command != "start"));
+    }
+
+    #[test]
+    fn help_lists_every_visible_command() {
+        let help = help_command_list(false);
+        for spec in COMMANDS {
+            let listed = help.contains(&format!("/{}", spec.name));
+            assert_eq!(listed, spec.audience == CommandAudience::Everyone);
+        }
+        assert!(help.contains("&lt;关键词&gt;"));
+    }
+}
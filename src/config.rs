@@ -5,6 +5,17 @@ use std::io::{BufRead, BufReader};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::secrets;
+use crate::utils::{HASHTAG_EXT_PLACEHOLDER, parse_hashtags};
+
+/// Browser User-Agent sent to NetEase unless `network.music_user_agent` is set
+pub const DEFAULT_MUSIC_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// Accepted `database.journal_mode` values
+const JOURNAL_MODES: &[&str] = &["wal", "delete", "truncate", "persist", "memory", "off"];
+/// Accepted `database.synchronous` values
+const SYNCHRONOUS_LEVELS: &[&str] = &["off", "normal", "full", "extra"];
+
 /// Storage mode for temporary files during download processing
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -27,6 +38,8 @@ pub enum CoverMode {
     Original,
     /// Download both original and thumbnail (legacy behavior)
     Both,
+    /// No cover art at all
+    None,
 }
 
 impl Default for CoverMode {
@@ -43,6 +56,7 @@ impl std::str::FromStr for CoverMode {
             "thumbnail" => Ok(Self::Thumbnail),
             "original" => Ok(Self::Original),
             "both" => Ok(Self::Both),
+            "none" => Ok(Self::None),
             _ => Err(anyhow::anyhow!("Invalid cover mode: {s}")),
         }
     }
@@ -67,6 +81,128 @@ impl std::str::FromStr for StorageMode {
     }
 }
 
+/// Output format of log lines
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable text (default)
+    #[default]
+    Text,
+    /// One JSON object per line, for Loki/ELK ingestion
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!("Invalid log format: {s}")),
+        }
+    }
+}
+
+/// When the log file configured by `log.file` is rotated
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Start a new file every day (default)
+    #[default]
+    Daily,
+    /// Start a new file once `log.max_size_mb` is reached
+    Size,
+}
+
+impl std::str::FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "daily" => Ok(Self::Daily),
+            "size" => Ok(Self::Size),
+            _ => Err(anyhow::anyhow!("Invalid log rotation: {s}")),
+        }
+    }
+}
+
+/// Caption attached to delivered audio (`bot.caption_style`, per chat via /caption)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionStyle {
+    /// Title, artists, album, format details and bot mention (default)
+    #[default]
+    Full,
+    /// Only "Title - Artists"
+    Compact,
+    /// No caption; the metadata is already in the file tags
+    None,
+}
+
+impl std::str::FromStr for CaptionStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "compact" => Ok(Self::Compact),
+            "none" => Ok(Self::None),
+            _ => Err(anyhow::anyhow!("Invalid caption style: {s}")),
+        }
+    }
+}
+
+/// HTTP versions offered by outgoing clients (`network.http_version`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// HTTP/1.1 only (default, what the bot has always used)
+    #[default]
+    Http1,
+    /// Negotiate HTTP/2 via ALPN, falling back to HTTP/1.1
+    Auto,
+    /// HTTP/2 without negotiation
+    Http2,
+}
+
+impl std::str::FromStr for HttpVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "http1" => Ok(Self::Http1),
+            "auto" => Ok(Self::Auto),
+            "http2" => Ok(Self::Http2),
+            _ => Err(anyhow::anyhow!("Invalid HTTP version: {s}")),
+        }
+    }
+}
+
+/// TLS implementation used by outgoing clients (`network.tls_backend`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    /// rustls (default)
+    #[default]
+    Rustls,
+    /// The platform TLS library (OpenSSL, SChannel, Security.framework);
+    /// requires the `native-tls` feature
+    Native,
+}
+
+impl std::str::FromStr for TlsBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rustls" => Ok(Self::Rustls),
+            "native" => Ok(Self::Native),
+            _ => Err(anyhow::anyhow!("Invalid TLS backend: {s}")),
+        }
+    }
+}
+
 impl std::fmt::Display for StorageMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -81,16 +217,58 @@ impl std::fmt::Display for StorageMode {
 pub struct Config {
     // Required fields
     pub bot_token: String,
+    /// Additional bot tokens served by the same process, sharing the cache database
+    pub extra_bot_tokens: Vec<String>,
     pub music_u: Option<String>,
 
     // Optional fields with defaults
     pub bot_api: String,
+    /// NetEase API base URLs, comma separated; the healthiest one is used
     pub music_api: String,
     pub bot_admin: Vec<i64>,
     pub bot_debug: bool,
+    /// Send audio messages without notification by default (per-chat override via /silent)
+    pub silent: bool,
+    /// Default caption style of audio messages (per-chat override via /caption)
+    pub caption_style: CaptionStyle,
+    /// Hashtags in full captions, without `#`; `{ext}` is the file extension
+    /// (empty = none, per-chat override via /hashtags)
+    pub hashtags: Vec<String>,
     pub database: String,
+    /// SQLite `journal_mode` (wal, delete, truncate, persist, memory, off)
+    pub db_journal_mode: String,
+    /// SQLite `synchronous` level (off, normal, full, extra)
+    pub db_synchronous: String,
+    /// How long a connection waits on a locked database before failing (ms)
+    pub db_busy_timeout_ms: u64,
+    /// Maximum number of pooled SQLite connections
+    pub db_max_connections: u32,
     pub log_level: String,
+    pub log_format: LogFormat,
+    /// Also write logs to this file (empty = stdout only)
+    pub log_file: String,
+    pub log_rotation: LogRotation,
+    /// Size limit per file for size-based rotation (MB)
+    pub log_max_size_mb: u64,
+    /// Number of rotated log files to keep
+    pub log_max_files: usize,
+    /// Warn about SQL statements slower than this (ms, 0 = off)
+    pub slow_query_ms: u64,
+    /// Warn about audio downloads slower than this (seconds, 0 = off)
+    pub slow_fetch_secs: u64,
+    /// Warn about tagging slower than this (ms, 0 = off)
+    pub slow_tag_ms: u64,
+    /// Warn about Telegram uploads slower than this (seconds, 0 = off)
+    pub slow_upload_secs: u64,
+    /// OTLP/HTTP traces endpoint (empty = disabled, requires the `otel` feature)
+    pub otel_endpoint: String,
+    pub otel_service_name: String,
+    /// Sentry DSN (empty = disabled, requires the `sentry` feature)
+    pub sentry_dsn: String,
+    pub sentry_environment: String,
     pub cache_dir: String,
+    /// Root of the local library `/fetch` writes to (empty = `/fetch` disabled)
+    pub library_dir: String,
     pub auto_update: bool,
     pub auto_retry: bool,
     pub max_retry_times: u32,
@@ -108,6 +286,11 @@ pub struct Config {
     pub memory_max_file_mb: u64,
     /// Maximum concurrent downloads (lower = less memory, higher = more throughput)
     pub max_concurrent_downloads: u32,
+    /// Let single songs take download permits ahead of collection and
+    /// precache tracks
+    pub download_priority_lane: bool,
+    /// Maximum number of tracks sent for one album/playlist request
+    pub max_collection_tracks: usize,
     /// Max idle connections per host for download client
     pub download_pool_max_idle_per_host: usize,
     /// Download connect timeout (seconds)
@@ -116,28 +299,213 @@ pub struct Config {
     pub download_chunk_size_kb: usize,
     /// Cover art mode: thumbnail, original, or both
     pub cover_mode: CoverMode,
+    /// Send the free trial clip of songs that cannot be downloaded in full
+    pub preview_fallback: bool,
     /// Upload client reuse request limit
     pub upload_client_reuse_requests: u32,
     /// Upload timeout (seconds)
     pub upload_timeout_secs: u64,
-    /// Memory release interval in handled requests
-    pub memory_release_interval_requests: u32,
+    /// Deadline for a whole song job, from song detail to upload (seconds, 0 = none)
+    pub job_timeout_secs: u64,
+    /// Age after which status messages of panicked jobs are marked aborted (minutes, 0 = never)
+    pub stuck_status_minutes: u64,
+
+    // Outgoing HTTP clients (`[network]`)
+    /// User-Agent of the Telegram polling and upload clients
+    pub telegram_user_agent: String,
+    /// User-Agent of the NetEase API and CDN client
+    pub music_user_agent: String,
+    pub http_version: HttpVersion,
+    pub tls_backend: TlsBackend,
+    /// Proxy URL for all outgoing clients (empty = `HTTP(S)_PROXY` environment variables)
+    pub proxy: String,
     /// Database analyze interval in handled requests
     pub db_analyze_interval_requests: u32,
+
+    // jemalloc tuning (None = allocator default)
+    /// Milliseconds before unused dirty pages are purged (0 = immediately, -1 = never)
+    pub memory_dirty_decay_ms: Option<i64>,
+    /// Milliseconds before muzzy pages are returned to the OS (0 = immediately, -1 = never)
+    pub memory_muzzy_decay_ms: Option<i64>,
+    /// Purge unused pages from jemalloc background threads
+    pub memory_background_thread: bool,
+    /// Release memory after this many seconds without downloads (0 = disabled)
+    pub memory_idle_release_secs: u64,
+    /// Available RAM in MB below which downloads are throttled (0 = disabled)
+    pub memory_pressure_floor_mb: u64,
+    /// Concurrent downloads allowed while under memory pressure
+    pub memory_pressure_concurrency: u32,
+
+    // Usage limits for public instances
+    /// Maximum new downloads per chat per day (0 = unlimited)
+    pub daily_limit_per_chat: u32,
+    /// Maximum new downloads per user per day (0 = unlimited)
+    pub daily_limit_per_user: u32,
+    /// Maximum `/report`s per user per day, so admins cannot be flooded (0 = unlimited)
+    pub daily_report_limit: u32,
+
+    // Premium (Telegram Stars)
+    /// Sell premium access with `/premium`
+    pub premium_enabled: bool,
+    /// Price of one premium period in Telegram Stars
+    pub premium_price_stars: u32,
+    /// Days of premium bought with one payment
+    pub premium_days: u32,
+    /// Daily downloads of premium users, replacing the chat/user limits (0 = unlimited)
+    pub premium_daily_limit: u32,
+    /// Reserve lossless downloads for premium users (others get at most 320 kbps)
+    pub premium_lossless_only: bool,
+
+    // Message dispatching
+    /// Message handlers running at once before new messages are rejected
+    pub dispatcher_max_handlers: usize,
+
+    // Group jukebox
+    /// Seconds between songs posted from a group's `/queue`
+    pub jukebox_interval_secs: u64,
+
+    // Music quiz
+    /// Seconds players have to guess a `/quiz` song
+    pub quiz_round_secs: u64,
+
+    // Scheduled tasks
+    /// Perform the daily NetEase sign-in with MUSIC_U
+    pub checkin_enabled: bool,
+    /// Local hour (0-23) at which the daily sign-in runs
+    pub checkin_hour: u32,
+    /// Directory for scheduled database backups (empty = disabled)
+    pub backup_dir: String,
+    /// Hours between database backups
+    pub backup_interval_hours: u32,
+    /// Number of backups to keep
+    pub backup_keep: usize,
+
+    // Admin alerts
+    /// Consecutive failures of one cause before admins are notified (0 = disabled)
+    pub alert_failure_threshold: u32,
+
+    /// Telegraph account token for `/lyric --telegraph` (empty = create one on first use)
+    pub telegraph_access_token: String,
+
+    // Scrobbling (`/scrobble`)
+    /// Key that encrypts stored scrobbling credentials (empty = scrobbling disabled);
+    /// hashed without a KDF, so it must be a long random string
+    pub scrobble_secret_key: String,
+    /// Last.fm API account of the bot, needed to link Last.fm users
+    pub lastfm_api_key: String,
+    pub lastfm_api_secret: String,
+
+    // Update checks (enabled by `autoupdate`)
+    /// GitHub repository (`owner/name`) to check for releases
+    pub update_repo: String,
+    /// Hours between release checks
+    pub update_check_interval_hours: u32,
+    /// Download, verify and install the new release binary for the next restart
+    pub update_stage_binary: bool,
+
+    /// Listen address of the `/healthz` and `/metrics` endpoints (empty = disabled)
+    pub health_listen: String,
+
+    // ffmpeg transcoding
+    /// Enable features that shell out to ffmpeg (/voice)
+    pub ffmpeg_enabled: bool,
+    /// ffmpeg executable name or path
+    pub ffmpeg_path: String,
+    /// Maximum runtime of one ffmpeg invocation (seconds)
+    pub ffmpeg_timeout_secs: u64,
+    /// Opus bitrate of voice notes (kbps)
+    pub voice_bitrate_kbps: u32,
+    /// Maximum length of a /clip segment (seconds)
+    pub clip_max_secs: u64,
+    /// Font file used for /card text (must cover CJK; empty = /card disabled)
+    pub card_font: String,
+
+    /// Private channel that receives a copy of every uploaded audio (0 = disabled)
+    pub backup_channel_id: i64,
+    /// Compare cached songs' audio hash with NetEase on cache hits and
+    /// re-download tracks that were replaced upstream
+    pub verify_audio_hash: bool,
+    /// Redis URL of the shared hot cache (empty = disabled)
+    pub redis_url: String,
+    /// Lifetime of hot cache entries (seconds)
+    pub redis_ttl_secs: u64,
+    /// Lifetime of the per-song download lock shared through Redis (seconds);
+    /// also the longest another instance waits for it
+    pub download_lock_secs: u64,
+    /// Days a song removed by /rmcache or /clearallcache can still be restored (0 = forever)
+    pub purge_deleted_after_days: u32,
+    /// Re-download cached songs below `quality_upgrade_below_kbps` once
+    /// `MUSIC_U` offers better quality, replacing their file_id
+    pub quality_upgrade_enabled: bool,
+    /// Cached bitrate (kbps) below which a song is worth upgrading
+    pub quality_upgrade_below_kbps: u64,
+    /// Hours between two upgrade batches
+    pub quality_upgrade_interval_hours: u64,
+    /// Songs checked per upgrade batch
+    pub quality_upgrade_batch: u32,
+
+    // S3-compatible storage for downloads too large for memory (`s3` feature)
+    /// S3 endpoint URL (empty = disabled)
+    pub s3_endpoint: String,
+    pub s3_bucket: String,
+    pub s3_region: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    /// Address buckets as `endpoint/bucket` (MinIO) instead of `bucket.endpoint`
+    pub s3_path_style: bool,
+
+    // WebDAV share `/fetch` uploads to instead of `library_dir`
+    /// WebDAV folder URL (empty = disabled)
+    pub webdav_url: String,
+    pub webdav_username: String,
+    pub webdav_password: String,
+
+    // Companion REST API (`api` feature)
+    /// Listen address of the REST API (empty = disabled)
+    pub api_listen: String,
+    /// Bearer token required by the REST API (empty = no authentication)
+    pub api_token: String,
+    /// Token for the `/admin` web dashboard (empty = dashboard disabled)
+    pub api_admin_token: String,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             bot_token: String::new(),
+            extra_bot_tokens: Vec::new(),
             music_u: None,
             bot_api: "https://api.telegram.org".to_string(),
             music_api: "https://music.163.com".to_string(),
             bot_admin: Vec::new(),
             bot_debug: false,
+            silent: false,
+            caption_style: CaptionStyle::Full,
+            hashtags: vec![
+                "网易云音乐".to_string(),
+                HASHTAG_EXT_PLACEHOLDER.to_string(),
+            ],
             database: "cache.db".to_string(),
+            db_journal_mode: "wal".to_string(),
+            db_synchronous: "normal".to_string(),
+            db_busy_timeout_ms: 30_000,
+            db_max_connections: 4,
             log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            log_file: String::new(),
+            log_rotation: LogRotation::Daily,
+            log_max_size_mb: 50,
+            log_max_files: 7,
+            slow_query_ms: 200,
+            slow_fetch_secs: 60,
+            slow_tag_ms: 2000,
+            slow_upload_secs: 120,
+            otel_endpoint: String::new(),
+            otel_service_name: "music163bot-rust".to_string(),
+            sentry_dsn: String::new(),
+            sentry_environment: String::new(),
             cache_dir: "./cache".to_string(),
+            library_dir: String::new(),
             auto_update: true,
             auto_retry: true,
             max_retry_times: 3,
@@ -149,29 +517,127 @@ impl Default for Config {
             memory_buffer_mb: 100,
             memory_max_file_mb: 100,
             max_concurrent_downloads: 3, // 从 10 减少到 3，减少内存峰值
+            download_priority_lane: true,
+            max_collection_tracks: 50,
             download_pool_max_idle_per_host: 2,
             download_connect_timeout_secs: 10,
             download_chunk_size_kb: 256,
             cover_mode: CoverMode::Thumbnail,
+            preview_fallback: false,
             upload_client_reuse_requests: 50,
             upload_timeout_secs: 300,
-            memory_release_interval_requests: 10,
+            job_timeout_secs: 900,
+            stuck_status_minutes: 10,
+            telegram_user_agent: "Go-http-client/2.0".to_string(),
+            music_user_agent: DEFAULT_MUSIC_USER_AGENT.to_string(),
+            http_version: HttpVersion::Http1,
+            tls_backend: TlsBackend::Rustls,
+            proxy: String::new(),
             db_analyze_interval_requests: 20,
+            memory_dirty_decay_ms: None,
+            memory_muzzy_decay_ms: None,
+            memory_background_thread: false,
+            memory_idle_release_secs: 60,
+            memory_pressure_floor_mb: 0,
+            memory_pressure_concurrency: 1,
+            daily_limit_per_chat: 0,
+            daily_limit_per_user: 0,
+            daily_report_limit: 5,
+            premium_enabled: false,
+            premium_price_stars: 100,
+            premium_days: 30,
+            premium_daily_limit: 0,
+            premium_lossless_only: false,
+            dispatcher_max_handlers: 256,
+            jukebox_interval_secs: 180,
+            quiz_round_secs: 30,
+            checkin_enabled: false,
+            checkin_hour: 8,
+            backup_dir: String::new(),
+            backup_interval_hours: 24,
+            backup_keep: 7,
+            alert_failure_threshold: 10,
+            telegraph_access_token: String::new(),
+            scrobble_secret_key: String::new(),
+            lastfm_api_key: String::new(),
+            lastfm_api_secret: String::new(),
+            update_repo: "Lemonawa/music163bot-rust".to_string(),
+            update_check_interval_hours: 24,
+            update_stage_binary: false,
+            health_listen: String::new(),
+            ffmpeg_enabled: false,
+            ffmpeg_path: "ffmpeg".to_string(),
+            ffmpeg_timeout_secs: 120,
+            voice_bitrate_kbps: 64,
+            clip_max_secs: 60,
+            card_font: String::new(),
+            backup_channel_id: 0,
+            verify_audio_hash: false,
+            purge_deleted_after_days: 7,
+            quality_upgrade_enabled: false,
+            quality_upgrade_below_kbps: 256,
+            quality_upgrade_interval_hours: 6,
+            quality_upgrade_batch: 10,
+            redis_url: String::new(),
+            redis_ttl_secs: 86400,
+            download_lock_secs: 300,
+            s3_endpoint: String::new(),
+            s3_bucket: String::new(),
+            s3_region: "us-east-1".to_string(),
+            s3_access_key: String::new(),
+            s3_secret_key: String::new(),
+            s3_path_style: true,
+            webdav_url: String::new(),
+            webdav_username: String::new(),
+            webdav_password: String::new(),
+            api_listen: String::new(),
+            api_token: String::new(),
+            api_admin_token: String::new(),
         }
     }
 }
 
 impl Config {
     pub fn load(config_path: &str) -> Result<Self> {
-        let mut config = Config::default();
+        Self::load_with_overrides(config_path, &[])
+    }
 
-        if !std::path::Path::new(config_path).exists() {
+    /// `load` with `(key, value)` pairs from the command line taking
+    /// precedence over the file
+    pub fn load_with_overrides(config_path: &str, overrides: &[(String, String)]) -> Result<Self> {
+        let exists = std::path::Path::new(config_path).exists();
+        if !exists {
             tracing::warn!("Config file {} not found, using defaults", config_path);
-            return Ok(config);
         }
+        let config = Self::from_values(&Self::read_values(config_path, overrides)?);
 
-        let file = File::open(config_path)?;
-        let reader = BufReader::new(file);
+        // Validate required fields
+        if exists && config.bot_token.is_empty() {
+            return Err(anyhow::anyhow!("BOT_TOKEN is required"));
+        }
+
+        Ok(config)
+    }
+
+    /// Read the INI file (if it exists) into `section.key` → value pairs
+    /// (bare `key` for top-level legacy keys), apply `overrides` and fill in
+    /// secrets stored in files or the OS keyring
+    pub fn read_values(
+        config_path: &str,
+        overrides: &[(String, String)],
+    ) -> Result<HashMap<String, String>> {
+        let mut values = if std::path::Path::new(config_path).exists() {
+            Self::parse_values(BufReader::new(File::open(config_path)?))?
+        } else {
+            HashMap::new()
+        };
+        values.extend(overrides.iter().cloned());
+        secrets::resolve(&mut values)?;
+        Ok(values)
+    }
+
+    /// `read_values` for INI text from any reader
+    pub fn parse_values(reader: impl BufRead) -> Result<HashMap<String, String>> {
         let mut config_map = HashMap::new();
         let mut current_section = String::new();
 
@@ -207,12 +673,33 @@ impl Config {
             }
         }
 
+        Ok(config_map)
+    }
+
+    /// Build a config from `read_values` output; invalid values are logged
+    /// and replaced by their defaults (`check-config` reports them instead)
+    #[must_use]
+    pub fn from_values(config_map: &HashMap<String, String>) -> Self {
+        let mut config = Config::default();
+
         // Map configuration values
         if let Some(token) = config_map.get("bot.token") {
             config.bot_token.clone_from(token);
         }
 
-        config.music_u = config_map.get("music.music_u").cloned();
+        if let Some(tokens) = config_map.get("bot.extra_tokens") {
+            config.extra_bot_tokens = tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        config.music_u = config_map
+            .get("music.music_u")
+            .filter(|cookie| !cookie.is_empty())
+            .cloned();
 
         if let Some(api) = config_map.get("bot.api") {
             config.bot_api.clone_from(api);
@@ -226,10 +713,40 @@ impl Config {
             config.database.clone_from(url);
         }
 
+        if let Some(mode) = config_map.get("database.journal_mode") {
+            let mode = mode.to_lowercase();
+            if JOURNAL_MODES.contains(&mode.as_str()) {
+                config.db_journal_mode = mode;
+            } else {
+                tracing::warn!("Invalid database.journal_mode '{}', using wal", mode);
+            }
+        }
+
+        if let Some(level) = config_map.get("database.synchronous") {
+            let level = level.to_lowercase();
+            if SYNCHRONOUS_LEVELS.contains(&level.as_str()) {
+                config.db_synchronous = level;
+            } else {
+                tracing::warn!("Invalid database.synchronous '{}', using normal", level);
+            }
+        }
+
+        if let Some(timeout) = config_map.get("database.busy_timeout_ms") {
+            config.db_busy_timeout_ms = timeout.parse().unwrap_or(30_000);
+        }
+
+        if let Some(max) = config_map.get("database.max_connections") {
+            config.db_max_connections = max.parse().unwrap_or(4);
+        }
+
         if let Some(dir) = config_map.get("download.dir") {
             config.cache_dir.clone_from(dir);
         }
 
+        if let Some(dir) = config_map.get("download.library_dir") {
+            config.library_dir.clone_from(dir);
+        }
+
         if let Some(admins) = config_map.get("bot.botadmin") {
             config.bot_admin = admins
                 .split(',')
@@ -245,6 +762,19 @@ impl Config {
             tracing::info!("Loaded bot admins (from bot.admin): {:?}", config.bot_admin);
         }
 
+        if let Some(silent) = config_map.get("bot.silent") {
+            config.silent = silent.to_lowercase() == "true";
+        }
+        if let Some(style) = config_map.get("bot.caption_style") {
+            match style.parse() {
+                Ok(s) => config.caption_style = s,
+                Err(e) => tracing::warn!("Invalid caption_style '{}': {}, using full", style, e),
+            }
+        }
+        if let Some(tags) = config_map.get("bot.hashtags") {
+            config.hashtags = parse_hashtags(tags);
+        }
+
         if let Some(debug) = config_map.get("botdebug") {
             config.bot_debug = debug.to_lowercase() == "true";
         }
@@ -257,6 +787,55 @@ impl Config {
             config.log_level.clone_from(level);
         }
 
+        if let Some(format) = config_map.get("log.format") {
+            match format.parse() {
+                Ok(f) => config.log_format = f,
+                Err(e) => tracing::warn!("Invalid log.format '{}': {}, using text", format, e),
+            }
+        }
+
+        if let Some(file) = config_map.get("log.file") {
+            config.log_file.clone_from(file);
+        }
+        if let Some(rotation) = config_map.get("log.rotation") {
+            match rotation.parse() {
+                Ok(r) => config.log_rotation = r,
+                Err(e) => tracing::warn!("Invalid log.rotation '{}': {}, using daily", rotation, e),
+            }
+        }
+        if let Some(size) = config_map.get("log.max_size_mb") {
+            config.log_max_size_mb = size.parse().unwrap_or(50);
+        }
+        if let Some(files) = config_map.get("log.max_files") {
+            config.log_max_files = files.parse().unwrap_or(7);
+        }
+        if let Some(ms) = config_map.get("log.slow_query_ms") {
+            config.slow_query_ms = ms.parse().unwrap_or(200);
+        }
+        if let Some(secs) = config_map.get("log.slow_fetch_secs") {
+            config.slow_fetch_secs = secs.parse().unwrap_or(60);
+        }
+        if let Some(ms) = config_map.get("log.slow_tag_ms") {
+            config.slow_tag_ms = ms.parse().unwrap_or(2000);
+        }
+        if let Some(secs) = config_map.get("log.slow_upload_secs") {
+            config.slow_upload_secs = secs.parse().unwrap_or(120);
+        }
+
+        if let Some(endpoint) = config_map.get("otel.endpoint") {
+            config.otel_endpoint.clone_from(endpoint);
+        }
+        if let Some(name) = config_map.get("otel.service_name") {
+            config.otel_service_name.clone_from(name);
+        }
+
+        if let Some(dsn) = config_map.get("sentry.dsn") {
+            config.sentry_dsn.clone_from(dsn);
+        }
+        if let Some(environment) = config_map.get("sentry.environment") {
+            config.sentry_environment.clone_from(environment);
+        }
+
         if let Some(auto_update) = config_map.get("autoupdate") {
             config.auto_update = auto_update.to_lowercase() == "true";
         }
@@ -291,11 +870,17 @@ impl Config {
             config.memory_buffer_mb = buffer.parse().unwrap_or(100);
         }
         if let Some(max_file) = config_map.get("download.memory_max_file_mb") {
-            config.memory_max_file_mb = max_file.parse().unwrap_or(64);
+            config.memory_max_file_mb = max_file.parse().unwrap_or(100);
         }
         if let Some(concurrent) = config_map.get("download.max_concurrent") {
             config.max_concurrent_downloads = concurrent.parse().unwrap_or(3);
         }
+        if let Some(priority) = config_map.get("download.priority_lane") {
+            config.download_priority_lane = priority.to_lowercase() == "true";
+        }
+        if let Some(max_tracks) = config_map.get("download.max_collection_tracks") {
+            config.max_collection_tracks = max_tracks.parse().unwrap_or(50);
+        }
 
         if let Some(pool_size) = config_map.get("download.pool_max_idle_per_host") {
             config.download_pool_max_idle_per_host = pool_size.parse().unwrap_or(2);
@@ -312,6 +897,9 @@ impl Config {
                 Err(e) => tracing::warn!("Invalid cover_mode '{}': {}, using default", mode, e),
             }
         }
+        if let Some(preview) = config_map.get("download.preview_fallback") {
+            config.preview_fallback = preview.to_lowercase() == "true";
+        }
 
         if let Some(reuse_requests) = config_map.get("upload.client_reuse_requests") {
             config.upload_client_reuse_requests = reuse_requests.parse().unwrap_or(50);
@@ -319,26 +907,1139 @@ impl Config {
         if let Some(timeout) = config_map.get("upload.timeout_secs") {
             config.upload_timeout_secs = timeout.parse().unwrap_or(300);
         }
+        if let Some(timeout) = config_map.get("download.job_timeout_secs") {
+            config.job_timeout_secs = timeout.parse().unwrap_or(900);
+        }
+        if let Some(minutes) = config_map.get("download.stuck_status_minutes") {
+            config.stuck_status_minutes = minutes.parse().unwrap_or(10);
+        }
 
-        if let Some(interval) = config_map.get("maintenance.memory_release_interval_requests") {
-            config.memory_release_interval_requests = interval.parse().unwrap_or(1);
+        if let Some(agent) = config_map.get("network.telegram_user_agent") {
+            config.telegram_user_agent.clone_from(agent);
+        }
+        if let Some(agent) = config_map
+            .get("network.music_user_agent")
+            .filter(|agent| !agent.is_empty())
+        {
+            config.music_user_agent.clone_from(agent);
+        }
+        if let Some(version) = config_map.get("network.http_version") {
+            match version.parse() {
+                Ok(v) => config.http_version = v,
+                Err(e) => tracing::warn!(
+                    "Invalid network.http_version '{}': {}, using http1",
+                    version,
+                    e
+                ),
+            }
+        }
+        if let Some(backend) = config_map.get("network.tls_backend") {
+            match backend.parse() {
+                Ok(TlsBackend::Native) if cfg!(not(feature = "native-tls")) => tracing::warn!(
+                    "network.tls_backend = native needs the `native-tls` feature, using rustls"
+                ),
+                Ok(b) => config.tls_backend = b,
+                Err(e) => tracing::warn!(
+                    "Invalid network.tls_backend '{}': {}, using rustls",
+                    backend,
+                    e
+                ),
+            }
+        }
+        if let Some(proxy) = config_map.get("network.proxy") {
+            config.proxy.clone_from(proxy);
+        }
+
+        if config_map.contains_key("maintenance.memory_release_interval_requests") {
+            tracing::warn!(
+                "maintenance.memory_release_interval_requests is deprecated and ignored, \
+                 memory is released when idle (memory.idle_release_secs)"
+            );
         }
         if let Some(interval) = config_map.get("maintenance.db_analyze_interval_requests") {
             config.db_analyze_interval_requests = interval.parse().unwrap_or(1);
         }
 
-        // Validate required fields
-        if config.bot_token.is_empty() {
-            return Err(anyhow::anyhow!("BOT_TOKEN is required"));
+        for (key, target) in [
+            ("memory.dirty_decay_ms", &mut config.memory_dirty_decay_ms),
+            ("memory.muzzy_decay_ms", &mut config.memory_muzzy_decay_ms),
+        ] {
+            if let Some(value) = config_map.get(key).filter(|value| !value.is_empty()) {
+                match value.parse::<i64>() {
+                    Ok(ms) if ms >= -1 => *target = Some(ms),
+                    _ => tracing::warn!("Invalid {} '{}', using allocator default", key, value),
+                }
+            }
+        }
+        if let Some(enabled) = config_map.get("memory.background_thread") {
+            config.memory_background_thread = enabled.to_lowercase() == "true";
+        }
+        if let Some(secs) = config_map.get("memory.idle_release_secs") {
+            config.memory_idle_release_secs = secs.parse().unwrap_or(60);
+        }
+        if let Some(floor) = config_map.get("memory.pressure_floor_mb") {
+            config.memory_pressure_floor_mb = floor.parse().unwrap_or(0);
+        }
+        if let Some(concurrency) = config_map.get("memory.pressure_concurrency") {
+            config.memory_pressure_concurrency = concurrency.parse::<u32>().unwrap_or(1).max(1);
         }
 
-        Ok(config)
+        if let Some(limit) = config_map.get("limits.daily_per_chat") {
+            config.daily_limit_per_chat = limit.parse().unwrap_or(0);
+        }
+        if let Some(limit) = config_map.get("limits.daily_per_user") {
+            config.daily_limit_per_user = limit.parse().unwrap_or(0);
+        }
+        if let Some(limit) = config_map.get("limits.daily_reports") {
+            config.daily_report_limit = limit.parse().unwrap_or(5);
+        }
+
+        if let Some(enabled) = config_map.get("premium.enabled") {
+            config.premium_enabled = enabled.to_lowercase() == "true";
+        }
+        if let Some(price) = config_map.get("premium.price_stars") {
+            config.premium_price_stars = price.parse::<u32>().unwrap_or(100).max(1);
+        }
+        if let Some(days) = config_map.get("premium.days") {
+            config.premium_days = days.parse::<u32>().unwrap_or(30).max(1);
+        }
+        if let Some(limit) = config_map.get("premium.daily_limit") {
+            config.premium_daily_limit = limit.parse().unwrap_or(0);
+        }
+        if let Some(lossless_only) = config_map.get("premium.lossless_only") {
+            config.premium_lossless_only = lossless_only.to_lowercase() == "true";
+        }
+
+        if let Some(max) = config_map.get("dispatcher.max_handlers") {
+            config.dispatcher_max_handlers = max.parse::<usize>().unwrap_or(256).max(1);
+        }
+
+        if let Some(secs) = config_map.get("jukebox.interval_secs") {
+            config.jukebox_interval_secs = secs.parse().unwrap_or(180);
+        }
+
+        if let Some(secs) = config_map.get("quiz.round_secs") {
+            config.quiz_round_secs = secs.parse::<u64>().unwrap_or(30).max(5);
+        }
+
+        if let Some(enabled) = config_map.get("checkin.enabled") {
+            config.checkin_enabled = enabled.to_lowercase() == "true";
+        }
+        if let Some(hour) = config_map.get("checkin.hour") {
+            config.checkin_hour = hour.parse::<u32>().unwrap_or(8).min(23);
+        }
+
+        if let Some(dir) = config_map.get("backup.dir") {
+            config.backup_dir.clone_from(dir);
+        }
+        if let Some(hours) = config_map.get("backup.interval_hours") {
+            config.backup_interval_hours = hours.parse().unwrap_or(24);
+        }
+        if let Some(keep) = config_map.get("backup.keep") {
+            config.backup_keep = keep.parse().unwrap_or(7);
+        }
+
+        if let Some(threshold) = config_map.get("alerts.failure_threshold") {
+            config.alert_failure_threshold = threshold.parse().unwrap_or(10);
+        }
+
+        if let Some(token) = config_map.get("telegraph.access_token") {
+            config.telegraph_access_token.clone_from(token);
+        }
+
+        if let Some(key) = config_map.get("scrobble.secret_key") {
+            config.scrobble_secret_key.clone_from(key);
+        }
+        if let Some(key) = config_map.get("scrobble.lastfm_api_key") {
+            config.lastfm_api_key.clone_from(key);
+        }
+        if let Some(secret) = config_map.get("scrobble.lastfm_api_secret") {
+            config.lastfm_api_secret.clone_from(secret);
+        }
+
+        if let Some(repo) = config_map.get("update.repo") {
+            config.update_repo.clone_from(repo);
+        }
+        if let Some(hours) = config_map.get("update.check_interval_hours") {
+            config.update_check_interval_hours = hours.parse().unwrap_or(24);
+        }
+        if let Some(stage) = config_map.get("update.stage_binary") {
+            config.update_stage_binary = stage.to_lowercase() == "true";
+        }
+
+        if let Some(listen) = config_map.get("health.listen") {
+            config.health_listen.clone_from(listen);
+        }
+
+        if let Some(enabled) = config_map.get("ffmpeg.enabled") {
+            config.ffmpeg_enabled = enabled.to_lowercase() == "true";
+        }
+        if let Some(path) = config_map
+            .get("ffmpeg.path")
+            .filter(|path| !path.is_empty())
+        {
+            config.ffmpeg_path.clone_from(path);
+        }
+        if let Some(timeout) = config_map.get("ffmpeg.timeout_secs") {
+            config.ffmpeg_timeout_secs = timeout.parse().unwrap_or(120);
+        }
+        if let Some(bitrate) = config_map.get("ffmpeg.voice_bitrate_kbps") {
+            config.voice_bitrate_kbps = bitrate.parse().unwrap_or(64);
+        }
+        if let Some(max_secs) = config_map.get("ffmpeg.clip_max_secs") {
+            config.clip_max_secs = max_secs.parse().unwrap_or(60);
+        }
+        if let Some(font) = config_map.get("ffmpeg.card_font") {
+            config.card_font.clone_from(font);
+        }
+
+        if let Some(channel) = config_map.get("cache.backup_channel_id") {
+            config.backup_channel_id = channel.parse().unwrap_or(0);
+        }
+
+        if let Some(verify) = config_map.get("cache.verify_audio_hash") {
+            config.verify_audio_hash = verify.to_lowercase() == "true";
+        }
+
+        if let Some(days) = config_map.get("cache.purge_deleted_after_days") {
+            config.purge_deleted_after_days = days.parse().unwrap_or(7);
+        }
+
+        if let Some(enabled) = config_map.get("cache.upgrade_enabled") {
+            config.quality_upgrade_enabled = enabled.to_lowercase() == "true";
+        }
+
+        if let Some(kbps) = config_map.get("cache.upgrade_below_kbps") {
+            config.quality_upgrade_below_kbps = kbps.parse().unwrap_or(256);
+        }
+
+        if let Some(hours) = config_map.get("cache.upgrade_interval_hours") {
+            config.quality_upgrade_interval_hours = hours.parse().unwrap_or(6).max(1);
+        }
+
+        if let Some(batch) = config_map.get("cache.upgrade_batch") {
+            config.quality_upgrade_batch = batch.parse().unwrap_or(10);
+        }
+
+        if let Some(url) = config_map.get("cache.redis_url") {
+            config.redis_url.clone_from(url);
+        }
+
+        if let Some(ttl) = config_map.get("cache.redis_ttl_secs") {
+            config.redis_ttl_secs = ttl.parse().unwrap_or(86400);
+        }
+
+        if let Some(secs) = config_map.get("cache.download_lock_secs") {
+            config.download_lock_secs = secs.parse().unwrap_or(300);
+        }
+
+        if let Some(endpoint) = config_map.get("s3.endpoint") {
+            config.s3_endpoint.clone_from(endpoint);
+        }
+
+        if let Some(bucket) = config_map.get("s3.bucket") {
+            config.s3_bucket.clone_from(bucket);
+        }
+
+        if let Some(region) = config_map.get("s3.region") {
+            config.s3_region.clone_from(region);
+        }
+
+        if let Some(key) = config_map.get("s3.access_key") {
+            config.s3_access_key.clone_from(key);
+        }
+
+        if let Some(secret) = config_map.get("s3.secret_key") {
+            config.s3_secret_key.clone_from(secret);
+        }
+
+        if let Some(path_style) = config_map.get("s3.path_style") {
+            config.s3_path_style = path_style.to_lowercase() == "true";
+        }
+
+        if let Some(url) = config_map.get("webdav.url") {
+            config.webdav_url.clone_from(url);
+        }
+
+        if let Some(username) = config_map.get("webdav.username") {
+            config.webdav_username.clone_from(username);
+        }
+
+        if let Some(password) = config_map.get("webdav.password") {
+            config.webdav_password.clone_from(password);
+        }
+
+        if let Some(listen) = config_map.get("api.listen") {
+            config.api_listen.clone_from(listen);
+        }
+
+        if let Some(token) = config_map.get("api.token") {
+            config.api_token.clone_from(token);
+        }
+
+        if let Some(token) = config_map.get("api.admin_token") {
+            config.api_admin_token.clone_from(token);
+        }
+
+        config
+    }
+}
+
+/// What a config key accepts, for `check-config`
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    Text,
+    Bool,
+    /// Any integer
+    Integer,
+    /// Integer in the inclusive range
+    Range(i64, i64),
+    Choice(&'static [&'static str]),
+    /// Comma separated user IDs
+    IdList,
+}
+
+const UNSIGNED: ValueKind = ValueKind::Range(0, i64::MAX);
+const POSITIVE: ValueKind = ValueKind::Range(1, i64::MAX);
+
+/// A key `Config::from_values` understands
+#[derive(Debug, Clone, Copy)]
+struct ConfigKey {
+    /// `section.key`, or bare for top-level legacy keys
+    key: &'static str,
+    kind: ValueKind,
+    /// `Config` field holding the value; `None` for aliases, which the
+    /// template leaves out
+    field: Option<&'static str>,
+    /// Template comment
+    doc: &'static str,
+}
+
+impl ConfigKey {
+    const fn new(
+        key: &'static str,
+        field: &'static str,
+        kind: ValueKind,
+        doc: &'static str,
+    ) -> Self {
+        Self {
+            key,
+            kind,
+            field: Some(field),
+            doc,
+        }
+    }
+
+    const fn alias(key: &'static str, kind: ValueKind) -> Self {
+        Self {
+            key,
+            kind,
+            field: None,
+            doc: "",
+        }
     }
 }
 
+/// Every key `Config::from_values` understands, in template order
+const KEYS: &[ConfigKey] = &[
+    ConfigKey::new(
+        "botdebug",
+        "bot_debug",
+        ValueKind::Bool,
+        "调试模式，输出更详细的日志",
+    ),
+    ConfigKey::new(
+        "loglevel",
+        "log_level",
+        ValueKind::Text,
+        "日志级别: trace / debug / info / warn / error",
+    ),
+    ConfigKey::new(
+        "autoupdate",
+        "auto_update",
+        ValueKind::Bool,
+        "检查 GitHub Release 新版本并通知管理员 (见 [update]，--no-update 可临时关闭)",
+    ),
+    ConfigKey::new(
+        "autoretry",
+        "auto_retry",
+        ValueKind::Bool,
+        "下载失败时自动重试",
+    ),
+    ConfigKey::new("maxretrytimes", "max_retry_times", UNSIGNED, "最大重试次数"),
+    ConfigKey::new(
+        "downloadtimeout",
+        "download_timeout",
+        UNSIGNED,
+        "单首歌曲下载超时时间 (秒)",
+    ),
+    ConfigKey::new(
+        "checkmd5",
+        "check_md5",
+        ValueKind::Bool,
+        "下载完成后校验音频 MD5 (--no-md5-check 可临时关闭)",
+    ),
+    ConfigKey::alias("database", ValueKind::Text),
+    ConfigKey::new(
+        "bot.token",
+        "bot_token",
+        ValueKind::Text,
+        "Telegram Bot Token (从 @BotFather 获取)",
+    ),
+    ConfigKey::new(
+        "bot.extra_tokens",
+        "extra_bot_tokens",
+        ValueKind::Text,
+        "额外的机器人 Token (逗号分隔)，与主机器人在同一进程中运行并共享缓存数据库，用于分摊上传负载",
+    ),
+    ConfigKey::new(
+        "bot.api",
+        "bot_api",
+        ValueKind::Text,
+        "Telegram Bot API 地址，自建 API 服务器时替换 (不需要在末尾添加 /bot)",
+    ),
+    ConfigKey::new(
+        "bot.botadmin",
+        "bot_admin",
+        ValueKind::IdList,
+        "管理员用户ID (逗号分隔)，可从 @userinfobot 获取",
+    ),
+    ConfigKey::alias("bot.admin", ValueKind::IdList),
+    ConfigKey::new(
+        "bot.silent",
+        "silent",
+        ValueKind::Bool,
+        "静默发送音频 (不触发通知)，每个对话可用 /silent 单独覆盖",
+    ),
+    ConfigKey::new(
+        "bot.caption_style",
+        "caption_style",
+        ValueKind::Choice(&["full", "compact", "none"]),
+        "音频消息的说明文字，每个对话可用 /caption 单独覆盖",
+    ),
+    ConfigKey::new(
+        "bot.hashtags",
+        "hashtags",
+        ValueKind::Text,
+        "完整说明文字中的话题标签 (逗号或空格分隔)，{ext} 会替换为文件格式，留空则不添加",
+    ),
+    ConfigKey::new(
+        "music.api",
+        "music_api",
+        ValueKind::Text,
+        "网易云音乐 API 地址，自建 API 服务器时替换；多个地址用逗号分隔，自动优先使用延迟最低、错误最少的节点",
+    ),
+    ConfigKey::new(
+        "music.music_u",
+        "music_u",
+        ValueKind::Text,
+        "网易云音乐 MUSIC_U Cookie (用于付费歌曲和无损音质，可选)",
+    ),
+    ConfigKey::new(
+        "database.url",
+        "database",
+        ValueKind::Text,
+        "数据库文件路径",
+    ),
+    ConfigKey::new(
+        "database.journal_mode",
+        "db_journal_mode",
+        ValueKind::Choice(JOURNAL_MODES),
+        "SQLite 日志模式，wal 读写可并发",
+    ),
+    ConfigKey::new(
+        "database.synchronous",
+        "db_synchronous",
+        ValueKind::Choice(SYNCHRONOUS_LEVELS),
+        "SQLite 同步级别，wal 模式下 normal 已足够安全",
+    ),
+    ConfigKey::new(
+        "database.busy_timeout_ms",
+        "db_busy_timeout_ms",
+        UNSIGNED,
+        "数据库被锁定时的等待时间 (毫秒)",
+    ),
+    ConfigKey::new(
+        "database.max_connections",
+        "db_max_connections",
+        POSITIVE,
+        "连接池最大连接数",
+    ),
+    ConfigKey::new(
+        "cache.backup_channel_id",
+        "backup_channel_id",
+        ValueKind::Integer,
+        "备份频道ID，每个上传成功的音频都会复制一份到该频道 (0 = 关闭)",
+    ),
+    ConfigKey::new(
+        "cache.verify_audio_hash",
+        "verify_audio_hash",
+        ValueKind::Bool,
+        "缓存命中时向网易云核对音频 MD5，音源被替换时重新下载",
+    ),
+    ConfigKey::new(
+        "cache.redis_url",
+        "redis_url",
+        ValueKind::Text,
+        "Redis 热缓存地址，多实例共享 file_id，留空关闭",
+    ),
+    ConfigKey::new(
+        "cache.redis_ttl_secs",
+        "redis_ttl_secs",
+        UNSIGNED,
+        "Redis 缓存条目有效期 (秒)",
+    ),
+    ConfigKey::new(
+        "cache.download_lock_secs",
+        "download_lock_secs",
+        UNSIGNED,
+        "多实例下载同一首歌时共享锁的有效期 (秒)",
+    ),
+    ConfigKey::new(
+        "cache.purge_deleted_after_days",
+        "purge_deleted_after_days",
+        UNSIGNED,
+        "被 /rmcache 标记删除的缓存超过此天数后永久删除 (0 = 永不删除)",
+    ),
+    ConfigKey::new(
+        "cache.upgrade_enabled",
+        "quality_upgrade_enabled",
+        ValueKind::Bool,
+        "MUSIC_U 可提供更高音质时，后台逐步重新下载低码率缓存并替换 file_id",
+    ),
+    ConfigKey::new(
+        "cache.upgrade_below_kbps",
+        "quality_upgrade_below_kbps",
+        POSITIVE,
+        "码率低于此值 (kbps) 的缓存会尝试升级",
+    ),
+    ConfigKey::new(
+        "cache.upgrade_interval_hours",
+        "quality_upgrade_interval_hours",
+        POSITIVE,
+        "两批音质升级之间的间隔 (小时)",
+    ),
+    ConfigKey::new(
+        "cache.upgrade_batch",
+        "quality_upgrade_batch",
+        POSITIVE,
+        "每批检查的歌曲数",
+    ),
+    ConfigKey::new("download.dir", "cache_dir", ValueKind::Text, "下载目录"),
+    ConfigKey::new(
+        "download.library_dir",
+        "library_dir",
+        ValueKind::Text,
+        "/fetch 保存歌曲的本地曲库目录 (留空关闭 /fetch)",
+    ),
+    ConfigKey::new(
+        "download.storage_mode",
+        "storage_mode",
+        ValueKind::Choice(&["disk", "memory", "hybrid"]),
+        "临时文件存储模式 (v1.1.0+): disk 磁盘，memory 内存，hybrid 小文件用内存、大文件用磁盘",
+    ),
+    ConfigKey::new(
+        "download.memory_threshold",
+        "memory_threshold_mb",
+        UNSIGNED,
+        "混合模式阈值 (MB)，文件大于此值使用磁盘",
+    ),
+    ConfigKey::new(
+        "download.memory_buffer",
+        "memory_buffer_mb",
+        UNSIGNED,
+        "内存安全缓冲区 (MB)，可用内存必须大于文件大小加此值才使用内存",
+    ),
+    ConfigKey::new(
+        "download.memory_max_file_mb",
+        "memory_max_file_mb",
+        UNSIGNED,
+        "内存模式文件大小上限 (MB)，大于此值强制使用磁盘",
+    ),
+    ConfigKey::new(
+        "download.max_concurrent",
+        "max_concurrent_downloads",
+        UNSIGNED,
+        "最大并发下载数，较低的值减少内存峰值",
+    ),
+    ConfigKey::new(
+        "download.priority_lane",
+        "download_priority_lane",
+        ValueKind::Bool,
+        "单曲请求优先于专辑/歌单/预缓存任务获取下载名额",
+    ),
+    ConfigKey::new(
+        "download.max_collection_tracks",
+        "max_collection_tracks",
+        UNSIGNED,
+        "单次专辑/歌单请求最多发送的歌曲数",
+    ),
+    ConfigKey::new(
+        "download.pool_max_idle_per_host",
+        "download_pool_max_idle_per_host",
+        UNSIGNED,
+        "下载客户端每个主机保留的空闲连接数",
+    ),
+    ConfigKey::new(
+        "download.connect_timeout_secs",
+        "download_connect_timeout_secs",
+        UNSIGNED,
+        "下载连接超时 (秒)",
+    ),
+    ConfigKey::new(
+        "download.chunk_size_kb",
+        "download_chunk_size_kb",
+        POSITIVE,
+        "下载缓冲块大小 (KB)",
+    ),
+    ConfigKey::new(
+        "download.cover_mode",
+        "cover_mode",
+        ValueKind::Choice(&["thumbnail", "original", "both", "none"]),
+        "封面模式，每个对话可用 /cover 单独设置",
+    ),
+    ConfigKey::new(
+        "download.preview_fallback",
+        "preview_fallback",
+        ValueKind::Bool,
+        "无法获取完整歌曲时发送约 30 秒的试听片段",
+    ),
+    ConfigKey::new(
+        "upload.client_reuse_requests",
+        "upload_client_reuse_requests",
+        UNSIGNED,
+        "上传客户端重建前处理的请求数",
+    ),
+    ConfigKey::new(
+        "upload.timeout_secs",
+        "upload_timeout_secs",
+        UNSIGNED,
+        "上传超时 (秒)",
+    ),
+    ConfigKey::new(
+        "download.job_timeout_secs",
+        "job_timeout_secs",
+        UNSIGNED,
+        "单首歌曲从获取信息到上传完成的总时限 (秒)，超时后取消并清理临时文件，0 表示不限制",
+    ),
+    ConfigKey::new(
+        "download.stuck_status_minutes",
+        "stuck_status_minutes",
+        UNSIGNED,
+        "任务崩溃后留下的状态消息在多少分钟后改为「任务异常终止」，0 表示关闭",
+    ),
+    ConfigKey::new(
+        "network.telegram_user_agent",
+        "telegram_user_agent",
+        ValueKind::Text,
+        "Telegram 客户端使用的 User-Agent",
+    ),
+    ConfigKey::new(
+        "network.music_user_agent",
+        "music_user_agent",
+        ValueKind::Text,
+        "网易云 API 和 CDN 使用的 User-Agent，留空使用内置的浏览器 UA",
+    ),
+    ConfigKey::new(
+        "network.http_version",
+        "http_version",
+        ValueKind::Choice(&["http1", "auto", "http2"]),
+        "HTTP 版本，auto 通过 ALPN 协商 HTTP/2",
+    ),
+    ConfigKey::new(
+        "network.tls_backend",
+        "tls_backend",
+        ValueKind::Choice(&["rustls", "native"]),
+        "TLS 实现，native 需使用 --features native-tls 编译",
+    ),
+    ConfigKey::new(
+        "network.proxy",
+        "proxy",
+        ValueKind::Text,
+        "所有出站请求使用的 HTTP 代理，留空时遵循 HTTP_PROXY/HTTPS_PROXY 环境变量",
+    ),
+    // Deprecated: memory is released when idle (`memory.idle_release_secs`)
+    ConfigKey::alias("maintenance.memory_release_interval_requests", UNSIGNED),
+    ConfigKey::new(
+        "maintenance.db_analyze_interval_requests",
+        "db_analyze_interval_requests",
+        UNSIGNED,
+        "每处理多少个请求执行一次数据库 ANALYZE",
+    ),
+    ConfigKey::new(
+        "memory.dirty_decay_ms",
+        "memory_dirty_decay_ms",
+        ValueKind::Range(-1, i64::MAX),
+        "jemalloc 脏页归还系统前的等待时间 (毫秒)，0 = 立即，-1 = 从不，留空使用默认值",
+    ),
+    ConfigKey::new(
+        "memory.muzzy_decay_ms",
+        "memory_muzzy_decay_ms",
+        ValueKind::Range(-1, i64::MAX),
+        "jemalloc muzzy 页归还系统前的等待时间 (毫秒)，留空使用默认值",
+    ),
+    ConfigKey::new(
+        "memory.background_thread",
+        "memory_background_thread",
+        ValueKind::Bool,
+        "启用 jemalloc 后台线程异步归还内存",
+    ),
+    ConfigKey::new(
+        "memory.idle_release_secs",
+        "memory_idle_release_secs",
+        UNSIGNED,
+        "无下载活动超过此秒数后主动归还内存 (0 = 关闭)",
+    ),
+    ConfigKey::new(
+        "memory.pressure_floor_mb",
+        "memory_pressure_floor_mb",
+        UNSIGNED,
+        "可用内存低于此值 (MB) 时强制磁盘模式并降低并发 (0 = 关闭)",
+    ),
+    ConfigKey::new(
+        "memory.pressure_concurrency",
+        "memory_pressure_concurrency",
+        POSITIVE,
+        "内存紧张时的并发下载数",
+    ),
+    ConfigKey::new(
+        "limits.daily_per_chat",
+        "daily_limit_per_chat",
+        UNSIGNED,
+        "每个对话每日下载额度 (0 = 不限制)",
+    ),
+    ConfigKey::new(
+        "limits.daily_per_user",
+        "daily_limit_per_user",
+        UNSIGNED,
+        "每个用户每日下载额度 (0 = 不限制)",
+    ),
+    ConfigKey::new(
+        "limits.daily_reports",
+        "daily_report_limit",
+        UNSIGNED,
+        "每个用户每日可提交的反馈数 (0 = 不限制)",
+    ),
+    ConfigKey::new(
+        "premium.enabled",
+        "premium_enabled",
+        ValueKind::Bool,
+        "出售高级会员 (Telegram Stars，私聊 /premium 购买)",
+    ),
+    ConfigKey::new(
+        "premium.price_stars",
+        "premium_price_stars",
+        POSITIVE,
+        "每期价格 (Stars)",
+    ),
+    ConfigKey::new("premium.days", "premium_days", POSITIVE, "每期天数"),
+    ConfigKey::new(
+        "premium.daily_limit",
+        "premium_daily_limit",
+        UNSIGNED,
+        "会员每日下载额度，替代 [limits] 中的额度 (0 = 不限制)",
+    ),
+    ConfigKey::new(
+        "premium.lossless_only",
+        "premium_lossless_only",
+        ValueKind::Bool,
+        "无损音质仅对会员开放，其他用户最高 320kbps",
+    ),
+    ConfigKey::new(
+        "dispatcher.max_handlers",
+        "dispatcher_max_handlers",
+        POSITIVE,
+        "同时处理的消息数量上限，超出时新消息会被拒绝",
+    ),
+    ConfigKey::new(
+        "jukebox.interval_secs",
+        "jukebox_interval_secs",
+        UNSIGNED,
+        "群组点歌台两首歌之间的间隔 (秒)",
+    ),
+    ConfigKey::new(
+        "quiz.round_secs",
+        "quiz_round_secs",
+        ValueKind::Range(5, i64::MAX),
+        "猜歌游戏每轮作答时间 (秒)",
+    ),
+    ConfigKey::new(
+        "checkin.enabled",
+        "checkin_enabled",
+        ValueKind::Bool,
+        "每日自动签到 (需要 MUSIC_U)",
+    ),
+    ConfigKey::new(
+        "checkin.hour",
+        "checkin_hour",
+        ValueKind::Range(0, 23),
+        "每日签到的本地时间 (时)",
+    ),
+    ConfigKey::new(
+        "backup.dir",
+        "backup_dir",
+        ValueKind::Text,
+        "数据库定时备份目录，留空关闭",
+    ),
+    ConfigKey::new(
+        "backup.interval_hours",
+        "backup_interval_hours",
+        UNSIGNED,
+        "备份间隔 (小时)",
+    ),
+    ConfigKey::new("backup.keep", "backup_keep", UNSIGNED, "保留的备份数量"),
+    ConfigKey::new(
+        "update.repo",
+        "update_repo",
+        ValueKind::Text,
+        "检查新版本的 GitHub 仓库",
+    ),
+    ConfigKey::new(
+        "update.check_interval_hours",
+        "update_check_interval_hours",
+        UNSIGNED,
+        "检查间隔 (小时)",
+    ),
+    ConfigKey::new(
+        "update.stage_binary",
+        "update_stage_binary",
+        ValueKind::Bool,
+        "自动下载并校验新版本，替换当前程序，重启后生效",
+    ),
+    ConfigKey::new(
+        "log.format",
+        "log_format",
+        ValueKind::Choice(&["text", "json"]),
+        "日志格式，json 便于 Loki/ELK 采集",
+    ),
+    ConfigKey::new(
+        "log.file",
+        "log_file",
+        ValueKind::Text,
+        "日志文件路径，留空则只输出到终端",
+    ),
+    ConfigKey::new(
+        "log.rotation",
+        "log_rotation",
+        ValueKind::Choice(&["daily", "size"]),
+        "日志轮转方式",
+    ),
+    ConfigKey::new(
+        "log.max_size_mb",
+        "log_max_size_mb",
+        POSITIVE,
+        "size 模式下单个文件大小上限 (MB)",
+    ),
+    ConfigKey::new(
+        "log.max_files",
+        "log_max_files",
+        UNSIGNED,
+        "保留的历史日志文件数量",
+    ),
+    ConfigKey::new(
+        "log.slow_query_ms",
+        "slow_query_ms",
+        UNSIGNED,
+        "SQL 语句耗时超过该值 (毫秒) 时输出警告，0 关闭",
+    ),
+    ConfigKey::new(
+        "log.slow_fetch_secs",
+        "slow_fetch_secs",
+        UNSIGNED,
+        "音频下载耗时超过该值 (秒) 时输出警告，0 关闭",
+    ),
+    ConfigKey::new(
+        "log.slow_tag_ms",
+        "slow_tag_ms",
+        UNSIGNED,
+        "写入标签耗时超过该值 (毫秒) 时输出警告，0 关闭",
+    ),
+    ConfigKey::new(
+        "log.slow_upload_secs",
+        "slow_upload_secs",
+        UNSIGNED,
+        "上传到 Telegram 耗时超过该值 (秒) 时输出警告，0 关闭",
+    ),
+    ConfigKey::new(
+        "otel.endpoint",
+        "otel_endpoint",
+        ValueKind::Text,
+        "OpenTelemetry OTLP/HTTP 接收地址，需 --features otel 编译，留空关闭",
+    ),
+    ConfigKey::new(
+        "otel.service_name",
+        "otel_service_name",
+        ValueKind::Text,
+        "上报的服务名",
+    ),
+    ConfigKey::new(
+        "sentry.dsn",
+        "sentry_dsn",
+        ValueKind::Text,
+        "Sentry DSN，需 --features sentry 编译，留空关闭",
+    ),
+    ConfigKey::new(
+        "sentry.environment",
+        "sentry_environment",
+        ValueKind::Text,
+        "Sentry 环境名",
+    ),
+    ConfigKey::new(
+        "health.listen",
+        "health_listen",
+        ValueKind::Text,
+        "健康检查 HTTP 监听地址 (GET /healthz，GET /metrics)，留空关闭",
+    ),
+    ConfigKey::new(
+        "ffmpeg.enabled",
+        "ffmpeg_enabled",
+        ValueKind::Bool,
+        "启用需要 ffmpeg 的功能 (/voice、/clip、/card)",
+    ),
+    ConfigKey::new(
+        "ffmpeg.path",
+        "ffmpeg_path",
+        ValueKind::Text,
+        "ffmpeg 可执行文件路径",
+    ),
+    ConfigKey::new(
+        "ffmpeg.timeout_secs",
+        "ffmpeg_timeout_secs",
+        UNSIGNED,
+        "单次转码最长时间 (秒)",
+    ),
+    ConfigKey::new(
+        "ffmpeg.voice_bitrate_kbps",
+        "voice_bitrate_kbps",
+        POSITIVE,
+        "语音消息 Opus 码率 (kbps)",
+    ),
+    ConfigKey::new(
+        "ffmpeg.clip_max_secs",
+        "clip_max_secs",
+        POSITIVE,
+        "/clip 片段最大长度 (秒)",
+    ),
+    ConfigKey::new(
+        "ffmpeg.card_font",
+        "card_font",
+        ValueKind::Text,
+        "/card 歌曲卡片使用的字体文件 (需包含中文字形)，留空关闭 /card",
+    ),
+    ConfigKey::new(
+        "s3.endpoint",
+        "s3_endpoint",
+        ValueKind::Text,
+        "S3 兼容对象存储地址，需 --features s3 编译，留空关闭",
+    ),
+    ConfigKey::new("s3.bucket", "s3_bucket", ValueKind::Text, "存储桶"),
+    ConfigKey::new("s3.region", "s3_region", ValueKind::Text, "区域"),
+    ConfigKey::new(
+        "s3.access_key",
+        "s3_access_key",
+        ValueKind::Text,
+        "Access Key",
+    ),
+    ConfigKey::new(
+        "s3.secret_key",
+        "s3_secret_key",
+        ValueKind::Text,
+        "Secret Key",
+    ),
+    ConfigKey::new(
+        "s3.path_style",
+        "s3_path_style",
+        ValueKind::Bool,
+        "使用 endpoint/bucket 形式的路径访问 (MinIO 需要)",
+    ),
+    ConfigKey::new(
+        "webdav.url",
+        "webdav_url",
+        ValueKind::Text,
+        "/fetch 上传到的 WebDAV 目录地址，设置后代替本地曲库目录，留空关闭",
+    ),
+    ConfigKey::new(
+        "webdav.username",
+        "webdav_username",
+        ValueKind::Text,
+        "WebDAV 用户名",
+    ),
+    ConfigKey::new(
+        "webdav.password",
+        "webdav_password",
+        ValueKind::Text,
+        "WebDAV 密码",
+    ),
+    ConfigKey::new(
+        "api.listen",
+        "api_listen",
+        ValueKind::Text,
+        "REST API 监听地址，需 --features api 编译，留空关闭",
+    ),
+    ConfigKey::new(
+        "api.token",
+        "api_token",
+        ValueKind::Text,
+        "REST API 访问令牌 (Authorization: Bearer)，监听非本机地址时必须设置",
+    ),
+    ConfigKey::new(
+        "api.admin_token",
+        "api_admin_token",
+        ValueKind::Text,
+        "/admin 管理面板令牌，留空关闭管理面板",
+    ),
+    ConfigKey::new(
+        "alerts.failure_threshold",
+        "alert_failure_threshold",
+        UNSIGNED,
+        "同一原因连续失败达到此次数时私信通知管理员 (0 = 关闭)",
+    ),
+    ConfigKey::new(
+        "scrobble.secret_key",
+        "scrobble_secret_key",
+        ValueKind::Text,
+        "加密用户 Last.fm/ListenBrainz 凭据的密钥，留空关闭 /scrobble",
+    ),
+    ConfigKey::new(
+        "scrobble.lastfm_api_key",
+        "lastfm_api_key",
+        ValueKind::Text,
+        "机器人的 Last.fm API Key，留空则只支持 ListenBrainz",
+    ),
+    ConfigKey::new(
+        "scrobble.lastfm_api_secret",
+        "lastfm_api_secret",
+        ValueKind::Text,
+        "机器人的 Last.fm API Secret",
+    ),
+    ConfigKey::new(
+        "telegraph.access_token",
+        "telegraph_access_token",
+        ValueKind::Text,
+        "/lyric --telegraph 使用的 telegra.ph 账号令牌，留空时自动创建",
+    ),
+];
+
+/// Why `value` is not acceptable for `kind`
+fn value_problem(kind: ValueKind, value: &str) -> Option<String> {
+    match kind {
+        ValueKind::Text => None,
+        ValueKind::Bool => (!value.eq_ignore_ascii_case("true")
+            && !value.eq_ignore_ascii_case("false"))
+        .then(|| "expected true or false".to_string()),
+        ValueKind::Integer => value
+            .parse::<i64>()
+            .is_err()
+            .then(|| "expected an integer".to_string()),
+        ValueKind::Range(min, max) => match value.parse::<i64>() {
+            Ok(n) if (min..=max).contains(&n) => None,
+            _ if max == i64::MAX => Some(format!("expected an integer >= {min}")),
+            _ => Some(format!("expected an integer from {min} to {max}")),
+        },
+        ValueKind::Choice(choices) => (!choices.contains(&value.to_lowercase().as_str()))
+            .then(|| format!("expected one of {}", choices.join(", "))),
+        ValueKind::IdList => value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .find(|id| id.parse::<i64>().is_err())
+            .map(|id| format!("`{id}` is not a numeric user ID")),
+    }
+}
+
+/// A template value: the `Config` field rendered the way `from_values` reads it
+fn template_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(template_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+/// Fully commented `config.ini` holding every key at its default value
+/// (`gen-config`). Built from `KEYS` and `Config::default()`, so new keys
+/// show up here once they are registered for `check-config`.
+#[must_use]
+pub fn template() -> String {
+    let defaults = serde_json::to_value(Config::default()).unwrap_or_default();
+    let mut text = String::from(
+        "# music163bot-rust 配置文件，由 `music163bot-rust gen-config` 生成\n\
+         # 所有键均为默认值，留空表示使用默认值或关闭对应功能\n",
+    );
+    let mut section = "";
+    for key in KEYS {
+        let Some(field) = key.field else { continue };
+        let (key_section, name) = key.key.split_once('.').unwrap_or(("", key.key));
+        if key_section != section {
+            section = key_section;
+            std::fmt::write(&mut text, format_args!("\n[{section}]\n")).unwrap();
+        }
+        text.push('\n');
+        std::fmt::write(&mut text, format_args!("# {}\n", key.doc)).unwrap();
+        if secrets::SECRET_KEYS.contains(&key.key) {
+            std::fmt::write(
+                &mut text,
+                format_args!(
+                    "# 也可用 {name}_file = <文件路径> 从文件读取，或 {name} = keyring:<名称> 从系统钥匙串读取\n"
+                ),
+            )
+            .unwrap();
+        }
+        if let ValueKind::Choice(choices) = key.kind {
+            std::fmt::write(
+                &mut text,
+                format_args!("# 可选值: {}\n", choices.join(" | ")),
+            )
+            .unwrap();
+        }
+        let value = template_value(&defaults[field]);
+        if value.is_empty() {
+            std::fmt::write(&mut text, format_args!("{name} =\n")).unwrap();
+        } else {
+            std::fmt::write(&mut text, format_args!("{name} = {value}\n")).unwrap();
+        }
+    }
+    text
+}
+
+/// Unknown keys and malformed values in `read_values` output, which
+/// `Config::from_values` would otherwise ignore or replace by defaults.
+/// Empty values are accepted, they select the default.
+#[must_use]
+pub fn lint_values<S: std::hash::BuildHasher>(values: &HashMap<String, String, S>) -> Vec<String> {
+    let mut keys: Vec<_> = values.keys().collect();
+    keys.sort();
+
+    let mut problems = Vec::new();
+    for key in keys {
+        let value = &values[key];
+        match KEYS.iter().find(|known| known.key == key.as_str()) {
+            None if secrets::is_secret_file_key(key) => {}
+            None => problems.push(format!("unknown key `{key}`")),
+            Some(_) if value.is_empty() => {}
+            Some(known) => {
+                if let Some(problem) = value_problem(known.kind, value) {
+                    problems.push(format!("{key} = {value}: {problem}"));
+                }
+            }
+        }
+    }
+    problems
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Config, CoverMode};
+    use std::collections::HashMap;
+
+    use super::{
+        CaptionStyle, Config, CoverMode, HttpVersion, KEYS, LogFormat, LogRotation, StorageMode,
+        TlsBackend, lint_values, template,
+    };
 
     #[test]
     fn download_pool_defaults_are_tunable() {
@@ -364,18 +2065,319 @@ mod tests {
         let config = Config::default();
         assert!(config.upload_client_reuse_requests > 0);
         assert!(config.upload_timeout_secs > 0);
+        assert!(config.job_timeout_secs > config.upload_timeout_secs);
+        assert_eq!(config.stuck_status_minutes, 10);
     }
 
     #[test]
     fn maintenance_interval_defaults_exist() {
         let config = Config::default();
-        assert!(config.memory_release_interval_requests >= 1);
         assert!(config.db_analyze_interval_requests >= 1);
     }
 
+    #[test]
+    fn jemalloc_tuning_keeps_allocator_defaults() {
+        let config = Config::default();
+        assert_eq!(config.memory_dirty_decay_ms, None);
+        assert_eq!(config.memory_muzzy_decay_ms, None);
+        assert!(!config.memory_background_thread);
+    }
+
+    #[test]
+    fn idle_memory_release_has_default() {
+        let config = Config::default();
+        assert!(config.memory_idle_release_secs > 0);
+    }
+
+    #[test]
+    fn memory_pressure_throttling_is_opt_in() {
+        let config = Config::default();
+        assert_eq!(config.memory_pressure_floor_mb, 0);
+        assert!(config.memory_pressure_concurrency >= 1);
+    }
+
+    #[test]
+    fn collection_track_limit_has_default() {
+        let config = Config::default();
+        assert!(config.max_collection_tracks > 0);
+    }
+
+    #[test]
+    fn silent_delivery_is_disabled_by_default() {
+        let config = Config::default();
+        assert!(!config.silent);
+    }
+
+    #[test]
+    fn full_captions_by_default() {
+        let config = Config::default();
+        assert_eq!(config.caption_style, CaptionStyle::Full);
+        assert_eq!(
+            "Compact".parse::<CaptionStyle>().unwrap(),
+            CaptionStyle::Compact
+        );
+        assert!("short".parse::<CaptionStyle>().is_err());
+    }
+
+    #[test]
+    fn default_hashtags_keep_previous_caption() {
+        let config = Config::default();
+        assert_eq!(config.hashtags, vec!["网易云音乐", "{ext}"]);
+    }
+
+    #[test]
+    fn daily_limits_are_unlimited_by_default() {
+        let config = Config::default();
+        assert_eq!(config.daily_limit_per_chat, 0);
+        assert_eq!(config.daily_limit_per_user, 0);
+        assert_eq!(config.daily_report_limit, 5);
+    }
+
+    #[test]
+    fn premium_is_disabled_by_default() {
+        let config = Config::default();
+        assert!(!config.premium_enabled);
+        assert!(!config.premium_lossless_only);
+        assert_eq!(config.premium_price_stars, 100);
+        assert_eq!(config.premium_days, 30);
+    }
+
+    #[test]
+    fn dispatcher_is_bounded_by_default() {
+        let config = Config::default();
+        assert_eq!(config.dispatcher_max_handlers, 256);
+    }
+
+    #[test]
+    fn jukebox_interval_defaults_to_three_minutes() {
+        let config = Config::default();
+        assert_eq!(config.jukebox_interval_secs, 180);
+    }
+
+    #[test]
+    fn quiz_rounds_last_thirty_seconds_by_default() {
+        let config = Config::default();
+        assert_eq!(config.quiz_round_secs, 30);
+    }
+
+    #[test]
+    fn daily_checkin_is_opt_in() {
+        let config = Config::default();
+        assert!(!config.checkin_enabled);
+        assert!(config.checkin_hour < 24);
+    }
+
+    #[test]
+    fn failure_alerts_have_default_threshold() {
+        let config = Config::default();
+        assert!(config.alert_failure_threshold > 0);
+    }
+
+    #[test]
+    fn scrobbling_is_opt_in() {
+        let config = Config::default();
+        assert!(config.scrobble_secret_key.is_empty());
+        assert!(config.lastfm_api_key.is_empty());
+    }
+
+    #[test]
+    fn telegraph_token_is_created_on_demand() {
+        let config = Config::default();
+        assert!(config.telegraph_access_token.is_empty());
+    }
+
+    #[test]
+    fn update_staging_is_opt_in() {
+        let config = Config::default();
+        assert!(!config.update_stage_binary);
+        assert!(config.update_check_interval_hours > 0);
+    }
+
+    #[test]
+    fn health_endpoint_is_disabled_by_default() {
+        let config = Config::default();
+        assert!(config.health_listen.is_empty());
+    }
+
+    #[test]
+    fn ffmpeg_features_are_opt_in() {
+        let config = Config::default();
+        assert!(!config.ffmpeg_enabled);
+        assert_eq!(config.ffmpeg_path, "ffmpeg");
+        assert!(config.voice_bitrate_kbps > 0);
+        assert!(config.clip_max_secs > 0);
+        assert!(config.card_font.is_empty());
+    }
+
+    #[test]
+    fn runs_a_single_bot_by_default() {
+        let config = Config::default();
+        assert!(config.extra_bot_tokens.is_empty());
+    }
+
+    #[test]
+    fn database_defaults_use_wal() {
+        let config = Config::default();
+        assert_eq!(config.db_journal_mode, "wal");
+        assert_eq!(config.db_synchronous, "normal");
+        assert_eq!(config.db_busy_timeout_ms, 30_000);
+        assert_eq!(config.db_max_connections, 4);
+    }
+
+    #[test]
+    fn database_backups_are_opt_in() {
+        let config = Config::default();
+        assert!(config.backup_dir.is_empty());
+        assert_eq!(config.backup_interval_hours, 24);
+        assert_eq!(config.backup_keep, 7);
+    }
+
+    #[test]
+    fn s3_storage_is_opt_in() {
+        let config = Config::default();
+        assert!(config.s3_endpoint.is_empty());
+        assert!(config.s3_path_style);
+        assert!(config.webdav_url.is_empty());
+    }
+
+    #[test]
+    fn rest_api_is_opt_in() {
+        let config = Config::default();
+        assert!(config.api_listen.is_empty());
+        assert!(config.api_token.is_empty());
+        assert!(config.api_admin_token.is_empty());
+    }
+
+    #[test]
+    fn backup_channel_is_disabled_by_default() {
+        let config = Config::default();
+        assert_eq!(config.backup_channel_id, 0);
+        assert_eq!(config.purge_deleted_after_days, 7);
+        assert!(!config.verify_audio_hash);
+        assert!(!config.quality_upgrade_enabled);
+        assert!(config.library_dir.is_empty());
+        assert_eq!(config.quality_upgrade_below_kbps, 256);
+    }
+
+    #[test]
+    fn redis_hot_cache_is_opt_in() {
+        let config = Config::default();
+        assert!(config.redis_url.is_empty());
+        assert!(config.redis_ttl_secs > 0);
+        assert_eq!(config.download_lock_secs, 300);
+    }
+
+    #[test]
+    fn log_format_defaults_to_text() {
+        let config = Config::default();
+        assert_eq!(config.log_format, LogFormat::Text);
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn file_logging_is_disabled_by_default() {
+        let config = Config::default();
+        assert!(config.log_file.is_empty());
+        assert_eq!(config.log_rotation, LogRotation::Daily);
+        assert!(config.log_max_size_mb > 0);
+        assert!(config.log_max_files > 0);
+        assert_eq!(config.slow_query_ms, 200);
+        assert_eq!(config.slow_upload_secs, 120);
+    }
+
+    #[test]
+    fn preview_fallback_is_opt_in() {
+        assert!(!Config::default().preview_fallback);
+    }
+
+    #[test]
+    fn priority_lane_is_on_by_default() {
+        assert!(Config::default().download_priority_lane);
+    }
+
     #[test]
     fn default_cover_mode_is_thumbnail() {
         let config = Config::default();
         assert_eq!(config.cover_mode, CoverMode::Thumbnail);
     }
+
+    #[test]
+    fn network_defaults_keep_previous_clients() {
+        let config = Config::default();
+        assert_eq!(config.telegram_user_agent, "Go-http-client/2.0");
+        assert_eq!(config.http_version, HttpVersion::Http1);
+        assert_eq!(config.tls_backend, TlsBackend::Rustls);
+        assert!(config.proxy.is_empty());
+        assert_eq!("AUTO".parse::<HttpVersion>().unwrap(), HttpVersion::Auto);
+        assert!("http3".parse::<HttpVersion>().is_err());
+    }
+
+    #[test]
+    fn template_lints_clean_and_reproduces_defaults() {
+        let values = Config::parse_values(template().as_bytes()).unwrap();
+        assert!(
+            lint_values(&values).is_empty(),
+            "{:?}",
+            lint_values(&values)
+        );
+        assert_eq!(
+            serde_json::to_value(Config::from_values(&values)).unwrap(),
+            serde_json::to_value(Config::default()).unwrap()
+        );
+        for key in KEYS.iter().filter(|key| key.field.is_some()) {
+            assert!(values.contains_key(key.key), "{} missing", key.key);
+        }
+    }
+
+    #[test]
+    fn command_line_overrides_win_over_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("music163bot-config-{}.ini", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "[bot]\ntoken = 1:file\n[download]\nmax_concurrent = 2\n",
+        )
+        .unwrap();
+
+        let overrides = [
+            ("bot.token".to_string(), "2:flag".to_string()),
+            ("download.storage_mode".to_string(), "memory".to_string()),
+        ];
+        let config = Config::load_with_overrides(path.to_str().unwrap(), &overrides).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.bot_token, "2:flag");
+        assert_eq!(config.storage_mode, StorageMode::Memory);
+        assert_eq!(config.max_concurrent_downloads, 2);
+    }
+
+    #[test]
+    fn lint_reports_every_bad_key_and_value() {
+        let values: HashMap<String, String> = [
+            ("bot.token", "123:abc"),
+            ("bot.silent", "yes"),
+            ("bot.botadmin", "1, 2,"),
+            ("download.storage_mode", "ram"),
+            ("download.max_concurrent", "-1"),
+            ("checkin.hour", "24"),
+            ("memory.dirty_decay_ms", "-1"),
+            ("download.max_concurent", "3"),
+            ("log.file", ""),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        assert_eq!(
+            lint_values(&values),
+            vec![
+                "bot.silent = yes: expected true or false",
+                "checkin.hour = 24: expected an integer from 0 to 23",
+                "unknown key `download.max_concurent`",
+                "download.max_concurrent = -1: expected an integer >= 0",
+                "download.storage_mode = ram: expected one of disk, memory, hybrid",
+            ]
+        );
+    }
 }
@@ -0,0 +1,192 @@
+//! Admin web dashboard served by the REST API (`api.admin_token`)
+//!
+//! `/admin` is a static page; it asks for the admin token once and calls the
+//! JSON endpoints below with it, so nothing sensitive is rendered server side.
+//! Actions taken here are recorded in the audit log as `dashboard:<action>`.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::alerting::ErrorEvent;
+use crate::api::is_authorized;
+use crate::bot::{BotState, clear_song_cache, database_file_size, forget_song};
+
+const PAGE: &str = include_str!("dashboard.html");
+
+/// Audit log actor name for dashboard actions
+const ACTOR_NAME: &str = "web dashboard";
+const DEFAULT_RECENT_LIMIT: i64 = 20;
+const MAX_RECENT_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    version: &'static str,
+    bot_username: String,
+    total_songs: i64,
+    songs_by_ext: Vec<(String, i64)>,
+    database_bytes: u64,
+    active_downloads: usize,
+    max_downloads: u32,
+    uploads_in_flight: u32,
+    peak_uploads: u32,
+    last_checkin: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecentDownload {
+    music_id: i64,
+    song_name: String,
+    artists: String,
+    file_ext: String,
+    size: i64,
+    from_user_name: String,
+    from_chat_name: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentParams {
+    limit: Option<i64>,
+}
+
+type DashboardResult<T> = std::result::Result<T, (StatusCode, String)>;
+
+fn internal_error(e: impl std::fmt::Display) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+async fn record_action(state: &BotState, action: &str, args: &str) {
+    if let Err(e) = state
+        .database
+        .add_audit_entry(0, ACTOR_NAME, 0, &format!("dashboard:{action}"), args)
+        .await
+    {
+        tracing::warn!("Failed to record dashboard action {}: {}", action, e);
+    }
+}
+
+async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+async fn stats(State(state): State<Arc<BotState>>) -> DashboardResult<Json<Stats>> {
+    let total_songs = state
+        .database
+        .count_total_songs()
+        .await
+        .map_err(internal_error)?;
+    let songs_by_ext = state
+        .database
+        .count_songs_by_ext()
+        .await
+        .map_err(internal_error)?;
+    let max_downloads = state.config.max_concurrent_downloads;
+    let last_checkin = state
+        .last_checkin
+        .lock()
+        .await
+        .as_ref()
+        .map(crate::checkin::CheckinReport::summary);
+
+    Ok(Json(Stats {
+        version: env!("CARGO_PKG_VERSION"),
+        bot_username: state.bot_username.clone(),
+        total_songs,
+        songs_by_ext,
+        database_bytes: database_file_size(&state.config.database).await,
+        active_downloads: (max_downloads as usize)
+            .saturating_sub(state.download_semaphore.available_permits()),
+        max_downloads,
+        uploads_in_flight: state.upload_counters.in_flight.load(Ordering::Relaxed),
+        peak_uploads: state.upload_counters.peak_in_flight.load(Ordering::Relaxed),
+        last_checkin,
+    }))
+}
+
+async fn recent_downloads(
+    State(state): State<Arc<BotState>>,
+    Query(params): Query<RecentParams>,
+) -> DashboardResult<Json<Vec<RecentDownload>>> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_RECENT_LIMIT)
+        .clamp(1, MAX_RECENT_LIMIT);
+    let songs = state
+        .database
+        .recent_songs(limit)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(
+        songs
+            .into_iter()
+            .map(|song| RecentDownload {
+                music_id: song.music_id,
+                song_name: song.song_name,
+                artists: song.song_artists,
+                file_ext: song.file_ext,
+                size: song.music_size,
+                from_user_name: song.from_user_name,
+                from_chat_name: song.from_chat_name,
+                created_at: song.created_at,
+            })
+            .collect(),
+    ))
+}
+
+async fn errors(State(state): State<Arc<BotState>>) -> Json<Vec<ErrorEvent>> {
+    Json(state.error_feed.recent())
+}
+
+async fn rmcache(
+    State(state): State<Arc<BotState>>,
+    Path(music_id): Path<i64>,
+) -> DashboardResult<Json<serde_json::Value>> {
+    let deleted = forget_song(&state, music_id)
+        .await
+        .map_err(internal_error)?;
+    record_action(&state, "rmcache", &music_id.to_string()).await;
+    Ok(Json(serde_json::json!({ "deleted": deleted })))
+}
+
+async fn clearallcache(
+    State(state): State<Arc<BotState>>,
+) -> DashboardResult<Json<serde_json::Value>> {
+    let count = clear_song_cache(&state).await.map_err(internal_error)?;
+    record_action(&state, "clearallcache", "").await;
+    tracing::info!("Dashboard cleared all cache, {} records deleted", count);
+    Ok(Json(serde_json::json!({ "deleted": count })))
+}
+
+async fn require_admin_token(
+    State(state): State<Arc<BotState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_authorized(request.headers(), &state.config.api_admin_token) {
+        next.run(request).await
+    } else {
+        (StatusCode::UNAUTHORIZED, "Invalid admin token").into_response()
+    }
+}
+
+/// Dashboard routes, to be merged into the REST API router
+pub fn router(state: Arc<BotState>) -> Router<Arc<BotState>> {
+    Router::new()
+        .route("/admin/api/stats", get(stats))
+        .route("/admin/api/downloads", get(recent_downloads))
+        .route("/admin/api/errors", get(errors))
+        .route("/admin/api/rmcache/{id}", post(rmcache))
+        .route("/admin/api/clearallcache", post(clearallcache))
+        .route_layer(middleware::from_fn_with_state(state, require_admin_token))
+        .route("/admin", get(page))
+}
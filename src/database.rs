@@ -1,10 +1,22 @@
 use chrono::{DateTime, Utc};
+use log::LevelFilter;
 use serde::{Deserialize, Serialize};
-use sqlx::{Row, SqlitePool};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow, SqliteSynchronous,
+};
+use sqlx::{ConnectOptions, Row, SqlitePool};
+use std::str::FromStr;
 use std::time::Duration;
 
+use crate::config::Config;
 use crate::error::Result;
 
+/// Schema version stored in `PRAGMA user_version`; bump when tables change
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Format of `CURRENT_TIMESTAMP` values
+const SQLITE_TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S";
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SongInfo {
     pub id: i64,
@@ -20,6 +32,11 @@ pub struct SongInfo {
     pub duration: i64,
     pub file_id: Option<String>,
     pub thumb_file_id: Option<String>,
+    /// Link to the copy in the backup channel (`cache.backup_channel_id`)
+    pub backup_message: Option<String>,
+    /// MD5 of the audio as served by NetEase (before tagging); a different hash
+    /// from the song URL API means the track was replaced upstream
+    pub audio_md5: Option<String>,
     pub from_user_id: i64,
     pub from_user_name: String,
     pub from_chat_id: i64,
@@ -28,13 +45,53 @@ pub struct SongInfo {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A user report about a broken song
+#[derive(Debug, Clone)]
+pub struct SongReport {
+    pub id: i64,
+    pub music_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub user_name: String,
+    pub reason: String,
+    /// "open", "resolved", "retried" or "ignored"
+    pub status: String,
+}
+
+/// An admin command recorded in the audit log
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub actor_id: i64,
+    pub actor_name: String,
+    pub chat_id: i64,
+    /// Command or button action, e.g. "rmcache" or "report:ignore"
+    pub action: String,
+    pub args: String,
+    /// UTC, as stored by `CURRENT_TIMESTAMP`
+    pub created_at: String,
+}
+
+/// A user's linked scrobbling account
+#[derive(Debug, Clone)]
+pub struct ScrobbleAccount {
+    /// "lastfm" or "listenbrainz"
+    pub service: String,
+    /// Encrypted session key / token (see `scrobble::CredentialCipher`)
+    pub credential: String,
+    pub enabled: bool,
+}
+
+#[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
 }
 
 impl Database {
     /// Create a new database connection with limited pool size
-    pub async fn new(database_url: &str) -> Result<Self> {
+    ///
+    /// Pragmas and the pool size come from the `[database]` config section.
+    pub async fn new(config: &Config) -> Result<Self> {
+        let database_url = config.database.as_str();
         // Create database directory if it doesn't exist
         if let Some(parent) = std::path::Path::new(database_url).parent()
             && !parent.exists()
@@ -42,17 +99,39 @@ impl Database {
             std::fs::create_dir_all(parent)?;
         }
 
-        // Configure connection pool with WAL mode for better concurrency
-        // WAL mode allows readers and writers to operate concurrently
-        let options = sqlx::sqlite::SqliteConnectOptions::new()
+        // WAL mode (the default) lets readers and writers operate concurrently;
+        // busy_timeout makes concurrent writers wait instead of failing with
+        // "database is locked"
+        let journal_mode = SqliteJournalMode::from_str(&config.db_journal_mode)?;
+        let synchronous = SqliteSynchronous::from_str(&config.db_synchronous)?;
+        let options = SqliteConnectOptions::new()
             .filename(database_url)
             .create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)      // 启用 WAL 模式
-            .busy_timeout(Duration::from_secs(30))                   // 忙等待超时
-            .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)    // 平衡性能和耐久性
-            .foreign_keys(true);
+            .journal_mode(journal_mode)
+            .busy_timeout(Duration::from_millis(config.db_busy_timeout_ms))
+            .synchronous(synchronous)
+            .foreign_keys(true)
+            // Reported as warnings under the `sqlx::query` target
+            .log_slow_statements(
+                if config.slow_query_ms == 0 {
+                    LevelFilter::Off
+                } else {
+                    LevelFilter::Warn
+                },
+                Duration::from_millis(config.slow_query_ms),
+            );
 
-        let pool = SqlitePool::connect_with(options).await?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.db_max_connections.max(1))
+            .connect_with(options)
+            .await?;
+        tracing::debug!(
+            "SQLite pool: journal_mode={}, synchronous={}, busy_timeout={}ms, max_connections={}",
+            config.db_journal_mode,
+            config.db_synchronous,
+            config.db_busy_timeout_ms,
+            config.db_max_connections
+        );
 
         // Create tables if they don't exist
         sqlx::query(
@@ -71,6 +150,8 @@ impl Database {
                 duration INTEGER NOT NULL,
                 file_id TEXT,
                 thumb_file_id TEXT,
+                backup_message TEXT,
+                audio_md5 TEXT,
                 from_user_id INTEGER NOT NULL,
                 from_user_name TEXT NOT NULL,
                 from_chat_id INTEGER NOT NULL,
@@ -82,63 +163,511 @@ impl Database {
         )
         .execute(&pool)
         .await?;
+        add_column_if_missing(&pool, "song_infos", "backup_message", "TEXT").await?;
+        add_column_if_missing(&pool, "song_infos", "audio_md5", "TEXT").await?;
+        // Set by /rmcache and /clearallcache; rows are purged after `cache.purge_deleted_after_days`
+        add_column_if_missing(&pool, "song_infos", "deleted_at", "TEXT").await?;
+
+        // file_ids are only valid for the bot that uploaded the file
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS bot_file_ids (
+                music_id INTEGER NOT NULL,
+                bot_id INTEGER NOT NULL,
+                file_id TEXT NOT NULL,
+                thumb_file_id TEXT,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (music_id, bot_id)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Uploads capped at 320k for users without lossless (`premium.lossless_only`),
+        // kept apart so they never replace the song's cached copy
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS lossy_copies (
+                music_id INTEGER NOT NULL,
+                bot_id INTEGER NOT NULL,
+                song_name TEXT NOT NULL,
+                song_artists TEXT NOT NULL,
+                song_album TEXT NOT NULL,
+                file_ext TEXT NOT NULL,
+                music_size INTEGER NOT NULL,
+                bit_rate INTEGER NOT NULL,
+                duration INTEGER NOT NULL,
+                file_id TEXT NOT NULL,
+                thumb_file_id TEXT,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (music_id, bot_id)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Reply-to-audio lookups resolve a delivered file_id back to its song
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_bot_file_ids_file_id ON bot_file_ids (file_id)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_song_infos_file_id ON song_infos (file_id)")
+            .execute(&pool)
+            .await?;
+
+        // Per-chat settings (key/value, e.g. silent delivery)
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS chat_settings (
+                chat_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (chat_id, key)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Daily usage counters (scope = "chat" or "user", day = local YYYY-MM-DD)
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS usage_counters (
+                scope TEXT NOT NULL,
+                subject_id INTEGER NOT NULL,
+                day TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (scope, subject_id, day)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Songs delivered to each user, for personalized inline results
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS send_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                chat_id INTEGER NOT NULL,
+                music_id INTEGER NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_send_log_user ON send_log (user_id, music_id)")
+            .execute(&pool)
+            .await?;
+
+        // User reports about broken songs
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS reports (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                music_id INTEGER NOT NULL,
+                chat_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                user_name TEXT NOT NULL DEFAULT '',
+                reason TEXT NOT NULL DEFAULT '',
+                status TEXT NOT NULL DEFAULT 'open',
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Admin actions, for accountability in multi-admin deployments
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                actor_id INTEGER NOT NULL,
+                actor_name TEXT NOT NULL DEFAULT '',
+                chat_id INTEGER NOT NULL,
+                action TEXT NOT NULL,
+                args TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Per-user scrobbling accounts (`/scrobble`)
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS scrobble_accounts (
+                user_id INTEGER NOT NULL,
+                service TEXT NOT NULL,
+                credential TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (user_id, service)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Users who bought premium with Telegram Stars (`/premium`)
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS premium_users (
+                user_id INTEGER PRIMARY KEY,
+                expires_at TEXT NOT NULL,
+                charge_id TEXT NOT NULL DEFAULT '',
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Per-chat `/quiz` scores
+        sqlx::query(
+            r"
+            CREATE TABLE IF NOT EXISTS quiz_scores (
+                chat_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                user_name TEXT NOT NULL DEFAULT '',
+                score INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (chat_id, user_id)
+            )
+            ",
+        )
+        .execute(&pool)
+        .await?;
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&pool)
+            .await?;
+        if version < SCHEMA_VERSION {
+            sqlx::query(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))
+                .execute(&pool)
+                .await?;
+        } else if version > SCHEMA_VERSION {
+            tracing::warn!(
+                "Database schema version {} is newer than this build supports ({})",
+                version,
+                SCHEMA_VERSION
+            );
+        }
 
         Ok(Self { pool })
     }
 
+    /// Schema version recorded in the database file
+    pub async fn schema_version(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await?)
+    }
+
     /// Get song info by music ID
     pub async fn get_song_by_music_id(&self, music_id: i64) -> Result<Option<SongInfo>> {
-        let row = sqlx::query("SELECT * FROM song_infos WHERE music_id = ? LIMIT 1")
-            .bind(music_id)
-            .fetch_optional(&self.pool)
+        let row = sqlx::query(
+            "SELECT * FROM song_infos WHERE music_id = ? AND deleted_at IS NULL LIMIT 1",
+        )
+        .bind(music_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(song_from_row))
+    }
+
+    /// Music ID of the song an audio `file_id` was delivered as (any bot)
+    pub async fn find_music_id_by_file_id(&self, file_id: &str) -> Result<Option<i64>> {
+        let row = sqlx::query(
+            r"
+            SELECT music_id FROM bot_file_ids WHERE file_id = ?
+            UNION ALL
+            SELECT music_id FROM song_infos WHERE file_id = ?
+            LIMIT 1
+            ",
+        )
+        .bind(file_id)
+        .bind(file_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.get("music_id")))
+    }
+
+    /// Every cached song, with the file_ids usable by `bot_id` (for `/exportcache`)
+    pub async fn list_songs_for_bot(&self, bot_id: i64) -> Result<Vec<SongInfo>> {
+        let rows = sqlx::query(
+            r"
+            SELECT s.*, b.file_id AS bot_file_id, b.thumb_file_id AS bot_thumb_file_id
+            FROM song_infos s
+            LEFT JOIN bot_file_ids b ON b.music_id = s.music_id AND b.bot_id = ?
+            WHERE s.deleted_at IS NULL
+            ORDER BY s.music_id
+            ",
+        )
+        .bind(bot_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| SongInfo {
+                file_id: row.get("bot_file_id"),
+                thumb_file_id: row.get("bot_thumb_file_id"),
+                ..song_from_row(row)
+            })
+            .collect())
+    }
+
+    /// Merge imported songs, keeping existing records untouched
+    ///
+    /// File_ids are recorded for `bot_id`; without one they are left for
+    /// `claim_legacy_file_ids`. Returns (new songs, new file_ids).
+    pub async fn import_songs(
+        &self,
+        songs: &[SongInfo],
+        bot_id: Option<i64>,
+    ) -> Result<(u64, u64)> {
+        let mut tx = self.pool.begin().await?;
+        let mut added_songs = 0;
+        let mut added_file_ids = 0;
+
+        for song in songs {
+            let result = sqlx::query(
+                r"
+                INSERT INTO song_infos (
+                    music_id, song_name, song_artists, song_album, file_ext,
+                    music_size, pic_size, emb_pic_size, bit_rate, duration,
+                    file_id, thumb_file_id, backup_message, audio_md5, from_user_id,
+                    from_user_name, from_chat_id, from_chat_name, created_at, updated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(music_id) DO NOTHING
+                ",
+            )
+            .bind(song.music_id)
+            .bind(&song.song_name)
+            .bind(&song.song_artists)
+            .bind(&song.song_album)
+            .bind(&song.file_ext)
+            .bind(song.music_size)
+            .bind(song.pic_size)
+            .bind(song.emb_pic_size)
+            .bind(song.bit_rate)
+            .bind(song.duration)
+            .bind(&song.file_id)
+            .bind(&song.thumb_file_id)
+            .bind(&song.backup_message)
+            .bind(&song.audio_md5)
+            .bind(song.from_user_id)
+            .bind(&song.from_user_name)
+            .bind(song.from_chat_id)
+            .bind(&song.from_chat_name)
+            .bind(song.created_at.format(SQLITE_TIMESTAMP).to_string())
+            .bind(song.updated_at.format(SQLITE_TIMESTAMP).to_string())
+            .execute(&mut *tx)
             .await?;
+            added_songs += result.rows_affected();
 
-        match row {
-            Some(row) => {
-                let song_info = SongInfo {
-                    id: row.get("id"),
-                    music_id: row.get("music_id"),
-                    song_name: row.get("song_name"),
-                    song_artists: row.get("song_artists"),
-                    song_album: row.get("song_album"),
-                    file_ext: row.get("file_ext"),
-                    music_size: row.get("music_size"),
-                    pic_size: row.get("pic_size"),
-                    emb_pic_size: row.get("emb_pic_size"),
-                    bit_rate: row.get("bit_rate"),
-                    duration: row.get("duration"),
-                    file_id: row.get("file_id"),
-                    thumb_file_id: row.get("thumb_file_id"),
-                    from_user_id: row.get("from_user_id"),
-                    from_user_name: row.get("from_user_name"),
-                    from_chat_id: row.get("from_chat_id"),
-                    from_chat_name: row.get("from_chat_name"),
-                    created_at: row
-                        .get::<String, _>("created_at")
-                        .parse()
-                        .unwrap_or_else(|_| Utc::now()),
-                    updated_at: row
-                        .get::<String, _>("updated_at")
-                        .parse()
-                        .unwrap_or_else(|_| Utc::now()),
-                };
-                Ok(Some(song_info))
+            if let (Some(file_id), Some(bot_id)) = (&song.file_id, bot_id) {
+                let result = sqlx::query(
+                    r"
+                    INSERT OR IGNORE INTO bot_file_ids (music_id, bot_id, file_id, thumb_file_id)
+                    VALUES (?, ?, ?, ?)
+                    ",
+                )
+                .bind(song.music_id)
+                .bind(bot_id)
+                .bind(file_id)
+                .bind(&song.thumb_file_id)
+                .execute(&mut *tx)
+                .await?;
+                added_file_ids += result.rows_affected();
             }
-            None => Ok(None),
         }
+
+        tx.commit().await?;
+        Ok((added_songs, added_file_ids))
+    }
+
+    /// Get song info with the file_ids usable by `bot_id` (`None` if that bot never uploaded it)
+    pub async fn get_song_for_bot(&self, music_id: i64, bot_id: i64) -> Result<Option<SongInfo>> {
+        let Some(mut song_info) = self.get_song_by_music_id(music_id).await? else {
+            return Ok(None);
+        };
+
+        let row = sqlx::query(
+            "SELECT file_id, thumb_file_id FROM bot_file_ids WHERE music_id = ? AND bot_id = ?",
+        )
+        .bind(music_id)
+        .bind(bot_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        song_info.file_id = row.as_ref().map(|row| row.get("file_id"));
+        song_info.thumb_file_id = row.and_then(|row| row.get("thumb_file_id"));
+
+        Ok(Some(song_info))
+    }
+
+    /// Lossy copy of a song uploaded by `bot_id`, see `save_lossy_copy`
+    pub async fn get_lossy_copy(&self, music_id: i64, bot_id: i64) -> Result<Option<SongInfo>> {
+        let row = sqlx::query("SELECT * FROM lossy_copies WHERE music_id = ? AND bot_id = ?")
+            .bind(music_id)
+            .bind(bot_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| SongInfo {
+            music_id,
+            song_name: row.get("song_name"),
+            song_artists: row.get("song_artists"),
+            song_album: row.get("song_album"),
+            file_ext: row.get("file_ext"),
+            music_size: row.get("music_size"),
+            bit_rate: row.get("bit_rate"),
+            duration: row.get("duration"),
+            file_id: Some(row.get("file_id")),
+            thumb_file_id: row.get("thumb_file_id"),
+            ..SongInfo::default()
+        }))
+    }
+
+    /// Save an upload made for a user limited to lossy quality, leaving the
+    /// song's cached copy alone
+    pub async fn save_lossy_copy(&self, song_info: &SongInfo, bot_id: i64) -> Result<()> {
+        let Some(file_id) = &song_info.file_id else {
+            return Ok(());
+        };
+        sqlx::query(
+            r"
+            INSERT INTO lossy_copies (
+                music_id, bot_id, song_name, song_artists, song_album, file_ext,
+                music_size, bit_rate, duration, file_id, thumb_file_id, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(music_id, bot_id) DO UPDATE SET
+                song_name = excluded.song_name,
+                song_artists = excluded.song_artists,
+                song_album = excluded.song_album,
+                file_ext = excluded.file_ext,
+                music_size = excluded.music_size,
+                bit_rate = excluded.bit_rate,
+                duration = excluded.duration,
+                file_id = excluded.file_id,
+                thumb_file_id = excluded.thumb_file_id,
+                updated_at = CURRENT_TIMESTAMP
+            ",
+        )
+        .bind(song_info.music_id)
+        .bind(bot_id)
+        .bind(&song_info.song_name)
+        .bind(&song_info.song_artists)
+        .bind(&song_info.song_album)
+        .bind(&song_info.file_ext)
+        .bind(song_info.music_size)
+        .bind(song_info.bit_rate)
+        .bind(song_info.duration)
+        .bind(file_id)
+        .bind(&song_info.thumb_file_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remember the file_ids `bot_id` got when uploading a song
+    pub async fn set_bot_file_id(
+        &self,
+        music_id: i64,
+        bot_id: i64,
+        file_id: &str,
+        thumb_file_id: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO bot_file_ids (music_id, bot_id, file_id, thumb_file_id, updated_at)
+            VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(music_id, bot_id) DO UPDATE SET
+                file_id = excluded.file_id,
+                thumb_file_id = excluded.thumb_file_id,
+                updated_at = CURRENT_TIMESTAMP
+            ",
+        )
+        .bind(music_id)
+        .bind(bot_id)
+        .bind(file_id)
+        .bind(thumb_file_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Assign file_ids cached before per-bot mapping existed to `bot_id`
+    ///
+    /// Only songs without any mapping are claimed, so this is safe to run on every start.
+    pub async fn claim_legacy_file_ids(&self, bot_id: i64) -> Result<u64> {
+        let result = sqlx::query(
+            r"
+            INSERT OR IGNORE INTO bot_file_ids (music_id, bot_id, file_id, thumb_file_id)
+            SELECT music_id, ?, file_id, thumb_file_id FROM song_infos
+            WHERE file_id IS NOT NULL AND deleted_at IS NULL
+              AND NOT EXISTS (SELECT 1 FROM bot_file_ids b WHERE b.music_id = song_infos.music_id)
+            ",
+        )
+        .bind(bot_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Forget the file_ids other bots got for `music_id` once `bot_id` has
+    /// uploaded a replacement
+    pub async fn clear_other_bot_file_ids(&self, music_id: i64, bot_id: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM bot_file_ids WHERE music_id = ? AND bot_id != ?")
+            .bind(music_id)
+            .bind(bot_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
     }
 
     /// Save or update song info
+    ///
+    /// Saving a soft-deleted song revives it; file_ids other bots had for the
+    /// deleted record are dropped, as a hard delete would have done.
     pub async fn save_song_info(&self, song_info: &SongInfo) -> Result<i64> {
+        sqlx::query(
+            r"
+            DELETE FROM bot_file_ids WHERE music_id = ? AND EXISTS (
+                SELECT 1 FROM song_infos WHERE music_id = ? AND deleted_at IS NOT NULL
+            )
+            ",
+        )
+        .bind(song_info.music_id)
+        .bind(song_info.music_id)
+        .execute(&self.pool)
+        .await?;
+
         let result = sqlx::query(
             r"
             INSERT INTO song_infos (
                 music_id, song_name, song_artists, song_album, file_ext,
                 music_size, pic_size, emb_pic_size, bit_rate, duration,
-                file_id, thumb_file_id, from_user_id, from_user_name,
+                file_id, thumb_file_id, audio_md5, from_user_id, from_user_name,
                 from_chat_id, from_chat_name, created_at, updated_at
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
             ON CONFLICT(music_id) DO UPDATE SET
                 song_name = excluded.song_name,
                 song_artists = excluded.song_artists,
@@ -151,6 +680,8 @@ impl Database {
                 duration = excluded.duration,
                 file_id = excluded.file_id,
                 thumb_file_id = excluded.thumb_file_id,
+                audio_md5 = excluded.audio_md5,
+                deleted_at = NULL,
                 updated_at = CURRENT_TIMESTAMP
             ",
         )
@@ -166,6 +697,7 @@ impl Database {
         .bind(song_info.duration)
         .bind(&song_info.file_id)
         .bind(&song_info.thumb_file_id)
+        .bind(&song_info.audio_md5)
         .bind(song_info.from_user_id)
         .bind(&song_info.from_user_name)
         .bind(song_info.from_chat_id)
@@ -195,9 +727,20 @@ impl Database {
         Ok(())
     }
 
+    /// Record where the backup copy of a song lives
+    pub async fn set_backup_message(&self, music_id: i64, link: &str) -> Result<()> {
+        sqlx::query("UPDATE song_infos SET backup_message = ? WHERE music_id = ?")
+            .bind(link)
+            .bind(music_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// Count total songs
     pub async fn count_total_songs(&self) -> Result<i64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos")
+        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos WHERE deleted_at IS NULL")
             .fetch_one(&self.pool)
             .await?;
 
@@ -206,7 +749,7 @@ impl Database {
 
     /// Count songs from specific user
     pub async fn count_songs_from_user(&self, user_id: i64) -> Result<i64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos WHERE from_user_id = ?")
+        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos WHERE from_user_id = ? AND deleted_at IS NULL")
             .bind(user_id)
             .fetch_one(&self.pool)
             .await?;
@@ -216,7 +759,7 @@ impl Database {
 
     /// Count songs from specific chat
     pub async fn count_songs_from_chat(&self, chat_id: i64) -> Result<i64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos WHERE from_chat_id = ?")
+        let row = sqlx::query("SELECT COUNT(*) as count FROM song_infos WHERE from_chat_id = ? AND deleted_at IS NULL")
             .bind(chat_id)
             .fetch_one(&self.pool)
             .await?;
@@ -224,26 +767,700 @@ impl Database {
         Ok(row.get("count"))
     }
 
-    /// Delete song by music ID
-    pub async fn delete_song_by_music_id(&self, music_id: i64) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM song_infos WHERE music_id = ?")
-            .bind(music_id)
-            .execute(&self.pool)
-            .await?;
+    /// Count songs per file extension, most common first
+    pub async fn count_songs_by_ext(&self) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            r"
+            SELECT file_ext, COUNT(*) as count FROM song_infos
+            WHERE deleted_at IS NULL
+            GROUP BY file_ext
+            ORDER BY count DESC
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        Ok(result.rows_affected() > 0)
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("file_ext"), row.get("count")))
+            .collect())
     }
 
-    /// Delete all songs from cache (admin only)
-    pub async fn clear_all_songs(&self) -> Result<u64> {
-        let result = sqlx::query("DELETE FROM song_infos")
-            .execute(&self.pool)
-            .await?;
+    /// Most recently cached songs, newest first
+    pub async fn recent_songs(&self, limit: i64) -> Result<Vec<SongInfo>> {
+        let rows = sqlx::query(
+            r"
+            SELECT * FROM song_infos
+            WHERE deleted_at IS NULL
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?
+            ",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
 
-        Ok(result.rows_affected())
+        Ok(rows.iter().map(song_from_row).collect())
     }
 
-    /// Optimize database by running VACUUM to reclaim space and defragment
+    /// Cached songs below `below_bit_rate` bps, in `music_id` order after
+    /// `after_music_id` (for `cache.upgrade_enabled`)
+    pub async fn low_bitrate_songs(
+        &self,
+        below_bit_rate: i64,
+        after_music_id: i64,
+        limit: i64,
+    ) -> Result<Vec<SongInfo>> {
+        let rows = sqlx::query(
+            r"
+            SELECT * FROM song_infos
+            WHERE deleted_at IS NULL AND file_id IS NOT NULL
+              AND bit_rate > 0 AND bit_rate < ? AND music_id > ?
+            ORDER BY music_id
+            LIMIT ?
+            ",
+        )
+        .bind(below_bit_rate)
+        .bind(after_music_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(song_from_row).collect())
+    }
+
+    /// Cached songs with the largest audio files
+    pub async fn largest_songs(&self, limit: i64) -> Result<Vec<SongInfo>> {
+        let rows = sqlx::query(
+            r"
+            SELECT * FROM song_infos
+            WHERE deleted_at IS NULL
+            ORDER BY music_size DESC
+            LIMIT ?
+            ",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(song_from_row).collect())
+    }
+
+    /// A random cached song (for `/quiz`)
+    pub async fn random_song(&self) -> Result<Option<SongInfo>> {
+        let row = sqlx::query(
+            "SELECT * FROM song_infos WHERE deleted_at IS NULL ORDER BY RANDOM() LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(song_from_row))
+    }
+
+    /// Soft-delete song by music ID (undo with `restore_song`)
+    pub async fn delete_song_by_music_id(&self, music_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE song_infos SET deleted_at = CURRENT_TIMESTAMP WHERE music_id = ? AND deleted_at IS NULL",
+        )
+        .bind(music_id)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM lossy_copies WHERE music_id = ?")
+            .bind(music_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Cached songs whose name, artists or album contain `keyword`
+    pub async fn find_cached_songs(&self, keyword: &str) -> Result<Vec<SongInfo>> {
+        let escaped = keyword
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let rows = sqlx::query(
+            r"
+            SELECT * FROM song_infos
+            WHERE deleted_at IS NULL
+              AND (song_name LIKE ?1 ESCAPE '\'
+                OR song_artists LIKE ?1 ESCAPE '\'
+                OR song_album LIKE ?1 ESCAPE '\')
+            ORDER BY music_id
+            ",
+        )
+        .bind(format!("%{escaped}%"))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(song_from_row).collect())
+    }
+
+    /// Cached songs with `start <= music_id <= end`
+    pub async fn cached_songs_in_range(&self, start: i64, end: i64) -> Result<Vec<SongInfo>> {
+        let rows = sqlx::query(
+            r"
+            SELECT * FROM song_infos
+            WHERE deleted_at IS NULL AND music_id BETWEEN ? AND ?
+            ORDER BY music_id
+            ",
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(song_from_row).collect())
+    }
+
+    /// Soft-delete several songs as one batch (undone together by `restore_last_deleted`)
+    pub async fn delete_songs(&self, music_ids: &[i64]) -> Result<u64> {
+        let ids = serde_json::to_string(music_ids)?;
+        let result = sqlx::query(
+            r"
+            UPDATE song_infos SET deleted_at = CURRENT_TIMESTAMP
+            WHERE deleted_at IS NULL AND music_id IN (SELECT value FROM json_each(?))
+            ",
+        )
+        .bind(&ids)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM lossy_copies WHERE music_id IN (SELECT value FROM json_each(?))")
+            .bind(ids)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Soft-delete all songs from cache (admin only)
+    ///
+    /// Every row gets the same timestamp, so `restore_last_deleted` undoes the whole batch.
+    pub async fn clear_all_songs(&self) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE song_infos SET deleted_at = CURRENT_TIMESTAMP WHERE deleted_at IS NULL",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM lossy_copies")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Undo the soft delete of a song
+    pub async fn restore_song(&self, music_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE song_infos SET deleted_at = NULL WHERE music_id = ? AND deleted_at IS NOT NULL",
+        )
+        .bind(music_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Undo the most recent deletion (a single /rmcache or a whole /clearallcache)
+    pub async fn restore_last_deleted(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r"
+            UPDATE song_infos SET deleted_at = NULL
+            WHERE deleted_at = (SELECT MAX(deleted_at) FROM song_infos)
+            ",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Permanently remove songs soft-deleted more than `days` days ago
+    pub async fn purge_deleted_songs(&self, days: u32) -> Result<u64> {
+        let cutoff = format!("-{days} days");
+        sqlx::query(
+            r"
+            DELETE FROM bot_file_ids WHERE music_id IN (
+                SELECT music_id FROM song_infos
+                WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)
+            )
+            ",
+        )
+        .bind(&cutoff)
+        .execute(&self.pool)
+        .await?;
+        let result = sqlx::query(
+            "DELETE FROM song_infos WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)",
+        )
+        .bind(&cutoff)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get a per-chat setting value
+    pub async fn get_chat_setting(&self, chat_id: i64, key: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT value FROM chat_settings WHERE chat_id = ? AND key = ?")
+            .bind(chat_id)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.get("value")))
+    }
+
+    /// Set a per-chat setting value
+    pub async fn set_chat_setting(&self, chat_id: i64, key: &str, value: &str) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO chat_settings (chat_id, key, value, updated_at)
+            VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(chat_id, key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = CURRENT_TIMESTAMP
+            ",
+        )
+        .bind(chat_id)
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove a per-chat setting (falls back to the global config value)
+    pub async fn delete_chat_setting(&self, chat_id: i64, key: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chat_settings WHERE chat_id = ? AND key = ?")
+            .bind(chat_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get usage count for a subject on a given day
+    pub async fn get_usage(&self, scope: &str, subject_id: i64, day: &str) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT count FROM usage_counters WHERE scope = ? AND subject_id = ? AND day = ?",
+        )
+        .bind(scope)
+        .bind(subject_id)
+        .bind(day)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map_or(0, |row| row.get("count")))
+    }
+
+    /// Increment usage count for a subject on a given day, returning the new count
+    pub async fn increment_usage(&self, scope: &str, subject_id: i64, day: &str) -> Result<i64> {
+        let row = sqlx::query(
+            r"
+            INSERT INTO usage_counters (scope, subject_id, day, count)
+            VALUES (?, ?, ?, 1)
+            ON CONFLICT(scope, subject_id, day) DO UPDATE SET count = count + 1
+            RETURNING count
+            ",
+        )
+        .bind(scope)
+        .bind(subject_id)
+        .bind(day)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Give back one use counted by `increment_usage`
+    pub async fn decrement_usage(&self, scope: &str, subject_id: i64, day: &str) -> Result<()> {
+        sqlx::query(
+            r"
+            UPDATE usage_counters SET count = MAX(count - 1, 0)
+            WHERE scope = ? AND subject_id = ? AND day = ?
+            ",
+        )
+        .bind(scope)
+        .bind(subject_id)
+        .bind(day)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove usage counters from days before the given day
+    pub async fn purge_usage_before(&self, day: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM usage_counters WHERE day < ?")
+            .bind(day)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Record a song delivered to a user
+    pub async fn log_send(&self, user_id: i64, chat_id: i64, music_id: i64) -> Result<()> {
+        sqlx::query("INSERT INTO send_log (user_id, chat_id, music_id) VALUES (?, ?, ?)")
+            .bind(user_id)
+            .bind(chat_id)
+            .bind(music_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// A user's most recently received songs, newest first
+    pub async fn recent_sends(&self, user_id: i64, limit: i64) -> Result<Vec<i64>> {
+        let rows = sqlx::query(
+            r"
+            SELECT music_id FROM send_log WHERE user_id = ?
+            GROUP BY music_id ORDER BY MAX(id) DESC LIMIT ?
+            ",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("music_id")).collect())
+    }
+
+    /// A user's most often received songs
+    pub async fn top_sends(&self, user_id: i64, limit: i64) -> Result<Vec<i64>> {
+        let rows = sqlx::query(
+            r"
+            SELECT music_id FROM send_log WHERE user_id = ?
+            GROUP BY music_id ORDER BY COUNT(*) DESC, MAX(id) DESC LIMIT ?
+            ",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("music_id")).collect())
+    }
+
+    /// Remove delivery history older than `days`
+    pub async fn purge_send_log(&self, days: u32) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM send_log WHERE created_at < datetime('now', ?)")
+            .bind(format!("-{days} days"))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Store a new song report, returning its ID
+    pub async fn add_report(
+        &self,
+        music_id: i64,
+        chat_id: i64,
+        user_id: i64,
+        user_name: &str,
+        reason: &str,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            r"
+            INSERT INTO reports (music_id, chat_id, user_id, user_name, reason)
+            VALUES (?, ?, ?, ?, ?)
+            ",
+        )
+        .bind(music_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(user_name)
+        .bind(reason)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Get a song report by ID
+    pub async fn get_report(&self, id: i64) -> Result<Option<SongReport>> {
+        let row = sqlx::query("SELECT * FROM reports WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| SongReport {
+            id: row.get("id"),
+            music_id: row.get("music_id"),
+            chat_id: row.get("chat_id"),
+            user_id: row.get("user_id"),
+            user_name: row.get("user_name"),
+            reason: row.get("reason"),
+            status: row.get("status"),
+        }))
+    }
+
+    /// Update the status of a song report
+    pub async fn set_report_status(&self, id: i64, status: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE reports SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(status)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record an admin action
+    pub async fn add_audit_entry(
+        &self,
+        actor_id: i64,
+        actor_name: &str,
+        chat_id: i64,
+        action: &str,
+        args: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO audit_log (actor_id, actor_name, chat_id, action, args)
+            VALUES (?, ?, ?, ?, ?)
+            ",
+        )
+        .bind(actor_id)
+        .bind(actor_name)
+        .bind(chat_id)
+        .bind(action)
+        .bind(args)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Most recent admin actions, newest first
+    pub async fn recent_audit_entries(&self, limit: i64) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query("SELECT * FROM audit_log ORDER BY id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AuditEntry {
+                actor_id: row.get("actor_id"),
+                actor_name: row.get("actor_name"),
+                chat_id: row.get("chat_id"),
+                action: row.get("action"),
+                args: row.get("args"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    /// Link (or relink) a scrobbling account; linking re-enables scrobbling
+    pub async fn set_scrobble_account(
+        &self,
+        user_id: i64,
+        service: &str,
+        credential: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO scrobble_accounts (user_id, service, credential, enabled, updated_at)
+            VALUES (?, ?, ?, 1, CURRENT_TIMESTAMP)
+            ON CONFLICT(user_id, service) DO UPDATE SET
+                credential = excluded.credential,
+                enabled = 1,
+                updated_at = CURRENT_TIMESTAMP
+            ",
+        )
+        .bind(user_id)
+        .bind(service)
+        .bind(credential)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Linked scrobbling accounts of a user
+    pub async fn get_scrobble_accounts(&self, user_id: i64) -> Result<Vec<ScrobbleAccount>> {
+        let rows = sqlx::query(
+            "SELECT service, credential, enabled FROM scrobble_accounts WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ScrobbleAccount {
+                service: row.get("service"),
+                credential: row.get("credential"),
+                enabled: row.get::<i64, _>("enabled") != 0,
+            })
+            .collect())
+    }
+
+    /// Pause or resume scrobbling for all of a user's accounts
+    pub async fn set_scrobble_enabled(&self, user_id: i64, enabled: bool) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE scrobble_accounts SET enabled = ?, updated_at = CURRENT_TIMESTAMP WHERE user_id = ?",
+        )
+        .bind(i64::from(enabled))
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Unlink all scrobbling accounts of a user
+    pub async fn delete_scrobble_accounts(&self, user_id: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM scrobble_accounts WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Extend a user's premium by `days` (from now, or from a later current
+    /// expiry), returning the new expiry in UTC
+    pub async fn extend_premium(&self, user_id: i64, days: u32, charge_id: &str) -> Result<String> {
+        let modifier = format!("+{days} days");
+        let row = sqlx::query(
+            r"
+            INSERT INTO premium_users (user_id, expires_at, charge_id, updated_at)
+            VALUES (?, datetime('now', ?), ?, CURRENT_TIMESTAMP)
+            ON CONFLICT(user_id) DO UPDATE SET
+                expires_at = datetime(MAX(expires_at, datetime('now')), ?),
+                charge_id = excluded.charge_id,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING expires_at
+            ",
+        )
+        .bind(user_id)
+        .bind(&modifier)
+        .bind(charge_id)
+        .bind(&modifier)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("expires_at"))
+    }
+
+    /// Premium expiry (UTC) of a user, if it has not passed yet
+    pub async fn premium_expiry(&self, user_id: i64) -> Result<Option<String>> {
+        let row = sqlx::query(
+            "SELECT expires_at FROM premium_users WHERE user_id = ? AND expires_at > datetime('now')",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.get("expires_at")))
+    }
+
+    /// Give a user a `/quiz` point in a chat, returning their new score
+    pub async fn add_quiz_point(&self, chat_id: i64, user_id: i64, user_name: &str) -> Result<i64> {
+        let row = sqlx::query(
+            r"
+            INSERT INTO quiz_scores (chat_id, user_id, user_name, score, updated_at)
+            VALUES (?, ?, ?, 1, CURRENT_TIMESTAMP)
+            ON CONFLICT(chat_id, user_id) DO UPDATE SET
+                user_name = excluded.user_name,
+                score = score + 1,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING score
+            ",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(user_name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("score"))
+    }
+
+    /// Highest `/quiz` scores of a chat as (user name, score)
+    pub async fn quiz_leaderboard(&self, chat_id: i64, limit: i64) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            r"
+            SELECT user_name, score FROM quiz_scores WHERE chat_id = ?
+            ORDER BY score DESC, updated_at ASC LIMIT ?
+            ",
+        )
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("user_name"), row.get("score")))
+            .collect())
+    }
+
+    /// Delete or anonymize every row referencing a user (`/deletemydata`),
+    /// including settings and counters of their private chat with the bot
+    ///
+    /// Cached songs they requested and their reports are kept without their
+    /// ID and name. Usage counters of `today` are kept so deleting cannot reset
+    /// the daily quota; they expire on their own. Returns the number of rows
+    /// changed and the music_ids of the anonymized songs.
+    pub async fn delete_user_data(&self, user_id: i64, today: &str) -> Result<(u64, Vec<i64>)> {
+        let mut tx = self.pool.begin().await?;
+        let mut changed = 0;
+
+        for statement in [
+            "DELETE FROM send_log WHERE user_id = ?1",
+            "DELETE FROM scrobble_accounts WHERE user_id = ?1",
+            "DELETE FROM premium_users WHERE user_id = ?1",
+            "DELETE FROM quiz_scores WHERE user_id = ?1",
+            "DELETE FROM chat_settings WHERE chat_id = ?1",
+            "UPDATE reports SET user_id = 0, user_name = '' WHERE user_id = ?1",
+            "UPDATE reports SET chat_id = 0 WHERE chat_id = ?1",
+        ] {
+            changed += sqlx::query(statement)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        changed += sqlx::query("DELETE FROM usage_counters WHERE subject_id = ? AND day <> ?")
+            .bind(user_id)
+            .bind(today)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let mut music_ids = Vec::new();
+        for statement in [
+            "UPDATE song_infos SET from_user_id = 0, from_user_name = '' WHERE from_user_id = ?1 RETURNING music_id",
+            "UPDATE song_infos SET from_chat_id = 0, from_chat_name = '' WHERE from_chat_id = ?1 RETURNING music_id",
+        ] {
+            let rows = sqlx::query(statement)
+                .bind(user_id)
+                .fetch_all(&mut *tx)
+                .await?;
+            changed += rows.len() as u64;
+            music_ids.extend(rows.iter().map(|row| row.get::<i64, _>("music_id")));
+        }
+        music_ids.sort_unstable();
+        music_ids.dedup();
+
+        tx.commit().await?;
+        Ok((changed, music_ids))
+    }
+
+    /// Run a trivial query to check the database is usable
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Optimize database by running VACUUM to reclaim space and defragment
     /// Should be called periodically after many deletions
     pub async fn optimize(&self) -> Result<()> {
         sqlx::query("VACUUM").execute(&self.pool).await?;
@@ -257,4 +1474,69 @@ impl Database {
         tracing::debug!("Database ANALYZE completed");
         Ok(())
     }
+
+    /// Write a consistent copy of the database to `path` (which must not exist)
+    pub async fn backup_to(&self, path: &std::path::Path) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+fn song_from_row(row: &SqliteRow) -> SongInfo {
+    SongInfo {
+        id: row.get("id"),
+        music_id: row.get("music_id"),
+        song_name: row.get("song_name"),
+        song_artists: row.get("song_artists"),
+        song_album: row.get("song_album"),
+        file_ext: row.get("file_ext"),
+        music_size: row.get("music_size"),
+        pic_size: row.get("pic_size"),
+        emb_pic_size: row.get("emb_pic_size"),
+        bit_rate: row.get("bit_rate"),
+        duration: row.get("duration"),
+        file_id: row.get("file_id"),
+        thumb_file_id: row.get("thumb_file_id"),
+        backup_message: row.get("backup_message"),
+        audio_md5: row.get("audio_md5"),
+        from_user_id: row.get("from_user_id"),
+        from_user_name: row.get("from_user_name"),
+        from_chat_id: row.get("from_chat_id"),
+        from_chat_name: row.get("from_chat_name"),
+        created_at: row
+            .get::<String, _>("created_at")
+            .parse()
+            .unwrap_or_else(|_| Utc::now()),
+        updated_at: row
+            .get::<String, _>("updated_at")
+            .parse()
+            .unwrap_or_else(|_| Utc::now()),
+    }
+}
+
+/// Add a column to tables created by older versions
+async fn add_column_if_missing(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM pragma_table_info(?) WHERE name = ?)")
+            .bind(table)
+            .bind(column)
+            .fetch_one(pool)
+            .await?;
+
+    if !exists {
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
 }
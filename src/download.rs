@@ -0,0 +1,109 @@
+//! Headless download pipeline: fetch, verify and tag a song into a directory
+//!
+//! Unlike the bot's pipeline this always writes to disk and never touches
+//! Telegram or the song cache, which makes it usable from the CLI and from
+//! other crates.
+
+use std::path::{Path, PathBuf};
+
+use futures_util::StreamExt;
+
+use crate::audio_buffer::AudioBuffer;
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::music_api::{MusicApi, format_artists};
+use crate::utils::{clean_filename, ensure_dir, incomplete_download, verify_md5};
+
+/// Download one song with tags and embedded cover into `output`, returning the written file
+///
+/// Picks the best quality `api` can access (see [`MusicApi::get_best_song_url`]) and,
+/// with `check_md5`, removes the file again if it does not match NetEase's checksum.
+pub async fn download_song(
+    api: &MusicApi,
+    music_id: u64,
+    output: &Path,
+    check_md5: bool,
+) -> Result<PathBuf> {
+    let song_detail = api.get_song_detail(music_id).await?;
+    let song_url = api.get_best_song_url(music_id).await?;
+    if song_url.url.is_empty() {
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Unavailable,
+            "No download URL, the song may require VIP",
+        ));
+    }
+
+    let file_ext = if song_url.url.contains(".flac") {
+        "flac"
+    } else {
+        "mp3"
+    };
+    let artists = format_artists(song_detail.ar.as_deref().unwrap_or(&[]));
+    let filename = clean_filename(&format!(
+        "{} - {}.{}",
+        artists.replace('/', ","),
+        song_detail.name,
+        file_ext
+    ));
+
+    let output_dir = output.to_string_lossy();
+    ensure_dir(&output_dir)?;
+    let mut audio_buffer = AudioBuffer::new_disk(filename, &output_dir).await?;
+
+    let response = api.download_file(&song_url.url).await?;
+    if !response.status().is_success() {
+        audio_buffer.cleanup().await.ok();
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Status,
+            format!("HTTP {}", response.status()),
+        ));
+    }
+    let content_length = response.content_length().unwrap_or(0);
+    let mut downloaded = 0u64;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        downloaded += chunk.len() as u64;
+        audio_buffer.write_chunk(&chunk).await?;
+    }
+    audio_buffer.finish().await?;
+    if let Some(reason) = incomplete_download(downloaded, content_length, song_url.size) {
+        audio_buffer.cleanup().await.ok();
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Incomplete,
+            format!("下载不完整: {reason}"),
+        ));
+    }
+
+    let path = audio_buffer
+        .path()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    if check_md5 && !song_url.md5.is_empty() {
+        let file = path.to_string_lossy().into_owned();
+        let expected = song_url.md5.clone();
+        let matches = tokio::task::spawn_blocking(move || verify_md5(&file, &expected))
+            .await
+            .map_err(|e| anyhow::anyhow!("MD5 task failed: {e}"))??;
+        if !matches {
+            audio_buffer.cleanup().await.ok();
+            return Err(BotError::Parse(format!(
+                "MD5 mismatch for music_id {music_id}"
+            )));
+        }
+    }
+
+    let artwork = match song_detail.al.as_ref().and_then(|al| al.pic_url.as_deref()) {
+        Some(pic_url) if !pic_url.is_empty() => api.download_album_art_original(pic_url).await.ok(),
+        _ => None,
+    };
+    let tagged = if file_ext == "flac" {
+        audio_buffer.add_flac_metadata(&song_detail, artwork.as_deref())
+    } else {
+        audio_buffer.add_id3_tags(&song_detail, artwork.as_deref())
+    };
+    if let Err(e) = tagged {
+        tracing::warn!("Failed to tag {}: {}", path.display(), e);
+    }
+
+    Ok(path)
+}
@@ -0,0 +1,123 @@
+//! Priority lane for interactive downloads (`download.priority_lane`)
+//!
+//! Every download holds a permit of `BotState::download_semaphore`. A long
+//! album, playlist or precache job used to queue for permits like anyone
+//! else, so a single song waited behind the whole backlog of tracks.
+//! Interactive downloads still queue on the semaphore, which hands a released
+//! permit straight to its oldest waiter; bulk tracks first only take permits
+//! nobody is waiting for, so a single song starts as soon as the current bulk
+//! track finishes. After `BULK_MAX_WAIT` a bulk track queues like everyone
+//! else, so a steady stream of single songs cannot starve a collection.
+
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// How often a bulk track checks for a free permit
+const BULK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a bulk track lets interactive downloads go first
+const BULK_MAX_WAIT: Duration = Duration::from_mins(1);
+
+/// Scheduling tier of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// A single song someone is waiting for, in any chat
+    Interactive,
+    /// Tracks of a collection, precache or background job
+    Bulk,
+}
+
+impl Lane {
+    /// The lane of a download for a collection (or a single song)
+    #[must_use]
+    pub fn for_request(priority_lane: bool, collection: bool) -> Self {
+        if priority_lane && collection {
+            Self::Bulk
+        } else {
+            Self::Interactive
+        }
+    }
+}
+
+/// Take a download permit in `lane`
+pub async fn acquire(semaphore: &Semaphore, lane: Lane) -> SemaphorePermit<'_> {
+    match lane {
+        Lane::Interactive => semaphore.acquire().await.unwrap(),
+        Lane::Bulk => {
+            let deadline = Instant::now() + BULK_MAX_WAIT;
+            while Instant::now() < deadline {
+                // Fails while interactive downloads wait: released permits go to them
+                if let Ok(permit) = semaphore.try_acquire() {
+                    return permit;
+                }
+                tokio::time::sleep(BULK_POLL_INTERVAL).await;
+            }
+            semaphore.acquire().await.unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::{Semaphore, mpsc};
+
+    use super::{BULK_MAX_WAIT, Lane, acquire};
+
+    #[test]
+    fn only_single_songs_take_the_priority_lane() {
+        assert_eq!(Lane::for_request(true, false), Lane::Interactive);
+        assert_eq!(Lane::for_request(true, true), Lane::Bulk);
+        assert_eq!(Lane::for_request(false, true), Lane::Interactive);
+    }
+
+    /// Spawn a download in `lane` that reports when it got its permit
+    fn spawn_download(
+        semaphore: &Arc<Semaphore>,
+        lane: Lane,
+        started: &mpsc::UnboundedSender<Lane>,
+    ) {
+        let semaphore = semaphore.clone();
+        let started = started.clone();
+        tokio::spawn(async move {
+            let _permit = acquire(&semaphore, lane).await;
+            started.send(lane).unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn interactive_download_gets_the_next_free_permit() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let running = semaphore.clone().try_acquire_owned().unwrap();
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+
+        spawn_download(&semaphore, Lane::Bulk, &started_tx);
+        tokio::task::yield_now().await;
+        spawn_download(&semaphore, Lane::Interactive, &started_tx);
+        tokio::task::yield_now().await;
+
+        drop(running);
+        assert_eq!(started.recv().await, Some(Lane::Interactive));
+        assert_eq!(started.recv().await, Some(Lane::Bulk));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bulk_download_queues_after_waiting_too_long() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let running = semaphore.clone().try_acquire_owned().unwrap();
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+
+        spawn_download(&semaphore, Lane::Bulk, &started_tx);
+        tokio::time::sleep(BULK_MAX_WAIT * 2).await;
+        spawn_download(&semaphore, Lane::Interactive, &started_tx);
+        tokio::task::yield_now().await;
+
+        drop(running);
+        assert_eq!(started.recv().await, Some(Lane::Bulk));
+        assert_eq!(started.recv().await, Some(Lane::Interactive));
+    }
+}
@@ -1,5 +1,24 @@
 use thiserror::Error;
 
+/// What went wrong talking to NetEase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusicApiErrorKind {
+    /// The song, album, playlist or cover does not exist
+    NotFound,
+    /// No download URL: VIP only, copyright restricted or region locked
+    Unavailable,
+    /// `MUSIC_U` is not configured or no longer logged in
+    Unauthorized,
+    /// NetEase or the CDN answered with an error status, API code or timeout
+    Status,
+    /// The response could not be decoded
+    InvalidResponse,
+    /// The download ended early or failed its checksum
+    Incomplete,
+    /// Local processing of a response failed
+    Other,
+}
+
 #[derive(Error, Debug)]
 pub enum BotError {
     #[error("Configuration error: {0}")]
@@ -14,11 +33,21 @@ pub enum BotError {
     #[error("Telegram error: {0}")]
     Telegram(#[from] teloxide::RequestError),
 
-    #[error("Music API error: {0}")]
-    MusicApi(String),
+    #[error("Music API error: {message}")]
+    MusicApi {
+        kind: MusicApiErrorKind,
+        message: String,
+    },
+
+    /// Telegram refused the upload because of its size
+    #[error("File too large for Telegram: {size} bytes (limit {limit} bytes)")]
+    FileTooLarge { size: u64, limit: u64 },
 
-    #[error("File operation error: {0}")]
-    FileOperation(#[from] std::io::Error),
+    #[error("Tagging error: {0}")]
+    Tagging(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
@@ -29,8 +58,167 @@ pub enum BotError {
     #[error("Parse error: {0}")]
     Parse(String),
 
+    /// `source` happened while handling a song and/or chat
+    #[error("{source} ({})", context_label(*music_id, *chat_id))]
+    Context {
+        music_id: Option<u64>,
+        chat_id: Option<i64>,
+        #[source]
+        source: Box<BotError>,
+    },
+
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
 
+/// The crate's error type under the name library consumers expect
+pub type Error = BotError;
+
 pub type Result<T> = std::result::Result<T, BotError>;
+
+fn context_label(music_id: Option<u64>, chat_id: Option<i64>) -> String {
+    [
+        music_id.map(|id| format!("music_id {id}")),
+        chat_id.map(|id| format!("chat_id {id}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+impl BotError {
+    pub fn music_api(kind: MusicApiErrorKind, message: impl Into<String>) -> Self {
+        Self::MusicApi {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Attach the song being handled
+    #[must_use]
+    pub fn with_music_id(self, music_id: u64) -> Self {
+        match self {
+            Self::Context {
+                chat_id, source, ..
+            } => Self::Context {
+                music_id: Some(music_id),
+                chat_id,
+                source,
+            },
+            source => Self::Context {
+                music_id: Some(music_id),
+                chat_id: None,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Attach the chat being served
+    #[must_use]
+    pub fn with_chat_id(self, chat_id: i64) -> Self {
+        match self {
+            Self::Context {
+                music_id, source, ..
+            } => Self::Context {
+                music_id,
+                chat_id: Some(chat_id),
+                source,
+            },
+            source => Self::Context {
+                music_id: None,
+                chat_id: Some(chat_id),
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// The error without its context, for matching on the variant
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    #[must_use]
+    pub fn music_id(&self) -> Option<u64> {
+        match self {
+            Self::Context { music_id, .. } => *music_id,
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn chat_id(&self) -> Option<i64> {
+        match self {
+            Self::Context { chat_id, .. } => *chat_id,
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn music_api_kind(&self) -> Option<MusicApiErrorKind> {
+        match self.root() {
+            Self::MusicApi { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// Size and limit of an upload Telegram refused as too large
+    #[must_use]
+    pub fn file_too_large(&self) -> Option<(u64, u64)> {
+        match self.root() {
+            Self::FileTooLarge { size, limit } => Some((*size, *limit)),
+            _ => None,
+        }
+    }
+
+    /// Whether trying again later may succeed (network trouble, rate
+    /// limits, CDN hiccups) as opposed to failing the same way every time
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self.root() {
+            Self::Network(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            Self::Telegram(e) => matches!(
+                e,
+                teloxide::RequestError::Network(_)
+                    | teloxide::RequestError::RetryAfter(_)
+                    | teloxide::RequestError::Io(_)
+            ),
+            Self::MusicApi { kind, .. } => {
+                matches!(
+                    kind,
+                    MusicApiErrorKind::Status | MusicApiErrorKind::Incomplete
+                )
+            }
+            Self::Database(sqlx::Error::PoolTimedOut) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BotError, MusicApiErrorKind};
+
+    #[test]
+    fn context_keeps_the_variant_matchable() {
+        let error = BotError::music_api(MusicApiErrorKind::Status, "HTTP 502")
+            .with_music_id(1)
+            .with_chat_id(-100);
+        assert_eq!(error.music_id(), Some(1));
+        assert_eq!(error.chat_id(), Some(-100));
+        assert_eq!(error.music_api_kind(), Some(MusicApiErrorKind::Status));
+        assert!(error.is_transient());
+        assert_eq!(
+            error.to_string(),
+            "Music API error: HTTP 502 (music_id 1, chat_id -100)"
+        );
+
+        let error = BotError::music_api(MusicApiErrorKind::Unavailable, "VIP only");
+        assert!(!error.is_transient());
+        assert!(matches!(error.root(), BotError::MusicApi { .. }));
+    }
+}
@@ -0,0 +1,233 @@
+//! Optional `/healthz` HTTP endpoint for container health probes, plus
+//! `/metrics` (Prometheus text format) for throughput trends
+//!
+//! A deliberately tiny HTTP/1.1 responder on top of `TcpListener`: every
+//! request gets a single response and the connection is closed.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use teloxide::prelude::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::bot::BotState;
+use crate::config::Config;
+use crate::database::Database;
+use crate::music_api::MusicApi;
+
+/// Upper bound for each dependency check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub telegram: CheckResult,
+    pub netease: CheckResult,
+    pub database: CheckResult,
+}
+
+impl HealthReport {
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.telegram.ok && self.netease.ok && self.database.ok
+    }
+}
+
+async fn run_check<F, T, E>(check: F) -> CheckResult
+where
+    F: Future<Output = std::result::Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let start = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(Ok(_)) => CheckResult {
+            ok: true,
+            latency_ms,
+            error: None,
+        },
+        Ok(Err(e)) => CheckResult {
+            ok: false,
+            latency_ms,
+            error: Some(e.to_string()),
+        },
+        Err(_) => CheckResult {
+            ok: false,
+            latency_ms,
+            error: Some("timeout".to_string()),
+        },
+    }
+}
+
+/// Check Telegram, the NetEase API and the database concurrently
+pub async fn check_health(bot: &Bot, state: &BotState) -> HealthReport {
+    let (telegram, netease, database) = tokio::join!(
+        run_check(async { bot.get_me().await }),
+        run_check(state.music_api.ping()),
+        run_check(state.database.ping()),
+    );
+
+    let mut report = HealthReport {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        telegram,
+        netease,
+        database,
+    };
+    if !report.is_healthy() {
+        report.status = "error";
+    }
+    report
+}
+
+/// Check the configured Telegram API, the NetEase API and the database without a running bot
+///
+/// Used by the `healthcheck` subcommand; unlike startup it does not fall back
+/// to the official Telegram API when a custom `bot.api` is unreachable.
+pub async fn check_config_health(config: &Config) -> HealthReport {
+    let mut bot = Bot::new(&config.bot_token);
+    if !config.bot_api.is_empty() {
+        match reqwest::Url::parse(&format!("{}/", config.bot_api.trim_end_matches("/bot"))) {
+            Ok(url) => bot = bot.set_api_url(url),
+            Err(e) => tracing::warn!("Invalid bot.api '{}': {}", config.bot_api, e),
+        }
+    }
+    let music_api = MusicApi::new_with_config(config);
+
+    let (telegram, netease, database) = tokio::join!(
+        run_check(async { bot.get_me().await }),
+        run_check(music_api.ping()),
+        run_check(async { Database::new(config).await?.ping().await }),
+    );
+
+    let mut report = HealthReport {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        telegram,
+        netease,
+        database,
+    };
+    if !report.is_healthy() {
+        report.status = "error";
+    }
+    report
+}
+
+/// Extract the request path from the request line (`GET /healthz HTTP/1.1`)
+fn request_path(request: &str) -> Option<&str> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let path = parts.next()?;
+    (method == "GET").then(|| path.split('?').next().unwrap_or(path))
+}
+
+async fn handle_connection(mut stream: TcpStream, bot: Bot, state: Arc<BotState>) {
+    let mut buf = vec![0u8; 4096];
+    let Ok(Ok(n)) = tokio::time::timeout(CHECK_TIMEOUT, stream.read(&mut buf)).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf[..n]);
+
+    let mut content_type = "application/json";
+    let (status_line, body) = match request_path(&request) {
+        Some("/metrics") => {
+            content_type = "text/plain; version=0.0.4";
+            ("200 OK", metrics(&state))
+        }
+        Some("/healthz") => {
+            let report = check_health(&bot, &state).await;
+            if !report.is_healthy() {
+                tracing::warn!("Health check failed: {:?}", report);
+            }
+            let status_line = if report.is_healthy() {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (
+                status_line,
+                serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string()),
+            )
+        }
+        Some(_) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        None => ("400 Bad Request", r#"{"error":"bad request"}"#.to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status_line}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        tracing::debug!("Failed to write health response: {}", e);
+    }
+    stream.shutdown().await.ok();
+}
+
+/// Upload concurrency and transfer throughput in the Prometheus text format
+fn metrics(state: &BotState) -> String {
+    let counters = &state.upload_counters;
+    format!(
+        "# TYPE music163bot_uploads_in_flight gauge\nmusic163bot_uploads_in_flight {}\n\
+         # TYPE music163bot_uploads_in_flight_peak gauge\nmusic163bot_uploads_in_flight_peak {}\n{}",
+        counters.in_flight.load(Ordering::Relaxed),
+        counters.peak_in_flight.load(Ordering::Relaxed),
+        state.transfers.prometheus()
+    )
+}
+
+/// Serve `/healthz` and `/metrics` on `addr` in the background
+pub async fn spawn_health_server(addr: &str, bot: Bot, state: Arc<BotState>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to bind health check listener on {}: {}", addr, e);
+            return;
+        }
+    };
+    tracing::info!("Health check endpoint listening on http://{}/healthz", addr);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, bot.clone(), state.clone()));
+                }
+                Err(e) => tracing::warn!("Health check accept error: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::request_path;
+
+    #[test]
+    fn parses_request_path() {
+        assert_eq!(
+            request_path("GET /healthz HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some("/healthz")
+        );
+        assert_eq!(
+            request_path("GET /healthz?full=1 HTTP/1.1\r\n"),
+            Some("/healthz")
+        );
+        assert_eq!(request_path("POST /healthz HTTP/1.1\r\n"), None);
+        assert_eq!(request_path(""), None);
+    }
+}
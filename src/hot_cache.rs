@@ -0,0 +1,367 @@
+//! Optional Redis layer in front of the song cache (`cache.redis_url`)
+//!
+//! Clustered deployments share one Redis so cache hits (music_id → file_id and
+//! caption data) are answered without touching the database, and a song
+//! uploaded by one instance is immediately reused by the others. Only the few
+//! commands needed here are spoken, over a single lazily (re)connected RESP
+//! connection; any Redis error simply falls back to the database.
+//!
+//! The same connection carries per-song download locks (`SET NX`), so two
+//! instances never download the same song at once and race on saving it.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::database::SongInfo;
+use crate::error::{BotError, Result};
+
+/// Upper bound for connecting plus one command round trip
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+const KEY_PREFIX: &str = "music163bot:song:";
+const LOCK_PREFIX: &str = "music163bot:lock:";
+/// Deletes the lock only while it still holds our token, so an expired lock
+/// taken over by another instance is left alone
+const UNLOCK_SCRIPT: &[u8] =
+    b"if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+/// A decoded RESP reply
+#[derive(Debug, PartialEq, Eq)]
+enum Reply {
+    /// Simple string or integer reply (`+OK`, `:1`)
+    Status,
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+/// Connection settings parsed from a `redis://[:password@]host[:port][/db]` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisTarget {
+    pub addr: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub db: u32,
+}
+
+impl RedisTarget {
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(url)
+            .map_err(|e| BotError::Config(format!("Invalid redis URL: {e}")))?;
+        if url.scheme() != "redis" {
+            return Err(BotError::Config(format!(
+                "Unsupported redis URL scheme: {}",
+                url.scheme()
+            )));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| BotError::Config("Redis URL has no host".to_string()))?;
+        let db = match url.path().trim_start_matches('/') {
+            "" => 0,
+            db => db
+                .parse()
+                .map_err(|_| BotError::Config(format!("Invalid redis database: {db}")))?,
+        };
+
+        Ok(Self {
+            addr: format!("{host}:{}", url.port().unwrap_or(6379)),
+            username: Some(url.username())
+                .filter(|user| !user.is_empty())
+                .map(str::to_string),
+            password: url.password().map(str::to_string),
+            db,
+        })
+    }
+}
+
+pub struct HotCache {
+    target: RedisTarget,
+    ttl_secs: u64,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl HotCache {
+    #[must_use]
+    pub fn new(target: RedisTarget, ttl_secs: u64) -> Self {
+        Self {
+            target,
+            ttl_secs: ttl_secs.max(1),
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn key(music_id: i64, bot_id: i64) -> String {
+        format!("{KEY_PREFIX}{music_id}:{bot_id}")
+    }
+
+    /// Cached song as seen by `bot_id`
+    pub async fn get(&self, music_id: i64, bot_id: i64) -> Result<Option<SongInfo>> {
+        match self
+            .command(&[b"GET", Self::key(music_id, bot_id).as_bytes()])
+            .await?
+        {
+            Reply::Bulk(Some(value)) => Ok(serde_json::from_slice(&value).ok()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Store `song` (with `bot_id`'s file_ids) for `cache.redis_ttl_secs`
+    pub async fn put(&self, song: &SongInfo, bot_id: i64) -> Result<()> {
+        let value = serde_json::to_vec(song)?;
+        let ttl = self.ttl_secs.to_string();
+        self.command(&[
+            b"SET",
+            Self::key(song.music_id, bot_id).as_bytes(),
+            &value,
+            b"EX",
+            ttl.as_bytes(),
+        ])
+        .await?;
+        Ok(())
+    }
+
+    /// Take the download lock of `music_id` for `ttl_secs`, returning its token,
+    /// or `None` while another instance holds it
+    pub async fn try_lock(&self, music_id: i64, ttl_secs: u64) -> Result<Option<String>> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let ttl = ttl_secs.max(1).to_string();
+        let reply = self
+            .command(&[
+                b"SET",
+                format!("{LOCK_PREFIX}{music_id}").as_bytes(),
+                token.as_bytes(),
+                b"NX",
+                b"EX",
+                ttl.as_bytes(),
+            ])
+            .await?;
+        Ok((reply == Reply::Status).then_some(token))
+    }
+
+    /// Release a lock taken with `try_lock`
+    pub async fn unlock(&self, music_id: i64, token: &str) -> Result<()> {
+        self.command(&[
+            b"EVAL",
+            UNLOCK_SCRIPT,
+            b"1",
+            format!("{LOCK_PREFIX}{music_id}").as_bytes(),
+            token.as_bytes(),
+        ])
+        .await?;
+        Ok(())
+    }
+
+    /// Drop a song for every bot
+    pub async fn remove(&self, music_id: i64) -> Result<()> {
+        self.delete_matching(&format!("{KEY_PREFIX}{music_id}:*"))
+            .await
+    }
+
+    /// Drop every cached song
+    pub async fn clear(&self) -> Result<()> {
+        self.delete_matching(&format!("{KEY_PREFIX}*")).await
+    }
+
+    async fn delete_matching(&self, pattern: &str) -> Result<()> {
+        let mut cursor = "0".to_string();
+        loop {
+            let reply = self
+                .command(&[
+                    b"SCAN",
+                    cursor.as_bytes(),
+                    b"MATCH",
+                    pattern.as_bytes(),
+                    b"COUNT",
+                    b"500",
+                ])
+                .await?;
+            let Reply::Array(mut parts) = reply else {
+                return Err(protocol_error("unexpected SCAN reply"));
+            };
+            let (Some(Reply::Array(keys)), Some(Reply::Bulk(Some(next)))) =
+                (parts.pop(), parts.pop())
+            else {
+                return Err(protocol_error("unexpected SCAN reply"));
+            };
+
+            let keys: Vec<Vec<u8>> = keys
+                .into_iter()
+                .filter_map(|key| match key {
+                    Reply::Bulk(Some(key)) => Some(key),
+                    _ => None,
+                })
+                .collect();
+            if !keys.is_empty() {
+                let mut args: Vec<&[u8]> = vec![b"DEL"];
+                args.extend(keys.iter().map(Vec::as_slice));
+                self.command(&args).await?;
+            }
+
+            cursor = String::from_utf8_lossy(&next).into_owned();
+            if cursor == "0" {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Send one command, reconnecting first if needed; the connection is
+    /// dropped on any failure so the next call starts clean
+    async fn command(&self, args: &[&[u8]]) -> Result<Reply> {
+        let mut conn = self.conn.lock().await;
+        let result = tokio::time::timeout(COMMAND_TIMEOUT, async {
+            if conn.is_none() {
+                *conn = Some(self.connect().await?);
+            }
+            let stream = conn.as_mut().expect("connection was just established");
+            stream.write_all(&encode_command(args)).await?;
+            read_reply(stream).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(protocol_error("command timed out")));
+
+        if result.is_err() {
+            *conn = None;
+        }
+        result
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let mut stream = BufReader::new(TcpStream::connect(&self.target.addr).await?);
+
+        if let Some(password) = &self.target.password {
+            let auth = match &self.target.username {
+                Some(user) => encode_command(&[b"AUTH", user.as_bytes(), password.as_bytes()]),
+                None => encode_command(&[b"AUTH", password.as_bytes()]),
+            };
+            stream.write_all(&auth).await?;
+            read_reply(&mut stream).await?;
+        }
+        if self.target.db != 0 {
+            let db = self.target.db.to_string();
+            stream
+                .write_all(&encode_command(&[b"SELECT", db.as_bytes()]))
+                .await?;
+            read_reply(&mut stream).await?;
+        }
+
+        tracing::debug!("Connected to redis at {}", self.target.addr);
+        Ok(stream)
+    }
+}
+
+fn protocol_error(message: &str) -> BotError {
+    BotError::Other(anyhow::anyhow!("Redis {message}"))
+}
+
+/// Encode a command as a RESP array of bulk strings
+fn encode_command(args: &[&[u8]]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Read one RESP reply; `-ERR` replies become errors
+async fn read_reply<R: AsyncBufRead + Unpin + Send>(reader: &mut R) -> Result<Reply> {
+    // Stack of partially filled arrays: (items so far, items expected)
+    let mut stack: Vec<(Vec<Reply>, usize)> = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(protocol_error("connection closed"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (kind, rest) = line.split_at(line.len().min(1));
+        let parse_len = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| protocol_error("sent an invalid length"))
+        };
+
+        let mut reply = match kind {
+            "+" | ":" => Reply::Status,
+            "-" => return Err(BotError::Other(anyhow::anyhow!("Redis error: {rest}"))),
+            "$" => match usize::try_from(parse_len(rest)?) {
+                Ok(len) => {
+                    let mut value = vec![0u8; len + 2];
+                    reader.read_exact(&mut value).await?;
+                    value.truncate(len);
+                    Reply::Bulk(Some(value))
+                }
+                Err(_) => Reply::Bulk(None),
+            },
+            "*" => match usize::try_from(parse_len(rest)?) {
+                Ok(0) | Err(_) => Reply::Array(Vec::new()),
+                Ok(len) => {
+                    stack.push((Vec::with_capacity(len), len));
+                    continue;
+                }
+            },
+            _ => return Err(protocol_error("sent an unknown reply type")),
+        };
+
+        // Fold completed values into their enclosing arrays
+        loop {
+            match stack.last_mut() {
+                Some((items, expected)) => {
+                    items.push(reply);
+                    if items.len() < *expected {
+                        break;
+                    }
+                    let (items, _) = stack.pop().expect("stack is not empty");
+                    reply = Reply::Array(items);
+                }
+                None => return Ok(reply),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RedisTarget, Reply, encode_command, read_reply};
+
+    #[test]
+    fn parses_redis_urls() {
+        let target = RedisTarget::parse("redis://:secret@cache.local:6380/2").unwrap();
+        assert_eq!(target.addr, "cache.local:6380");
+        assert_eq!(target.username, None);
+        assert_eq!(target.password.as_deref(), Some("secret"));
+        assert_eq!(target.db, 2);
+
+        let target = RedisTarget::parse("redis://localhost").unwrap();
+        assert_eq!(target.addr, "localhost:6379");
+        assert_eq!(target.db, 0);
+
+        assert!(RedisTarget::parse("http://localhost").is_err());
+    }
+
+    #[test]
+    fn encodes_commands_as_bulk_arrays() {
+        assert_eq!(
+            encode_command(&[b"GET", b"key"]),
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_nested_replies() {
+        let mut input: &[u8] = b"*2\r\n$1\r\n0\r\n*2\r\n$1\r\na\r\n$-1\r\n";
+        assert_eq!(
+            read_reply(&mut input).await.unwrap(),
+            Reply::Array(vec![
+                Reply::Bulk(Some(b"0".to_vec())),
+                Reply::Array(vec![Reply::Bulk(Some(b"a".to_vec())), Reply::Bulk(None)]),
+            ])
+        );
+
+        let mut input: &[u8] = b"-WRONGPASS invalid password\r\n";
+        assert!(read_reply(&mut input).await.is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Outgoing HTTP clients, built from named profiles
+//!
+//! Every reqwest client of the bot starts from `base_builder`, which applies
+//! the `[network]` section (User-Agent, TLS backend, HTTP version, proxy).
+//! The profiles add the timeouts and connection pool tuning of each use case
+//! so they are defined in one place.
+
+use std::time::Duration;
+
+use crate::config::{Config, HttpVersion};
+
+/// What a client is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Telegram Bot API requests and update polling
+    Api,
+    /// Multipart uploads to Telegram and object storage
+    Upload,
+    /// NetEase API calls and CDN downloads
+    Download,
+}
+
+/// Timeouts and pool settings of a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSettings {
+    pub user_agent: String,
+    /// Whole-request timeout (`None` = unlimited, for long streaming downloads)
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// 0 disables keep-alive, avoiding stale connections after long transfers
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Option<Duration>,
+    /// Skip gzip so multipart boundaries and Cloudflare-fronted APIs are untouched
+    pub no_gzip: bool,
+    pub tcp_nodelay: bool,
+}
+
+impl Profile {
+    #[must_use]
+    pub fn settings(self, config: &Config) -> ProfileSettings {
+        match self {
+            Self::Api => ProfileSettings {
+                user_agent: config.telegram_user_agent.clone(),
+                timeout: Some(Duration::from_secs(30)),
+                connect_timeout: None,
+                pool_max_idle_per_host: 2,
+                pool_idle_timeout: Some(Duration::from_mins(1)),
+                no_gzip: true,
+                tcp_nodelay: false,
+            },
+            Self::Upload => ProfileSettings {
+                user_agent: config.telegram_user_agent.clone(),
+                timeout: Some(Duration::from_secs(config.upload_timeout_secs)),
+                connect_timeout: None,
+                pool_max_idle_per_host: 0,
+                pool_idle_timeout: None,
+                no_gzip: true,
+                tcp_nodelay: false,
+            },
+            Self::Download => ProfileSettings {
+                user_agent: config.music_user_agent.clone(),
+                timeout: None,
+                connect_timeout: Some(Duration::from_secs(config.download_connect_timeout_secs)),
+                pool_max_idle_per_host: config.download_pool_max_idle_per_host,
+                pool_idle_timeout: None,
+                no_gzip: false,
+                tcp_nodelay: true,
+            },
+        }
+    }
+}
+
+/// Client builder with the `[network]` settings applied
+pub fn base_builder(config: &Config, user_agent: &str) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder().user_agent(user_agent);
+    builder = match config.tls_backend {
+        #[cfg(feature = "native-tls")]
+        crate::config::TlsBackend::Native => builder.use_native_tls(),
+        _ => builder.use_rustls_tls(),
+    };
+    builder = match config.http_version {
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Auto => builder,
+        HttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+    if !config.proxy.is_empty() {
+        match reqwest::Proxy::all(&config.proxy) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => tracing::warn!("Invalid network.proxy '{}': {}", config.proxy, e),
+        }
+    }
+    builder
+}
+
+/// Client builder for `profile`, for callers that need to add more options
+pub fn builder(config: &Config, profile: Profile) -> reqwest::ClientBuilder {
+    let settings = profile.settings(config);
+    let mut builder = base_builder(config, &settings.user_agent)
+        .pool_max_idle_per_host(settings.pool_max_idle_per_host)
+        .tcp_nodelay(settings.tcp_nodelay);
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(timeout) = settings.connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = settings.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if settings.no_gzip {
+        builder = builder.no_gzip();
+    }
+    builder
+}
+
+/// Build the client for `profile`
+pub fn build(config: &Config, profile: Profile) -> reqwest::Result<reqwest::Client> {
+    builder(config, profile).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Profile, build};
+    use crate::config::{Config, HttpVersion};
+
+    #[test]
+    fn profiles_follow_config() {
+        let config = Config {
+            upload_timeout_secs: 600,
+            download_pool_max_idle_per_host: 5,
+            music_user_agent: "test-agent".to_string(),
+            ..Config::default()
+        };
+
+        let upload = Profile::Upload.settings(&config);
+        assert_eq!(upload.timeout, Some(Duration::from_mins(10)));
+        assert_eq!(upload.pool_max_idle_per_host, 0);
+        assert_eq!(upload.user_agent, config.telegram_user_agent);
+
+        let download = Profile::Download.settings(&config);
+        assert_eq!(download.timeout, None);
+        assert_eq!(download.pool_max_idle_per_host, 5);
+        assert_eq!(download.user_agent, "test-agent");
+
+        assert_eq!(
+            Profile::Api.settings(&config).timeout,
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn every_profile_builds() {
+        let config = Config {
+            http_version: HttpVersion::Auto,
+            proxy: "http://127.0.0.1:8080".to_string(),
+            ..Config::default()
+        };
+        for profile in [Profile::Api, Profile::Upload, Profile::Download] {
+            assert!(build(&config, profile).is_ok());
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Language of song delivery messages and audio captions (`/lang`)
+//!
+//! The locale of a chat is pinned with `/lang zh|en`; otherwise it follows the
+//! Telegram client language of the user who sent the message. Only the
+//! strings of the song delivery flow and audio captions are translated so far.
+
+use std::str::FromStr;
+
+/// Language of the song delivery messages and audio captions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Locale {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Zh => "zh",
+            Self::En => "en",
+        }
+    }
+
+    /// Locale for a Telegram `language_code` (IETF tag such as `zh-hans` or
+    /// `en`); unknown users keep the Chinese default
+    #[must_use]
+    pub fn from_language_code(code: Option<&str>) -> Self {
+        match code {
+            Some(code) if !code.to_lowercase().starts_with("zh") => Self::En,
+            _ => Self::Zh,
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "zh" | "cn" | "中文" => Ok(Self::Zh),
+            "en" | "english" => Ok(Self::En),
+            _ => Err(anyhow::anyhow!("Unknown language: {s}")),
+        }
+    }
+}
+
+/// Translatable message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    FetchingSong,
+    FetchSongFailed,
+    Downloading,
+    RemainingQuota,
+    ProcessingFailed,
+    JobTimedOut,
+    JobAborted,
+    FileTooLarge,
+    FileTooLargeHint,
+    LocalBotApiHint,
+    CaptionAlbum,
+    PreviewNotice,
+    PreviewTitle,
+    Busy,
+    LanguageCurrent,
+    LanguageSet,
+    LanguageUsage,
+    LanguageScope,
+    AdminOnly,
+    SaveSettingFailed,
+}
+
+impl Text {
+    #[must_use]
+    pub fn get(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::FetchingSong, Locale::Zh) => "🔄 正在获取歌曲信息...",
+            (Self::FetchingSong, Locale::En) => "🔄 Fetching song info...",
+            (Self::FetchSongFailed, Locale::Zh) => "❌ 获取歌曲信息失败",
+            (Self::FetchSongFailed, Locale::En) => "❌ Failed to fetch song info",
+            (Self::Downloading, Locale::Zh) => "📥 正在下载",
+            (Self::Downloading, Locale::En) => "📥 Downloading",
+            (Self::RemainingQuota, Locale::Zh) => "今日剩余额度",
+            (Self::RemainingQuota, Locale::En) => "Downloads left today",
+            (Self::ProcessingFailed, Locale::Zh) => "❌ 处理失败",
+            (Self::ProcessingFailed, Locale::En) => "❌ Processing failed",
+            (Self::JobTimedOut, Locale::Zh) => "⏱ 处理超时，已取消，请稍后重试",
+            (Self::JobTimedOut, Locale::En) => "⏱ Timed out and cancelled, please try again later",
+            (Self::JobAborted, Locale::Zh) => "❌ 任务异常终止",
+            (Self::JobAborted, Locale::En) => "❌ The job was aborted unexpectedly",
+            (Self::FileTooLarge, Locale::Zh) => "❌ 文件超过 Telegram 上传上限",
+            (Self::FileTooLarge, Locale::En) => "❌ The file exceeds Telegram's upload limit",
+            (Self::FileTooLargeHint, Locale::Zh) => "可选择下方较低音质重新发送",
+            (Self::FileTooLargeHint, Locale::En) => {
+                "Pick a lower quality below to get a smaller file"
+            }
+            (Self::LocalBotApiHint, Locale::Zh) => {
+                "管理员可部署本地 Bot API 服务器并配置 bot.api，将上限提高到 2000 MB"
+            }
+            (Self::LocalBotApiHint, Locale::En) => {
+                "Admins can run a local Bot API server (bot.api) to raise the limit to 2000 MB"
+            }
+            (Self::CaptionAlbum, Locale::Zh) => "专辑",
+            (Self::CaptionAlbum, Locale::En) => "Album",
+            (Self::PreviewNotice, Locale::Zh) => "「试听片段」完整歌曲需要 VIP 权限",
+            (Self::PreviewNotice, Locale::En) => {
+                "「试听片段」Preview clip, the full song requires VIP"
+            }
+            (Self::PreviewTitle, Locale::Zh) => "试听片段",
+            (Self::PreviewTitle, Locale::En) => "Preview",
+            (Self::Busy, Locale::Zh) => "⏳ 机器人当前繁忙，请稍后再试",
+            (Self::Busy, Locale::En) => "⏳ The bot is busy, please try again later",
+            (Self::LanguageCurrent, Locale::Zh) => "🌐 当前语言: 中文",
+            (Self::LanguageCurrent, Locale::En) => "🌐 Current language: English",
+            (Self::LanguageSet, Locale::Zh) => "✅ 已切换为中文",
+            (Self::LanguageSet, Locale::En) => "✅ Switched to English",
+            (Self::LanguageUsage, Locale::Zh) => "用法: /lang zh|en|default",
+            (Self::LanguageUsage, Locale::En) => "Usage: /lang zh|en|default",
+            (Self::LanguageScope, Locale::Zh) => "仅用于歌曲下载的状态消息与音频说明文字",
+            (Self::LanguageScope, Locale::En) => {
+                "Applies to song download messages and audio captions"
+            }
+            (Self::AdminOnly, Locale::Zh) => "❌ 只有群组管理员可以修改此设置",
+            (Self::AdminOnly, Locale::En) => "❌ Only group admins can change this setting",
+            (Self::SaveSettingFailed, Locale::Zh) => "❌ 保存设置失败",
+            (Self::SaveSettingFailed, Locale::En) => "❌ Failed to save the setting",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Locale, Text};
+
+    #[test]
+    fn detects_locale_from_language_code() {
+        assert_eq!(Locale::from_language_code(Some("zh-hans")), Locale::Zh);
+        assert_eq!(Locale::from_language_code(Some("en")), Locale::En);
+        assert_eq!(Locale::from_language_code(Some("ja")), Locale::En);
+        assert_eq!(Locale::from_language_code(None), Locale::Zh);
+        assert_eq!("EN".parse::<Locale>().unwrap(), Locale::En);
+        assert!("fr".parse::<Locale>().is_err());
+        assert_eq!(Text::CaptionAlbum.get(Locale::En), "Album");
+    }
+}
@@ -0,0 +1,94 @@
+//! Audio previews of inline search results
+//!
+//! Inline queries fire on every keystroke, so preview URLs are kept for a few
+//! minutes and each user may trigger a NetEase lookup at most once per
+//! `FETCH_INTERVAL`; results typed in between only get the previews already
+//! known. The URLs are fetched without the account's MUSIC_U (see
+//! `MusicApi::get_public_song_urls`), so VIP songs never become free streams.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// NetEase links expire after about 20 minutes
+const URL_TTL: Duration = Duration::from_mins(10);
+
+/// Minimum time between two preview lookups for the same user
+const FETCH_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Default)]
+pub struct InlinePreviews {
+    urls: Mutex<HashMap<u64, (reqwest::Url, Instant)>>,
+    last_fetch: Mutex<HashMap<u64, Instant>>,
+}
+
+impl InlinePreviews {
+    /// Known preview URLs of `ids`
+    pub fn cached(&self, ids: &[u64]) -> HashMap<u64, reqwest::Url> {
+        let now = Instant::now();
+        let mut urls = self
+            .urls
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        urls.retain(|_, (_, at)| now.duration_since(*at) < URL_TTL);
+        ids.iter()
+            .filter_map(|id| Some((*id, urls.get(id)?.0.clone())))
+            .collect()
+    }
+
+    /// Remember freshly fetched preview URLs
+    pub fn store(&self, fetched: &HashMap<u64, reqwest::Url>) {
+        let now = Instant::now();
+        let mut urls = self
+            .urls
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for (id, url) in fetched {
+            urls.insert(*id, (url.clone(), now));
+        }
+    }
+
+    /// Whether `user_id` may trigger a preview lookup now
+    pub fn may_fetch(&self, user_id: u64) -> bool {
+        self.may_fetch_at(user_id, Instant::now())
+    }
+
+    fn may_fetch_at(&self, user_id: u64, now: Instant) -> bool {
+        let mut last_fetch = self
+            .last_fetch
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        last_fetch.retain(|_, at| now.duration_since(*at) < FETCH_INTERVAL);
+        if last_fetch.contains_key(&user_id) {
+            return false;
+        }
+        last_fetch.insert(user_id, now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use super::{FETCH_INTERVAL, InlinePreviews};
+
+    #[test]
+    fn throttles_lookups_per_user() {
+        let previews = InlinePreviews::default();
+        let now = Instant::now();
+        assert!(previews.may_fetch_at(1, now));
+        assert!(!previews.may_fetch_at(1, now + Duration::from_secs(1)));
+        assert!(previews.may_fetch_at(2, now + Duration::from_secs(1)));
+        assert!(previews.may_fetch_at(1, now + FETCH_INTERVAL));
+    }
+
+    #[test]
+    fn serves_stored_urls() {
+        let previews = InlinePreviews::default();
+        let url = reqwest::Url::parse("https://m801.music.126.net/a.mp3").unwrap();
+        previews.store(&HashMap::from([(7, url.clone())]));
+        assert_eq!(previews.cached(&[7, 8]), HashMap::from([(7, url)]));
+    }
+}
@@ -0,0 +1,186 @@
+//! Group jukebox ("点歌台", `/jukebox on`)
+//!
+//! Songs requested with `/queue` are posted one at a time, `jukebox.interval_secs`
+//! apart, by a player task that runs while the chat's queue is not empty.
+//! Queues live in memory and are lost on restart.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use teloxide::types::Message;
+use tokio::sync::{Mutex, Notify};
+
+/// Songs a chat may have waiting at once
+pub const MAX_QUEUE: usize = 30;
+
+/// A requested song
+#[derive(Debug, Clone)]
+pub struct QueuedSong {
+    pub music_id: u64,
+    /// "Title - Artists"
+    pub title: String,
+    pub requested_by: String,
+    pub requester_id: i64,
+    /// The `/queue` message, which the song is posted as a reply to
+    pub request: Message,
+}
+
+#[derive(Debug, Default)]
+struct ChatQueue {
+    pending: VecDeque<QueuedSong>,
+    now_playing: Option<QueuedSong>,
+    player_running: bool,
+    skip: Arc<Notify>,
+}
+
+/// What a chat is playing and what comes next
+#[derive(Debug, Clone)]
+pub struct QueueView {
+    pub now_playing: Option<QueuedSong>,
+    pub pending: Vec<QueuedSong>,
+}
+
+/// Outcome of `Jukebox::enqueue`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueued {
+    /// Position in the queue (1 = next); `start_player` when no player runs yet
+    Queued {
+        position: usize,
+        start_player: bool,
+    },
+    Full,
+}
+
+#[derive(Debug, Default)]
+pub struct Jukebox {
+    chats: Mutex<HashMap<i64, ChatQueue>>,
+}
+
+impl Jukebox {
+    pub async fn enqueue(&self, chat_id: i64, song: QueuedSong) -> Enqueued {
+        let mut chats = self.chats.lock().await;
+        let queue = chats.entry(chat_id).or_default();
+        if queue.pending.len() >= MAX_QUEUE {
+            return Enqueued::Full;
+        }
+        queue.pending.push_back(song);
+        let start_player = !queue.player_running;
+        queue.player_running = true;
+        Enqueued::Queued {
+            position: queue.pending.len(),
+            start_player,
+        }
+    }
+
+    /// Move the next song to "now playing"; `None` stops the player
+    pub async fn advance(&self, chat_id: i64) -> Option<QueuedSong> {
+        let mut chats = self.chats.lock().await;
+        let queue = chats.get_mut(&chat_id)?;
+        queue.now_playing = queue.pending.pop_front();
+        if queue.now_playing.is_none() {
+            chats.remove(&chat_id);
+            return None;
+        }
+        queue.now_playing.clone()
+    }
+
+    /// Signalled by `/skip` to end the current song's turn early
+    pub async fn skip_signal(&self, chat_id: i64) -> Arc<Notify> {
+        let mut chats = self.chats.lock().await;
+        chats.entry(chat_id).or_default().skip.clone()
+    }
+
+    /// Skip the current song, returning it
+    pub async fn skip(&self, chat_id: i64) -> Option<QueuedSong> {
+        let chats = self.chats.lock().await;
+        let queue = chats.get(&chat_id)?;
+        let current = queue.now_playing.clone()?;
+        queue.skip.notify_one();
+        Some(current)
+    }
+
+    pub async fn view(&self, chat_id: i64) -> QueueView {
+        let chats = self.chats.lock().await;
+        match chats.get(&chat_id) {
+            Some(queue) => QueueView {
+                now_playing: queue.now_playing.clone(),
+                pending: queue.pending.iter().cloned().collect(),
+            },
+            None => QueueView {
+                now_playing: None,
+                pending: Vec::new(),
+            },
+        }
+    }
+
+    /// Drop a chat's queue (`/jukebox off`); a running player stops after
+    /// the current song
+    pub async fn clear(&self, chat_id: i64) {
+        let mut chats = self.chats.lock().await;
+        if let Some(queue) = chats.get_mut(&chat_id) {
+            queue.pending.clear();
+            queue.skip.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::Message;
+
+    use super::{Enqueued, Jukebox, QueuedSong};
+
+    fn song(music_id: u64) -> QueuedSong {
+        let request: Message = serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": -100, "type": "supergroup", "title": "group" },
+            "text": "/queue"
+        }))
+        .unwrap();
+        QueuedSong {
+            music_id,
+            title: format!("song {music_id}"),
+            requested_by: "user".to_string(),
+            requester_id: 1,
+            request,
+        }
+    }
+
+    #[tokio::test]
+    async fn plays_songs_in_request_order() {
+        let jukebox = Jukebox::default();
+        assert_eq!(
+            jukebox.enqueue(-100, song(1)).await,
+            Enqueued::Queued {
+                position: 1,
+                start_player: true
+            }
+        );
+        assert_eq!(
+            jukebox.enqueue(-100, song(2)).await,
+            Enqueued::Queued {
+                position: 2,
+                start_player: false
+            }
+        );
+
+        assert_eq!(jukebox.advance(-100).await.unwrap().music_id, 1);
+        let view = jukebox.view(-100).await;
+        assert_eq!(view.now_playing.unwrap().music_id, 1);
+        assert_eq!(view.pending.len(), 1);
+        assert_eq!(jukebox.skip(-100).await.unwrap().music_id, 1);
+
+        assert_eq!(jukebox.advance(-100).await.unwrap().music_id, 2);
+        assert!(jukebox.advance(-100).await.is_none());
+
+        // The player stopped, so the next request starts a new one
+        assert_eq!(
+            jukebox.enqueue(-100, song(3)).await,
+            Enqueued::Queued {
+                position: 1,
+                start_player: true
+            }
+        );
+    }
+}
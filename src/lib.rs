@@ -0,0 +1,89 @@
+//! NetEase Cloud Music downloader and Telegram bot
+//!
+//! The binary runs the bot; the library exposes the pieces it is built from so
+//! other frontends can reuse the NetEase handling:
+//!
+//! - [`music_api::MusicApi`] - song details, download URLs, lyrics, search
+//! - [`download::download_song`] - fetch, verify and tag one song into a directory
+//! - [`audio_buffer::AudioBuffer`] - disk/memory buffers with ID3 and FLAC tagging
+//! - [`database::Database`] - the SQLite song cache
+//! - [`config::Config`] - `config.ini` parsing
+//!
+//! ```no_run
+//! use music163bot_rust::config::Config;
+//! use music163bot_rust::download::download_song;
+//! use music163bot_rust::music_api::MusicApi;
+//!
+//! # async fn example() -> music163bot_rust::error::Result<()> {
+//! let config = Config::load("config.ini")?;
+//! let api = MusicApi::new_with_config(&config);
+//! let song = api.get_song_detail(1_234_567).await?;
+//! let path = download_song(&api, song.id, std::path::Path::new("./music"), true).await?;
+//! println!("{} -> {}", song.name, path.display());
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(clippy::all, clippy::pedantic)]
+// Allow certain pedantic warnings that are acceptable for this codebase
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::too_many_lines,
+    clippy::struct_excessive_bools,
+    clippy::doc_markdown,
+    clippy::needless_pass_by_value
+)]
+
+pub mod alerting;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod api_endpoints;
+pub mod audio_buffer;
+pub mod backup;
+pub mod bot;
+pub mod cache_dump;
+pub mod card;
+pub mod checkin;
+pub mod cli;
+pub mod commands;
+pub mod config;
+#[cfg(feature = "api")]
+pub mod dashboard;
+pub mod database;
+pub mod download;
+pub mod download_lanes;
+pub mod error;
+pub mod health;
+pub mod hot_cache;
+pub mod http_client;
+pub mod i18n;
+pub mod inline_previews;
+pub mod jukebox;
+pub mod library;
+pub mod logging;
+pub mod lyric;
+pub mod memory;
+pub mod migrate;
+pub mod music_api;
+#[cfg(feature = "s3")]
+pub mod object_store;
+pub mod pipeline;
+pub mod quiz;
+pub mod scrobble;
+pub mod secrets;
+pub mod selftest;
+pub mod status_tracker;
+pub mod systemd;
+pub mod telegraph;
+pub mod throughput;
+pub mod transcode;
+pub mod updater;
+pub mod upgrade;
+pub mod utils;
+pub mod webdav;
+pub mod worker_pool;
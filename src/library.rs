@@ -0,0 +1,166 @@
+//! Download-only mode: `/fetch` into `download.library_dir` or a WebDAV share
+//!
+//! Songs are tagged exactly like uploads but written to
+//! `<library>/<Artist>/<Album>/<Artist - Title>.<ext>` instead of being sent
+//! to Telegram, for operators who keep a local music library. With
+//! `webdav.url` set, the same layout is created on the share instead.
+
+use std::path::Path;
+
+use tokio_util::io::ReaderStream;
+
+use crate::audio_buffer::AudioBuffer;
+use crate::bot::BotState;
+use crate::download_lanes::{self, Lane};
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::music_api::SongDetail;
+use crate::pipeline::{self, CoverPolicy, JobFiles, Source};
+use crate::utils::clean_filename;
+use crate::webdav::WebDav;
+
+/// Library files are for players, so the cover is always embedded
+const LIBRARY_COVERS: CoverPolicy = CoverPolicy {
+    download_original: true,
+    download_thumbnail: false,
+    embed_cover: true,
+};
+
+/// What `/fetch` did with one song, with where the file is
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fetched {
+    Saved(String),
+    /// The file was already in the library
+    Exists(String),
+}
+
+/// Folders of a song in the library: `[<Artist>, <Album>]`
+#[must_use]
+pub fn song_folders(song_detail: &SongDetail) -> [String; 2] {
+    let artist = song_detail
+        .ar
+        .as_deref()
+        .and_then(<[_]>::first)
+        .map(|artist| artist.name.as_str());
+    let album = song_detail.al.as_ref().map(|album| album.name.as_str());
+    [
+        folder_name(artist, "Unknown Artist"),
+        folder_name(album, "Unknown Album"),
+    ]
+}
+
+fn folder_name(name: Option<&str>, fallback: &str) -> String {
+    let name = clean_filename(name.unwrap_or_default());
+    // "." and ".." would escape the layout
+    if name.trim_matches('.').is_empty() {
+        fallback.to_string()
+    } else {
+        name
+    }
+}
+
+/// Download and tag one song into the library
+pub async fn fetch_song(state: &BotState, music_id: u64) -> Result<Fetched> {
+    let song_url = state.music_api.get_best_song_url(music_id).await?;
+    if song_url.url.is_empty() || song_url.trial_secs().is_some() {
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Unavailable,
+            "no full-length download URL",
+        ));
+    }
+    let song_detail = state.music_api.get_song_detail(music_id).await?;
+    let source = Source {
+        song_detail: &song_detail,
+        song_url: &song_url,
+        preview: false,
+    };
+    match &state.webdav {
+        Some(webdav) => fetch_to_webdav(state, webdav, &source).await,
+        None => fetch_to_disk(state, &source).await,
+    }
+}
+
+async fn fetch_to_disk(state: &BotState, source: &Source<'_>) -> Result<Fetched> {
+    let [artist, album] = song_folders(source.song_detail);
+    let dir = Path::new(&state.config.library_dir)
+        .join(artist)
+        .join(album);
+    let dest = dir.join(source.filename());
+    let location = dest.display().to_string();
+    if tokio::fs::try_exists(&dest).await.unwrap_or(false) {
+        return Ok(Fetched::Exists(location));
+    }
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let audio = prepare(state, source).await?;
+    audio.save_to(&dest).await?;
+    tracing::info!("Saved music_id {} to {}", source.song_detail.id, location);
+    Ok(Fetched::Saved(location))
+}
+
+async fn fetch_to_webdav(
+    state: &BotState,
+    webdav: &WebDav,
+    source: &Source<'_>,
+) -> Result<Fetched> {
+    let [artist, album] = song_folders(source.song_detail);
+    let filename = source.filename();
+    let segments = [artist.as_str(), album.as_str(), filename.as_str()];
+    let location = segments.join("/");
+    if webdav.exists(&segments).await? {
+        return Ok(Fetched::Exists(location));
+    }
+    webdav.create_dirs(&segments[..2]).await?;
+
+    let audio = prepare(state, source).await?;
+    let len = audio.size().await;
+    // Disk files are streamed; the other storage modes already hold the bytes
+    let body = match audio.path() {
+        Some(path) => {
+            reqwest::Body::wrap_stream(ReaderStream::new(tokio::fs::File::open(path).await?))
+        }
+        None => reqwest::Body::from(audio.get_data().await?),
+    };
+    let uploaded = webdav.put(&segments, body, len).await;
+    audio.cleanup().await.ok();
+    uploaded?;
+    tracing::info!(
+        "Uploaded music_id {} to WebDAV: {}",
+        source.song_detail.id,
+        location
+    );
+    Ok(Fetched::Saved(location))
+}
+
+async fn prepare(state: &BotState, source: &Source<'_>) -> Result<AudioBuffer> {
+    let _permit = download_lanes::acquire(&state.download_semaphore, Lane::Bulk).await;
+    let files = JobFiles::default();
+    let prepared = pipeline::prepare(state, source, LIBRARY_COVERS, &files).await?;
+    Ok(prepared.tagged.audio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::song_folders;
+    use crate::music_api::{Album, Artist, SongDetail};
+
+    #[test]
+    fn lays_out_artist_and_album_folders() {
+        let mut song = SongDetail {
+            id: 1,
+            name: "Song".to_string(),
+            dt: None,
+            ar: Some(vec![Artist {
+                id: 2,
+                name: "AC/DC".to_string(),
+            }]),
+            al: Some(Album {
+                id: 3,
+                name: "..".to_string(),
+                pic_url: None,
+            }),
+        };
+        assert_eq!(song_folders(&song), ["AC DC", "Unknown Album"]);
+        song.ar = None;
+        assert_eq!(song_folders(&song)[0], "Unknown Artist");
+    }
+}
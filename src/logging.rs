@@ -0,0 +1,327 @@
+//! Tracing subscriber setup driven by the `[log]` config section
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+use crate::config::{Config, LogFormat, LogRotation};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Level filter from `RUST_LOG`, falling back to `default_level` and then `info`
+#[must_use]
+pub fn env_filter(default_level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(default_level))
+        .unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// Build an fmt layer in the configured format writing to `writer`
+fn fmt_layer<W>(format: LogFormat, writer: W) -> BoxedLayer
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.with_target(false).boxed(),
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+/// Keeps background log writers alive; flushes them when dropped
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    #[cfg(feature = "otel")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    #[cfg(feature = "sentry")]
+    _sentry: Option<sentry::ClientInitGuard>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.tracer_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+/// Install the global subscriber
+///
+/// JSON output flattens event fields (`music_id`, `chat_id`, `duration_ms`,
+/// `bytes`, ...) to the top level and includes the current span plus the list
+/// of enclosing spans, so the per-update `request_id` appears on every line.
+/// The returned guard must be kept alive for the whole run so buffered file
+/// lines and trace spans are flushed on exit.
+pub fn init(config: &Config, default_level: &str) -> anyhow::Result<LogGuard> {
+    let mut layers = vec![fmt_layer(config.log_format, io::stdout)];
+    let mut file_guard = None;
+
+    if !config.log_file.is_empty() {
+        let (writer, worker_guard) = tracing_appender::non_blocking(open_log_writer(config)?);
+        layers.push(fmt_layer(config.log_format, writer));
+        file_guard = Some(worker_guard);
+    }
+
+    #[cfg(feature = "otel")]
+    let mut tracer_provider = None;
+    #[cfg(feature = "otel")]
+    if !config.otel_endpoint.is_empty() {
+        let (layer, provider) = otel_layer(config)?;
+        layers.push(layer);
+        tracer_provider = Some(provider);
+    }
+
+    #[cfg(feature = "sentry")]
+    let mut sentry_guard = None;
+    #[cfg(feature = "sentry")]
+    if !config.sentry_dsn.is_empty() {
+        let (layer, guard) = sentry_layer(config);
+        layers.push(layer);
+        sentry_guard = Some(guard);
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(env_filter(default_level))
+        .try_init()?;
+
+    #[cfg(not(feature = "otel"))]
+    if !config.otel_endpoint.is_empty() {
+        tracing::warn!("otel.endpoint is set but the binary was built without the `otel` feature");
+    }
+    #[cfg(not(feature = "sentry"))]
+    if !config.sentry_dsn.is_empty() {
+        tracing::warn!("sentry.dsn is set but the binary was built without the `sentry` feature");
+    }
+
+    Ok(LogGuard {
+        _file: file_guard,
+        #[cfg(feature = "otel")]
+        tracer_provider,
+        #[cfg(feature = "sentry")]
+        _sentry: sentry_guard,
+    })
+}
+
+/// Export spans to an OTLP/HTTP collector (Jaeger, Tempo, ...)
+#[cfg(feature = "otel")]
+fn otel_layer(
+    config: &Config,
+) -> anyhow::Result<(BoxedLayer, opentelemetry_sdk::trace::SdkTracerProvider)> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let endpoint = config.otel_endpoint.clone();
+    // The blocking HTTP client must not be created inside the async runtime
+    let exporter = std::thread::spawn(move || {
+        opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("OTLP exporter initialization panicked"))??;
+
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(config.otel_service_name.clone())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer("music163bot-rust");
+    let layer = tracing_opentelemetry::layer().with_tracer(tracer).boxed();
+
+    Ok((layer, provider))
+}
+
+fn open_log_writer(config: &Config) -> anyhow::Result<Box<dyn Write + Send>> {
+    let path = Path::new(&config.log_file);
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+
+    match config.log_rotation {
+        LogRotation::Daily => {
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow::anyhow!("Invalid log file path: {}", config.log_file))?;
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(file_name)
+                .max_log_files(config.log_max_files.max(1))
+                .build(dir)?;
+            Ok(Box::new(appender))
+        }
+        LogRotation::Size => Ok(Box::new(SizeRotatingFile::open(
+            path,
+            config.log_max_size_mb.max(1) * 1024 * 1024,
+            config.log_max_files,
+        )?)),
+    }
+}
+
+/// Report panics and error-level events to Sentry
+///
+/// Warnings and info lines become breadcrumbs; fields of enclosing spans
+/// (`music_id`, `chat_id`, `request_id`) are attached to each event. The bot
+/// token and MUSIC_U are scrubbed before anything leaves the process.
+#[cfg(feature = "sentry")]
+fn sentry_layer(config: &Config) -> (BoxedLayer, sentry::ClientInitGuard) {
+    let secrets: Vec<String> = std::iter::once(config.bot_token.clone())
+        .chain(config.music_u.clone())
+        .filter(|secret| !secret.is_empty())
+        .collect();
+
+    let guard = sentry::init((
+        config.sentry_dsn.as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: (!config.sentry_environment.is_empty())
+                .then(|| config.sentry_environment.clone().into()),
+            send_default_pii: false,
+            before_send: Some(std::sync::Arc::new(move |event| {
+                Some(redact_event(event, &secrets))
+            })),
+            ..Default::default()
+        },
+    ));
+
+    let layer = sentry::integrations::tracing::layer()
+        .enable_span_attributes()
+        .boxed();
+    (layer, guard)
+}
+
+/// Replace every occurrence of `secrets` anywhere in the event
+#[cfg(feature = "sentry")]
+fn redact_event(
+    event: sentry::protocol::Event<'static>,
+    secrets: &[String],
+) -> sentry::protocol::Event<'static> {
+    let Ok(mut json) = serde_json::to_string(&event) else {
+        return event;
+    };
+    for secret in secrets {
+        json = json.replace(secret.as_str(), "[redacted]");
+    }
+    serde_json::from_str(&json).unwrap_or(event)
+}
+
+/// Log file that is renamed to `<file>.1` (shifting older ones to `.2`, ...)
+/// once it grows past `max_bytes`
+pub struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingFile {
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.backup_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.backup_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.backup_path(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SizeRotatingFile;
+    use std::io::Write;
+
+    #[test]
+    fn size_rotation_keeps_limited_backups() {
+        let dir = std::env::temp_dir().join(format!("music163bot-log-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bot.log");
+
+        let mut writer = SizeRotatingFile::open(&path, 10, 2).unwrap();
+        for line in [
+            "first-line\n",
+            "second-line\n",
+            "third-line\n",
+            "fourth-line\n",
+        ] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("bot.log"), "fourth-line\n");
+        assert_eq!(read("bot.log.1"), "third-line\n");
+        assert_eq!(read("bot.log.2"), "second-line\n");
+        assert!(!dir.join("bot.log.3").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,381 @@
+//! LRC lyric processing for `/lyric`: parsing, timestamp shifting and
+//! plain-text output
+//!
+//! NetEase lyrics are LRC with optional metadata tags (`[ar:...]`) and JSON
+//! credit lines (`{"t":0,"c":[{"tx":"作词: "},{"tx":"..."}]}`), which are
+//! turned into regular timed lines here. Word-level `yrc` lyrics
+//! (`[start,duration](start,duration,0)word...`) are converted to enhanced
+//! LRC (`[mm:ss.xxx]<mm:ss.xxx>word...`) for karaoke display.
+
+use serde::Deserialize;
+
+/// A lyric line with the times (ms) it is sung at; untimed lines (metadata
+/// tags, free text) have no times and are kept verbatim
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcLine {
+    pub times: Vec<u64>,
+    pub text: String,
+}
+
+/// Output file type of `/lyric`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricFormat {
+    Lrc,
+    Txt,
+}
+
+impl LyricFormat {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Lrc => "lrc",
+            Self::Txt => "txt",
+        }
+    }
+}
+
+/// `/lyric` flags: `--plain`, `--offset=<±ms>`, `--lrc` / `--txt`, `--karaoke`,
+/// `--telegraph`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricOptions {
+    /// Strip timestamps and metadata, leaving only the sung text
+    pub plain: bool,
+    /// Added to every timestamp (negative = earlier)
+    pub offset_ms: i64,
+    pub format: LyricFormat,
+    /// Prefer word-level timestamps (enhanced LRC) when the song has `yrc` lyrics
+    pub karaoke: bool,
+    /// Publish the plain text to telegra.ph instead of sending a file
+    pub telegraph: bool,
+}
+
+impl Default for LyricOptions {
+    fn default() -> Self {
+        Self {
+            plain: false,
+            offset_ms: 0,
+            format: LyricFormat::Lrc,
+            karaoke: false,
+            telegraph: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditLine {
+    t: u64,
+    c: Vec<CreditSegment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditSegment {
+    tx: String,
+}
+
+/// Parse `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx` into milliseconds
+fn parse_time(tag: &str) -> Option<u64> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let seconds: u64 = seconds.parse().ok()?;
+    if seconds >= 60 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<u64>().ok()? * 100,
+        2 => fraction.parse::<u64>().ok()? * 10,
+        _ => fraction[..3].parse().ok()?,
+    };
+    Some(minutes * 60_000 + seconds * 1000 + millis)
+}
+
+fn format_stamp(ms: u64) -> String {
+    format!("{:02}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+/// Format milliseconds as an LRC timestamp (`[mm:ss.xxx]`)
+#[must_use]
+pub fn format_time(ms: u64) -> String {
+    format!("[{}]", format_stamp(ms))
+}
+
+/// Format milliseconds as an enhanced LRC word timestamp (`<mm:ss.xxx>`)
+#[must_use]
+pub fn format_word_time(ms: u64) -> String {
+    format!("<{}>", format_stamp(ms))
+}
+
+fn parse_line(line: &str) -> LrcLine {
+    if line.starts_with('{')
+        && let Ok(credit) = serde_json::from_str::<CreditLine>(line)
+    {
+        return LrcLine {
+            times: vec![credit.t],
+            text: credit.c.into_iter().map(|segment| segment.tx).collect(),
+        };
+    }
+
+    let mut times = Vec::new();
+    let mut rest = line;
+    while let Some(tag) = rest.strip_prefix('[')
+        && let Some((tag, after)) = tag.split_once(']')
+        && let Some(time) = parse_time(tag)
+    {
+        times.push(time);
+        rest = after;
+    }
+    LrcLine {
+        text: if times.is_empty() { line } else { rest }
+            .trim()
+            .to_string(),
+        times,
+    }
+}
+
+/// Parse LRC text into lines, in their original order
+#[must_use]
+pub fn parse_lrc(lrc: &str) -> Vec<LrcLine> {
+    lrc.lines()
+        .map(|line| parse_line(line.trim_end_matches('\r')))
+        .filter(|line| !line.times.is_empty() || !line.text.is_empty())
+        .collect()
+}
+
+/// Move every timestamp by `offset_ms`, clamping at 0
+pub fn shift(lines: &mut [LrcLine], offset_ms: i64) {
+    for time in lines.iter_mut().flat_map(|line| line.times.iter_mut()) {
+        *time = time.saturating_add_signed(offset_ms);
+    }
+}
+
+#[must_use]
+pub fn to_lrc(lines: &[LrcLine]) -> String {
+    let mut lrc = String::new();
+    for line in lines {
+        for &time in &line.times {
+            lrc.push_str(&format_time(time));
+        }
+        lrc.push_str(&line.text);
+        lrc.push('\n');
+    }
+    lrc
+}
+
+/// Only the sung text: timed lines without their timestamps, no metadata tags
+#[must_use]
+pub fn to_plain_text(lines: &[LrcLine]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        if !line.times.is_empty() && !line.text.is_empty() {
+            text.push_str(&line.text);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Apply `/lyric` options to raw LRC text
+#[must_use]
+pub fn render(lrc: &str, options: &LyricOptions) -> String {
+    let mut lines = parse_lrc(lrc);
+    shift(&mut lines, options.offset_ms);
+    if options.plain {
+        to_plain_text(&lines)
+    } else {
+        to_lrc(&lines)
+    }
+}
+
+/// Parse a `yrc` word timing (`start,duration,0`) into start and end (ms)
+fn parse_word_timing(timing: &str) -> Option<(u64, u64)> {
+    let mut fields = timing.split(',');
+    let start: u64 = fields.next()?.parse().ok()?;
+    let duration: u64 = fields.next()?.parse().ok()?;
+    fields.next()?.parse::<u64>().ok()?;
+    Some((start, start + duration))
+}
+
+/// Convert one timed `yrc` line to enhanced LRC, ending with the end time of
+/// the last word
+fn yrc_line_to_enhanced(line: &str, offset_ms: i64) -> Option<String> {
+    let (header, mut rest) = line.strip_prefix('[')?.split_once(']')?;
+    let start: u64 = header.split_once(',')?.0.parse().ok()?;
+
+    let mut output = format_time(start.saturating_add_signed(offset_ms));
+    let mut end = None;
+    while let Some(timing) = rest.strip_prefix('(') {
+        let (timing, after) = timing.split_once(')')?;
+        let (word_start, word_end) = parse_word_timing(timing)?;
+        // Words may contain parentheses themselves, so the text runs up to the
+        // next valid timing
+        let text_len = after
+            .match_indices('(')
+            .map(|(index, _)| index)
+            .find(|&index| {
+                after[index + 1..]
+                    .split_once(')')
+                    .is_some_and(|(timing, _)| parse_word_timing(timing).is_some())
+            })
+            .unwrap_or(after.len());
+        output.push_str(&format_word_time(
+            word_start.saturating_add_signed(offset_ms),
+        ));
+        output.push_str(&after[..text_len]);
+        end = Some(word_end);
+        rest = &after[text_len..];
+    }
+    if let Some(end) = end {
+        output.push_str(&format_word_time(end.saturating_add_signed(offset_ms)));
+    }
+    Some(output)
+}
+
+/// Convert word-level `yrc` lyrics to enhanced LRC, moving every timestamp by
+/// `offset_ms`; credit lines become regular timed lines
+#[must_use]
+pub fn yrc_to_enhanced_lrc(yrc: &str, offset_ms: i64) -> String {
+    let mut lrc = String::new();
+    for line in yrc.lines().map(|line| line.trim_end_matches('\r')) {
+        if line.starts_with('{') {
+            let mut credit = [parse_line(line)];
+            shift(&mut credit, offset_ms);
+            lrc.push_str(&to_lrc(&credit));
+        } else if let Some(line) = yrc_line_to_enhanced(line, offset_ms) {
+            lrc.push_str(&line);
+            lrc.push('\n');
+        }
+    }
+    lrc
+}
+
+/// Split `/lyric` flags off the arguments, returning the remaining arguments
+/// (song ID or keywords) and the options
+///
+/// `--plain` also switches the output to `.txt` unless `--lrc` is given.
+pub fn split_lyric_flags(args: &str) -> Result<(String, LyricOptions), String> {
+    let mut options = LyricOptions::default();
+    let mut format = None;
+    let mut rest = Vec::new();
+    for word in args.split_whitespace() {
+        if let Some(offset) = word.strip_prefix("--offset=") {
+            options.offset_ms = offset
+                .trim_start_matches('+')
+                .parse()
+                .map_err(|_| format!("无效的偏移量: {offset} (单位为毫秒，如 --offset=-500)"))?;
+        } else {
+            match word {
+                "--plain" => options.plain = true,
+                "--lrc" => format = Some(LyricFormat::Lrc),
+                "--txt" => format = Some(LyricFormat::Txt),
+                "--karaoke" => options.karaoke = true,
+                "--telegraph" => options.telegraph = true,
+                _ => rest.push(word),
+            }
+        }
+    }
+    options.format = format.unwrap_or(if options.plain {
+        LyricFormat::Txt
+    } else {
+        LyricFormat::Lrc
+    });
+    Ok((rest.join(" "), options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        LrcLine, LyricFormat, LyricOptions, format_time, parse_lrc, parse_time, render,
+        split_lyric_flags, yrc_to_enhanced_lrc,
+    };
+
+    const SAMPLE: &str = "[ar:周杰伦]\n\
+        {\"t\":0,\"c\":[{\"tx\":\"作词: \"},{\"tx\":\"方文山\"}]}\n\
+        [00:12.34]故事的小黄花\n\
+        [00:15.5][01:20.120]从出生那年就飘着\n\
+        \n\
+        [00:20.00]\n";
+
+    #[test]
+    fn parses_lrc_timestamps() {
+        assert_eq!(parse_time("00:12.34"), Some(12_340));
+        assert_eq!(parse_time("01:20.120"), Some(80_120));
+        assert_eq!(parse_time("00:15.5"), Some(15_500));
+        assert_eq!(parse_time("02:03"), Some(123_000));
+        assert_eq!(parse_time("ar:周杰伦"), None);
+        assert_eq!(format_time(80_120), "[01:20.120]");
+    }
+
+    #[test]
+    fn parses_lines_tags_and_credits() {
+        let lines = parse_lrc(SAMPLE);
+        assert_eq!(
+            lines[0],
+            LrcLine {
+                times: Vec::new(),
+                text: "[ar:周杰伦]".to_string()
+            }
+        );
+        assert_eq!(lines[1].times, vec![0]);
+        assert_eq!(lines[1].text, "作词: 方文山");
+        assert_eq!(lines[3].times, vec![15_500, 80_120]);
+        assert_eq!(lines[3].text, "从出生那年就飘着");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn shifts_and_strips_timestamps() {
+        let shifted = render(
+            SAMPLE,
+            &LyricOptions {
+                offset_ms: -500,
+                ..LyricOptions::default()
+            },
+        );
+        assert!(shifted.contains("[00:00.000]作词: 方文山\n"));
+        assert!(shifted.contains("[00:11.840]故事的小黄花\n"));
+        assert!(shifted.contains("[00:15.000][01:19.620]从出生那年就飘着\n"));
+
+        let plain = render(
+            SAMPLE,
+            &LyricOptions {
+                plain: true,
+                ..LyricOptions::default()
+            },
+        );
+        assert_eq!(plain, "作词: 方文山\n故事的小黄花\n从出生那年就飘着\n");
+    }
+
+    #[test]
+    fn converts_yrc_to_enhanced_lrc() {
+        let yrc = "{\"t\":0,\"c\":[{\"tx\":\"作词: \"},{\"tx\":\"方文山\"}]}\n\
+            [16210,3460](16210,670,0)故事(16880,410,0)的(17290,2380,0)(小)黄花\n\
+            \n\
+            not a yrc line\n";
+        assert_eq!(
+            yrc_to_enhanced_lrc(yrc, 0),
+            "[00:00.000]作词: 方文山\n\
+             [00:16.210]<00:16.210>故事<00:16.880>的<00:17.290>(小)黄花<00:19.670>\n"
+        );
+        assert!(yrc_to_enhanced_lrc(yrc, -210).contains("[00:16.000]<00:16.000>故事"));
+    }
+
+    #[test]
+    fn splits_lyric_flags() {
+        let (rest, options) = split_lyric_flags("晴天 --plain --offset=+300").unwrap();
+        assert_eq!(rest, "晴天");
+        assert!(options.plain);
+        assert_eq!(options.offset_ms, 300);
+        assert_eq!(options.format, LyricFormat::Txt);
+
+        let (_, options) = split_lyric_flags("186016 --plain --lrc").unwrap();
+        assert_eq!(options.format, LyricFormat::Lrc);
+        assert!(!options.telegraph);
+        assert!(split_lyric_flags("晴天 --telegraph").unwrap().1.telegraph);
+        assert!(split_lyric_flags("晴天 --karaoke").unwrap().1.karaoke);
+        assert!(split_lyric_flags("--offset=abc").is_err());
+    }
+}
@@ -21,65 +21,208 @@ use tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
-pub mod audio_buffer;
-pub mod bot;
-pub mod config;
-pub mod database;
-pub mod error;
-pub mod memory;
-pub mod music_api;
-pub mod utils;
-
 use anyhow::Result;
-use clap::Parser;
-use config::Config;
+use clap::{Parser, Subcommand};
+use music163bot_rust::config::{self, Config};
+use music163bot_rust::{bot, cli, database, logging, memory, migrate, utils};
 use tracing::info;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use tracing_subscriber::FmtSubscriber;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Configuration file path
-    #[arg(short, long, default_value = "config.ini")]
+    #[arg(short, long, default_value = "config.ini", global = true)]
     config: String,
 
     /// Disable update checks
-    #[arg(long)]
+    #[arg(long, global = true)]
     no_update: bool,
 
     /// Disable MD5 verification
-    #[arg(long)]
+    #[arg(long, global = true)]
     no_md5_check: bool,
 
-    /// Log level
-    #[arg(long, default_value = "info")]
-    log_level: String,
+    /// Log level (overrides `loglevel`, default info)
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+
+    /// Telegram bot token (overrides `bot.token`)
+    #[arg(long, global = true, value_name = "TOKEN")]
+    bot_token: Option<String>,
+
+    /// NetEase MUSIC_U cookie (overrides `music.music_u`)
+    #[arg(long, global = true, value_name = "COOKIE")]
+    music_u: Option<String>,
+
+    /// Database file (overrides `database.url`)
+    #[arg(long, global = true, value_name = "PATH")]
+    database: Option<String>,
+
+    /// Download directory (overrides `download.dir`)
+    #[arg(long, global = true, value_name = "DIR")]
+    download_dir: Option<String>,
+
+    /// Temporary file storage (overrides `download.storage_mode`)
+    #[arg(long, global = true, value_parser = ["disk", "memory", "hybrid"])]
+    storage_mode: Option<String>,
+
+    /// Maximum concurrent downloads (overrides `download.max_concurrent`)
+    #[arg(long, global = true, value_name = "N")]
+    max_concurrent: Option<u32>,
+
+    /// Proxy for all outgoing requests (overrides `network.proxy`)
+    #[arg(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Override any config key, e.g. `--set ffmpeg.enabled=true` (repeatable)
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+
+    /// Import the song cache of a Music163bot-Go database before starting
+    #[arg(long, value_name = "PATH")]
+    migrate_from_go: Option<String>,
+
+    /// Validate the configuration file and exit (same as `check-config`)
+    #[arg(long)]
+    check_config: bool,
+}
+
+impl Args {
+    /// Config values given on the command line, applied over the config file
+    fn config_overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = self.overrides.clone();
+        for (key, value) in [
+            ("loglevel", self.log_level.clone()),
+            ("bot.token", self.bot_token.clone()),
+            ("music.music_u", self.music_u.clone()),
+            ("database.url", self.database.clone()),
+            ("download.dir", self.download_dir.clone()),
+            ("download.storage_mode", self.storage_mode.clone()),
+            (
+                "download.max_concurrent",
+                self.max_concurrent.map(|n| n.to_string()),
+            ),
+            ("network.proxy", self.proxy.clone()),
+            ("autoupdate", self.no_update.then(|| "false".to_string())),
+            ("checkmd5", self.no_md5_check.then(|| "false".to_string())),
+        ] {
+            if let Some(value) = value {
+                overrides.push((key.to_string(), value));
+            }
+        }
+        overrides
+    }
+}
+
+fn parse_override(arg: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{arg}`"))?;
+    Ok((key.trim().to_lowercase(), value.trim().to_string()))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the Telegram bot (default)
+    Run,
+    /// Download a song with tags and cover, without starting the bot
+    Download {
+        /// NetEase song ID or link
+        id: String,
+        /// Output directory
+        #[arg(short, long, default_value = ".")]
+        output: std::path::PathBuf,
+    },
+    /// Search NetEase and print matching song IDs
+    Search {
+        /// Search keywords
+        #[arg(required = true)]
+        keyword: Vec<String>,
+        /// Maximum number of results
+        #[arg(short, long, default_value_t = 10)]
+        limit: u32,
+    },
+    /// Validate the configuration file and exit
+    CheckConfig,
+    /// Print a commented config.ini with every key at its default value
+    GenConfig,
+    /// Check Telegram, NetEase and the database, exiting 0 when all are reachable
+    Healthcheck,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    // Setup logging
-    let filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(&args.log_level))
-        .unwrap_or_else(|_| EnvFilter::new("info"));
-
-    let subscriber = FmtSubscriber::builder()
-        .with_env_filter(filter)
+    if matches!(args.command, Some(Command::GenConfig)) {
+        print!("{}", config::template());
+        return Ok(());
+    }
+
+    let overrides = args.config_overrides();
+
+    // Before Config::load, which stops at the first fatal problem
+    if args.check_config || matches!(args.command, Some(Command::CheckConfig)) {
+        cli::check_config(&args.config, &overrides).await?;
+        return Ok(());
+    }
+
+    // Load configuration before installing the subscriber so logging can honor
+    // the [log] section; messages emitted while loading use a temporary one
+    let load_subscriber = FmtSubscriber::builder()
+        .with_env_filter(logging::env_filter(
+            args.log_level.as_deref().unwrap_or("info"),
+        ))
         .with_target(false)
         .finish();
-
-    tracing::subscriber::set_global_default(subscriber)?;
+    let config = tracing::subscriber::with_default(load_subscriber, || {
+        Config::load_with_overrides(&args.config, &overrides)
+    })?;
+
+    // Keep the guard alive so the file logger flushes on exit
+    let _log_guard = logging::init(&config, &config.log_level)?;
+
+    match args.command.unwrap_or(Command::Run) {
+        Command::Run | Command::CheckConfig | Command::GenConfig => {}
+        Command::Download { id, output } => {
+            let music_id = utils::parse_music_id(&id)
+                .ok_or_else(|| anyhow::anyhow!("Invalid song ID or link: {id}"))?;
+            let path = cli::download(&config, music_id, &output).await?;
+            println!("{}", path.display());
+            return Ok(());
+        }
+        Command::Search { keyword, limit } => {
+            cli::search(&config, &keyword.join(" "), limit).await?;
+            return Ok(());
+        }
+        Command::Healthcheck => {
+            if !cli::healthcheck(&config).await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
 
     info!("Music163bot-Rust starting...");
-
-    // Load configuration
-    let config = Config::load(&args.config)?;
     info!("Configuration loaded from {}", args.config);
 
+    memory::apply_tuning(&config);
+
+    if let Some(path) = &args.migrate_from_go {
+        let database = database::Database::new(&config).await?;
+        let (read, added) = migrate::migrate_from_go(path, &database).await?;
+        info!(
+            "Migrated Music163bot-Go database {}: {} songs read, {} imported",
+            path, read, added
+        );
+    }
+
     // Start the bot
-    bot::run(config).await?;
+    Box::pin(bot::run(config)).await?;
 
     Ok(())
 }
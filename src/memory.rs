@@ -2,6 +2,88 @@
 //!
 //! Provides functions to force memory release and reduce process footprint.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::audio_buffer::{self, AudioBuffer};
+use crate::bot::BotState;
+use crate::config::Config;
+
+/// Download activity used to release memory once the bot goes quiet
+#[derive(Debug, Default)]
+pub struct ActivityTracker {
+    /// Unix timestamp (ms) of the last download or upload
+    last_activity_ms: AtomicI64,
+    /// Set by activity, cleared when memory is released
+    pending_release: AtomicBool,
+    /// Signalled when the last in-flight upload finishes
+    uploads_drained: Notify,
+}
+
+impl ActivityTracker {
+    /// Record download/upload activity
+    pub fn touch(&self) {
+        self.last_activity_ms
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+        self.pending_release.store(true, Ordering::Relaxed);
+    }
+
+    /// Called when no uploads remain in flight, so large buffers are reclaimable
+    pub fn uploads_drained(&self) {
+        self.touch();
+        self.uploads_drained.notify_one();
+    }
+
+    /// Time elapsed since the last activity
+    #[must_use]
+    pub fn idle_for(&self) -> Duration {
+        let elapsed =
+            chrono::Utc::now().timestamp_millis() - self.last_activity_ms.load(Ordering::Relaxed);
+        Duration::from_millis(elapsed.max(0) as u64)
+    }
+}
+
+/// Release memory after `memory.idle_release_secs` without downloads and
+/// whenever the last in-flight upload finishes
+pub fn spawn_idle_release(state: Arc<BotState>) {
+    let idle = Duration::from_secs(state.config.memory_idle_release_secs);
+    if idle.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let activity = &state.activity;
+        loop {
+            let wait = idle
+                .saturating_sub(activity.idle_for())
+                .max(Duration::from_secs(1));
+            tokio::select! {
+                () = activity.uploads_drained.notified() => {}
+                () = tokio::time::sleep(wait) => {
+                    if activity.idle_for() < idle {
+                        continue;
+                    }
+                }
+            }
+
+            if state.upload_counters.in_flight.load(Ordering::Relaxed) > 0
+                || !activity.pending_release.swap(false, Ordering::Relaxed)
+            {
+                continue;
+            }
+
+            // Let finished tasks drop their buffers before purging
+            tokio::task::yield_now().await;
+            tracing::debug!("Releasing memory after idle period");
+            force_memory_release();
+            log_memory_stats();
+        }
+    });
+}
+
 /// Force jemalloc to release unused memory back to the OS
 ///
 /// This should be called periodically (e.g., after large operations)
@@ -38,43 +120,263 @@ pub fn force_memory_release() {
     // Windows uses system allocator, no explicit purge available
 }
 
-/// Report current memory usage stats (debug builds only)
-#[cfg(all(debug_assertions, not(target_env = "msvc")))]
-pub fn log_memory_stats() {
-    unsafe {
-        let mut epoch: u64 = 1;
-        let mut epoch_size = std::mem::size_of::<u64>();
-        let _ = tikv_jemalloc_sys::mallctl(
-            c"epoch".as_ptr().cast(),
-            (&raw mut epoch).cast(),
-            &raw mut epoch_size,
-            std::ptr::null_mut(),
-            0,
-        );
+/// Allocator statistics in bytes (see jemalloc `stats.*`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryStats {
+    /// Bytes allocated by the application
+    pub allocated: usize,
+    /// Bytes in active pages (allocated plus fragmentation)
+    pub active: usize,
+    /// Bytes in physically resident pages mapped by the allocator
+    pub resident: usize,
+    /// Bytes in active extents mapped by the allocator
+    pub mapped: usize,
+    /// Bytes retained (unmapped but kept in virtual memory for reuse)
+    pub retained: usize,
+}
 
-        let mut allocated: usize = 0;
-        let mut size = std::mem::size_of::<usize>();
-        let _ = tikv_jemalloc_sys::mallctl(
-            c"stats.allocated".as_ptr().cast(),
-            (&raw mut allocated).cast(),
+/// Read a jemalloc control value
+#[cfg(not(target_env = "msvc"))]
+fn mallctl_read<T: Copy + Default>(name: &std::ffi::CStr) -> std::io::Result<T> {
+    let mut value = T::default();
+    let mut size = std::mem::size_of::<T>();
+    let code = unsafe {
+        tikv_jemalloc_sys::mallctl(
+            name.as_ptr(),
+            (&raw mut value).cast(),
             &raw mut size,
             std::ptr::null_mut(),
             0,
-        );
+        )
+    };
+    if code == 0 {
+        Ok(value)
+    } else {
+        Err(std::io::Error::from_raw_os_error(code))
+    }
+}
 
-        let mut resident: usize = 0;
-        let _ = tikv_jemalloc_sys::mallctl(
-            c"stats.resident".as_ptr().cast(),
-            (&raw mut resident).cast(),
-            &raw mut size,
+/// Write a jemalloc control value
+#[cfg(not(target_env = "msvc"))]
+fn mallctl_write<T>(name: &std::ffi::CStr, mut value: T) -> std::io::Result<()> {
+    let code = unsafe {
+        tikv_jemalloc_sys::mallctl(
+            name.as_ptr(),
             std::ptr::null_mut(),
-            0,
-        );
+            std::ptr::null_mut(),
+            (&raw mut value).cast(),
+            std::mem::size_of::<T>(),
+        )
+    };
+    if code == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::from_raw_os_error(code))
+    }
+}
+
+/// Interval between available-memory checks of the pressure watchdog
+const PRESSURE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether the system is under memory pressure
+///
+/// Pressure starts below `floor_mb` and only ends once available memory is 25%
+/// above the floor, so the state does not flap around the threshold.
+#[must_use]
+pub fn under_pressure(currently: bool, available_mb: u64, floor_mb: u64) -> bool {
+    if currently {
+        available_mb < floor_mb + floor_mb / 4
+    } else {
+        available_mb < floor_mb
+    }
+}
+
+/// Throttle downloads while free RAM is below `memory.pressure_floor_mb`
+///
+/// Under pressure new buffers are forced to disk and the watchdog holds download
+/// permits until only `memory.pressure_concurrency` remain usable; permits are
+/// taken as running downloads finish and returned once memory recovers.
+pub fn spawn_pressure_watchdog(state: Arc<BotState>) {
+    let floor_mb = state.config.memory_pressure_floor_mb;
+    if floor_mb == 0 {
+        return;
+    }
+    let reserve = (state.config.max_concurrent_downloads as usize)
+        .saturating_sub(state.config.memory_pressure_concurrency.max(1) as usize);
+
+    tokio::spawn(async move {
+        let mut pressured = false;
+        let mut held_permits = Vec::new();
+        let mut interval = tokio::time::interval(PRESSURE_CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let available_mb = AudioBuffer::get_available_memory_mb();
+
+            if under_pressure(pressured, available_mb, floor_mb) != pressured {
+                pressured = !pressured;
+                audio_buffer::set_memory_pressure(pressured);
+                if pressured {
+                    tracing::warn!(
+                        "Low memory ({}MB available < {}MB), forcing disk mode and reducing download concurrency",
+                        available_mb,
+                        floor_mb
+                    );
+                    force_memory_release();
+                } else {
+                    held_permits.clear();
+                    tracing::info!(
+                        "Memory recovered ({}MB available), restoring download concurrency",
+                        available_mb
+                    );
+                }
+            }
+
+            while pressured && held_permits.len() < reserve {
+                match state.download_semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => held_permits.push(permit),
+                    Err(_) => break,
+                }
+            }
+        }
+    });
+}
+
+/// Apply the `[memory]` jemalloc tuning from the config
+///
+/// Decay times are set both as the default for new arenas and on every arena
+/// that already exists, so they take effect for the whole process.
+#[cfg(not(target_env = "msvc"))]
+pub fn apply_tuning(config: &Config) {
+    let narenas = mallctl_read::<u32>(c"arenas.narenas").unwrap_or(0);
+
+    for (name, value) in [
+        ("dirty_decay_ms", config.memory_dirty_decay_ms),
+        ("muzzy_decay_ms", config.memory_muzzy_decay_ms),
+    ] {
+        let Some(ms) = value else { continue };
+        let ms = ms as isize;
+
+        let Ok(default_key) = std::ffi::CString::new(format!("arenas.{name}")) else {
+            continue;
+        };
+        if let Err(e) = mallctl_write(&default_key, ms) {
+            tracing::warn!("Failed to set jemalloc {}: {}", name, e);
+            continue;
+        }
+        for arena in 0..narenas {
+            // Arenas that were never initialized reject the write; skip them
+            if let Ok(key) = std::ffi::CString::new(format!("arena.{arena}.{name}")) {
+                let _ = mallctl_write(&key, ms);
+            }
+        }
+        tracing::info!("jemalloc {} set to {}", name, ms);
+    }
+
+    if config.memory_background_thread {
+        match mallctl_write(c"background_thread", true) {
+            Ok(()) => tracing::info!("jemalloc background threads enabled"),
+            Err(e) => tracing::warn!("Failed to enable jemalloc background threads: {}", e),
+        }
+    }
+}
+
+#[cfg(target_env = "msvc")]
+pub fn apply_tuning(config: &Config) {
+    if config.memory_dirty_decay_ms.is_some()
+        || config.memory_muzzy_decay_ms.is_some()
+        || config.memory_background_thread
+    {
+        tracing::warn!("[memory] tuning is ignored: jemalloc is not used on this platform");
+    }
+}
+
+/// Current allocator statistics (`None` when not using jemalloc)
+#[cfg(not(target_env = "msvc"))]
+#[must_use]
+pub fn memory_stats() -> Option<MemoryStats> {
+    // Statistics are cached; bumping the epoch refreshes them
+    mallctl_write(c"epoch", 1u64).ok()?;
+
+    Some(MemoryStats {
+        allocated: mallctl_read(c"stats.allocated").ok()?,
+        active: mallctl_read(c"stats.active").ok()?,
+        resident: mallctl_read(c"stats.resident").ok()?,
+        mapped: mallctl_read(c"stats.mapped").ok()?,
+        retained: mallctl_read(c"stats.retained").unwrap_or(0),
+    })
+}
+
+#[cfg(target_env = "msvc")]
+#[must_use]
+pub fn memory_stats() -> Option<MemoryStats> {
+    None
+}
+
+/// Whether the process was started with heap profiling available
+/// (`_RJEM_MALLOC_CONF=prof:true`)
+#[cfg(not(target_env = "msvc"))]
+#[must_use]
+pub fn profiling_available() -> bool {
+    mallctl_read::<bool>(c"opt.prof").unwrap_or(false)
+}
+
+#[cfg(target_env = "msvc")]
+#[must_use]
+pub fn profiling_available() -> bool {
+    false
+}
+
+/// Whether heap profiling is currently sampling allocations
+#[cfg(not(target_env = "msvc"))]
+#[must_use]
+pub fn profiling_active() -> bool {
+    mallctl_read::<bool>(c"prof.active").unwrap_or(false)
+}
+
+#[cfg(target_env = "msvc")]
+#[must_use]
+pub fn profiling_active() -> bool {
+    false
+}
+
+/// Start or stop heap profiling sampling at runtime
+#[cfg(not(target_env = "msvc"))]
+pub fn set_profiling_active(active: bool) -> std::io::Result<()> {
+    mallctl_write(c"prof.active", active)
+}
+
+#[cfg(target_env = "msvc")]
+pub fn set_profiling_active(_active: bool) -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
 
+/// Write a heap profile (readable with `jeprof`) into `dir`
+#[cfg(not(target_env = "msvc"))]
+pub fn dump_heap_profile(dir: &str) -> std::io::Result<std::path::PathBuf> {
+    let path = std::path::Path::new(dir).join(format!(
+        "heap-{}.prof",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let c_path = std::ffi::CString::new(path.to_string_lossy().into_owned())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    mallctl_write(c"prof.dump", c_path.as_ptr())?;
+    Ok(path)
+}
+
+#[cfg(target_env = "msvc")]
+pub fn dump_heap_profile(_dir: &str) -> std::io::Result<std::path::PathBuf> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Report current memory usage stats (debug builds only)
+#[cfg(all(debug_assertions, not(target_env = "msvc")))]
+pub fn log_memory_stats() {
+    if let Some(stats) = memory_stats() {
         tracing::debug!(
             "jemalloc stats: allocated={}MB, resident={}MB",
-            allocated / 1024 / 1024,
-            resident / 1024 / 1024
+            stats.allocated / 1024 / 1024,
+            stats.resident / 1024 / 1024
         );
     }
 }
@@ -83,3 +385,17 @@ pub fn log_memory_stats() {
 pub fn log_memory_stats() {
     // No-op in release builds or on Windows
 }
+
+#[cfg(test)]
+mod tests {
+    use super::under_pressure;
+
+    #[test]
+    fn pressure_has_hysteresis() {
+        assert!(!under_pressure(false, 250, 200));
+        assert!(under_pressure(false, 150, 200));
+        // Stays pressured until 25% above the floor
+        assert!(under_pressure(true, 240, 200));
+        assert!(!under_pressure(true, 250, 200));
+    }
+}
@@ -0,0 +1,121 @@
+//! One-off import of a Music163bot-Go cache database (`--migrate-from-go`)
+//!
+//! The Go version stores its cache in a GORM-managed `song_infos` table with
+//! the same columns as ours plus GORM's `deleted_at`. Rows are merged into the
+//! configured database without overwriting existing songs. Their file_ids are
+//! claimed by the bot on the next start, so the migration must be run with the
+//! same bot token the Go version used.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Row, SqlitePool};
+
+use crate::database::{Database, SongInfo};
+use crate::error::{BotError, Result};
+
+/// Parse a GORM timestamp (`2006-01-02 15:04:05.999999999-07:00`), falling back to now
+#[must_use]
+pub fn parse_go_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z")
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| DateTime::from_str(value))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").map(|dt| dt.and_utc())
+        })
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn song_from_go_row(row: &SqliteRow) -> SongInfo {
+    // Go writes empty strings instead of NULL for missing file_ids
+    let optional = |column: &str| {
+        row.try_get::<Option<String>, _>(column)
+            .ok()
+            .flatten()
+            .filter(|value| !value.is_empty())
+    };
+    let text = |column: &str| optional(column).unwrap_or_default();
+    let number = |column: &str| {
+        row.try_get::<Option<i64>, _>(column)
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+    };
+
+    SongInfo {
+        id: 0,
+        music_id: number("music_id"),
+        song_name: text("song_name"),
+        song_artists: text("song_artists"),
+        song_album: text("song_album"),
+        file_ext: text("file_ext"),
+        music_size: number("music_size"),
+        pic_size: number("pic_size"),
+        emb_pic_size: number("emb_pic_size"),
+        bit_rate: number("bit_rate"),
+        duration: number("duration"),
+        file_id: optional("file_id"),
+        thumb_file_id: optional("thumb_file_id"),
+        backup_message: None,
+        audio_md5: None,
+        from_user_id: number("from_user_id"),
+        from_user_name: text("from_user_name"),
+        from_chat_id: number("from_chat_id"),
+        from_chat_name: text("from_chat_name"),
+        created_at: parse_go_timestamp(&text("created_at")),
+        updated_at: parse_go_timestamp(&text("updated_at")),
+    }
+}
+
+/// Import every live song of the Go database at `path`, returning (rows read, new songs)
+pub async fn migrate_from_go(path: &str, database: &Database) -> Result<(usize, u64)> {
+    if !Path::new(path).exists() {
+        return Err(BotError::Config(format!("Go database not found: {path}")));
+    }
+
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePool::connect_with(options).await?;
+
+    let has_deleted_at: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('song_infos') WHERE name = 'deleted_at')",
+    )
+    .fetch_one(&pool)
+    .await?;
+    if !has_deleted_at {
+        pool.close().await;
+        return Err(BotError::Config(format!(
+            "{path} does not look like a Music163bot-Go database (no song_infos.deleted_at)"
+        )));
+    }
+
+    // Soft-deleted rows are cache entries the Go bot removed
+    let rows = sqlx::query("SELECT * FROM song_infos WHERE deleted_at IS NULL AND music_id > 0")
+        .fetch_all(&pool)
+        .await?;
+    pool.close().await;
+
+    let songs: Vec<SongInfo> = rows.iter().map(song_from_go_row).collect();
+    let (added, _) = database.import_songs(&songs, None).await?;
+    Ok((songs.len(), added))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_go_timestamp;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn parses_gorm_timestamps() {
+        assert_eq!(
+            parse_go_timestamp("2023-04-01 20:15:30.123456789+08:00"),
+            Utc.with_ymd_and_hms(2023, 4, 1, 12, 15, 30).unwrap()
+                + chrono::Duration::nanoseconds(123_456_789)
+        );
+        assert_eq!(
+            parse_go_timestamp("2023-04-01 12:15:30"),
+            Utc.with_ymd_and_hms(2023, 4, 1, 12, 15, 30).unwrap()
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 use std::io::Cursor;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use aes::Aes128;
 use cipher::{BlockDecryptMut, BlockEncryptMut, KeyInit, block_padding::Pkcs7};
@@ -13,14 +14,29 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::api_endpoints::ApiEndpoints;
 use crate::config::Config;
-use crate::error::{BotError, Result};
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::http_client;
 
 #[derive(Debug, Clone)]
 pub struct MusicApi {
     client: Client,
     pub music_u: Option<String>,
-    base_url: String,
+    endpoints: Arc<ApiEndpoints>,
+}
+
+/// The `code` every NetEase API reply carries
+#[derive(Debug, Deserialize)]
+struct ApiCode {
+    #[serde(default)]
+    code: i32,
+}
+
+/// Decode a JSON reply along with its `code`
+fn json_with_code<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<(T, i32)> {
+    let code = serde_json::from_slice::<ApiCode>(body)?.code;
+    Ok((serde_json::from_slice(body)?, code))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +57,107 @@ pub struct SongDetail {
     pub al: Option<Album>, // Album info (may be missing)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SongOverviewResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub songs: Vec<SongOverview>,
+    #[serde(default)]
+    pub privileges: Vec<Privilege>,
+}
+
+/// Song detail with publishing and quality information (`/api/v3/song/detail`)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SongOverview {
+    #[serde(flatten)]
+    pub detail: SongDetail,
+    /// Publish date in milliseconds since the epoch (0 = unknown)
+    #[serde(rename = "publishTime", default)]
+    pub publish_time: i64,
+    /// Popularity score, 0-100
+    #[serde(default)]
+    pub pop: f64,
+    #[serde(default)]
+    pub fee: i32,
+    pub l: Option<QualityInfo>,
+    pub m: Option<QualityInfo>,
+    pub h: Option<QualityInfo>,
+    pub sq: Option<QualityInfo>,
+    pub hr: Option<QualityInfo>,
+}
+
+/// An available quality of a song
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityInfo {
+    pub br: u64,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// What the current account may do with a song
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Privilege {
+    pub id: u64,
+    #[serde(default)]
+    pub fee: i32,
+    /// Highest bitrate of the song
+    #[serde(default)]
+    pub maxbr: u64,
+    /// Highest playable bitrate for this account (0 = not playable)
+    #[serde(default)]
+    pub pl: u64,
+    /// Negative when the song is taken down or blocked by copyright
+    #[serde(default)]
+    pub st: i32,
+}
+
+impl SongOverview {
+    /// Available qualities from lowest to highest, with their display names
+    #[must_use]
+    pub fn qualities(&self) -> Vec<(&'static str, &QualityInfo)> {
+        [
+            ("标准", &self.l),
+            ("较高", &self.m),
+            ("极高", &self.h),
+            ("无损", &self.sq),
+            ("Hi-Res", &self.hr),
+        ]
+        .into_iter()
+        .filter_map(|(label, quality)| quality.as_ref().map(|quality| (label, quality)))
+        .filter(|(_, quality)| quality.br > 0)
+        .collect()
+    }
+}
+
+/// Explain why a song has no download URL, from its `fee` and this account's
+/// privilege
+#[must_use]
+pub fn unavailable_reason(fee: i32, privilege: Option<&Privilege>, has_music_u: bool) -> String {
+    if privilege.is_some_and(|privilege| privilege.st < 0) {
+        return "该歌曲因版权原因已下架".to_string();
+    }
+    match fee {
+        1 if has_music_u => "该歌曲为 VIP 专享，当前 MUSIC_U 账号没有 VIP 权限".to_string(),
+        1 => "该歌曲为 VIP 专享，机器人未配置 MUSIC_U 账号".to_string(),
+        4 => "该歌曲所在专辑需要单独购买".to_string(),
+        _ if privilege.is_some_and(|privilege| privilege.pl == 0) => {
+            "该歌曲在机器人所在地区不可播放 (地区版权限制)".to_string()
+        }
+        _ => "无法获取下载链接，可能需要VIP权限".to_string(),
+    }
+}
+
+/// Human-readable meaning of a song's `fee` field
+#[must_use]
+pub fn fee_label(fee: i32) -> &'static str {
+    match fee {
+        0 | 8 => "免费",
+        1 => "VIP 专享",
+        4 => "付费专辑",
+        _ => "未知",
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Artist {
     pub id: u64,
@@ -61,15 +178,46 @@ pub struct SongUrlResponse {
     pub data: Vec<SongUrl>,
 }
 
+/// Unavailable songs come back with `null` URL fields; read them as empty
+fn null_as_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SongUrl {
     pub id: u64,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub url: String,
     pub br: u64,
     pub size: u64,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub md5: String,
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default, deserialize_with = "null_as_default")]
     pub format: String,
+    /// Set when the URL is only a trial clip of a paid song
+    #[serde(rename = "freeTrialInfo", default)]
+    pub free_trial_info: Option<FreeTrialInfo>,
+}
+
+/// Range (seconds) of a trial clip within the song
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FreeTrialInfo {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl SongUrl {
+    /// Length of the trial clip, if this URL is one
+    #[must_use]
+    pub fn trial_secs(&self) -> Option<u64> {
+        self.free_trial_info
+            .as_ref()
+            .map(|trial| trial.end.saturating_sub(trial.start))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,6 +225,8 @@ pub struct LyricResponse {
     pub code: i32,
     pub lrc: Option<LyricContent>,
     pub tlyric: Option<LyricContent>,
+    /// Word-level karaoke lyrics (`/api/song/lyric/v1` only)
+    pub yrc: Option<LyricContent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -112,53 +262,340 @@ pub struct SearchSong {
     pub duration: u64,
 }
 
-impl MusicApi {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlbumDetailResponse {
+    pub code: i32,
+    pub album: Option<Album>,
+    #[serde(default)]
+    pub songs: Vec<SongDetail>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArtistAlbumsResponse {
+    pub code: i32,
+    pub artist: Option<Artist>,
+    #[serde(rename = "hotAlbums", default)]
+    pub hot_albums: Vec<Album>,
+    #[serde(default)]
+    pub more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaylistDetailResponse {
+    pub code: i32,
+    pub playlist: Option<Playlist>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Playlist {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "trackCount", default)]
+    pub track_count: u64,
+    #[serde(rename = "trackIds", default)]
+    pub track_ids: Vec<TrackId>,
+    /// Track details (playlist detail only; may be capped for huge playlists)
+    #[serde(default)]
+    pub tracks: Vec<SongDetail>,
+    /// Subscribed (favorited) rather than created by the user
+    #[serde(default)]
+    pub subscribed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntelligenceResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub data: Vec<TrackId>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloudResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub data: Vec<CloudSong>,
+    #[serde(rename = "hasMore", default)]
+    pub has_more: bool,
+}
+
+/// A song in the account's cloud disk (云盘)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloudSong {
+    #[serde(rename = "songId")]
+    pub song_id: u64,
+    #[serde(rename = "songName")]
+    pub song_name: String,
+    #[serde(default)]
+    pub artist: String,
+    #[serde(rename = "fileSize", default)]
+    pub file_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserPlaylistResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub playlist: Vec<Playlist>,
+    #[serde(default)]
+    pub more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackId {
+    pub id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimiSongsResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub songs: Vec<SongDetail>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HotCommentsResponse {
+    pub code: i32,
+    #[serde(rename = "hotComments", default)]
+    pub hot_comments: Vec<Comment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Comment {
+    pub user: CommentUser,
+    pub content: String,
+    #[serde(rename = "likedCount", default)]
+    pub liked_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentUser {
+    pub nickname: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountResponse {
+    pub code: i32,
+    pub account: Option<Account>,
+    pub profile: Option<Profile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub id: u64,
+    #[serde(rename = "vipType", default)]
+    pub vip_type: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(rename = "userId")]
+    pub user_id: u64,
+    pub nickname: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VipInfoResponse {
+    pub code: i32,
+    pub data: Option<VipInfo>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VipInfo {
+    #[serde(rename = "redVipLevel", default)]
+    pub red_vip_level: i32,
+    /// 黑胶 VIP
+    pub associator: Option<VipPackage>,
+    /// 音乐包
+    #[serde(rename = "musicPackage")]
+    pub music_package: Option<VipPackage>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VipPackage {
+    #[serde(rename = "vipCode", default)]
+    pub vip_code: i32,
+    /// Expiry timestamp in milliseconds (0 if never subscribed)
+    #[serde(rename = "expireTime", default)]
+    pub expire_time: i64,
+}
+
+impl VipPackage {
+    /// Whether the package is still valid at the given time (milliseconds)
     #[must_use]
-    pub fn new(music_u: Option<String>, base_url: String) -> Self {
-        Self::new_with_options(music_u, base_url, 0, 10)
+    pub fn is_active_at(&self, now_ms: i64) -> bool {
+        self.expire_time > now_ms
     }
+}
+
+/// Which client the daily sign-in is performed as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigninKind {
+    Mobile,
+    Web,
+}
 
+impl SigninKind {
+    /// Value of the `type` parameter expected by the API
     #[must_use]
-    pub fn new_with_config(config: &Config) -> Self {
-        Self::new_with_options(
-            config.music_u.clone(),
-            config.music_api.clone(),
-            config.download_pool_max_idle_per_host,
-            config.download_connect_timeout_secs,
-        )
+    pub fn api_type(self) -> u8 {
+        match self {
+            Self::Mobile => 0,
+            Self::Web => 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailySigninResponse {
+    pub code: i32,
+    #[serde(default)]
+    pub point: i64,
+    #[serde(default)]
+    pub msg: Option<String>,
+}
+
+/// Result of a single daily sign-in request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigninOutcome {
+    /// Signed in, earning the given points
+    Signed(i64),
+    /// Already signed in today
+    AlreadySigned,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserInfoResponse {
+    pub code: i32,
+    #[serde(rename = "userPoint")]
+    pub user_point: Option<UserPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserPoint {
+    #[serde(default)]
+    pub balance: i64,
+}
+
+/// NetEase audio CDN mirrors (`<mirror>.music.126.net`), in failover order
+const CDN_MIRRORS: &[&str] = &["m701", "m7", "m801", "m8"];
+/// Time a CDN mirror gets to send response headers before the next one is tried
+const CDN_RESPONSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+/// Mirror that replaces `mirror` first, as the original Go project does to avoid 403s
+fn preferred_mirror(mirror: &str) -> &str {
+    match mirror {
+        "m8" => "m7",
+        "m801" | "m804" | "m704" => "m701",
+        other => other,
     }
+}
 
-    fn new_with_options(
-        music_u: Option<String>,
-        base_url: String,
-        pool_max_idle_per_host: usize,
-        connect_timeout_secs: u64,
-    ) -> Self {
-        let mut client_builder = Client::builder();
+/// URLs to try for a song file: the preferred mirror, the original host, then
+/// the remaining known mirrors. Non-NetEase URLs are returned unchanged.
+pub(crate) fn cdn_candidates(url: &str) -> Vec<String> {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return vec![url.to_string()];
+    };
+    let Some((mirror, domain)) = parsed
+        .host_str()
+        .and_then(|host| host.split_once('.'))
+        .map(|(mirror, domain)| (mirror.to_string(), domain.to_string()))
+    else {
+        return vec![url.to_string()];
+    };
+    let is_mirror = mirror.len() > 1
+        && mirror.starts_with('m')
+        && mirror[1..].bytes().all(|b| b.is_ascii_digit());
+    if domain != "music.126.net" || !is_mirror {
+        return vec![url.to_string()];
+    }
 
-        // Use rustls TLS for better compatibility
-        client_builder = client_builder.use_rustls_tls();
+    let mut mirrors = vec![preferred_mirror(&mirror), mirror.as_str()];
+    mirrors.extend_from_slice(CDN_MIRRORS);
+    let mut candidates: Vec<String> = Vec::new();
+    for mirror in mirrors {
+        if parsed.set_host(Some(&format!("{mirror}.{domain}"))).is_ok() {
+            let candidate = parsed.to_string();
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
 
-        // Performance optimizations
-        // pool_max_idle_per_host(0) prevents connection pool memory accumulation
-        client_builder = client_builder
-            .tcp_nodelay(true)
-            .pool_max_idle_per_host(pool_max_idle_per_host)
-            .connect_timeout(std::time::Duration::from_secs(connect_timeout_secs));
+impl MusicApi {
+    #[must_use]
+    pub fn new(music_u: Option<String>, base_url: String) -> Self {
+        let network = Config {
+            download_pool_max_idle_per_host: 0,
+            ..Config::default()
+        };
+        Self::new_with_options(music_u, base_url, &network)
+    }
 
-        // Add user agent
-        client_builder = client_builder
-            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36");
+    #[must_use]
+    pub fn new_with_config(config: &Config) -> Self {
+        Self::new_with_options(config.music_u.clone(), config.music_api.clone(), config)
+    }
 
-        let client = client_builder.build().unwrap();
+    fn new_with_options(music_u: Option<String>, base_url: String, network: &Config) -> Self {
+        // User agent, TLS, proxy, pool and connect timeout come from the download profile
+        let client = http_client::build(network, http_client::Profile::Download).unwrap();
 
         Self {
             client,
             music_u,
-            base_url,
+            endpoints: Arc::new(ApiEndpoints::parse(&base_url)),
         }
     }
 
+    /// Healthiest configured endpoint, which API requests go to
+    fn base_url(&self) -> String {
+        self.endpoints.best()
+    }
+
+    /// Rolling latency and error numbers of the configured endpoints
+    #[must_use]
+    pub fn endpoints(&self) -> &ApiEndpoints {
+        &self.endpoints
+    }
+
+    /// Send an API request and decode its reply with `decode`, which returns
+    /// the value and the NetEase `code`
+    ///
+    /// The endpoint is recorded as failed on transport errors, bodies that do
+    /// not decode (5xx error pages) and codes outside `ok_codes`, so a mirror
+    /// answering fast with errors never ranks as healthy.
+    async fn send_decoded<T>(
+        &self,
+        request: reqwest::RequestBuilder,
+        ok_codes: &[i32],
+        decode: impl FnOnce(&[u8]) -> Result<(T, i32)>,
+    ) -> Result<T> {
+        let request = request.build()?;
+        let url = request.url().to_string();
+        let start = Instant::now();
+        let body = match self.client.execute(request).await {
+            Ok(response) => response.bytes().await,
+            Err(e) => Err(e),
+        };
+        let decoded = body.map_err(BotError::from).and_then(|body| decode(&body));
+        let ok = decoded
+            .as_ref()
+            .is_ok_and(|(_, code)| ok_codes.contains(code));
+        self.endpoints.record(&url, start.elapsed(), ok);
+        decoded.map(|(value, _)| value)
+    }
+
+    /// Send an API request and decode its JSON reply, which succeeds with code 200
+    async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        self.send_decoded(request, &[200], json_with_code).await
+    }
+
     fn build_eapi_cookie(&self) -> String {
         let device_id = Uuid::new_v4().simple().to_string();
         let appver = "9.3.40";
@@ -199,18 +636,25 @@ impl MusicApi {
         let encrypted = Encryptor::<Aes128>::new_from_slice(b"e82ckenh8dichen8")
             .expect("eapi key length")
             .encrypt_padded_mut::<Pkcs7>(&mut buf, data_len)
-            .map_err(|_| BotError::MusicApi("Failed to encrypt eapi payload".to_string()))
+            .map_err(|_| {
+                BotError::music_api(
+                    MusicApiErrorKind::InvalidResponse,
+                    "Failed to encrypt eapi payload",
+                )
+            })
             .unwrap_or(&[]);
         encode_upper(encrypted)
     }
 
     fn eapi_decrypt(hex_data: &str) -> Result<String> {
-        let mut bytes = hex::decode(hex_data).map_err(|e| BotError::MusicApi(e.to_string()))?;
+        let mut bytes = hex::decode(hex_data)
+            .map_err(|e| BotError::music_api(MusicApiErrorKind::InvalidResponse, e.to_string()))?;
         let decrypted = Decryptor::<Aes128>::new_from_slice(b"e82ckenh8dichen8")
             .expect("eapi key length")
             .decrypt_padded_mut::<Pkcs7>(&mut bytes)
-            .map_err(|e| BotError::MusicApi(e.to_string()))?;
-        String::from_utf8(decrypted.to_vec()).map_err(|e| BotError::MusicApi(e.to_string()))
+            .map_err(|e| BotError::music_api(MusicApiErrorKind::InvalidResponse, e.to_string()))?;
+        String::from_utf8(decrypted.to_vec())
+            .map_err(|e| BotError::music_api(MusicApiErrorKind::InvalidResponse, e.to_string()))
     }
 
     fn eapi_params(path: &str, json: &str) -> String {
@@ -224,8 +668,9 @@ impl MusicApi {
     }
 
     /// Get song details
+    #[tracing::instrument(skip(self))]
     pub async fn get_song_detail(&self, song_id: u64) -> Result<SongDetail> {
-        let url = format!("{}/api/song/detail", self.base_url);
+        let url = format!("{}/api/song/detail", self.base_url());
         let mut params = HashMap::new();
         params.insert("id", song_id.to_string());
         params.insert("ids", format!("[{song_id}]"));
@@ -237,54 +682,179 @@ impl MusicApi {
             request = request.header("Cookie", format!("MUSIC_U={music_u}"));
         }
 
-        let response = request.send().await?;
-        let data: SongDetailResponse = response.json().await?;
+        let data: SongDetailResponse = self.send_json(request).await?;
 
         if data.code != 200 {
-            return Err(BotError::MusicApi(format!(
-                "API returned code {}",
-                data.code
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
         }
 
         data.songs
             .into_iter()
             .next()
-            .ok_or_else(|| BotError::MusicApi("No song found".to_string()))
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No song found"))
+    }
+
+    /// Get song details along with its qualities and this account's privilege
+    #[tracing::instrument(skip(self))]
+    pub async fn get_song_overview(
+        &self,
+        song_id: u64,
+    ) -> Result<(SongOverview, Option<Privilege>)> {
+        let url = format!("{}/api/v3/song/detail", self.base_url());
+        let mut params = HashMap::new();
+        params.insert("c", format!("[{{\"id\":{song_id}}}]"));
+
+        let mut request = self.client.post(url).form(&params);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: SongOverviewResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        let privilege = data.privileges.into_iter().find(|p| p.id == song_id);
+        let song = data
+            .songs
+            .into_iter()
+            .next()
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No song found"))?;
+        Ok((song, privilege))
     }
 
     /// Get song download URL
+    #[tracing::instrument(skip(self))]
     pub async fn get_song_url(&self, song_id: u64, br: u64) -> Result<SongUrl> {
-        let url = format!("{}/api/song/enhance/player/url", self.base_url);
+        self.get_song_urls(&[song_id], br)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                BotError::music_api(MusicApiErrorKind::Unavailable, "No download URL found")
+            })
+    }
+
+    /// Get download URLs of several songs in one request (order not guaranteed)
+    #[tracing::instrument(skip(self))]
+    pub async fn get_song_urls(&self, song_ids: &[u64], br: u64) -> Result<Vec<SongUrl>> {
+        self.song_urls(song_ids, br, true).await
+    }
+
+    /// Song URLs as an anonymous listener gets them, without MUSIC_U: VIP
+    /// songs only come back as trial clips or not at all
+    pub async fn get_public_song_urls(&self, song_ids: &[u64], br: u64) -> Result<Vec<SongUrl>> {
+        self.song_urls(song_ids, br, false).await
+    }
+
+    async fn song_urls(
+        &self,
+        song_ids: &[u64],
+        br: u64,
+        with_account: bool,
+    ) -> Result<Vec<SongUrl>> {
+        let url = format!("{}/api/song/enhance/player/url", self.base_url());
+        let ids = song_ids
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
         let mut params = HashMap::new();
-        params.insert("ids", format!("[{song_id}]"));
+        params.insert("ids", format!("[{ids}]"));
         params.insert("br", br.to_string());
 
         let mut request = self.client.post(url).form(&params);
 
+        if with_account && let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: SongUrlResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data.data)
+    }
+
+    /// Get the best available download URL: FLAC when MUSIC_U is set, then 320k/128k MP3
+    pub async fn get_best_song_url(&self, song_id: u64) -> Result<SongUrl> {
+        if self.music_u.is_some() {
+            match self.get_song_url(song_id, 999_000).await {
+                Ok(url) if !url.url.is_empty() => {
+                    tracing::info!("Using FLAC quality for music_id {}", song_id);
+                    return Ok(url);
+                }
+                _ => {
+                    tracing::info!(
+                        "FLAC not available, falling back to MP3 for music_id {}",
+                        song_id
+                    );
+                    return self.get_song_url(song_id, 320_000).await;
+                }
+            }
+        }
+
+        match self.get_song_url(song_id, 320_000).await {
+            Ok(url) => Ok(url),
+            Err(_) => self.get_song_url(song_id, 128_000).await,
+        }
+    }
+
+    /// Get the free trial clip (usually 30 seconds) of a song the account
+    /// cannot play in full
+    #[tracing::instrument(skip(self))]
+    pub async fn get_preview_url(&self, song_id: u64) -> Result<SongUrl> {
+        let url = format!("{}/api/song/enhance/player/url/v1", self.base_url());
+        let mut params = HashMap::new();
+        params.insert("ids", format!("[{song_id}]"));
+        params.insert("level", "standard".to_string());
+        params.insert("encodeType", "mp3".to_string());
+
+        let mut request = self.client.post(url).form(&params);
+
         if let Some(music_u) = &self.music_u {
             request = request.header("Cookie", format!("MUSIC_U={music_u}"));
         }
 
-        let response = request.send().await?;
-        let data: SongUrlResponse = response.json().await?;
+        let data: SongUrlResponse = self.send_json(request).await?;
 
         if data.code != 200 {
-            return Err(BotError::MusicApi(format!(
-                "API returned code {}",
-                data.code
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
         }
 
         data.data
             .into_iter()
             .next()
-            .ok_or_else(|| BotError::MusicApi("No download URL found".to_string()))
+            .filter(|url| !url.url.is_empty())
+            .ok_or_else(|| {
+                BotError::music_api(MusicApiErrorKind::Unavailable, "No preview URL found")
+            })
     }
 
     /// Get song lyrics
+    #[tracing::instrument(skip(self))]
     pub async fn get_song_lyric(&self, song_id: u64) -> Result<String> {
-        let url = format!("{}/api/song/lyric?id={}&lv=1&tv=1", self.base_url, song_id);
+        let url = format!(
+            "{}/api/song/lyric?id={}&lv=1&tv=1",
+            self.base_url(),
+            song_id
+        );
 
         let mut request = self.client.get(&url);
 
@@ -292,14 +862,13 @@ impl MusicApi {
             request = request.header("Cookie", format!("MUSIC_U={music_u}"));
         }
 
-        let response = request.send().await?;
-        let data: LyricResponse = response.json().await?;
+        let data: LyricResponse = self.send_json(request).await?;
 
         if data.code != 200 {
-            return Err(BotError::MusicApi(format!(
-                "API returned code {}",
-                data.code
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
         }
 
         let lyric = data
@@ -309,10 +878,448 @@ impl MusicApi {
         Ok(lyric)
     }
 
+    /// Get the word-level `yrc` lyrics of a song, if it has any
+    #[tracing::instrument(skip(self))]
+    pub async fn get_song_yrc(&self, song_id: u64) -> Result<Option<String>> {
+        let url = format!(
+            "{}/api/song/lyric/v1?id={}&cp=false&lv=0&tv=0&yv=0",
+            self.base_url(),
+            song_id
+        );
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: LyricResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data
+            .yrc
+            .map(|yrc| yrc.lyric)
+            .filter(|yrc| !yrc.trim().is_empty()))
+    }
+
+    /// Get album info and its track list
+    #[tracing::instrument(skip(self))]
+    pub async fn get_album(&self, album_id: u64) -> Result<(Album, Vec<SongDetail>)> {
+        let url = format!("{}/api/v1/album/{}", self.base_url(), album_id);
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: AlbumDetailResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        let album = data
+            .album
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No album found"))?;
+
+        Ok((album, data.songs))
+    }
+
+    /// Get a page of an artist's albums (newest first), and whether more follow
+    #[tracing::instrument(skip(self))]
+    pub async fn get_artist_albums(
+        &self,
+        artist_id: u64,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(Artist, Vec<Album>, bool)> {
+        let url = format!(
+            "{}/api/artist/albums/{}?limit={}&offset={}",
+            self.base_url(),
+            artist_id,
+            limit,
+            offset
+        );
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: ArtistAlbumsResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        let artist = data
+            .artist
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No artist found"))?;
+
+        Ok((artist, data.hot_albums, data.more))
+    }
+
+    /// Get playlist info including the full list of track IDs
+    #[tracing::instrument(skip(self))]
+    pub async fn get_playlist(&self, playlist_id: u64) -> Result<Playlist> {
+        let url = format!("{}/api/v6/playlist/detail", self.base_url());
+        let mut params = HashMap::new();
+        params.insert("id", playlist_id.to_string());
+        params.insert("n", "100000".to_string());
+        params.insert("s", "8".to_string());
+
+        let mut request = self.client.post(url).form(&params);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: PlaylistDetailResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        data.playlist
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No playlist found"))
+    }
+
+    /// Get a page of a user's created and subscribed playlists (created first),
+    /// and whether more follow
+    #[tracing::instrument(skip(self))]
+    pub async fn get_user_playlists(
+        &self,
+        user_id: u64,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(Vec<Playlist>, bool)> {
+        let url = format!(
+            "{}/api/user/playlist?uid={}&limit={}&offset={}",
+            self.base_url(),
+            user_id,
+            limit,
+            offset
+        );
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: UserPlaylistResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok((data.playlist, data.more))
+    }
+
+    /// Get the heartbeat mode (心动模式) mix seeded by a song
+    ///
+    /// The API needs the account's "liked songs" playlist, which is the first
+    /// playlist the account created.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_intelligence_list(&self, song_id: u64) -> Result<Vec<u64>> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let user_id = self
+            .get_account_info()
+            .await?
+            .profile
+            .map(|profile| profile.user_id)
+            .ok_or_else(|| {
+                BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not logged in")
+            })?;
+        let liked = self
+            .get_user_playlists(user_id, 1, 0)
+            .await?
+            .0
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                BotError::music_api(MusicApiErrorKind::NotFound, "No liked songs playlist")
+            })?;
+
+        let url = format!(
+            "{}/api/playmode/intelligence/list?songId={}&type=fromPlayOne&playlistId={}&startMusicId={}&count=1",
+            self.base_url(),
+            song_id,
+            liked.id,
+            song_id
+        );
+        let request = self
+            .client
+            .get(&url)
+            .header("Cookie", format!("MUSIC_U={music_u}"));
+        let data: IntelligenceResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data.data.into_iter().map(|track| track.id).collect())
+    }
+
+    /// Get a page of the MUSIC_U account's cloud disk, and whether more follow
+    ///
+    /// Cloud songs are only visible to their owner, but otherwise work like
+    /// catalog songs: `get_song_detail` and `get_song_url` accept their IDs
+    /// with the same cookie.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_cloud_songs(&self, limit: u32, offset: u32) -> Result<(Vec<CloudSong>, bool)> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let url = format!(
+            "{}/api/v1/cloud/get?limit={}&offset={}",
+            self.base_url(),
+            limit,
+            offset
+        );
+
+        let request = self
+            .client
+            .get(&url)
+            .header("Cookie", format!("MUSIC_U={music_u}"));
+        let data: CloudResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok((data.data, data.has_more))
+    }
+
+    /// Get songs similar to a song ("people also listen to")
+    #[tracing::instrument(skip(self))]
+    pub async fn get_simi_songs(&self, song_id: u64) -> Result<Vec<SongDetail>> {
+        let url = format!(
+            "{}/api/v1/discovery/simiSong?songid={}&limit=10&offset=0",
+            self.base_url(),
+            song_id
+        );
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: SimiSongsResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data.songs)
+    }
+
+    /// Get the hot comments of a song, most liked first
+    #[tracing::instrument(skip(self))]
+    pub async fn get_hot_comments(&self, song_id: u64) -> Result<Vec<Comment>> {
+        let url = format!(
+            "{}/api/v1/resource/hotcomments/R_SO_4_{}?limit=15&offset=0",
+            self.base_url(),
+            song_id
+        );
+
+        let mut request = self.client.get(&url);
+
+        if let Some(music_u) = &self.music_u {
+            request = request.header("Cookie", format!("MUSIC_U={music_u}"));
+        }
+
+        let data: HotCommentsResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data.hot_comments)
+    }
+
+    /// Get the account and profile bound to MUSIC_U
+    pub async fn get_account_info(&self) -> Result<AccountResponse> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let url = format!("{}/api/nuser/account/get", self.base_url());
+
+        let request = self
+            .client
+            .post(url)
+            .header("Cookie", format!("MUSIC_U={music_u}"));
+        let data: AccountResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        if data.account.is_none() {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Unauthorized,
+                "Not logged in (MUSIC_U may be expired)",
+            ));
+        }
+
+        Ok(data)
+    }
+
+    /// Get VIP membership info of the account bound to MUSIC_U
+    pub async fn get_vip_info(&self) -> Result<VipInfo> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let url = format!(
+            "{}/api/music-vip-membership/client/vip/info",
+            self.base_url()
+        );
+
+        let request = self
+            .client
+            .post(url)
+            .header("Cookie", format!("MUSIC_U={music_u}"));
+        let data: VipInfoResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        Ok(data.data.unwrap_or_default())
+    }
+
+    /// Perform the daily sign-in of the account bound to MUSIC_U
+    pub async fn daily_signin(&self, kind: SigninKind) -> Result<SigninOutcome> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let url = format!("{}/api/point/dailyTask", self.base_url());
+        let mut params = HashMap::new();
+        params.insert("type", kind.api_type().to_string());
+
+        let os = match kind {
+            SigninKind::Mobile => "android",
+            SigninKind::Web => "pc",
+        };
+
+        let request = self
+            .client
+            .post(url)
+            .form(&params)
+            .header("Cookie", format!("MUSIC_U={music_u}; os={os}"));
+        let data: DailySigninResponse = self
+            .send_decoded(request, &[200, -2], json_with_code)
+            .await?;
+
+        match data.code {
+            200 => Ok(SigninOutcome::Signed(data.point)),
+            // -2: already signed in today
+            -2 => Ok(SigninOutcome::AlreadySigned),
+            code => Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!(
+                    "API returned code {}{}",
+                    code,
+                    data.msg.map(|m| format!(": {m}")).unwrap_or_default()
+                ),
+            )),
+        }
+    }
+
+    /// Get the current point balance of the account bound to MUSIC_U
+    pub async fn get_user_points(&self) -> Result<i64> {
+        let music_u = self.music_u.as_ref().ok_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Unauthorized, "MUSIC_U is not configured")
+        })?;
+        let url = format!("{}/api/v1/user/info", self.base_url());
+
+        let request = self
+            .client
+            .post(url)
+            .header("Cookie", format!("MUSIC_U={music_u}"));
+        let data: UserInfoResponse = self.send_json(request).await?;
+
+        if data.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
+        }
+
+        data.user_point
+            .map(|point| point.balance)
+            .ok_or_else(|| BotError::music_api(MusicApiErrorKind::NotFound, "No point info found"))
+    }
+
+    /// Check that the NetEase API is reachable
+    pub async fn ping(&self) -> Result<()> {
+        self.ping_endpoint(&self.base_url()).await
+    }
+
+    /// Ping one of the configured endpoints (`music.api`)
+    pub async fn ping_endpoint(&self, base_url: &str) -> Result<()> {
+        let url = format!("{base_url}/api/search/hot");
+        let request = self
+            .client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(5));
+        let reply: ApiCode = self.send_json(request).await?;
+        if reply.code != 200 {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", reply.code),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Search songs
+    #[tracing::instrument(skip(self))]
     pub async fn search_songs(&self, keyword: &str, limit: u32) -> Result<Vec<SearchSong>> {
         let path = "/api/v1/search/song/get";
-        let url = format!("{}/eapi/v1/search/song/get", self.base_url);
+        let url = format!("{}/eapi/v1/search/song/get", self.base_url());
         let payload = serde_json::json!({
             "s": keyword,
             "offset": 0,
@@ -328,37 +1335,91 @@ impl MusicApi {
             .header("Cookie", self.build_eapi_cookie())
             .body(body);
 
-        let response = request.send().await?;
-        let raw_body = response.text().await?;
-        let trimmed = raw_body.trim_start();
-        let data: EapiSearchResponse = if trimmed.starts_with('{') {
-            serde_json::from_str(trimmed)?
-        } else {
-            let decrypted = Self::eapi_decrypt(trimmed)?;
-            serde_json::from_str(&decrypted)?
-        };
+        let data: EapiSearchResponse = self
+            .send_decoded(request, &[200], |body| {
+                let raw_body = String::from_utf8_lossy(body);
+                let trimmed = raw_body.trim_start();
+                let data: EapiSearchResponse = if trimmed.starts_with('{') {
+                    serde_json::from_str(trimmed)?
+                } else {
+                    let decrypted = Self::eapi_decrypt(trimmed)?;
+                    serde_json::from_str(&decrypted)?
+                };
+                let code = data.code;
+                Ok((data, code))
+            })
+            .await?;
 
         if data.code != 200 {
-            return Err(BotError::MusicApi(format!(
-                "API returned code {}",
-                data.code
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("API returned code {}", data.code),
+            ));
         }
 
         Ok(data.result.songs)
     }
 
     /// Download file with proper headers and cookies
+    ///
+    /// The URL is rewritten to the preferred CDN mirror first; on 403, server
+    /// errors, connection failures or a slow response the other known mirrors
+    /// are tried in turn.
+    #[tracing::instrument(skip_all)]
     pub async fn download_file(&self, url: &str) -> Result<reqwest::Response> {
-        // Apply host replacement similar to the original Go project
-        // This helps avoid 403 errors from NetEase servers
-        let processed_url = url
-            .replace("m8.", "m7.")
-            .replace("m801.", "m701.")
-            .replace("m804.", "m701.")
-            .replace("m704.", "m701.");
+        let candidates = cdn_candidates(url);
+        let last = candidates.len() - 1;
+        let mut last_error = None;
+
+        for (index, candidate) in candidates.iter().enumerate() {
+            match tokio::time::timeout(CDN_RESPONSE_TIMEOUT, self.cdn_request(candidate).send())
+                .await
+            {
+                Ok(Ok(response)) => {
+                    let status = response.status();
+                    if index == last
+                        || !(status == reqwest::StatusCode::FORBIDDEN || status.is_server_error())
+                    {
+                        return Ok(response);
+                    }
+                    tracing::warn!(
+                        "CDN {} returned HTTP {}, trying next mirror",
+                        candidate,
+                        status
+                    );
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!(
+                        "CDN {} request failed: {}, trying next mirror",
+                        candidate,
+                        e
+                    );
+                    last_error = Some(BotError::Network(e));
+                }
+                Err(_) => {
+                    tracing::warn!(
+                        "CDN {} did not respond within {}s, trying next mirror",
+                        candidate,
+                        CDN_RESPONSE_TIMEOUT.as_secs()
+                    );
+                    last_error = Some(BotError::music_api(
+                        MusicApiErrorKind::Status,
+                        format!(
+                            "CDN response timeout after {}s",
+                            CDN_RESPONSE_TIMEOUT.as_secs()
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            BotError::music_api(MusicApiErrorKind::Status, "All CDN mirrors failed")
+        }))
+    }
 
-        let mut request = self.client.get(&processed_url);
+    fn cdn_request(&self, url: &str) -> reqwest::RequestBuilder {
+        let mut request = self.client.get(url);
 
         // Add MUSIC_U cookie if available
         if let Some(music_u) = &self.music_u {
@@ -366,7 +1427,7 @@ impl MusicApi {
         }
 
         // Add comprehensive headers to avoid 403 errors
-        request = request
+        request
             .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
             .header("Referer", "https://music.163.com/")
             .header("Accept", "audio/mpeg, audio/*, */*")
@@ -375,10 +1436,7 @@ impl MusicApi {
             .header("DNT", "1")
             .header("Sec-Fetch-Dest", "audio")
             .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "cross-site");
-
-        let response = request.send().await?;
-        Ok(response)
+            .header("Sec-Fetch-Site", "cross-site")
     }
 
     /// Download and resize album art image
@@ -392,7 +1450,10 @@ impl MusicApi {
     /// Uses spawn_blocking for CPU-intensive image processing to avoid blocking async runtime
     pub async fn download_album_art_data(&self, pic_url: &str) -> Result<Vec<u8>> {
         if pic_url.is_empty() {
-            return Err(BotError::MusicApi("Empty album art URL".to_string()));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::NotFound,
+                "Empty album art URL",
+            ));
         }
 
         // Download the image
@@ -412,10 +1473,10 @@ impl MusicApi {
 
         let response = request.send().await?;
         if !response.status().is_success() {
-            return Err(BotError::MusicApi(format!(
-                "Failed to download album art: {}",
-                response.status()
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("Failed to download album art: {}", response.status()),
+            ));
         }
 
         let bytes = response.bytes().await?;
@@ -424,8 +1485,12 @@ impl MusicApi {
         // Process image in spawn_blocking to avoid blocking async runtime
         // Use a dedicated blocking task that completes and releases resources
         let processed = tokio::task::spawn_blocking(move || {
-            let img = image::load_from_memory(&bytes_vec)
-                .map_err(|e| BotError::MusicApi(format!("Failed to decode image: {e}")))?;
+            let img = image::load_from_memory(&bytes_vec).map_err(|e| {
+                BotError::music_api(
+                    MusicApiErrorKind::InvalidResponse,
+                    format!("Failed to decode image: {e}"),
+                )
+            })?;
 
             // Resize to 320x320 with black padding (like original Go project)
             let resized = resize_image_with_padding(img, 320, 320);
@@ -434,12 +1499,22 @@ impl MusicApi {
             let mut cursor = Cursor::new(Vec::new());
             resized
                 .write_to(&mut cursor, ImageFormat::Jpeg)
-                .map_err(|e| BotError::MusicApi(format!("Failed to encode image: {e}")))?;
+                .map_err(|e| {
+                    BotError::music_api(
+                        MusicApiErrorKind::Other,
+                        format!("Failed to encode image: {e}"),
+                    )
+                })?;
 
             Ok::<Vec<u8>, BotError>(cursor.into_inner())
         })
         .await
-        .map_err(|e| BotError::MusicApi(format!("Image processing task failed: {e}")))??;
+        .map_err(|e| {
+            BotError::music_api(
+                MusicApiErrorKind::Other,
+                format!("Image processing task failed: {e}"),
+            )
+        })??;
 
         Ok(processed)
     }
@@ -447,7 +1522,10 @@ impl MusicApi {
     /// Download original high-resolution album art without resizing (for embedding in audio files)
     pub async fn download_album_art_original(&self, pic_url: &str) -> Result<Vec<u8>> {
         if pic_url.is_empty() {
-            return Err(BotError::MusicApi("Empty album art URL".to_string()));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::NotFound,
+                "Empty album art URL",
+            ));
         }
 
         // Download the image
@@ -467,10 +1545,10 @@ impl MusicApi {
 
         let response = request.send().await?;
         if !response.status().is_success() {
-            return Err(BotError::MusicApi(format!(
-                "Failed to download album art: {}",
-                response.status()
-            )));
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("Failed to download album art: {}", response.status()),
+            ));
         }
 
         let bytes = response.bytes().await?;
@@ -537,3 +1615,107 @@ fn resize_image_with_padding(
 
     DynamicImage::ImageRgb8(canvas)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ArtistAlbumsResponse, Privilege, SongOverviewResponse, SongUrlResponse, cdn_candidates,
+        fee_label, json_with_code, unavailable_reason,
+    };
+
+    #[test]
+    fn reads_artist_album_pages() {
+        let data: ArtistAlbumsResponse = serde_json::from_str(
+            r#"{"code":200,"more":true,"artist":{"id":6452,"name":"周杰伦"},
+                "hotAlbums":[{"id":18918,"name":"范特西","picUrl":null,"size":10}]}"#,
+        )
+        .unwrap();
+        assert_eq!(data.artist.unwrap().name, "周杰伦");
+        assert_eq!(data.hot_albums[0].id, 18918);
+        assert!(data.more);
+    }
+
+    #[test]
+    fn decodes_replies_with_their_code() {
+        let (data, code): (SongUrlResponse, i32) =
+            json_with_code(br#"{"code":-460,"data":[]}"#).unwrap();
+        assert_eq!(code, -460);
+        assert!(data.data.is_empty());
+        assert!(json_with_code::<SongUrlResponse>(b"<html>502 Bad Gateway</html>").is_err());
+    }
+
+    #[test]
+    fn reads_unavailable_song_urls_as_empty() {
+        let data: SongUrlResponse = serde_json::from_str(
+            r#"{"code":200,"data":[
+                {"id":1,"url":"http://m701.music.126.net/a.mp3","br":128000,"size":10,"md5":"abc","type":"mp3"},
+                {"id":2,"url":null,"br":0,"size":0,"md5":null,"type":null}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(data.data[0].format, "mp3");
+        assert!(data.data[1].url.is_empty());
+        assert!(data.data[1].md5.is_empty());
+    }
+
+    #[test]
+    fn orders_cdn_mirrors_for_failover() {
+        assert_eq!(
+            cdn_candidates("http://m804.music.126.net/2024/a.flac?vuutv=x"),
+            vec![
+                "http://m701.music.126.net/2024/a.flac?vuutv=x",
+                "http://m804.music.126.net/2024/a.flac?vuutv=x",
+                "http://m7.music.126.net/2024/a.flac?vuutv=x",
+                "http://m801.music.126.net/2024/a.flac?vuutv=x",
+                "http://m8.music.126.net/2024/a.flac?vuutv=x",
+            ]
+        );
+        assert_eq!(cdn_candidates("http://m7.music.126.net/a.mp3").len(), 4);
+    }
+
+    #[test]
+    fn leaves_other_hosts_alone() {
+        assert_eq!(
+            cdn_candidates("https://example.com/a.mp3"),
+            vec!["https://example.com/a.mp3"]
+        );
+        assert_eq!(cdn_candidates("not a url"), vec!["not a url"]);
+    }
+
+    #[test]
+    fn parses_song_overview_qualities() {
+        let data: SongOverviewResponse = serde_json::from_str(
+            r#"{"code":200,"songs":[{"id":186016,"name":"晴天","dt":269000,
+                "ar":[{"id":6452,"name":"周杰伦"}],"al":{"id":18905,"name":"叶惠美","picUrl":""},
+                "publishTime":1059580800000,"pop":100.0,"fee":1,
+                "l":{"br":128000,"size":4308000},"m":{"br":192000,"size":6462000},
+                "h":{"br":320000,"size":10770000},"sq":{"br":919000,"size":30900000},"hr":null}],
+                "privileges":[{"id":186016,"fee":1,"maxbr":999000,"pl":128000}]}"#,
+        )
+        .unwrap();
+        let song = &data.songs[0];
+        assert_eq!(song.detail.name, "晴天");
+        assert_eq!(song.publish_time, 1_059_580_800_000);
+        let labels: Vec<&str> = song.qualities().iter().map(|(label, _)| *label).collect();
+        assert_eq!(labels, ["标准", "较高", "极高", "无损"]);
+        assert_eq!(data.privileges[0].pl, 128_000);
+        assert_eq!(fee_label(song.fee), "VIP 专享");
+    }
+
+    #[test]
+    fn explains_unavailable_songs() {
+        let privilege = |pl, st| Privilege {
+            id: 1,
+            fee: 0,
+            maxbr: 320_000,
+            pl,
+            st,
+        };
+        assert!(unavailable_reason(0, Some(&privilege(0, -200)), true).contains("下架"));
+        assert!(unavailable_reason(1, Some(&privilege(0, 0)), true).contains("没有 VIP"));
+        assert!(unavailable_reason(1, None, false).contains("未配置 MUSIC_U"));
+        assert!(unavailable_reason(4, Some(&privilege(0, 0)), true).contains("单独购买"));
+        assert!(unavailable_reason(8, Some(&privilege(0, 0)), true).contains("地区"));
+        assert!(unavailable_reason(0, None, true).contains("可能需要VIP"));
+    }
+}
@@ -0,0 +1,1080 @@
+//! Stages of delivering one song
+//!
+//! `bot::download_and_send_music` runs Fetch and Artwork in parallel, then
+//! Tag, Upload and Persist. The stages hand plain structs to each other and
+//! know nothing about status messages or batches, so each can be timed,
+//! retried and tested on its own.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardMarkup, MediaKind, MessageId, MessageKind, ReplyParameters};
+use tracing::Instrument;
+
+use crate::audio_buffer::{AudioBuffer, ThumbnailBuffer};
+use crate::bot::BotState;
+use crate::config::{Config, CoverMode};
+use crate::database::SongInfo;
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::music_api::{SongDetail, SongUrl, format_artists};
+use crate::utils::{
+    channel_message_link, clean_filename, incomplete_download, is_file_too_large, throughput_mbps,
+    update_peak, upload_limit,
+};
+
+/// Attempts at downloading the audio before a truncated file is reported
+const DOWNLOAD_ATTEMPTS: u32 = 2;
+/// Smaller files are error pages, not audio
+const MIN_AUDIO_BYTES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Download the audio from the CDN
+    Fetch,
+    /// Download the album cover
+    Artwork,
+    /// Validate the audio and embed tags
+    Tag,
+    /// Send the audio to Telegram
+    Upload,
+    /// Save the file_id and back the upload up
+    Persist,
+}
+
+impl Stage {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fetch => "fetch",
+            Self::Artwork => "artwork",
+            Self::Tag => "tag",
+            Self::Upload => "upload",
+            Self::Persist => "persist",
+        }
+    }
+
+    /// Duration above which the stage is logged as slow (`log.slow_*`)
+    #[must_use]
+    pub fn slow_threshold(self, config: &Config) -> Option<Duration> {
+        let threshold = match self {
+            Self::Fetch => Duration::from_secs(config.slow_fetch_secs),
+            Self::Tag => Duration::from_millis(config.slow_tag_ms),
+            Self::Upload => Duration::from_secs(config.slow_upload_secs),
+            Self::Artwork | Self::Persist => Duration::ZERO,
+        };
+        (!threshold.is_zero()).then_some(threshold)
+    }
+}
+
+/// Run `stage` of `music_id` in its own span, logging how long it took and
+/// warning when it exceeded its `log.slow_*` threshold
+pub async fn timed<F: Future>(
+    config: &Config,
+    stage: Stage,
+    music_id: u64,
+    future: F,
+) -> F::Output {
+    let start = Instant::now();
+    let output = future
+        .instrument(tracing::info_span!("stage", stage = stage.name(), music_id))
+        .await;
+    let elapsed = start.elapsed();
+    match stage.slow_threshold(config) {
+        Some(threshold) if elapsed > threshold => tracing::warn!(
+            stage = stage.name(),
+            music_id,
+            duration_ms = elapsed.as_millis() as u64,
+            threshold_ms = threshold.as_millis() as u64,
+            "Slow stage {} of music_id {}: {:.2}s (threshold {:.2}s)",
+            stage.name(),
+            music_id,
+            elapsed.as_secs_f64(),
+            threshold.as_secs_f64()
+        ),
+        _ => tracing::debug!(
+            stage = stage.name(),
+            duration_ms = elapsed.as_millis() as u64,
+            "Stage {} of music_id {} took {:.2}s",
+            stage.name(),
+            music_id,
+            elapsed.as_secs_f64()
+        ),
+    }
+    output
+}
+
+/// Temporary files written by a job, removed if it is cancelled midway
+#[derive(Debug, Default)]
+pub struct JobFiles(Mutex<Vec<PathBuf>>);
+
+impl JobFiles {
+    pub fn track(&self, path: PathBuf) {
+        self.0.lock().unwrap().push(path);
+    }
+
+    pub async fn remove_all(&self) {
+        let paths = std::mem::take(&mut *self.0.lock().unwrap());
+        for path in paths {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => tracing::info!("Removed {} of a timed out job", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// Time a job spent queued for a download permit or lock, which does not
+/// count against `download.job_timeout_secs`
+#[derive(Debug, Default)]
+pub struct QueueTime(Mutex<Waits>);
+
+#[derive(Debug, Default)]
+struct Waits {
+    total: Duration,
+    since: Option<Instant>,
+}
+
+impl QueueTime {
+    /// Await `future`, counting the time as queue time
+    pub async fn wait<F: Future>(&self, future: F) -> F::Output {
+        self.0.lock().unwrap().since = Some(Instant::now());
+        let output = future.await;
+        let mut waits = self.0.lock().unwrap();
+        if let Some(since) = waits.since.take() {
+            waits.total += since.elapsed();
+        }
+        output
+    }
+
+    /// Queue time so far, including a wait in progress
+    #[must_use]
+    pub fn waited(&self) -> Duration {
+        let waits = self.0.lock().unwrap();
+        waits.total + waits.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+/// Run `work` for at most `budget` of time not spent in `queue`, returning
+/// `None` when it ran out
+pub async fn run_with_budget<F: Future>(
+    budget: Duration,
+    queue: &QueueTime,
+    work: F,
+) -> Option<F::Output> {
+    let start = tokio::time::Instant::now();
+    // Boxed: `work` is the whole download pipeline
+    let mut work = Box::pin(work);
+    loop {
+        let deadline = start + budget + queue.waited();
+        if let Ok(output) = tokio::time::timeout_at(deadline, &mut work).await {
+            return Some(output);
+        }
+        // Still within budget when part of the time was spent queueing
+        if tokio::time::Instant::now() >= start + budget + queue.waited() {
+            return None;
+        }
+    }
+}
+
+/// Which cover images a job downloads and where they go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverPolicy {
+    pub download_original: bool,
+    pub download_thumbnail: bool,
+    pub embed_cover: bool,
+}
+
+impl CoverPolicy {
+    #[must_use]
+    pub fn for_mode(cover_mode: CoverMode) -> Self {
+        let download_original = matches!(cover_mode, CoverMode::Original | CoverMode::Both);
+        Self {
+            download_original,
+            download_thumbnail: matches!(cover_mode, CoverMode::Thumbnail | CoverMode::Both),
+            embed_cover: download_original,
+        }
+    }
+}
+
+/// The song being delivered
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    pub song_detail: &'a SongDetail,
+    pub song_url: &'a SongUrl,
+    /// Trial clip of a paid song (`download.preview_fallback`)
+    pub preview: bool,
+}
+
+impl Source<'_> {
+    #[must_use]
+    pub fn file_ext(&self) -> &'static str {
+        if self.song_url.url.contains(".flac") {
+            "flac"
+        } else {
+            "mp3"
+        }
+    }
+
+    #[must_use]
+    pub fn artists(&self) -> String {
+        format_artists(self.song_detail.ar.as_deref().unwrap_or(&[]))
+    }
+
+    /// `Artist - Title.ext`, safe to use as a file name
+    #[must_use]
+    pub fn filename(&self) -> String {
+        clean_filename(&format!(
+            "{} - {}.{}",
+            self.artists().replace('/', ","),
+            self.song_detail.name,
+            self.file_ext()
+        ))
+    }
+
+    /// Length in seconds, of the clip for previews
+    #[must_use]
+    pub fn duration_secs(&self) -> i64 {
+        self.song_url
+            .trial_secs()
+            .unwrap_or(self.song_detail.dt.unwrap_or(0) / 1000) as i64
+    }
+}
+
+/// Output of the Fetch stage
+pub struct Fetched {
+    pub audio: AudioBuffer,
+    pub bytes: u64,
+    /// MD5 of the download, kept only when it matches the API's
+    pub md5: Option<String>,
+}
+
+/// Download the audio, refreshing an expired URL once and retrying a
+/// truncated download
+pub async fn fetch(state: &BotState, source: &Source<'_>, files: &JobFiles) -> Result<Fetched> {
+    let Source {
+        song_detail,
+        song_url,
+        preview,
+    } = *source;
+    let filename = source.filename();
+    files.track(Path::new(&state.config.cache_dir).join(&filename));
+
+    let download_start = Instant::now();
+    let mut attempt = 1;
+    let mut url = song_url.url.clone();
+    let mut url_refreshed = false;
+    let (audio, bytes, md5) = loop {
+        let response = state.music_api.download_file(&url).await?;
+
+        // Jobs that waited in the queue may hold a link that has since
+        // expired; fetch a fresh one once before giving up
+        if crate::utils::song_url_expired(response.status().as_u16()) && !url_refreshed {
+            tracing::warn!(
+                "Song URL for music_id {} rejected with HTTP {}, fetching a fresh one",
+                song_detail.id,
+                response.status()
+            );
+            url_refreshed = true;
+            let fresh = if preview {
+                state.music_api.get_preview_url(song_detail.id).await?
+            } else {
+                state
+                    .music_api
+                    .get_song_url(song_detail.id, song_url.br)
+                    .await?
+            };
+            if fresh.url.is_empty() {
+                return Err(BotError::music_api(
+                    MusicApiErrorKind::Unavailable,
+                    "歌曲链接已过期且无法重新获取",
+                ));
+            }
+            url = fresh.url;
+            continue;
+        }
+
+        if !response.status().is_success() {
+            return Err(BotError::music_api(
+                MusicApiErrorKind::Status,
+                format!("HTTP {}", response.status()),
+            ));
+        }
+
+        // Chunked or compressed responses carry no Content-Length; they are
+        // buffered in size-unknown mode, choosing memory or disk as bytes arrive
+        let content_length = match response.content_length() {
+            Some(0) => {
+                return Err(BotError::music_api(
+                    MusicApiErrorKind::Incomplete,
+                    "Empty file",
+                ));
+            }
+            Some(length) => length,
+            None => {
+                tracing::info!("No Content-Length from CDN, downloading with unknown size");
+                0
+            }
+        };
+
+        // Create audio buffer based on storage mode configuration
+        let mut audio = AudioBuffer::new(
+            &state.config,
+            content_length,
+            filename.clone(),
+            source.file_ext(),
+            &state.config.cache_dir,
+        )
+        .await?;
+
+        let mut stream = response.bytes_stream();
+        let mut downloaded = 0u64;
+        let mut hasher = md5::Context::new();
+        let chunk_size = state.config.download_chunk_size_kb * 1024;
+        let mut buffer = Vec::with_capacity(chunk_size);
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            downloaded += chunk.len() as u64;
+            hasher.consume(&chunk);
+
+            if buffer.len() + chunk.len() > chunk_size {
+                if !buffer.is_empty() {
+                    audio.write_chunk(&buffer).await?;
+                    buffer.clear();
+                }
+                if chunk.len() >= chunk_size {
+                    audio.write_chunk(&chunk).await?;
+                } else {
+                    buffer.extend_from_slice(&chunk);
+                }
+            } else {
+                buffer.extend_from_slice(&chunk);
+            }
+        }
+        if !buffer.is_empty() {
+            audio.write_chunk(&buffer).await?;
+        }
+        audio.finish().await?;
+
+        // A dropped connection can end the stream early without an error;
+        // never pass a truncated file on to tagging and upload
+        match incomplete_download(downloaded, content_length, song_url.size) {
+            None => break (audio, downloaded, format!("{:x}", hasher.finalize())),
+            Some(reason) => {
+                audio.cleanup().await.ok();
+                if attempt >= DOWNLOAD_ATTEMPTS {
+                    return Err(BotError::music_api(
+                        MusicApiErrorKind::Incomplete,
+                        format!("下载不完整: {reason}"),
+                    ));
+                }
+                tracing::warn!(
+                    "Incomplete download (attempt {}/{}): {}, retrying",
+                    attempt,
+                    DOWNLOAD_ATTEMPTS,
+                    reason
+                );
+                attempt += 1;
+            }
+        }
+    };
+    let download_duration = download_start.elapsed();
+    state.transfers.downloads.record(bytes, download_duration);
+    tracing::info!(
+        duration_ms = download_duration.as_millis() as u64,
+        bytes,
+        "Audio download completed in {:.2}s ({:.2} MB/s, mode: {})",
+        download_duration.as_secs_f64(),
+        throughput_mbps(bytes, download_duration),
+        if audio.is_memory() { "memory" } else { "disk" }
+    );
+
+    // Only a hash that matches the API's can later detect upstream replacements
+    let md5 = if song_url.md5.is_empty() || song_url.md5.eq_ignore_ascii_case(&md5) {
+        Some(md5)
+    } else {
+        tracing::warn!(
+            "Downloaded audio MD5 {} differs from API MD5 {} for music_id {}",
+            md5,
+            song_url.md5,
+            song_detail.id
+        );
+        None
+    };
+
+    Ok(Fetched { audio, bytes, md5 })
+}
+
+/// Output of the Artwork stage; a missing cover never fails the job
+#[derive(Default)]
+pub struct Artwork {
+    /// Full size cover to embed in the tags
+    pub original: Option<Vec<u8>>,
+    /// 320x320 cover shown by Telegram
+    pub thumbnail: Option<ThumbnailBuffer>,
+}
+
+/// Download the covers `policy` asks for, in parallel
+pub async fn fetch_artwork(
+    state: &BotState,
+    song_detail: &SongDetail,
+    policy: CoverPolicy,
+    files: &JobFiles,
+) -> Artwork {
+    let Some(al) = &song_detail.al else {
+        tracing::warn!("No album info found for music_id {}", song_detail.id);
+        return Artwork::default();
+    };
+    let pic_url = match al.pic_url.as_deref() {
+        Some("") => {
+            tracing::warn!("Album art URL is empty for music_id {}", song_detail.id);
+            return Artwork::default();
+        }
+        Some(pic_url) => pic_url,
+        None => {
+            tracing::warn!("No pic_url found in album for music_id {}", song_detail.id);
+            return Artwork::default();
+        }
+    };
+    tracing::info!(
+        "Starting album art download for music_id {} ({:?}), pic_url: {}",
+        song_detail.id,
+        policy,
+        pic_url
+    );
+
+    let original = async {
+        if !policy.download_original {
+            return None;
+        }
+        match state.music_api.download_album_art_original(pic_url).await {
+            Ok(data) => {
+                tracing::info!(
+                    "Downloaded original album art for music_id {} ({} bytes)",
+                    song_detail.id,
+                    data.len()
+                );
+                Some(data)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to download original album art for music_id {}: {}",
+                    song_detail.id,
+                    e
+                );
+                None
+            }
+        }
+    };
+    let thumbnail = async {
+        if !policy.download_thumbnail {
+            return None;
+        }
+        match state.music_api.download_album_art_data(pic_url).await {
+            Ok(data) => {
+                tracing::info!(
+                    "Downloaded thumbnail for music_id {} ({} bytes)",
+                    song_detail.id,
+                    data.len()
+                );
+                let thumb_filename = format!(
+                    "thumb_{}_{}.jpg",
+                    song_detail.id,
+                    chrono::Utc::now().timestamp()
+                );
+                files.track(Path::new(&state.config.cache_dir).join(&thumb_filename));
+                ThumbnailBuffer::new(
+                    &state.config,
+                    data,
+                    &state.config.cache_dir,
+                    &thumb_filename,
+                )
+                .await
+                .ok()
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to download thumbnail for music_id {}: {}",
+                    song_detail.id,
+                    e
+                );
+                None
+            }
+        }
+    };
+
+    let (original, thumbnail) = tokio::join!(original, thumbnail);
+    Artwork {
+        original,
+        thumbnail,
+    }
+}
+
+/// Output of the Tag stage
+pub struct Tagged {
+    pub audio: AudioBuffer,
+    /// Size with tags, as uploaded
+    pub size: u64,
+}
+
+/// Reject empty or error-page downloads and embed tags (and `cover`);
+/// failing to write tags only logs a warning
+pub async fn tag(
+    mut audio: AudioBuffer,
+    source: &Source<'_>,
+    cover: Option<&[u8]>,
+) -> Result<Tagged> {
+    let size = audio.size().await;
+    if size < MIN_AUDIO_BYTES {
+        audio.cleanup().await.ok();
+        let message = if size == 0 {
+            "文件为空".to_string()
+        } else {
+            format!("文件太小({size} bytes)")
+        };
+        return Err(BotError::music_api(MusicApiErrorKind::Incomplete, message));
+    }
+    tracing::info!("File validation passed: {} bytes", size);
+
+    let cover_label = if cover.is_some() { "original" } else { "none" };
+    if source.file_ext() == "flac" {
+        tracing::info!("Adding FLAC metadata (cover: {})", cover_label);
+        match audio.add_flac_metadata(source.song_detail, cover) {
+            Ok(()) => tracing::info!("FLAC metadata added successfully"),
+            Err(e) => tracing::warn!("Failed to add FLAC metadata: {}", e),
+        }
+    } else {
+        tracing::info!("Adding ID3 tags to MP3 (cover: {})", cover_label);
+        match audio.add_id3_tags(source.song_detail, cover) {
+            Ok(()) => tracing::info!("MP3 tags added successfully"),
+            Err(e) => tracing::warn!("Failed to add MP3 tags: {}", e),
+        }
+    }
+
+    let size = audio.size().await;
+    if size == 0 {
+        audio.cleanup().await.ok();
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Other,
+            "Audio file is empty after processing",
+        ));
+    }
+    Ok(Tagged { audio, size })
+}
+
+/// Fetch, Artwork and Tag together: the song ready for the Upload stage
+pub struct Prepared {
+    pub tagged: Tagged,
+    pub thumbnail: Option<ThumbnailBuffer>,
+    /// MD5 of the download, kept only when it matches the API's
+    pub md5: Option<String>,
+}
+
+/// Download the audio and covers in parallel, then tag the audio
+pub async fn prepare(
+    state: &BotState,
+    source: &Source<'_>,
+    policy: CoverPolicy,
+    files: &JobFiles,
+) -> Result<Prepared> {
+    let music_id = source.song_detail.id;
+    let (fetched, artwork) = tokio::join!(
+        timed(
+            &state.config,
+            Stage::Fetch,
+            music_id,
+            fetch(state, source, files)
+        ),
+        timed(
+            &state.config,
+            Stage::Artwork,
+            music_id,
+            fetch_artwork(state, source.song_detail, policy, files)
+        ),
+    );
+    let cover = artwork.original.as_deref().filter(|_| policy.embed_cover);
+    let tagged = match fetched {
+        Ok(fetched) => timed(
+            &state.config,
+            Stage::Tag,
+            music_id,
+            tag(fetched.audio, source, cover),
+        )
+        .await
+        .map(|tagged| (tagged, fetched.md5)),
+        Err(e) => Err(e),
+    };
+    match tagged {
+        Ok((tagged, md5)) => Ok(Prepared {
+            tagged,
+            thumbnail: artwork.thumbnail,
+            md5,
+        }),
+        Err(e) => {
+            if let Some(thumbnail) = artwork.thumbnail {
+                thumbnail.cleanup().await.ok();
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Bitrate (bps) from the tagged file's size, which is what the listener
+/// gets; the API's `br` is often the format's theoretical maximum
+#[must_use]
+pub fn actual_bitrate(size: u64, duration_secs: i64, api_bitrate: u64) -> i64 {
+    if duration_secs > 0 {
+        (8 * size as i64) / duration_secs
+    } else {
+        api_bitrate as i64
+    }
+}
+
+/// Cache record of a tagged song; the caller fills in who asked for it
+#[must_use]
+pub fn describe(source: &Source<'_>, tagged: &Tagged, md5: Option<String>) -> SongInfo {
+    let duration = source.duration_secs();
+    let bit_rate = actual_bitrate(tagged.size, duration, source.song_url.br);
+    tracing::info!(
+        "Bitrate - API: {} bps, Calculated from file: {} bps (duration: {}s)",
+        source.song_url.br,
+        bit_rate,
+        duration
+    );
+    SongInfo {
+        music_id: source.song_detail.id as i64,
+        song_name: source.song_detail.name.clone(),
+        song_artists: source.artists(),
+        song_album: source
+            .song_detail
+            .al
+            .as_ref()
+            .map_or_else(|| "Unknown Album".to_string(), |al| al.name.clone()),
+        file_ext: source.file_ext().to_string(),
+        music_size: tagged.size as i64,
+        bit_rate,
+        duration,
+        audio_md5: md5,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        ..Default::default()
+    }
+}
+
+/// The audio message to send in the Upload stage
+pub struct AudioMessage {
+    pub chat_id: ChatId,
+    pub reply_to: Option<MessageId>,
+    pub caption: String,
+    pub keyboard: Option<InlineKeyboardMarkup>,
+    pub title: String,
+    pub performer: String,
+    pub duration_secs: u32,
+    pub silent: bool,
+}
+
+/// Output of the Upload stage
+pub struct Uploaded {
+    pub message: Message,
+    pub file_id: Option<String>,
+}
+
+/// file_id of the audio in a sent message
+#[must_use]
+pub fn audio_file_id(message: &Message) -> Option<String> {
+    if let MessageKind::Common(common) = &message.kind
+        && let MediaKind::Audio(audio) = &common.media_kind
+    {
+        Some(audio.audio.file.id.to_string())
+    } else {
+        None
+    }
+}
+
+/// Send the tagged audio with `upload_bot`, counting it as in flight
+pub async fn upload(
+    state: &BotState,
+    upload_bot: &Bot,
+    tagged: Tagged,
+    thumbnail: Option<ThumbnailBuffer>,
+    message: AudioMessage,
+) -> Result<Uploaded> {
+    let Tagged { audio, size } = tagged;
+    tracing::info!(
+        "Sending audio file: {} ({:.2} MB)",
+        audio.filename(),
+        size as f64 / 1024.0 / 1024.0
+    );
+
+    let in_flight = state
+        .upload_counters
+        .in_flight
+        .fetch_add(1, Ordering::Relaxed)
+        + 1;
+    let peak_in_flight = update_peak(&state.upload_counters.peak_in_flight, in_flight);
+    let upload_start = Instant::now();
+    // into_input_file consumes the buffers, so nothing is left to clean up
+    let mut request = upload_bot
+        .send_audio(message.chat_id, audio.into_input_file())
+        .caption(message.caption)
+        .title(message.title)
+        .performer(message.performer)
+        .duration(message.duration_secs)
+        .disable_notification(message.silent);
+    if let Some(keyboard) = message.keyboard {
+        request = request.reply_markup(keyboard);
+    }
+    if let Some(reply_to) = message.reply_to {
+        request = request.reply_parameters(ReplyParameters::new(reply_to));
+    }
+    if let Some(thumbnail) = thumbnail {
+        request = request.thumbnail(thumbnail.into_input_file());
+    }
+    let result = request.await;
+
+    let upload_duration = upload_start.elapsed();
+    let in_flight_after = state
+        .upload_counters
+        .in_flight
+        .fetch_sub(1, Ordering::Relaxed)
+        - 1;
+    if in_flight_after == 0 {
+        state.activity.uploads_drained();
+    }
+    let upload_mbps = throughput_mbps(size, upload_duration);
+    match result {
+        Ok(sent) => {
+            state.transfers.uploads.record(size, upload_duration);
+            tracing::info!(
+                duration_ms = upload_duration.as_millis() as u64,
+                bytes = size,
+                "Upload completed in {:.2}s ({:.2} MB/s, inflight: {}, peak: {})",
+                upload_duration.as_secs_f64(),
+                upload_mbps,
+                in_flight_after,
+                peak_in_flight
+            );
+            Ok(Uploaded {
+                file_id: audio_file_id(&sent),
+                message: sent,
+            })
+        }
+        Err(e) => {
+            tracing::warn!(
+                duration_ms = upload_duration.as_millis() as u64,
+                bytes = size,
+                "Upload failed after {:.2}s ({:.2} MB/s, inflight: {}, peak: {}): {}",
+                upload_duration.as_secs_f64(),
+                upload_mbps,
+                in_flight_after,
+                peak_in_flight,
+                e
+            );
+            if is_file_too_large(&e) {
+                return Err(BotError::FileTooLarge {
+                    size,
+                    limit: upload_limit(&state.config.bot_api),
+                });
+            }
+            Err(e.into())
+        }
+    }
+}
+
+/// Where a freshly uploaded song is cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheTarget {
+    /// Previews and explicitly chosen bitrates are not cached
+    None,
+    /// The song's cached copy, served to everyone
+    Song,
+    /// A copy for users limited to lossy quality (`premium.lossless_only`),
+    /// kept apart from the song's cached copy
+    LossyCopy,
+}
+
+impl CacheTarget {
+    /// `lossy_only` is set when `premium.lossless_only` capped the bitrate
+    #[must_use]
+    pub fn for_request(preview: bool, bitrate: Option<u64>, lossy_only: bool) -> Self {
+        if preview {
+            Self::None
+        } else if lossy_only {
+            Self::LossyCopy
+        } else if bitrate.is_none() {
+            Self::Song
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Save a freshly uploaded song as `target` says
+pub async fn cache_upload(
+    state: &BotState,
+    bot: &Bot,
+    song_info: &SongInfo,
+    sent: &Message,
+    target: CacheTarget,
+) -> Result<()> {
+    match target {
+        CacheTarget::None => Ok(()),
+        CacheTarget::Song => persist(state, bot, song_info, sent).await,
+        CacheTarget::LossyCopy => {
+            state
+                .database
+                .save_lossy_copy(song_info, state.bot_id)
+                .await
+        }
+    }
+}
+
+/// Save a freshly uploaded song with this bot's file_ids and copy it to the
+/// backup channel
+pub async fn persist(
+    state: &BotState,
+    bot: &Bot,
+    song_info: &SongInfo,
+    sent: &Message,
+) -> Result<()> {
+    save_uploaded_song(state, song_info).await?;
+    backup_upload(bot, state, sent, song_info.music_id).await;
+    Ok(())
+}
+
+async fn save_uploaded_song(state: &BotState, song_info: &SongInfo) -> Result<()> {
+    state.database.save_song_info(song_info).await?;
+    if let Some(file_id) = &song_info.file_id {
+        state
+            .database
+            .set_bot_file_id(
+                song_info.music_id,
+                state.bot_id,
+                file_id,
+                song_info.thumb_file_id.as_deref(),
+            )
+            .await?;
+        if let Some(hot_cache) = &state.hot_cache
+            && let Err(e) = hot_cache.put(song_info, state.bot_id).await
+        {
+            tracing::warn!(
+                "Failed to cache music_id {} in redis: {}",
+                song_info.music_id,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Copy a freshly uploaded audio to the backup channel and remember its link
+///
+/// Failures are only logged: the backup is an archive, not part of delivery.
+async fn backup_upload(bot: &Bot, state: &BotState, sent_msg: &Message, music_id: i64) {
+    let channel_id = state.config.backup_channel_id;
+    if channel_id == 0 {
+        return;
+    }
+
+    let copied = match bot
+        .copy_message(ChatId(channel_id), sent_msg.chat.id, sent_msg.id)
+        .disable_notification(true)
+        .await
+    {
+        Ok(copied) => copied,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to copy music_id {} to backup channel: {}",
+                music_id,
+                e
+            );
+            return;
+        }
+    };
+
+    let Some(link) = channel_message_link(channel_id, copied.0) else {
+        return;
+    };
+    if let Err(e) = state.database.set_backup_message(music_id, &link).await {
+        tracing::warn!(
+            "Failed to save backup link for music_id {}: {}",
+            music_id,
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        CacheTarget, CoverPolicy, QueueTime, Source, Stage, actual_bitrate, run_with_budget,
+    };
+    use crate::config::{Config, CoverMode};
+    use crate::database::{Database, SongInfo};
+    use crate::music_api::{Artist, FreeTrialInfo, SongDetail, SongUrl};
+
+    #[test]
+    fn cover_modes_pick_downloads() {
+        let both = CoverPolicy::for_mode(CoverMode::Both);
+        assert!(both.download_original && both.download_thumbnail && both.embed_cover);
+        let thumbnail = CoverPolicy::for_mode(CoverMode::Thumbnail);
+        assert!(!thumbnail.download_original && thumbnail.download_thumbnail);
+        assert!(!thumbnail.embed_cover);
+        let none = CoverPolicy::for_mode(CoverMode::None);
+        assert!(!none.download_original && !none.download_thumbnail);
+    }
+
+    #[tokio::test]
+    async fn queue_time_does_not_count_against_the_budget() {
+        let budget = Duration::from_millis(50);
+        let queue = QueueTime::default();
+        let queued = run_with_budget(budget, &queue, async {
+            queue
+                .wait(tokio::time::sleep(Duration::from_millis(100)))
+                .await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        })
+        .await;
+        assert!(queued.is_some());
+        assert!(queue.waited() >= Duration::from_millis(100));
+
+        let stalled = run_with_budget(budget, &QueueTime::default(), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        })
+        .await;
+        assert!(stalled.is_none());
+    }
+
+    #[test]
+    fn slow_thresholds_follow_config() {
+        let config = Config {
+            slow_tag_ms: 0,
+            ..Config::default()
+        };
+        assert_eq!(
+            Stage::Upload.slow_threshold(&config),
+            Some(Duration::from_mins(2))
+        );
+        assert_eq!(
+            Stage::Fetch.slow_threshold(&config),
+            Some(Duration::from_mins(1))
+        );
+        assert_eq!(Stage::Tag.slow_threshold(&config), None);
+        assert_eq!(Stage::Persist.slow_threshold(&config), None);
+    }
+
+    #[test]
+    fn source_names_the_file_and_measures_the_clip() {
+        let song_detail = SongDetail {
+            id: 1,
+            name: "Song".to_string(),
+            dt: Some(200_000),
+            ar: Some(vec![Artist {
+                id: 2,
+                name: "A/B".to_string(),
+            }]),
+            al: None,
+        };
+        let mut song_url = SongUrl {
+            id: 1,
+            url: "https://cdn.example.com/1.flac?x=1".to_string(),
+            br: 999_000,
+            size: 0,
+            md5: String::new(),
+            format: "flac".to_string(),
+            free_trial_info: None,
+        };
+        let source = Source {
+            song_detail: &song_detail,
+            song_url: &song_url,
+            preview: false,
+        };
+        assert_eq!(source.file_ext(), "flac");
+        assert_eq!(source.filename(), "A,B - Song.flac");
+        assert_eq!(source.duration_secs(), 200);
+
+        song_url.free_trial_info = Some(FreeTrialInfo { start: 10, end: 40 });
+        let preview = Source {
+            song_detail: &song_detail,
+            song_url: &song_url,
+            preview: true,
+        };
+        assert_eq!(preview.duration_secs(), 30);
+    }
+
+    #[test]
+    fn bitrate_comes_from_the_file_size() {
+        assert_eq!(actual_bitrate(24_000_000, 200, 999_000), 960_000);
+        assert_eq!(actual_bitrate(24_000_000, 0, 999_000), 999_000);
+    }
+
+    #[tokio::test]
+    async fn lossy_copies_leave_the_cached_song_untouched() {
+        let path =
+            std::env::temp_dir().join(format!("music163bot-pipeline-{}.db", uuid::Uuid::new_v4()));
+        let config = Config {
+            database: path.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        let database = Database::new(&config).await.unwrap();
+        let flac = SongInfo {
+            music_id: 42,
+            song_name: "Song".to_string(),
+            file_ext: "flac".to_string(),
+            music_size: 30_000_000,
+            bit_rate: 999_000,
+            file_id: Some("flac-file".to_string()),
+            ..SongInfo::default()
+        };
+        database.save_song_info(&flac).await.unwrap();
+        database
+            .set_bot_file_id(42, 1, "flac-file", None)
+            .await
+            .unwrap();
+
+        // A non-premium collection track under `premium.lossless_only`
+        let target = CacheTarget::for_request(false, Some(320_000), true);
+        assert_eq!(target, CacheTarget::LossyCopy);
+        let mp3 = SongInfo {
+            file_ext: "mp3".to_string(),
+            music_size: 8_000_000,
+            bit_rate: 320_000,
+            file_id: Some("mp3-file".to_string()),
+            ..flac.clone()
+        };
+        database.save_lossy_copy(&mp3, 1).await.unwrap();
+
+        let cached = database.get_song_for_bot(42, 1).await.unwrap().unwrap();
+        assert_eq!(cached.bit_rate, 999_000);
+        assert_eq!(cached.file_id.as_deref(), Some("flac-file"));
+        let lossy = database.get_lossy_copy(42, 1).await.unwrap().unwrap();
+        assert_eq!(lossy.bit_rate, 320_000);
+        assert_eq!(lossy.file_id.as_deref(), Some("mp3-file"));
+
+        assert_eq!(
+            CacheTarget::for_request(false, None, false),
+            CacheTarget::Song
+        );
+        assert_eq!(
+            CacheTarget::for_request(false, Some(128_000), false),
+            CacheTarget::None
+        );
+        assert_eq!(
+            CacheTarget::for_request(true, Some(320_000), true),
+            CacheTarget::None
+        );
+
+        drop(database);
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{suffix}", path.display())).ok();
+        }
+    }
+}
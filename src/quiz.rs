@@ -0,0 +1,179 @@
+//! Music quiz mini-game (`/quiz`)
+//!
+//! The bot posts a short clip or a blurred cover of a random cached song and
+//! the first member to send the title scores a point. Running rounds live in
+//! memory; scores are stored per chat in the `quiz_scores` table.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::str::FromStr;
+
+use image::ImageFormat;
+use image::imageops::FilterType;
+use tokio::sync::Mutex;
+
+use crate::error::{BotError, Result};
+
+/// Edge length (px) the cover is shrunk to before blurring
+const BLUR_SIZE: u32 = 48;
+const BLUR_SIGMA: f32 = 4.0;
+const OUTPUT_SIZE: u32 = 512;
+
+/// What the players get to see or hear
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    Clip,
+    Cover,
+}
+
+impl FromStr for Hint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clip" | "audio" => Ok(Self::Clip),
+            "cover" | "image" => Ok(Self::Cover),
+            _ => Err(anyhow::anyhow!("Unknown quiz hint: {s}")),
+        }
+    }
+}
+
+/// A running round
+#[derive(Debug, Clone)]
+pub struct Round {
+    /// Distinguishes rounds of the same chat for the timeout task
+    pub id: uuid::Uuid,
+    pub music_id: i64,
+    pub title: String,
+    pub artists: String,
+}
+
+impl Round {
+    #[must_use]
+    pub fn new(music_id: i64, title: &str, artists: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            music_id,
+            title: title.to_string(),
+            artists: artists.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Quiz {
+    rounds: Mutex<HashMap<i64, Round>>,
+}
+
+impl Quiz {
+    /// Start `round` unless the chat already has one running
+    pub async fn start(&self, chat_id: i64, round: Round) -> bool {
+        let mut rounds = self.rounds.lock().await;
+        if rounds.contains_key(&chat_id) {
+            return false;
+        }
+        rounds.insert(chat_id, round);
+        true
+    }
+
+    /// End the chat's round if `guess` names its song
+    pub async fn guess(&self, chat_id: i64, guess: &str) -> Option<Round> {
+        let mut rounds = self.rounds.lock().await;
+        if !is_correct_guess(guess, &rounds.get(&chat_id)?.title) {
+            return None;
+        }
+        rounds.remove(&chat_id)
+    }
+
+    /// End round `id` if nobody guessed it yet
+    pub async fn expire(&self, chat_id: i64, id: uuid::Uuid) -> Option<Round> {
+        let mut rounds = self.rounds.lock().await;
+        if rounds.get(&chat_id)?.id != id {
+            return None;
+        }
+        rounds.remove(&chat_id)
+    }
+
+    /// Drop a round whose hint could not be posted
+    pub async fn cancel(&self, chat_id: i64) {
+        self.rounds.lock().await.remove(&chat_id);
+    }
+}
+
+/// Lowercased letters and digits of a title, without bracketed suffixes such
+/// as "(Live)" or "（伴奏）"
+fn normalize_title(title: &str) -> String {
+    let mut depth = 0usize;
+    let mut normalized = String::new();
+    for c in title.chars() {
+        match c {
+            '(' | '（' | '[' | '【' => depth += 1,
+            ')' | '）' | ']' | '】' => depth = depth.saturating_sub(1),
+            c if depth == 0 && c.is_alphanumeric() => normalized.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+/// Whether `guess` names the song, ignoring case, punctuation and bracketed suffixes
+#[must_use]
+pub fn is_correct_guess(guess: &str, title: &str) -> bool {
+    let title = normalize_title(title);
+    !title.is_empty() && normalize_title(guess) == title
+}
+
+/// Pseudo-random number in `0..bound` (`bound` > 0)
+#[must_use]
+pub fn random_below(bound: u64) -> u64 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value) % bound.max(1)
+}
+
+/// Blur a cover image beyond recognition of any text, returning a JPEG
+pub fn blur_cover(cover: &[u8]) -> Result<Vec<u8>> {
+    let cover = image::load_from_memory(cover)
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Failed to decode cover: {e}")))?;
+    let blurred = cover
+        .resize_to_fill(BLUR_SIZE, BLUR_SIZE, FilterType::Triangle)
+        .blur(BLUR_SIGMA)
+        .resize_exact(OUTPUT_SIZE, OUTPUT_SIZE, FilterType::Triangle)
+        .to_rgb8();
+
+    let mut output = Cursor::new(Vec::new());
+    blurred
+        .write_to(&mut output, ImageFormat::Jpeg)
+        .map_err(|e| BotError::Other(anyhow::anyhow!("Failed to encode cover: {e}")))?;
+    Ok(output.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quiz, Round, is_correct_guess};
+
+    #[test]
+    fn accepts_loose_title_guesses() {
+        assert!(is_correct_guess("晴天", "晴天"));
+        assert!(is_correct_guess("  Shape of you!", "Shape Of You"));
+        assert!(is_correct_guess("光年之外", "光年之外 (Live)"));
+        assert!(is_correct_guess("起风了", "起风了（伴奏）"));
+        assert!(!is_correct_guess("晴", "晴天"));
+        assert!(!is_correct_guess("", "(Intro)"));
+    }
+
+    #[tokio::test]
+    async fn first_correct_guess_ends_the_round() {
+        let quiz = Quiz::default();
+        let round = Round::new(1, "晴天", "周杰伦");
+        let id = round.id;
+        assert!(quiz.start(-100, round).await);
+        assert!(!quiz.start(-100, Round::new(2, "稻香", "周杰伦")).await);
+
+        assert!(quiz.guess(-100, "七里香").await.is_none());
+        assert_eq!(quiz.guess(-100, "晴天").await.unwrap().music_id, 1);
+        assert!(quiz.guess(-100, "晴天").await.is_none());
+        assert!(quiz.expire(-100, id).await.is_none());
+    }
+}
@@ -0,0 +1,288 @@
+//! Scrobbling delivered songs to Last.fm and ListenBrainz (`/scrobble`)
+//!
+//! Users link their own accounts; the Last.fm session key or ListenBrainz
+//! token is stored AES-256-GCM encrypted with a key derived from
+//! `scrobble.secret_key`, so a leaked database does not leak credentials.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::error::{BotError, Result};
+use crate::http_client::{self, Profile};
+
+const LASTFM_API: &str = "https://ws.audioscrobbler.com/2.0/";
+const LISTENBRAINZ_API: &str = "https://api.listenbrainz.org/1/submit-listens";
+const NONCE_LEN: usize = 12;
+
+/// Scrobbling service a user can link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    LastFm,
+    ListenBrainz,
+}
+
+impl Service {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LastFm => "lastfm",
+            Self::ListenBrainz => "listenbrainz",
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::LastFm => "Last.fm",
+            Self::ListenBrainz => "ListenBrainz",
+        }
+    }
+}
+
+impl FromStr for Service {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lastfm" | "last.fm" => Ok(Self::LastFm),
+            "listenbrainz" => Ok(Self::ListenBrainz),
+            _ => Err(anyhow::anyhow!("Unknown scrobbling service: {s}")),
+        }
+    }
+}
+
+/// A delivered song to scrobble
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub duration_secs: u64,
+}
+
+/// Encrypts stored credentials; values are base64 of nonce || ciphertext
+///
+/// The key is the plain SHA-256 of `scrobble.secret_key` without a slow KDF,
+/// so the secret must be long and random (e.g. `openssl rand -hex 32`), not
+/// a password.
+pub struct CredentialCipher {
+    cipher: Aes256Gcm,
+}
+
+impl CredentialCipher {
+    #[must_use]
+    pub fn new(secret: &str) -> Self {
+        let key = Sha256::digest(secret.as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(&key),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| BotError::Other(anyhow::anyhow!("Failed to encrypt credential")))?;
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        Ok(BASE64.encode(data))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<String> {
+        let data = BASE64
+            .decode(encoded)
+            .map_err(|e| BotError::Parse(format!("Invalid stored credential: {e}")))?;
+        if data.len() <= NONCE_LEN {
+            return Err(BotError::Parse(
+                "Stored credential is too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                BotError::Other(anyhow::anyhow!(
+                    "Failed to decrypt credential (was scrobble.secret_key changed?)"
+                ))
+            })?;
+        String::from_utf8(plaintext).map_err(|e| BotError::Parse(e.to_string()))
+    }
+}
+
+/// Last.fm `api_sig`: MD5 of the sorted `key value` pairs followed by the secret
+fn lastfm_signature(params: &BTreeMap<&str, String>, api_secret: &str) -> String {
+    let mut payload = String::new();
+    for (key, value) in params {
+        payload.push_str(key);
+        payload.push_str(value);
+    }
+    payload.push_str(api_secret);
+    format!("{:x}", md5::compute(payload))
+}
+
+#[derive(Debug, Deserialize)]
+struct LastFmSessionResponse {
+    session: Option<LastFmSession>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LastFmSession {
+    key: String,
+}
+
+pub struct Scrobbler {
+    client: reqwest::Client,
+    pub cipher: CredentialCipher,
+    lastfm_api_key: String,
+    lastfm_api_secret: String,
+}
+
+impl Scrobbler {
+    /// `None` when `scrobble.secret_key` is not set
+    #[must_use]
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.scrobble_secret_key.is_empty() {
+            return None;
+        }
+        Some(Self {
+            client: http_client::build(config, Profile::Api).unwrap_or_default(),
+            cipher: CredentialCipher::new(&config.scrobble_secret_key),
+            lastfm_api_key: config.lastfm_api_key.clone(),
+            lastfm_api_secret: config.lastfm_api_secret.clone(),
+        })
+    }
+
+    /// Whether the operator configured a Last.fm API account
+    #[must_use]
+    pub fn lastfm_available(&self) -> bool {
+        !self.lastfm_api_key.is_empty() && !self.lastfm_api_secret.is_empty()
+    }
+
+    async fn lastfm_call(&self, mut params: BTreeMap<&str, String>) -> Result<reqwest::Response> {
+        params.insert("api_key", self.lastfm_api_key.clone());
+        let signature = lastfm_signature(&params, &self.lastfm_api_secret);
+        params.insert("api_sig", signature);
+        params.insert("format", "json".to_string());
+        Ok(self.client.post(LASTFM_API).form(&params).send().await?)
+    }
+
+    /// Exchange a Last.fm username and password for a session key (the
+    /// password itself is never stored)
+    pub async fn lastfm_session(&self, username: &str, password: &str) -> Result<String> {
+        let params = BTreeMap::from([
+            ("method", "auth.getMobileSession".to_string()),
+            ("username", username.to_string()),
+            ("password", password.to_string()),
+        ]);
+        let response: LastFmSessionResponse = self.lastfm_call(params).await?.json().await?;
+        match response.session {
+            Some(session) => Ok(session.key),
+            None => Err(BotError::Other(anyhow::anyhow!(
+                "Last.fm login failed: {}",
+                response.message.unwrap_or_default()
+            ))),
+        }
+    }
+
+    /// Submit a listen with a decrypted credential (Last.fm session key or
+    /// ListenBrainz user token)
+    pub async fn submit(
+        &self,
+        service: Service,
+        credential: &str,
+        track: &Track,
+        listened_at: i64,
+    ) -> Result<()> {
+        let response = match service {
+            Service::LastFm => {
+                let params = BTreeMap::from([
+                    ("method", "track.scrobble".to_string()),
+                    ("sk", credential.to_string()),
+                    ("artist", track.artist.clone()),
+                    ("track", track.title.clone()),
+                    ("album", track.album.clone()),
+                    ("duration", track.duration_secs.to_string()),
+                    ("timestamp", listened_at.to_string()),
+                ]);
+                self.lastfm_call(params).await?
+            }
+            Service::ListenBrainz => {
+                let body = serde_json::json!({
+                    "listen_type": "single",
+                    "payload": [{
+                        "listened_at": listened_at,
+                        "track_metadata": {
+                            "artist_name": track.artist,
+                            "track_name": track.title,
+                            "release_name": track.album,
+                            "additional_info": {
+                                "duration_ms": track.duration_secs * 1000,
+                                "submission_client": "music163bot-rust",
+                            },
+                        },
+                    }],
+                });
+                self.client
+                    .post(LISTENBRAINZ_API)
+                    .header("Authorization", format!("Token {credential}"))
+                    .json(&body)
+                    .send()
+                    .await?
+            }
+        };
+        if !response.status().is_success() {
+            return Err(BotError::Other(anyhow::anyhow!(
+                "{} returned HTTP {}",
+                service.label(),
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{CredentialCipher, Service, lastfm_signature};
+
+    #[test]
+    fn encrypts_credentials() {
+        let cipher = CredentialCipher::new("secret");
+        let encrypted = cipher.encrypt("session-key").unwrap();
+        assert!(!encrypted.contains("session-key"));
+        assert_ne!(encrypted, cipher.encrypt("session-key").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "session-key");
+        assert!(CredentialCipher::new("other").decrypt(&encrypted).is_err());
+        assert!(cipher.decrypt("not base64!").is_err());
+    }
+
+    #[test]
+    fn signs_lastfm_requests() {
+        let params = BTreeMap::from([
+            ("method", "auth.getMobileSession".to_string()),
+            ("api_key", "key".to_string()),
+        ]);
+        assert_eq!(
+            lastfm_signature(&params, "secret"),
+            format!(
+                "{:x}",
+                md5::compute("api_keykeymethodauth.getMobileSessionsecret")
+            )
+        );
+        assert_eq!("Last.fm".parse::<Service>().unwrap(), Service::LastFm);
+        assert!("spotify".parse::<Service>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Secrets kept outside `config.ini`
+//!
+//! Every key in `SECRET_KEYS` can instead be read from a file named by
+//! `<key>_file` (Docker/Kubernetes secrets), or from the OS keyring with
+//! `<key> = keyring:<name>`. Both are resolved right after the INI file is
+//! read, so the rest of the bot only ever sees plain values.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use anyhow::{Result, anyhow};
+
+/// Config keys that hold credentials
+pub const SECRET_KEYS: &[&str] = &[
+    "bot.token",
+    "music.music_u",
+    "s3.access_key",
+    "s3.secret_key",
+    "webdav.password",
+    "api.token",
+    "api.admin_token",
+    "scrobble.secret_key",
+    "scrobble.lastfm_api_secret",
+    "telegraph.access_token",
+    "sentry.dsn",
+];
+
+/// Keyring service the secrets are stored under
+pub const KEYRING_SERVICE: &str = "music163bot-rust";
+
+const KEYRING_PREFIX: &str = "keyring:";
+
+/// Whether `key` is the `<secret>_file` companion of a secret key
+#[must_use]
+pub fn is_secret_file_key(key: &str) -> bool {
+    key.strip_suffix("_file")
+        .is_some_and(|secret| SECRET_KEYS.contains(&secret))
+}
+
+/// Replace `<key>_file` and `keyring:` references in `values` by the secrets
+/// they point to, reporting every unreadable one together
+pub fn resolve<S: std::hash::BuildHasher>(values: &mut HashMap<String, String, S>) -> Result<()> {
+    let mut problems = Vec::new();
+    for key in SECRET_KEYS {
+        let file_key = format!("{key}_file");
+        let path = values
+            .get(&file_key)
+            .filter(|path| !path.is_empty())
+            .cloned();
+        let value = values.get(*key).filter(|value| !value.is_empty()).cloned();
+
+        let resolved = match (path, value) {
+            (Some(_), Some(_)) => Err(anyhow!("set either {key} or {file_key}, not both")),
+            (Some(path), None) => read_secret_file(&path).map(Some),
+            (None, Some(value)) => match value.strip_prefix(KEYRING_PREFIX) {
+                Some(name) => keyring_lookup(name.trim()).map(Some),
+                None => Ok(None),
+            },
+            (None, None) => Ok(None),
+        };
+        match resolved {
+            Ok(Some(secret)) => {
+                values.insert((*key).to_string(), secret);
+            }
+            Ok(None) => {}
+            Err(e) => problems.push(format!("{key}: {e}")),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(problems.join("; ")))
+    }
+}
+
+/// File contents without the trailing newline most editors and
+/// `kubectl create secret` leave behind
+fn read_secret_file(path: &str) -> Result<String> {
+    let secret = std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {path}: {e}"))?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        return Err(anyhow!("{path} is empty"));
+    }
+    Ok(secret.to_string())
+}
+
+/// Look `name` up in the OS keyring: libsecret (`secret-tool`) on Linux and
+/// the BSDs, the login keychain (`security`) on macOS
+fn keyring_lookup(name: &str) -> Result<String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args([
+            "find-generic-password",
+            "-s",
+            KEYRING_SERVICE,
+            "-a",
+            name,
+            "-w",
+        ]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", KEYRING_SERVICE, "account", name]);
+        command
+    } else {
+        return Err(anyhow!(
+            "keyring secrets are not supported on this platform"
+        ));
+    };
+
+    let output = command
+        .output()
+        .map_err(|e| anyhow!("keyring lookup of `{name}` failed: {e}"))?;
+    let secret = String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\r', '\n'])
+        .to_string();
+    if !output.status.success() || secret.is_empty() {
+        return Err(anyhow!(
+            "`{name}` not found in keyring service {KEYRING_SERVICE}"
+        ));
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{is_secret_file_key, resolve};
+
+    #[test]
+    fn reads_secrets_from_files() {
+        let path =
+            std::env::temp_dir().join(format!("music163bot-secret-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "123456:ABC-DEF\n").unwrap();
+
+        let mut values = HashMap::from([
+            ("bot.token_file".to_string(), path.display().to_string()),
+            ("music.music_u".to_string(), "cookie".to_string()),
+        ]);
+        resolve(&mut values).unwrap();
+        assert_eq!(values["bot.token"], "123456:ABC-DEF");
+        assert_eq!(values["music.music_u"], "cookie");
+
+        let mut values = HashMap::from([
+            ("bot.token".to_string(), "123456:ABC-DEF".to_string()),
+            ("bot.token_file".to_string(), path.display().to_string()),
+            (
+                "music.music_u_file".to_string(),
+                "/nonexistent/secret".to_string(),
+            ),
+        ]);
+        let error = resolve(&mut values).unwrap_err().to_string();
+        assert!(error.contains("bot.token: set either bot.token or bot.token_file"));
+        assert!(error.contains("music.music_u: cannot read /nonexistent/secret"));
+
+        std::fs::remove_file(path).ok();
+        assert!(is_secret_file_key("s3.secret_key_file"));
+        assert!(!is_secret_file_key("download.dir_file"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Startup self-test (`/selftest`)
+//!
+//! Runs once at boot before the dispatcher starts (and on demand for admins)
+//! so misconfiguration shows up as one readable pass/fail summary instead of
+//! scattered errors on the first requests.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use teloxide::prelude::*;
+
+use crate::bot::BotState;
+use crate::database::SCHEMA_VERSION;
+use crate::utils::ensure_dir;
+
+/// Upper bound for each network check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    pub checks: Vec<Check>,
+}
+
+impl SelfTestReport {
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let failed = self.checks.iter().filter(|check| !check.ok).count();
+        let mut text = if failed == 0 {
+            format!("✅ 自检通过 ({} 项)\n\n", self.checks.len())
+        } else {
+            format!("❌ 自检失败 ({failed}/{} 项)\n\n", self.checks.len())
+        };
+        for check in &self.checks {
+            let _ = writeln!(
+                text,
+                "{} {}: {}",
+                if check.ok { "✅" } else { "❌" },
+                check.name,
+                check.detail
+            );
+        }
+        text
+    }
+}
+
+/// Problems with a configured `bot.api` that make every request fail
+#[must_use]
+pub fn bot_api_problem(bot_api: &str) -> Option<String> {
+    if bot_api.is_empty() {
+        return None;
+    }
+    let url = match reqwest::Url::parse(bot_api) {
+        Ok(url) => url,
+        Err(e) => return Some(format!("无法解析: {e}")),
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(format!("不支持的协议 {}", url.scheme()));
+    }
+    // teloxide appends `bot<TOKEN>/<method>` itself
+    if url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .any(|segment| segment.starts_with("bot") && segment.contains(':'))
+    {
+        return Some("不应包含 bot<TOKEN> 路径，只填写服务器地址".to_string());
+    }
+    None
+}
+
+async fn check_telegram(bot: &Bot, state: &BotState) -> Check {
+    const NAME: &str = "Telegram Bot API";
+    if let Some(problem) = bot_api_problem(&state.config.bot_api) {
+        return Check::fail(NAME, format!("bot.api {problem}"));
+    }
+    match tokio::time::timeout(CHECK_TIMEOUT, bot.get_me()).await {
+        Ok(Ok(me)) => {
+            let api_url = bot.api_url();
+            let custom = state.config.bot_api.trim_end_matches("/bot");
+            if !custom.is_empty() && !api_url.as_str().starts_with(custom) {
+                Check::fail(
+                    NAME,
+                    format!("bot.api 不可用，已回退到 {api_url} (@{})", me.username()),
+                )
+            } else {
+                Check::pass(NAME, format!("@{} via {api_url}", me.username()))
+            }
+        }
+        Ok(Err(e)) => Check::fail(NAME, e.to_string()),
+        Err(_) => Check::fail(NAME, "超时"),
+    }
+}
+
+async fn check_music_api(state: &BotState) -> Check {
+    const NAME: &str = "网易云 API";
+    match tokio::time::timeout(CHECK_TIMEOUT, state.music_api.ping()).await {
+        Ok(Ok(())) => Check::pass(NAME, state.config.music_api.clone()),
+        Ok(Err(e)) => Check::fail(NAME, format!("{}: {e}", state.config.music_api)),
+        Err(_) => Check::fail(NAME, format!("{}: 超时", state.config.music_api)),
+    }
+}
+
+async fn check_music_u(state: &BotState) -> Check {
+    const NAME: &str = "MUSIC_U";
+    if state.music_api.music_u.is_none() {
+        return Check::pass(NAME, "未配置 (仅能获取免费歌曲)");
+    }
+    match tokio::time::timeout(CHECK_TIMEOUT, state.music_api.get_account_info()).await {
+        Ok(Ok(account)) => match account.profile {
+            Some(profile) => Check::pass(NAME, format!("已登录: {}", profile.nickname)),
+            None => Check::fail(NAME, "已失效，请重新获取"),
+        },
+        Ok(Err(e)) => Check::fail(NAME, e.to_string()),
+        Err(_) => Check::fail(NAME, "超时"),
+    }
+}
+
+async fn check_cache_dir(state: &BotState) -> Check {
+    const NAME: &str = "缓存目录";
+    let dir = &state.config.cache_dir;
+    if let Err(e) = ensure_dir(dir) {
+        return Check::fail(NAME, format!("{dir}: {e}"));
+    }
+    let probe = std::path::Path::new(dir).join(format!(".selftest_{}", uuid::Uuid::new_v4()));
+    match tokio::fs::write(&probe, b"ok").await {
+        Ok(()) => {
+            tokio::fs::remove_file(&probe).await.ok();
+            Check::pass(NAME, format!("{dir} 可写"))
+        }
+        Err(e) => Check::fail(NAME, format!("{dir} 不可写: {e}")),
+    }
+}
+
+async fn check_database(state: &BotState) -> Check {
+    const NAME: &str = "数据库";
+    match state.database.schema_version().await {
+        Ok(version) if version == SCHEMA_VERSION => {
+            Check::pass(NAME, format!("结构版本 {version}"))
+        }
+        Ok(version) => Check::fail(
+            NAME,
+            format!("结构版本 {version}，当前程序支持 {SCHEMA_VERSION}，请升级程序"),
+        ),
+        Err(e) => Check::fail(NAME, e.to_string()),
+    }
+}
+
+/// Run every check concurrently
+pub async fn run(bot: &Bot, state: &BotState) -> SelfTestReport {
+    let (telegram, music_api, music_u, cache_dir, database) = tokio::join!(
+        check_telegram(bot, state),
+        check_music_api(state),
+        check_music_u(state),
+        check_cache_dir(state),
+        check_database(state),
+    );
+    SelfTestReport {
+        checks: vec![telegram, music_api, music_u, cache_dir, database],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, SelfTestReport, bot_api_problem};
+
+    #[test]
+    fn rejects_broken_bot_api_urls() {
+        assert!(bot_api_problem("").is_none());
+        assert!(bot_api_problem("https://tg.example.com").is_none());
+        assert!(bot_api_problem("https://tg.example.com/bot").is_none());
+        assert!(bot_api_problem("tg.example.com").is_some());
+        assert!(bot_api_problem("ftp://tg.example.com").is_some());
+        assert!(bot_api_problem("https://tg.example.com/bot123:abc").is_some());
+    }
+
+    #[test]
+    fn summarizes_failures() {
+        let report = SelfTestReport {
+            checks: vec![Check::pass("a", "fine"), Check::fail("b", "broken")],
+        };
+        assert!(!report.passed());
+        assert!(report.summary().starts_with("❌ 自检失败 (1/2 项)"));
+        assert!(report.summary().contains("❌ b: broken"));
+    }
+}
@@ -0,0 +1,115 @@
+//! Status messages of running downloads (`download.stuck_status_minutes`)
+//!
+//! A job that panics never edits or deletes its "正在下载" message. Each
+//! status message is registered while its job runs: the guard unregisters it
+//! when the job returns, but leaves it behind when the job unwinds, so the
+//! reaper can later tell the user the job died.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Entry {
+    since: Instant,
+    /// Dead once the job's guard is gone
+    job: Weak<()>,
+}
+
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    messages: Mutex<HashMap<(i64, i32), Entry>>,
+}
+
+/// Keeps a status message registered for as long as its job runs
+#[derive(Debug)]
+pub struct StatusGuard<'a> {
+    tracker: &'a StatusTracker,
+    key: (i64, i32),
+    job: Arc<()>,
+}
+
+impl StatusTracker {
+    /// Register the status message `message_id` of `chat_id` until the
+    /// returned guard is dropped
+    #[must_use]
+    pub fn track(&self, chat_id: i64, message_id: i32) -> StatusGuard<'_> {
+        let job = Arc::new(());
+        self.messages.lock().unwrap().insert(
+            (chat_id, message_id),
+            Entry {
+                since: Instant::now(),
+                job: Arc::downgrade(&job),
+            },
+        );
+        StatusGuard {
+            tracker: self,
+            key: (chat_id, message_id),
+            job,
+        }
+    }
+
+    /// Status messages currently registered
+    #[must_use]
+    pub fn active(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    /// Unregister and return the messages older than `max_age` whose job is gone
+    pub fn reap(&self, max_age: Duration) -> Vec<(i64, i32)> {
+        let mut messages = self.messages.lock().unwrap();
+        let stuck = messages
+            .iter()
+            .filter(|(_, entry)| entry.since.elapsed() >= max_age && entry.job.strong_count() == 0)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in &stuck {
+            messages.remove(key);
+        }
+        stuck
+    }
+}
+
+impl Drop for StatusGuard<'_> {
+    fn drop(&mut self) {
+        // Unwinding out of a panicked job: leave the message to the reaper
+        if std::thread::panicking() {
+            return;
+        }
+        let mut messages = self.tracker.messages.lock().unwrap();
+        if messages
+            .get(&self.key)
+            .is_some_and(|entry| entry.job.ptr_eq(&Arc::downgrade(&self.job)))
+        {
+            messages.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::StatusTracker;
+
+    #[test]
+    fn reaps_only_messages_of_panicked_jobs() {
+        let tracker = StatusTracker::default();
+        drop(tracker.track(1, 10));
+        assert_eq!(tracker.active(), 0);
+
+        let running = tracker.track(1, 11);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = tracker.track(2, 20);
+            panic!("job failed");
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(tracker.active(), 2);
+
+        assert!(tracker.reap(Duration::from_hours(1)).is_empty());
+        assert_eq!(tracker.reap(Duration::ZERO), vec![(2, 20)]);
+        assert_eq!(tracker.active(), 1);
+        drop(running);
+        assert_eq!(tracker.active(), 0);
+    }
+}
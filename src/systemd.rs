@@ -0,0 +1,91 @@
+//! systemd `Type=notify` readiness and watchdog support
+//!
+//! All functions are no-ops when not running under systemd (no
+//! `NOTIFY_SOCKET`) and on platforms other than Linux.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bot::BotState;
+
+/// Tell systemd the bot is up (after `getMe` succeeded)
+pub fn notify_ready() {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        tracing::warn!("Failed to send READY=1 to systemd: {}", e);
+    }
+}
+
+/// Tell systemd the bot is shutting down
+pub fn notify_stopping() {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]) {
+        tracing::debug!("Failed to send STOPPING=1 to systemd: {}", e);
+    }
+}
+
+/// Watchdog interval requested by the unit (`WatchdogSec=`), if any
+fn watchdog_interval() -> Option<Duration> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut usec = 0;
+        if sd_notify::watchdog_enabled(false, &mut usec) && usec > 0 {
+            return Some(Duration::from_micros(usec));
+        }
+    }
+    None
+}
+
+/// Check that the parts of the bot most likely to hang still respond
+///
+/// Every upload goes through the upload client mutex, so a deadlock there
+/// stalls all deliveries; the database check catches a wedged connection pool.
+async fn is_alive(state: &BotState, timeout: Duration) -> bool {
+    let upload_lock = tokio::time::timeout(timeout, state.upload_client_state.lock()).await;
+    if upload_lock.is_err() {
+        tracing::error!(
+            "Watchdog: upload client lock not acquired within {:?}",
+            timeout
+        );
+        return false;
+    }
+    drop(upload_lock);
+
+    match tokio::time::timeout(timeout, state.database.ping()).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::error!("Watchdog: database check failed: {}", e);
+            false
+        }
+        Err(_) => {
+            tracing::error!("Watchdog: database check timed out after {:?}", timeout);
+            false
+        }
+    }
+}
+
+/// Send `WATCHDOG=1` at half the configured interval while the bot is healthy
+///
+/// Pings are withheld when a liveness check fails so systemd restarts the
+/// service once `WatchdogSec=` elapses.
+pub fn spawn_watchdog(state: Arc<BotState>) {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    let period = interval / 2;
+    tracing::info!("systemd watchdog enabled, pinging every {:?}", period);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            if !is_alive(&state, period).await {
+                continue;
+            }
+            #[cfg(target_os = "linux")]
+            if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Watchdog]) {
+                tracing::warn!("Failed to send WATCHDOG=1 to systemd: {}", e);
+            }
+        }
+    });
+}
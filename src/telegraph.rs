@@ -0,0 +1,181 @@
+//! Publishing lyrics as telegra.ph pages (`/lyric --telegraph`)
+//!
+//! Pages are created through the Telegraph API with `telegraph.access_token`;
+//! without one, an account is created on first use and kept for the lifetime
+//! of the process.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::error::{BotError, Result};
+use crate::http_client::{self, Profile};
+
+const API_BASE: &str = "https://api.telegra.ph";
+/// Telegraph limits page titles to 256 characters
+const MAX_TITLE_CHARS: usize = 256;
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    url: String,
+}
+
+/// Song lyrics to publish
+#[derive(Debug, Clone, Copy)]
+pub struct LyricPage<'a> {
+    pub title: &'a str,
+    pub artists: &'a str,
+    pub album: &'a str,
+    pub cover_url: Option<&'a str>,
+    /// Plain lyric text, one line per sung line
+    pub lyrics: &'a str,
+}
+
+pub struct Telegraph {
+    client: reqwest::Client,
+    access_token: Mutex<Option<String>>,
+}
+
+impl Telegraph {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let token = config.telegraph_access_token.clone();
+        Self {
+            client: http_client::build(config, Profile::Api).unwrap_or_default(),
+            access_token: Mutex::new((!token.is_empty()).then_some(token)),
+        }
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(&self, method: &str, body: &Value) -> Result<T> {
+        let response: ApiResponse<T> = self
+            .client
+            .post(format!("{API_BASE}/{method}"))
+            .json(body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        match response {
+            ApiResponse {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            ApiResponse { error, .. } => Err(BotError::Other(anyhow::anyhow!(
+                "Telegraph {method} failed: {}",
+                error.unwrap_or_else(|| "unknown error".to_string())
+            ))),
+        }
+    }
+
+    async fn token(&self, author_name: &str) -> Result<String> {
+        let mut token = self.access_token.lock().await;
+        if let Some(token) = token.as_ref() {
+            return Ok(token.clone());
+        }
+        let account: Account = self
+            .call(
+                "createAccount",
+                &json!({ "short_name": author_name, "author_name": author_name }),
+            )
+            .await?;
+        tracing::info!(
+            "Created a Telegraph account; set telegraph.access_token to keep pages editable across restarts"
+        );
+        Ok(token.insert(account.access_token).clone())
+    }
+
+    /// Publish `page` and return its URL
+    pub async fn publish_lyrics(&self, page: &LyricPage<'_>, author_name: &str) -> Result<String> {
+        let access_token = self.token(author_name).await?;
+        let title: String = format!("{} - {}", page.title, page.artists)
+            .chars()
+            .take(MAX_TITLE_CHARS)
+            .collect();
+        let created: Page = self
+            .call(
+                "createPage",
+                &json!({
+                    "access_token": access_token,
+                    "title": title,
+                    "author_name": author_name,
+                    "content": page_content(page),
+                }),
+            )
+            .await?;
+        Ok(created.url)
+    }
+}
+
+/// Telegraph node list: cover, artist/album line, then the lyrics with line breaks
+fn page_content(page: &LyricPage<'_>) -> Value {
+    let mut nodes = Vec::new();
+    if let Some(cover_url) = page.cover_url.filter(|url| !url.is_empty()) {
+        nodes.push(json!({ "tag": "img", "attrs": { "src": cover_url } }));
+    }
+    let subtitle = if page.album.is_empty() {
+        page.artists.to_string()
+    } else {
+        format!("{} · {}", page.artists, page.album)
+    };
+    nodes.push(json!({ "tag": "h4", "children": [subtitle] }));
+
+    let mut lines = Vec::new();
+    for (index, line) in page.lyrics.lines().enumerate() {
+        if index > 0 {
+            lines.push(json!({ "tag": "br" }));
+        }
+        lines.push(Value::String(line.to_string()));
+    }
+    nodes.push(json!({ "tag": "p", "children": lines }));
+    Value::Array(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{LyricPage, page_content};
+
+    #[test]
+    fn builds_page_nodes() {
+        let page = LyricPage {
+            title: "晴天",
+            artists: "周杰伦",
+            album: "叶惠美",
+            cover_url: Some("https://p1.music.126.net/cover.jpg"),
+            lyrics: "故事的小黄花\n从出生那年就飘着\n",
+        };
+        assert_eq!(
+            page_content(&page),
+            json!([
+                { "tag": "img", "attrs": { "src": "https://p1.music.126.net/cover.jpg" } },
+                { "tag": "h4", "children": ["周杰伦 · 叶惠美"] },
+                { "tag": "p", "children": ["故事的小黄花", { "tag": "br" }, "从出生那年就飘着"] },
+            ])
+        );
+
+        let page = LyricPage {
+            cover_url: None,
+            album: "",
+            ..page
+        };
+        assert_eq!(
+            page_content(&page)[0],
+            json!({ "tag": "h4", "children": ["周杰伦"] })
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Recent transfer history for `/status` and `/metrics`
+//!
+//! The peak counters only show how busy uploads got; a slow Cloudflare edge
+//! or Bot API server shows up as falling throughput instead. The last few
+//! hundred downloads and uploads are kept so their throughput percentiles
+//! can be compared over time.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::utils::throughput_mbps;
+
+/// Transfers remembered per direction
+const HISTORY_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    bytes: u64,
+    duration: Duration,
+}
+
+/// Ring buffer of recent transfers in one direction
+#[derive(Debug, Default)]
+pub struct TransferHistory {
+    transfers: Mutex<VecDeque<Transfer>>,
+}
+
+/// Throughput percentiles (MB/s) of the recorded transfers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    pub p50_mbps: f64,
+    /// 95% of transfers were at least this fast
+    pub p95_mbps: f64,
+    pub samples: usize,
+    /// Average size (bytes)
+    pub avg_bytes: u64,
+}
+
+impl TransferHistory {
+    pub fn record(&self, bytes: u64, duration: Duration) {
+        if bytes == 0 {
+            return;
+        }
+        let mut transfers = self.transfers.lock().unwrap();
+        if transfers.len() == HISTORY_LEN {
+            transfers.pop_front();
+        }
+        transfers.push_back(Transfer { bytes, duration });
+    }
+
+    /// None until something was transferred
+    #[must_use]
+    pub fn throughput(&self) -> Option<Throughput> {
+        let transfers = self.transfers.lock().unwrap();
+        let samples = transfers.len();
+        if samples == 0 {
+            return None;
+        }
+        let mut speeds = transfers
+            .iter()
+            .map(|transfer| throughput_mbps(transfer.bytes, transfer.duration))
+            .collect::<Vec<_>>();
+        speeds.sort_by(f64::total_cmp);
+        let total_bytes = transfers.iter().map(|transfer| transfer.bytes).sum::<u64>();
+        Some(Throughput {
+            p50_mbps: speeds[(samples - 1) / 2],
+            p95_mbps: speeds[(samples - 1) * 5 / 100],
+            samples,
+            avg_bytes: total_bytes / samples as u64,
+        })
+    }
+}
+
+/// Download and upload history of a bot
+#[derive(Debug, Default)]
+pub struct TransferStats {
+    pub downloads: TransferHistory,
+    pub uploads: TransferHistory,
+}
+
+impl TransferStats {
+    /// Throughput percentiles in the Prometheus text format
+    #[must_use]
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+        for (direction, history) in [("download", &self.downloads), ("upload", &self.uploads)] {
+            let name = format!("music163bot_{direction}_throughput_mbps");
+            let (p50, p95, samples) = history
+                .throughput()
+                .map_or((0.0, 0.0, 0), |t| (t.p50_mbps, t.p95_mbps, t.samples));
+            let _ = writeln!(
+                out,
+                "# HELP {name} Throughput of recent {direction}s in MB/s (0.95: reached by 95% of them)"
+            );
+            let _ = writeln!(out, "# TYPE {name} summary");
+            let _ = writeln!(out, "{name}{{quantile=\"0.5\"}} {p50:.3}");
+            let _ = writeln!(out, "{name}{{quantile=\"0.95\"}} {p95:.3}");
+            let _ = writeln!(out, "{name}_count {samples}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{TransferHistory, TransferStats};
+
+    #[test]
+    fn reports_median_and_slow_tail() {
+        let history = TransferHistory::default();
+        assert!(history.throughput().is_none());
+
+        // 1..=100 MB/s
+        for speed in 1..=100u64 {
+            history.record(speed * 1024 * 1024, Duration::from_secs(1));
+        }
+        let throughput = history.throughput().unwrap();
+        assert_eq!(throughput.samples, 100);
+        assert!((throughput.p50_mbps - 50.0).abs() < 1e-9);
+        assert!((throughput.p95_mbps - 5.0).abs() < 1e-9);
+
+        for _ in 0..300 {
+            history.record(1024 * 1024, Duration::from_secs(1));
+        }
+        assert_eq!(history.throughput().unwrap().samples, 200);
+    }
+
+    #[test]
+    fn renders_prometheus_summaries() {
+        let stats = TransferStats::default();
+        stats
+            .uploads
+            .record(2 * 1024 * 1024, Duration::from_secs(1));
+        let text = stats.prometheus();
+        assert!(text.contains("music163bot_download_throughput_mbps_count 0\n"));
+        assert!(text.contains("music163bot_upload_throughput_mbps{quantile=\"0.5\"} 2.000\n"));
+    }
+}
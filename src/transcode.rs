@@ -0,0 +1,171 @@
+//! ffmpeg-based transcoding for voice notes, clips and song cards (`[ffmpeg]` config section)
+//!
+//! ffmpeg is run as a subprocess on files in the cache directory; the bot
+//! never links against any codec library itself.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::Config;
+use crate::error::{BotError, MusicApiErrorKind, Result};
+use crate::music_api::MusicApi;
+
+/// Download a song at bitrate `br` from the NetEase CDN into `dir`, returning the file path
+///
+/// Transcoded outputs are lossy, so there is no point in fetching lossless sources.
+pub async fn download_source(api: &MusicApi, music_id: u64, br: u64, dir: &str) -> Result<PathBuf> {
+    let song_url = api.get_song_url(music_id, br).await?;
+    if song_url.url.is_empty() {
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Unavailable,
+            "No download URL (VIP or copyright restricted)",
+        ));
+    }
+
+    let response = api.download_file(&song_url.url).await?;
+    if !response.status().is_success() {
+        return Err(BotError::music_api(
+            MusicApiErrorKind::Status,
+            format!("CDN returned HTTP {}", response.status()),
+        ));
+    }
+
+    let ext = if song_url.format.is_empty() {
+        "mp3"
+    } else {
+        song_url.format.as_str()
+    };
+    let path = Path::new(dir).join(format!("src_{music_id}_{}.{ext}", uuid::Uuid::new_v4()));
+    let mut file = tokio::fs::File::create(&path).await?;
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                drop(file);
+                tokio::fs::remove_file(&path).await.ok();
+                return Err(e.into());
+            }
+        };
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    Ok(path)
+}
+
+/// Run ffmpeg with `args`, failing on a non-zero exit or after `timeout`
+async fn run_ffmpeg(config: &Config, args: &[&OsStr]) -> Result<()> {
+    let mut command = Command::new(&config.ffmpeg_path);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"])
+        .args(args)
+        .kill_on_drop(true);
+
+    let timeout = Duration::from_secs(config.ffmpeg_timeout_secs.max(1));
+    let output = tokio::time::timeout(timeout, command.output())
+        .await
+        .map_err(|_| BotError::Other(anyhow::anyhow!("ffmpeg timed out after {timeout:?}")))??;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .last()
+            .unwrap_or("no output")
+            .trim()
+            .to_string();
+        return Err(BotError::Other(anyhow::anyhow!(
+            "ffmpeg exited with {}: {}",
+            output.status,
+            reason
+        )));
+    }
+
+    Ok(())
+}
+
+/// Encode `input` as OGG/Opus so Telegram plays it as a voice bubble
+pub async fn to_voice(config: &Config, input: &Path, output: &Path) -> Result<()> {
+    let bitrate = format!("{}k", config.voice_bitrate_kbps.max(8));
+    run_ffmpeg(
+        config,
+        &[
+            "-i".as_ref(),
+            input.as_os_str(),
+            "-vn".as_ref(),
+            "-map_metadata".as_ref(),
+            "-1".as_ref(),
+            "-c:a".as_ref(),
+            "libopus".as_ref(),
+            "-b:a".as_ref(),
+            bitrate.as_ref(),
+            "-f".as_ref(),
+            "ogg".as_ref(),
+            output.as_os_str(),
+        ],
+    )
+    .await
+}
+
+/// Cut `duration` seconds starting at `start` out of `input` as an MP3
+pub async fn to_clip(
+    config: &Config,
+    input: &Path,
+    output: &Path,
+    start: u64,
+    duration: u64,
+) -> Result<()> {
+    let start = start.to_string();
+    let duration = duration.to_string();
+    run_ffmpeg(
+        config,
+        &[
+            "-ss".as_ref(),
+            start.as_ref(),
+            "-i".as_ref(),
+            input.as_os_str(),
+            "-t".as_ref(),
+            duration.as_ref(),
+            "-vn".as_ref(),
+            "-map_metadata".as_ref(),
+            "-1".as_ref(),
+            "-c:a".as_ref(),
+            "libmp3lame".as_ref(),
+            "-q:a".as_ref(),
+            "2".as_ref(),
+            output.as_os_str(),
+        ],
+    )
+    .await
+}
+
+/// Apply the video filter graph `filter` to the image `input`, writing a JPEG to `output`
+pub async fn render_image(
+    config: &Config,
+    input: &Path,
+    filter: &str,
+    output: &Path,
+) -> Result<()> {
+    run_ffmpeg(
+        config,
+        &[
+            "-i".as_ref(),
+            input.as_os_str(),
+            "-vf".as_ref(),
+            filter.as_ref(),
+            "-frames:v".as_ref(),
+            "1".as_ref(),
+            "-q:v".as_ref(),
+            "2".as_ref(),
+            output.as_os_str(),
+        ],
+    )
+    .await
+}
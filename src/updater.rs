@@ -0,0 +1,586 @@
+//! Periodic GitHub release check (`autoupdate`)
+//!
+//! Notifies bot admins when a newer release is published and, if
+//! `update.stage_binary` is enabled, downloads the release archive built for
+//! this platform, checks it against its `.sha256` asset, unpacks the binary and
+//! replaces the running executable with it (the previous one is kept as
+//! `<exe>.old`). The running process is unaffected until it is restarted.
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use teloxide::prelude::*;
+
+use crate::bot::BotState;
+use crate::error::{BotError, Result};
+use crate::http_client;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Telegram messages are limited to 4096 characters
+const MAX_CHANGELOG_CHARS: usize = 3000;
+
+/// Suffix of the checksum asset published next to each release archive
+const CHECKSUM_SUFFIX: &str = ".sha256";
+
+#[derive(Debug, Deserialize)]
+pub struct GitHubRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Parse `v1.2.3` / `1.2.3` (pre-release suffixes are ignored)
+#[must_use]
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Whether `latest` is a newer version than `current`
+#[must_use]
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Pick the release asset built for the running platform
+///
+/// Asset names are split on `-` and `.` and the OS and architecture must match
+/// whole tokens (`music163bot-rust-macOS-aarch64.tar.gz`), so "darwin" is not
+/// taken for "win". Checksum and signature files are skipped.
+#[must_use]
+pub fn asset_for_current_target(assets: &[ReleaseAsset]) -> Option<&ReleaseAsset> {
+    asset_for_target(assets, std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn asset_for_target<'a>(
+    assets: &'a [ReleaseAsset],
+    os: &str,
+    arch: &str,
+) -> Option<&'a ReleaseAsset> {
+    let os_names: &[&str] = match os {
+        "macos" => &["macos", "darwin", "apple"],
+        "windows" => &["windows", "win", "win64"],
+        os => &[os],
+    };
+    let arch_names: &[&str] = match arch {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        arch => &[arch],
+    };
+
+    assets.iter().find(|asset| {
+        let name = asset.name.to_lowercase();
+        let is_auxiliary = [CHECKSUM_SUFFIX, ".sig", ".txt"]
+            .iter()
+            .any(|ext| name.ends_with(ext));
+        let tokens: Vec<&str> = name.split(['-', '.']).collect();
+        !is_auxiliary
+            && os_names.iter().any(|os| tokens.contains(os))
+            && arch_names.iter().any(|arch| tokens.contains(arch))
+    })
+}
+
+/// Expected SHA-256 of `asset`, read from its `<asset>.sha256` companion
+/// (`sha256sum` format: the hex digest, optionally followed by the file name)
+async fn expected_sha256(
+    client: &reqwest::Client,
+    assets: &[ReleaseAsset],
+    asset: &ReleaseAsset,
+) -> Result<String> {
+    let checksum_name = format!("{}{CHECKSUM_SUFFIX}", asset.name);
+    let checksum = assets
+        .iter()
+        .find(|candidate| candidate.name == checksum_name)
+        .ok_or_else(|| {
+            BotError::Other(anyhow::anyhow!(
+                "Release has no {checksum_name} to verify against"
+            ))
+        })?;
+    let text = download(client, checksum).await?;
+    String::from_utf8_lossy(&text)
+        .split_whitespace()
+        .next()
+        .filter(|digest| digest.len() == 64)
+        .map(str::to_lowercase)
+        .ok_or_else(|| BotError::Parse(format!("Invalid checksum file {checksum_name}")))
+}
+
+/// Binary `name` inside a gzip-compressed tar archive
+fn untar_gz_entry(archive: &[u8], name: &str) -> Result<Vec<u8>> {
+    let mut tar = Vec::new();
+    flate2::read::GzDecoder::new(archive).read_to_end(&mut tar)?;
+
+    let mut offset = 0;
+    let mut long_name = None;
+    while offset + 512 <= tar.len() {
+        let header = &tar[offset..offset + 512];
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+        };
+        let size = usize::from_str_radix(&field(124..136), 8)
+            .map_err(|_| BotError::Parse("Corrupt tar header".to_string()))?;
+        let data_start = offset + 512;
+        let data = tar
+            .get(data_start..data_start + size)
+            .ok_or_else(|| BotError::Parse("Truncated tar archive".to_string()))?;
+        offset = data_start + size.div_ceil(512) * 512;
+
+        let entry_name = long_name.take().unwrap_or_else(|| field(0..100));
+        match header[156] {
+            // GNU long name: the name of the next entry
+            b'L' => {
+                let end = data
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(data.len());
+                long_name = Some(String::from_utf8_lossy(&data[..end]).into_owned());
+            }
+            b'0' | 0 if entry_name.rsplit('/').next() == Some(name) => return Ok(data.to_vec()),
+            _ => {}
+        }
+    }
+    Err(BotError::Parse(format!(
+        "{name} not found in the release archive"
+    )))
+}
+
+/// Binary `name` inside a zip archive (stored or deflated, as `Compress-Archive` writes)
+fn unzip_entry(archive: &[u8], name: &str) -> Result<Vec<u8>> {
+    let corrupt = || BotError::Parse("Corrupt zip archive".to_string());
+    let u16_at = |at: usize| {
+        archive
+            .get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(corrupt)
+    };
+    let u32_at = |at: usize| {
+        archive
+            .get(at..at + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            .ok_or_else(corrupt)
+    };
+
+    // End of central directory record, followed by a comment of up to 64 KiB
+    let end = (0..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&at| archive[at..].starts_with(&[0x50, 0x4b, 0x05, 0x06]))
+        .ok_or_else(corrupt)?;
+    let entries = u16_at(end + 10)?;
+    let mut at = u32_at(end + 16)?;
+
+    for _ in 0..entries {
+        if u32_at(at)? != 0x0201_4b50 {
+            return Err(corrupt());
+        }
+        let method = u16_at(at + 10)?;
+        let crc = u32_at(at + 16)?;
+        let compressed_size = u32_at(at + 20)?;
+        let name_len = u16_at(at + 28)?;
+        let local_header = u32_at(at + 42)?;
+        let entry_name = archive
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(corrupt)?;
+        let entry_name = String::from_utf8_lossy(entry_name);
+        at += 46 + name_len + u16_at(at + 30)? + u16_at(at + 32)?;
+
+        if entry_name.rsplit(['/', '\\']).next() != Some(name) {
+            continue;
+        }
+        let data_start =
+            local_header + 30 + u16_at(local_header + 26)? + u16_at(local_header + 28)?;
+        let compressed = archive
+            .get(data_start..data_start + compressed_size)
+            .ok_or_else(corrupt)?;
+        let data = match method {
+            0 => compressed.to_vec(),
+            8 => {
+                let mut data = Vec::new();
+                flate2::read::DeflateDecoder::new(compressed).read_to_end(&mut data)?;
+                data
+            }
+            method => {
+                return Err(BotError::Parse(format!(
+                    "Unsupported zip compression method {method}"
+                )));
+            }
+        };
+        let mut checksum = flate2::Crc::new();
+        checksum.update(&data);
+        if checksum.sum() as usize != crc {
+            return Err(corrupt());
+        }
+        return Ok(data);
+    }
+    Err(BotError::Parse(format!(
+        "{name} not found in the release archive"
+    )))
+}
+
+/// The executable inside a downloaded release asset (raw binaries are used as is)
+fn extract_binary(asset_name: &str, bytes: &[u8]) -> Result<Vec<u8>> {
+    let binary = format!("{}{}", env!("CARGO_PKG_NAME"), std::env::consts::EXE_SUFFIX);
+    let lower = asset_name.to_lowercase();
+    if [".tar.gz", ".tgz"].iter().any(|ext| lower.ends_with(ext)) {
+        untar_gz_entry(bytes, &binary)
+    } else if std::path::Path::new(asset_name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        unzip_entry(bytes, &binary)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
+async fn download(client: &reqwest::Client, asset: &ReleaseAsset) -> Result<Vec<u8>> {
+    let response = client.get(&asset.browser_download_url).send().await?;
+    if !response.status().is_success() {
+        return Err(BotError::Other(anyhow::anyhow!(
+            "Asset download returned HTTP {}",
+            response.status()
+        )));
+    }
+    let bytes = response.bytes().await?;
+    if asset.size > 0 && bytes.len() as u64 != asset.size {
+        return Err(BotError::Other(anyhow::anyhow!(
+            "Asset size mismatch: expected {} bytes, got {}",
+            asset.size,
+            bytes.len()
+        )));
+    }
+    Ok(bytes.to_vec())
+}
+
+/// Fetch the latest published release of `repo` (`owner/name`)
+pub async fn fetch_latest_release(client: &reqwest::Client, repo: &str) -> Result<GitHubRelease> {
+    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
+    let response = client
+        .get(url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(BotError::Other(anyhow::anyhow!(
+            "GitHub API returned HTTP {}",
+            response.status()
+        )));
+    }
+
+    Ok(response.json().await?)
+}
+
+/// Download `asset`, verify its checksum and swap the binary it contains in
+/// place of the running executable
+///
+/// The old binary is kept as `<exe>.old` (and put back if the swap fails); the
+/// running process is unaffected until it is restarted.
+pub async fn stage_binary(
+    client: &reqwest::Client,
+    assets: &[ReleaseAsset],
+    asset: &ReleaseAsset,
+) -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let staged = exe.with_extension("new");
+    let backup = exe.with_extension("old");
+
+    let expected = expected_sha256(client, assets, asset).await?;
+    let bytes = download(client, asset).await?;
+    let actual = hex::encode(Sha256::digest(&bytes));
+    if actual != expected {
+        return Err(BotError::Other(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {expected}, got {actual}",
+            asset.name
+        )));
+    }
+    let binary = extract_binary(&asset.name, &bytes)?;
+
+    tokio::fs::write(&staged, &binary).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+
+    tokio::fs::rename(&exe, &backup).await?;
+    if let Err(e) = tokio::fs::rename(&staged, &exe).await {
+        // Never leave the bot without an executable to restart into
+        tokio::fs::rename(&backup, &exe).await?;
+        tokio::fs::remove_file(&staged).await.ok();
+        return Err(e.into());
+    }
+
+    Ok(exe)
+}
+
+fn truncate_changelog(body: &str) -> String {
+    if body.chars().count() <= MAX_CHANGELOG_CHARS {
+        return body.to_string();
+    }
+    let truncated: String = body.chars().take(MAX_CHANGELOG_CHARS).collect();
+    format!("{truncated}\n...")
+}
+
+/// Check for a new release once, notifying admins about versions not yet announced
+async fn check_once(
+    bot: &Bot,
+    state: &BotState,
+    client: &reqwest::Client,
+    announced: &mut Option<String>,
+) -> Result<()> {
+    let release = fetch_latest_release(client, &state.config.update_repo).await?;
+
+    if !is_newer(&release.tag_name, CURRENT_VERSION) {
+        tracing::debug!("No update available (latest {})", release.tag_name);
+        return Ok(());
+    }
+    if announced.as_deref() == Some(release.tag_name.as_str()) {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "New release available: {} (current v{})",
+        release.tag_name,
+        CURRENT_VERSION
+    );
+
+    let staged_line = if state.config.update_stage_binary {
+        match asset_for_current_target(&release.assets) {
+            Some(asset) => match stage_binary(client, &release.assets, asset).await {
+                Ok(path) => format!("\n\n📦 新版本已下载到 {}，重启后生效", path.display()),
+                Err(e) => {
+                    tracing::warn!("Failed to stage update {}: {}", asset.name, e);
+                    format!("\n\n⚠️ 新版本下载失败: {e}")
+                }
+            },
+            None => "\n\n⚠️ 未找到适用于当前平台的发布文件，请手动更新".to_string(),
+        }
+    } else {
+        String::new()
+    };
+
+    let changelog = release
+        .body
+        .as_deref()
+        .map(str::trim)
+        .filter(|body| !body.is_empty())
+        .map_or_else(|| "(无更新说明)".to_string(), truncate_changelog);
+    let text = format!(
+        "🆕 发现新版本 {} (当前 v{})\n\n{}\n\n{}{}",
+        release.name.as_deref().unwrap_or(&release.tag_name),
+        CURRENT_VERSION,
+        changelog,
+        release.html_url,
+        staged_line
+    );
+
+    for &admin in &state.config.bot_admin {
+        if let Err(e) = bot.send_message(ChatId(admin), text.clone()).await {
+            tracing::warn!("Failed to send update notice to admin {}: {}", admin, e);
+        }
+    }
+
+    *announced = Some(release.tag_name);
+    Ok(())
+}
+
+/// Check GitHub releases every `update.check_interval_hours`
+pub fn spawn_update_checker(bot: Bot, state: Arc<BotState>) {
+    tokio::spawn(async move {
+        let client = match http_client::base_builder(
+            &state.config,
+            &format!("music163bot-rust/{CURRENT_VERSION}"),
+        )
+        .timeout(Duration::from_mins(5))
+        .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::warn!("Failed to build update check client: {}", e);
+                return;
+            }
+        };
+
+        let interval =
+            Duration::from_secs(u64::from(state.config.update_check_interval_hours.max(1)) * 3600);
+        let mut announced = None;
+
+        loop {
+            if let Err(e) = check_once(&bot, &state, &client, &mut announced).await {
+                tracing::warn!("Update check failed: {}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{
+        ReleaseAsset, asset_for_target, is_newer, parse_version, untar_gz_entry, unzip_entry,
+    };
+
+    #[test]
+    fn parses_tag_versions() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("1.10"), Some((1, 10, 0)));
+        assert_eq!(parse_version("v2.0.0-beta.1"), Some((2, 0, 0)));
+        assert_eq!(parse_version("nightly"), None);
+    }
+
+    #[test]
+    fn compares_versions_numerically() {
+        assert!(is_newer("v1.1.13", "1.1.12"));
+        assert!(is_newer("v1.10.0", "1.9.9"));
+        assert!(!is_newer("v1.1.12", "1.1.12"));
+        assert!(!is_newer("latest", "1.1.12"));
+    }
+
+    fn asset(name: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: String::new(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn picks_the_ci_archive_for_each_platform() {
+        let assets: Vec<ReleaseAsset> = [
+            "music163bot-rust-Linux-x86_64.tar.gz",
+            "music163bot-rust-Linux-x86_64.tar.gz.sha256",
+            "music163bot-rust-macOS-x86_64.tar.gz",
+            "music163bot-rust-macOS-aarch64.tar.gz",
+            "music163bot-rust-Windows-x86_64.zip",
+        ]
+        .into_iter()
+        .map(asset)
+        .collect();
+        let pick = |os, arch| asset_for_target(&assets, os, arch).map(|a| a.name.as_str());
+
+        assert_eq!(
+            pick("linux", "x86_64"),
+            Some("music163bot-rust-Linux-x86_64.tar.gz")
+        );
+        assert_eq!(
+            pick("macos", "aarch64"),
+            Some("music163bot-rust-macOS-aarch64.tar.gz")
+        );
+        assert_eq!(
+            pick("windows", "x86_64"),
+            Some("music163bot-rust-Windows-x86_64.zip")
+        );
+        assert_eq!(pick("linux", "aarch64"), None);
+    }
+
+    #[test]
+    fn does_not_take_darwin_for_windows() {
+        let assets = vec![asset("music163bot-rust-darwin-x64.tar.gz")];
+        assert!(asset_for_target(&assets, "windows", "x86_64").is_none());
+        assert!(asset_for_target(&assets, "macos", "x86_64").is_some());
+    }
+
+    #[test]
+    fn unpacks_tar_gz_archives() {
+        let mut header = [0u8; 512];
+        header[..20].copy_from_slice(b"./music163bot-rust\0\0");
+        header[124..136].copy_from_slice(b"00000000005\0");
+        header[156] = b'0';
+        let mut tar = header.to_vec();
+        tar.extend_from_slice(b"hello");
+        tar.resize(512 * 4, 0);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&tar).unwrap();
+        let archive = gz.finish().unwrap();
+
+        assert_eq!(
+            untar_gz_entry(&archive, "music163bot-rust").unwrap(),
+            b"hello"
+        );
+        assert!(untar_gz_entry(&archive, "other").is_err());
+    }
+
+    #[test]
+    fn unpacks_deflated_zip_entries() {
+        let name = b"music163bot-rust.exe";
+        let content = b"hello hello hello";
+        let mut deflate =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        deflate.write_all(content).unwrap();
+        let compressed = deflate.finish().unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+
+        let mut zip = Vec::new();
+        zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 22]);
+        zip.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(&compressed);
+
+        let central = zip.len();
+        zip.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 6]);
+        zip.extend_from_slice(&8u16.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&crc.sum().to_le_bytes());
+        zip.extend_from_slice(&u32::try_from(compressed.len()).unwrap().to_le_bytes());
+        zip.extend_from_slice(&u32::try_from(content.len()).unwrap().to_le_bytes());
+        zip.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+        zip.extend_from_slice(&[0; 12]);
+        zip.extend_from_slice(&0u32.to_le_bytes());
+        zip.extend_from_slice(name);
+        let central_size = zip.len() - central;
+
+        zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&u32::try_from(central_size).unwrap().to_le_bytes());
+        zip.extend_from_slice(&u32::try_from(central).unwrap().to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+
+        assert_eq!(unzip_entry(&zip, "music163bot-rust.exe").unwrap(), content);
+        assert!(unzip_entry(&zip, "music163bot-rust").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Background quality upgrade of old cache entries (`cache.upgrade_enabled`)
+//!
+//! Songs cached at 128k/192k before `MUSIC_U` was configured (or before the
+//! account could get lossless) keep being served at that quality. Every
+//! `cache.upgrade_interval_hours` a small batch of them is checked; when
+//! NetEase now offers a clearly better file, it is downloaded, uploaded to the
+//! first admin's private chat and its file_id replaces the cached one.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use teloxide::prelude::*;
+
+use crate::bot::{self, BotState};
+use crate::database::SongInfo;
+use crate::download_lanes::{self, Lane};
+use crate::error::Result;
+use crate::pipeline::{self, CoverPolicy, JobFiles, Stage};
+
+/// Whether a song cached at `cached_bit_rate` bps is worth replacing with
+/// one NetEase now serves at `offered_br` bps
+#[must_use]
+pub fn is_upgrade(offered_br: u64, cached_bit_rate: i64, below_kbps: u64) -> bool {
+    offered_br >= below_kbps * 1000 && offered_br > u64::try_from(cached_bit_rate).unwrap_or(0)
+}
+
+/// Check one batch of low-bitrate songs every `cache.upgrade_interval_hours`
+pub fn spawn_quality_upgrade(bot: Bot, state: Arc<BotState>) {
+    tokio::spawn(async move {
+        let interval = Duration::from_hours(state.config.quality_upgrade_interval_hours);
+        // Walk the cache in music_id order, starting over once the end is reached
+        let mut cursor = 0;
+        loop {
+            tokio::time::sleep(interval).await;
+            cursor = match run_batch(&bot, &state, cursor).await {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::warn!("Quality upgrade batch failed: {}", e);
+                    cursor
+                }
+            };
+        }
+    });
+}
+
+/// Upgrade the songs after `cursor`, returning where the next batch starts
+async fn run_batch(bot: &Bot, state: &BotState, cursor: i64) -> Result<i64> {
+    let below = state.config.quality_upgrade_below_kbps;
+    let songs = state
+        .database
+        .low_bitrate_songs(
+            i64::try_from(below * 1000).unwrap_or(i64::MAX),
+            cursor,
+            i64::from(state.config.quality_upgrade_batch),
+        )
+        .await?;
+    let Some(last) = songs.last().map(|song| song.music_id) else {
+        return Ok(0);
+    };
+
+    let mut upgraded = 0;
+    for song in &songs {
+        match upgrade_song(bot, state, song).await {
+            Ok(true) => upgraded += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to upgrade music_id {}: {}", song.music_id, e),
+        }
+    }
+    tracing::info!(
+        "Quality upgrade: {} of {} low-bitrate songs upgraded",
+        upgraded,
+        songs.len()
+    );
+    Ok(last)
+}
+
+/// Replace one cached song if a better file is available now
+async fn upgrade_song(bot: &Bot, state: &BotState, cached: &SongInfo) -> Result<bool> {
+    let music_id = cached.music_id as u64;
+    let song_url = state.music_api.get_best_song_url(music_id).await?;
+    if song_url.url.is_empty()
+        || song_url.trial_secs().is_some()
+        || !is_upgrade(
+            song_url.br,
+            cached.bit_rate,
+            state.config.quality_upgrade_below_kbps,
+        )
+    {
+        return Ok(false);
+    }
+    let song_detail = state.music_api.get_song_detail(music_id).await?;
+    tracing::info!(
+        "Upgrading music_id {} from {} bps to {} bps",
+        music_id,
+        cached.bit_rate,
+        song_url.br
+    );
+
+    let _permit = download_lanes::acquire(&state.download_semaphore, Lane::Bulk).await;
+    let source = pipeline::Source {
+        song_detail: &song_detail,
+        song_url: &song_url,
+        preview: false,
+    };
+    let files = JobFiles::default();
+    let prepared = pipeline::prepare(
+        state,
+        &source,
+        CoverPolicy::for_mode(state.config.cover_mode),
+        &files,
+    )
+    .await?;
+
+    let mut song_info = pipeline::describe(&source, &prepared.tagged, prepared.md5);
+    song_info.from_user_id = cached.from_user_id;
+    song_info.from_user_name.clone_from(&cached.from_user_name);
+    song_info.from_chat_id = cached.from_chat_id;
+    song_info.from_chat_name.clone_from(&cached.from_chat_name);
+
+    let chat_id = ChatId(state.config.bot_admin[0]);
+    let message = pipeline::AudioMessage {
+        chat_id,
+        reply_to: None,
+        caption: format!("⬆️ {} - {}", song_info.song_artists, song_info.song_name),
+        keyboard: None,
+        title: song_info.song_name.clone(),
+        performer: song_info.song_artists.clone(),
+        duration_secs: song_info.duration as u32,
+        silent: true,
+    };
+    let upload_bot = bot::upload_bot(state).await;
+    let uploaded = pipeline::timed(
+        &state.config,
+        Stage::Upload,
+        music_id,
+        pipeline::upload(
+            state,
+            &upload_bot,
+            prepared.tagged,
+            prepared.thumbnail,
+            message,
+        ),
+    )
+    .await?;
+    song_info.file_id = uploaded.file_id;
+    pipeline::timed(
+        &state.config,
+        Stage::Persist,
+        music_id,
+        pipeline::persist(state, bot, &song_info, &uploaded.message),
+    )
+    .await?;
+
+    // The file_id stays valid once the carrier message is gone
+    if let Err(e) = bot.delete_message(chat_id, uploaded.message.id).await {
+        tracing::debug!("Failed to delete upgrade upload of {}: {}", music_id, e);
+    }
+    bot::drop_replaced_uploads(state, music_id).await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_upgrade;
+
+    #[test]
+    fn upgrades_only_to_clearly_better_quality() {
+        assert!(is_upgrade(999_000, 128_000, 256));
+        assert!(is_upgrade(320_000, 192_000, 256));
+        // NetEase still only offers the cached quality
+        assert!(!is_upgrade(128_000, 128_000, 256));
+        assert!(!is_upgrade(192_000, 128_000, 256));
+        assert!(!is_upgrade(320_000, 320_000, 256));
+    }
+}
@@ -11,6 +11,15 @@ static SHARE_LINK_REGEX: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|
         .unwrap()
 });
 
+static ALBUM_REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"music\.163\.com/.*?album.*?[?&]id=(\d+)").unwrap());
+
+static PLAYLIST_REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"music\.163\.com/.*?playlist.*?[?&]id=(\d+)").unwrap());
+
+static ARTIST_REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"music\.163\.com/.*?artist.*?[?&]id=(\d+)").unwrap());
+
 static NUMBER_REGEX: std::sync::LazyLock<Regex> =
     std::sync::LazyLock::new(|| Regex::new(r"\d+").unwrap());
 
@@ -43,6 +52,125 @@ pub fn parse_music_id(text: &str) -> Option<u64> {
     None
 }
 
+/// Parse an inclusive music ID range such as `1000-2000`
+#[must_use]
+pub fn parse_music_id_range(text: &str) -> Option<(u64, u64)> {
+    let (start, end) = text.trim().split_once('-')?;
+    let start = start.trim().parse().ok()?;
+    let end = end.trim().parse().ok()?;
+    (start <= end).then_some((start, end))
+}
+
+/// Extract album ID from an album link or a bare number
+#[must_use]
+pub fn parse_album_id(text: &str) -> Option<u64> {
+    parse_collection_id(&ALBUM_REGEX, text)
+}
+
+/// Extract playlist ID from a playlist link or a bare number
+#[must_use]
+pub fn parse_playlist_id(text: &str) -> Option<u64> {
+    parse_collection_id(&PLAYLIST_REGEX, text)
+}
+
+/// Extract artist ID from an artist link or a bare number
+#[must_use]
+pub fn parse_artist_id(text: &str) -> Option<u64> {
+    parse_collection_id(&ARTIST_REGEX, text)
+}
+
+fn parse_collection_id(regex: &Regex, text: &str) -> Option<u64> {
+    if let Some(captures) = regex.captures(text)
+        && let Some(id_str) = captures.get(1)
+    {
+        return id_str.as_str().parse().ok();
+    }
+
+    text.trim().parse().ok()
+}
+
+/// Structured `/start` deep-link payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartPayload {
+    Song(u64),
+    Album(u64),
+    Playlist(u64),
+}
+
+/// Parse a `/start` parameter
+///
+/// Accepts a bare song ID (`12345`), prefixed IDs (`al_12345`, `pl_67890`,
+/// `song_12345`) and the same forms encoded as URL-safe base64, so external
+/// sites can generate opaque links.
+#[must_use]
+pub fn parse_start_payload(payload: &str) -> Option<StartPayload> {
+    use base64::Engine;
+
+    let payload = payload.trim();
+    if let Some(parsed) = parse_plain_start_payload(payload) {
+        return Some(parsed);
+    }
+
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    parse_plain_start_payload(decoded.trim())
+}
+
+fn parse_plain_start_payload(payload: &str) -> Option<StartPayload> {
+    if let Ok(id) = payload.parse::<u64>() {
+        return Some(StartPayload::Song(id));
+    }
+
+    let (prefix, id) = payload.split_once('_')?;
+    let id = id.parse::<u64>().ok()?;
+    match prefix {
+        "s" | "song" => Some(StartPayload::Song(id)),
+        "al" | "album" => Some(StartPayload::Album(id)),
+        "pl" | "playlist" => Some(StartPayload::Playlist(id)),
+        _ => None,
+    }
+}
+
+/// Parse an album or playlist reference: a link, `al_<id>` / `pl_<id>`, or a
+/// bare number (treated as a playlist, since NetEase charts are playlists)
+#[must_use]
+pub fn parse_collection_target(text: &str) -> Option<StartPayload> {
+    let text = text.trim();
+    if ALBUM_REGEX.is_match(text) {
+        return parse_album_id(text).map(StartPayload::Album);
+    }
+    if PLAYLIST_REGEX.is_match(text) {
+        return parse_playlist_id(text).map(StartPayload::Playlist);
+    }
+    match parse_plain_start_payload(text)? {
+        StartPayload::Song(id) if text.parse::<u64>().is_ok() => Some(StartPayload::Playlist(id)),
+        StartPayload::Song(_) => None,
+        collection => Some(collection),
+    }
+}
+
+/// Parse a `/fetch` target: album or playlist links and `al_`/`pl_` IDs
+/// select a collection, anything else (including a bare number) a song
+#[must_use]
+pub fn parse_fetch_target(text: &str) -> Option<StartPayload> {
+    let text = text.trim();
+    if text.parse::<u64>().is_err()
+        && let Some(collection) = parse_collection_target(text)
+    {
+        return Some(collection);
+    }
+    parse_music_id(text).map(StartPayload::Song)
+}
+
+/// Public link to a message in a private channel or supergroup (`-100...` chat IDs)
+#[must_use]
+pub fn channel_message_link(chat_id: i64, message_id: i32) -> Option<String> {
+    let internal_id = chat_id.checked_neg()?.checked_sub(1_000_000_000_000)?;
+    (internal_id > 0).then(|| format!("https://t.me/c/{internal_id}/{message_id}"))
+}
+
 /// Extract the first URL from text
 pub fn extract_first_url(text: &str) -> Option<String> {
     SHARE_LINK_REGEX
@@ -119,6 +247,36 @@ pub fn format_duration(seconds: u64) -> String {
     format!("{minutes:02}:{seconds:02}")
 }
 
+/// Format a count the way NetEase shows it (`9999`, `1.2万`, `3.5亿`)
+#[must_use]
+pub fn format_count(count: u64) -> String {
+    match count {
+        0..10_000 => count.to_string(),
+        10_000..100_000_000 => format!("{:.1}万", count as f64 / 10_000.0),
+        _ => format!("{:.1}亿", count as f64 / 100_000_000.0),
+    }
+}
+
+/// Parse a clip timestamp: `ss`, `m:ss` or `h:mm:ss`, returning seconds
+#[must_use]
+pub fn parse_timestamp(text: &str) -> Option<u64> {
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3 || parts.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+
+    let mut seconds = 0u64;
+    for (index, part) in parts.iter().enumerate() {
+        let value: u64 = part.parse().ok()?;
+        // Every field but the first is limited to 0-59
+        if index > 0 && value >= 60 {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(value)?;
+    }
+    Some(seconds)
+}
+
 #[must_use]
 pub fn throughput_mbps(bytes: u64, duration: std::time::Duration) -> f64 {
     let duration_secs = duration.as_secs_f64();
@@ -129,6 +287,141 @@ pub fn throughput_mbps(bytes: u64, duration: std::time::Duration) -> f64 {
     mb / duration_secs
 }
 
+/// Split a `--cover=<mode>` (or `--cover <mode>`) flag off command arguments,
+/// returning the remaining arguments and the flag value
+#[must_use]
+pub fn split_cover_flag(args: &str) -> (String, Option<String>) {
+    let mut rest = Vec::new();
+    let mut cover = None;
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        if let Some(mode) = word.strip_prefix("--cover=") {
+            cover = Some(mode.to_string());
+        } else if word == "--cover" {
+            cover = Some(words.next().unwrap_or_default().to_string());
+        } else {
+            rest.push(word);
+        }
+    }
+    (rest.join(" "), cover)
+}
+
+/// Part of an album or playlist to send (`/playlist <ID> [起-止] [--shuffle]`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackSelection {
+    /// 1-based inclusive range of track positions
+    pub range: Option<(usize, usize)>,
+    pub shuffle: bool,
+}
+
+impl TrackSelection {
+    /// Keep the selected range of `tracks` (empty when it starts past the
+    /// end), then shuffle it with `random_below(n)` returning `0..n`
+    pub fn apply<T>(&self, tracks: &mut Vec<T>, mut random_below: impl FnMut(u64) -> u64) {
+        if let Some((start, end)) = self.range {
+            tracks.truncate(end);
+            tracks.drain(..(start - 1).min(tracks.len()));
+        }
+        if self.shuffle {
+            for i in (1..tracks.len()).rev() {
+                let j = random_below(i as u64 + 1) as usize;
+                tracks.swap(i, j);
+            }
+        }
+    }
+
+    /// Note for the status message, e.g. `第 1-20 首，随机顺序`
+    #[must_use]
+    pub fn describe(&self) -> Option<String> {
+        let range = self
+            .range
+            .map(|(start, end)| format!("第 {start}-{end} 首"));
+        let shuffle = self.shuffle.then(|| "随机顺序".to_string());
+        let parts = [range, shuffle].into_iter().flatten().collect::<Vec<_>>();
+        (!parts.is_empty()).then(|| parts.join("，"))
+    }
+}
+
+/// Split `--shuffle` and a `起-止` track range off collection arguments,
+/// returning the remaining arguments and the selection
+#[must_use]
+pub fn split_track_selection(args: &str) -> (String, TrackSelection) {
+    let mut rest = Vec::new();
+    let mut selection = TrackSelection::default();
+    for word in args.split_whitespace() {
+        if word == "--shuffle" {
+            selection.shuffle = true;
+        } else if let Some((start, end)) = parse_music_id_range(word)
+            && start >= 1
+            && !rest.is_empty()
+        {
+            selection.range = Some((start as usize, end as usize));
+        } else {
+            rest.push(word);
+        }
+    }
+    (rest.join(" "), selection)
+}
+
+/// Placeholder in a hashtag list replaced by the audio file extension
+pub const HASHTAG_EXT_PLACEHOLDER: &str = "{ext}";
+
+/// Parse a hashtag list separated by commas or whitespace; leading `#` are optional
+#[must_use]
+pub fn parse_hashtags(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| c == ',' || c == '，' || c.is_whitespace())
+        .map(|tag| tag.trim_start_matches('#'))
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Render hashtags for a caption, e.g. `#网易云音乐 #flac`
+#[must_use]
+pub fn render_hashtags(tags: &[String], file_ext: &str) -> String {
+    let ext = file_ext.to_lowercase();
+    tags.iter()
+        .map(|tag| format!("#{}", tag.replace(HASHTAG_EXT_PLACEHOLDER, &ext)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Why a finished download looks truncated: fewer bytes than the response's
+/// `Content-Length` or the size reported by the song URL API (0 = unknown)
+#[must_use]
+pub fn incomplete_download(downloaded: u64, content_length: u64, api_size: u64) -> Option<String> {
+    if content_length > 0 && downloaded != content_length {
+        return Some(format!(
+            "received {downloaded} of {content_length} bytes (Content-Length)"
+        ));
+    }
+    if api_size > 0 && downloaded != api_size {
+        return Some(format!(
+            "received {downloaded} bytes, API reported {api_size}"
+        ));
+    }
+    None
+}
+
+/// Small variant of a NetEase cover image (`?param=WxH` is resized by the
+/// image CDN), over HTTPS as Telegram clients expect
+#[must_use]
+pub fn cover_thumbnail_url(pic_url: &str, size: u32) -> String {
+    let base = pic_url.split('?').next().unwrap_or(pic_url);
+    let base = base
+        .strip_prefix("http://")
+        .map_or_else(|| base.to_string(), |rest| format!("https://{rest}"));
+    format!("{base}?param={size}y{size}")
+}
+
+/// Whether a CDN status means the signed song URL expired (NetEase links
+/// live for about 20 minutes) and a fresh one should be requested
+#[must_use]
+pub fn song_url_expired(status: u16) -> bool {
+    matches!(status, 403 | 410)
+}
+
 pub fn update_peak(counter: &std::sync::atomic::AtomicU32, value: u32) -> u32 {
     use std::sync::atomic::Ordering;
 
@@ -142,6 +435,58 @@ pub fn update_peak(counter: &std::sync::atomic::AtomicU32, value: u32) -> u32 {
     current
 }
 
+/// Upload size limit of the official Bot API
+pub const TELEGRAM_UPLOAD_LIMIT: u64 = 50 * 1024 * 1024;
+/// Upload size limit of a local Bot API server
+pub const LOCAL_BOT_API_UPLOAD_LIMIT: u64 = 2000 * 1024 * 1024;
+
+/// Upload size limit for the `bot.api` server
+#[must_use]
+pub fn upload_limit(bot_api: &str) -> u64 {
+    if bot_api.is_empty() || bot_api.trim_end_matches('/') == "https://api.telegram.org" {
+        TELEGRAM_UPLOAD_LIMIT
+    } else {
+        LOCAL_BOT_API_UPLOAD_LIMIT
+    }
+}
+
+/// Whether Telegram (or a proxy in front of it) rejected an upload for its size
+#[must_use]
+pub fn is_file_too_large(error: &teloxide::RequestError) -> bool {
+    use teloxide::ApiError;
+    use teloxide::RequestError;
+
+    match error {
+        RequestError::Api(ApiError::RequestEntityTooLarge) => true,
+        // "Bad Request: file is too big" from local Bot API servers
+        RequestError::Api(ApiError::Unknown(description)) => {
+            let description = description.to_lowercase();
+            description.contains("too big") || description.contains("too large")
+        }
+        // HTML 413 page of a reverse proxy
+        RequestError::InvalidJson { raw, .. } => {
+            let raw = raw.to_lowercase();
+            raw.contains("413 request entity too large") || raw.contains("413 payload too large")
+        }
+        _ => false,
+    }
+}
+
+/// Lower bitrates worth offering for a file of `size` bytes at `bit_rate`
+/// that exceeds `limit`, with their estimated sizes
+#[must_use]
+pub fn smaller_bitrates(bit_rate: u64, size: u64, limit: u64) -> Vec<(u64, u64)> {
+    if bit_rate == 0 {
+        return Vec::new();
+    }
+    [320_000u64, 192_000, 128_000]
+        .into_iter()
+        .filter(|&br| br < bit_rate)
+        .map(|br| (br, size / bit_rate * br + size % bit_rate * br / bit_rate))
+        .filter(|&(_, estimate)| estimate < limit)
+        .collect()
+}
+
 /// Check if an error is a timeout error
 pub fn is_timeout_error(error: &dyn std::error::Error) -> bool {
     error.to_string().contains("timeout") || error.to_string().contains("deadline")
@@ -151,7 +496,34 @@ pub fn is_timeout_error(error: &dyn std::error::Error) -> bool {
 mod tests {
     use std::time::Duration;
 
-    use super::{throughput_mbps, update_peak};
+    use super::{
+        LOCAL_BOT_API_UPLOAD_LIMIT, StartPayload, TELEGRAM_UPLOAD_LIMIT, TrackSelection,
+        channel_message_link, cover_thumbnail_url, format_count, incomplete_download,
+        is_file_too_large, parse_album_id, parse_artist_id, parse_collection_target,
+        parse_fetch_target, parse_hashtags, parse_music_id_range, parse_playlist_id,
+        parse_start_payload, parse_timestamp, render_hashtags, smaller_bitrates, song_url_expired,
+        split_cover_flag, split_track_selection, throughput_mbps, update_peak, upload_limit,
+    };
+
+    #[test]
+    fn parses_music_id_ranges() {
+        assert_eq!(parse_music_id_range("100-200"), Some((100, 200)));
+        assert_eq!(parse_music_id_range(" 5 - 5 "), Some((5, 5)));
+        assert_eq!(parse_music_id_range("200-100"), None);
+        assert_eq!(parse_music_id_range("周杰伦"), None);
+        assert_eq!(parse_music_id_range("a-b"), None);
+    }
+
+    #[test]
+    fn parses_clip_timestamps() {
+        assert_eq!(parse_timestamp("45"), Some(45));
+        assert_eq!(parse_timestamp("1:05"), Some(65));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3723));
+        assert_eq!(parse_timestamp("1:75"), None);
+        assert_eq!(parse_timestamp("1:"), None);
+        assert_eq!(parse_timestamp("abc"), None);
+        assert_eq!(parse_timestamp("18446744073709551615:00"), None);
+    }
 
     #[test]
     fn throughput_mbps_calculates_expected_value() {
@@ -169,4 +541,261 @@ mod tests {
         assert_eq!(update_peak(&counter, 2), 2);
         assert_eq!(update_peak(&counter, 1), 2);
     }
+
+    #[test]
+    fn parse_start_payload_handles_prefixes() {
+        assert_eq!(
+            parse_start_payload("12345"),
+            Some(StartPayload::Song(12345))
+        );
+        assert_eq!(
+            parse_start_payload("al_12345"),
+            Some(StartPayload::Album(12345))
+        );
+        assert_eq!(
+            parse_start_payload("pl_67890"),
+            Some(StartPayload::Playlist(67890))
+        );
+        assert_eq!(parse_start_payload("xx_1"), None);
+        assert_eq!(parse_start_payload("al_abc"), None);
+    }
+
+    #[test]
+    fn parse_start_payload_decodes_base64() {
+        // "pl_67890" encoded as URL-safe base64 without padding
+        assert_eq!(
+            parse_start_payload("cGxfNjc4OTA"),
+            Some(StartPayload::Playlist(67890))
+        );
+        assert_eq!(parse_start_payload("not base64!"), None);
+    }
+
+    #[test]
+    fn parse_collection_ids_from_links() {
+        assert_eq!(
+            parse_album_id("https://music.163.com/#/album?id=34720827"),
+            Some(34_720_827)
+        );
+        assert_eq!(
+            parse_playlist_id("https://music.163.com/playlist?id=19723756&userid=1"),
+            Some(19_723_756)
+        );
+        assert_eq!(parse_playlist_id(" 3778678 "), Some(3_778_678));
+        assert_eq!(parse_album_id("https://music.163.com/song?id=1"), None);
+        assert_eq!(
+            parse_artist_id("https://music.163.com/#/artist?id=6452"),
+            Some(6452)
+        );
+        assert_eq!(parse_artist_id("https://music.163.com/album?id=1"), None);
+    }
+
+    #[test]
+    fn parse_collection_target_prefers_explicit_kind() {
+        assert_eq!(
+            parse_collection_target("https://music.163.com/#/album?id=34720827"),
+            Some(StartPayload::Album(34_720_827))
+        );
+        assert_eq!(
+            parse_collection_target("al_123"),
+            Some(StartPayload::Album(123))
+        );
+        assert_eq!(
+            parse_collection_target("3778678"),
+            Some(StartPayload::Playlist(3_778_678))
+        );
+        assert_eq!(parse_collection_target("song_1"), None);
+    }
+
+    #[test]
+    fn parse_fetch_target_defaults_to_songs() {
+        assert_eq!(
+            parse_fetch_target("3778678"),
+            Some(StartPayload::Song(3_778_678))
+        );
+        assert_eq!(
+            parse_fetch_target("pl_12"),
+            Some(StartPayload::Playlist(12))
+        );
+        assert_eq!(
+            parse_fetch_target("https://music.163.com/#/album?id=34720827"),
+            Some(StartPayload::Album(34_720_827))
+        );
+        assert_eq!(
+            parse_fetch_target("https://music.163.com/song?id=1"),
+            Some(StartPayload::Song(1))
+        );
+        assert_eq!(parse_fetch_target("hello"), None);
+    }
+
+    #[test]
+    fn builds_private_channel_links() {
+        assert_eq!(
+            channel_message_link(-1_001_234_567_890, 42),
+            Some("https://t.me/c/1234567890/42".to_string())
+        );
+        assert_eq!(channel_message_link(123_456, 1), None);
+        assert_eq!(channel_message_link(-123_456, 1), None);
+    }
+
+    #[test]
+    fn detects_truncated_downloads() {
+        assert_eq!(incomplete_download(100, 100, 100), None);
+        assert_eq!(incomplete_download(100, 0, 0), None);
+        assert_eq!(
+            incomplete_download(60, 100, 100).as_deref(),
+            Some("received 60 of 100 bytes (Content-Length)")
+        );
+        assert_eq!(
+            incomplete_download(100, 100, 120).as_deref(),
+            Some("received 100 bytes, API reported 120")
+        );
+    }
+
+    #[test]
+    fn builds_cover_thumbnail_urls() {
+        assert_eq!(
+            cover_thumbnail_url("http://p1.music.126.net/abc/109.jpg", 100),
+            "https://p1.music.126.net/abc/109.jpg?param=100y100"
+        );
+        assert_eq!(
+            cover_thumbnail_url("https://p2.music.126.net/x.jpg?param=640y640", 80),
+            "https://p2.music.126.net/x.jpg?param=80y80"
+        );
+    }
+
+    #[test]
+    fn detects_expired_song_urls() {
+        assert!(song_url_expired(403));
+        assert!(song_url_expired(410));
+        assert!(!song_url_expired(404));
+        assert!(!song_url_expired(200));
+    }
+
+    #[test]
+    fn parses_and_renders_hashtags() {
+        let tags = parse_hashtags("#网易云音乐, {ext}  音乐分享，");
+        assert_eq!(tags, vec!["网易云音乐", "{ext}", "音乐分享"]);
+        assert_eq!(
+            render_hashtags(&tags, "FLAC"),
+            "#网易云音乐 #flac #音乐分享"
+        );
+        assert!(parse_hashtags("").is_empty());
+        assert_eq!(render_hashtags(&[], "mp3"), "");
+    }
+
+    #[test]
+    fn splits_cover_flag_from_arguments() {
+        assert_eq!(
+            split_cover_flag("晴天 --cover=original"),
+            ("晴天".to_string(), Some("original".to_string()))
+        );
+        assert_eq!(
+            split_cover_flag("--cover none 186016"),
+            ("186016".to_string(), Some("none".to_string()))
+        );
+        assert_eq!(
+            split_cover_flag("周杰伦 晴天"),
+            ("周杰伦 晴天".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn selects_part_of_a_collection() {
+        let (args, selection) = split_track_selection("123 1-20 --shuffle");
+        assert_eq!(args, "123");
+        assert_eq!(selection.range, Some((1, 20)));
+        assert!(selection.shuffle);
+        assert_eq!(selection.describe().unwrap(), "第 1-20 首，随机顺序");
+        assert_eq!(
+            split_track_selection("https://music.163.com/playlist?id=1"),
+            (
+                "https://music.163.com/playlist?id=1".to_string(),
+                TrackSelection::default()
+            )
+        );
+        assert_eq!(split_track_selection("0-5").1, TrackSelection::default());
+
+        let mut tracks = (1..=10).collect::<Vec<_>>();
+        let range = TrackSelection {
+            range: Some((3, 5)),
+            shuffle: false,
+        };
+        range.apply(&mut tracks, |_| 0);
+        assert_eq!(tracks, [3, 4, 5]);
+
+        let mut tracks = vec![1, 2, 3];
+        TrackSelection {
+            range: Some((5, 9)),
+            shuffle: false,
+        }
+        .apply(&mut tracks, |_| 0);
+        assert!(tracks.is_empty());
+
+        let mut tracks = vec![1, 2, 3, 4];
+        TrackSelection {
+            range: None,
+            shuffle: true,
+        }
+        .apply(&mut tracks, |_| 0);
+        assert_eq!(tracks, [2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn formats_counts() {
+        assert_eq!(format_count(9999), "9999");
+        assert_eq!(format_count(12_345), "1.2万");
+        assert_eq!(format_count(350_000_000), "3.5亿");
+    }
+
+    #[test]
+    fn detects_file_too_large_errors() {
+        use teloxide::{ApiError, RequestError};
+
+        assert!(is_file_too_large(&RequestError::Api(
+            ApiError::RequestEntityTooLarge
+        )));
+        assert!(is_file_too_large(&RequestError::Api(ApiError::Unknown(
+            "Bad Request: file is too big".to_string()
+        ))));
+        assert!(!is_file_too_large(&RequestError::Api(
+            ApiError::MessageNotModified
+        )));
+        let proxy_error = |raw: &str| RequestError::InvalidJson {
+            source: std::sync::Arc::new(serde_json::from_str::<()>("<").unwrap_err()),
+            raw: raw.into(),
+        };
+        assert!(is_file_too_large(&proxy_error(
+            "<html><head><title>413 Request Entity Too Large</title></head></html>"
+        )));
+        assert!(!is_file_too_large(&proxy_error(
+            "<html>502 Bad Gateway, ray id 8f413c2a</html>"
+        )));
+
+        assert_eq!(
+            upload_limit("https://api.telegram.org"),
+            TELEGRAM_UPLOAD_LIMIT
+        );
+        assert_eq!(
+            upload_limit("http://localhost:8081"),
+            LOCAL_BOT_API_UPLOAD_LIMIT
+        );
+    }
+
+    #[test]
+    fn offers_bitrates_that_fit_the_limit() {
+        let mb = 1024 * 1024;
+        // 60 MB lossless at 1000 kbps: 320 kbps is ~19 MB
+        let offers = smaller_bitrates(1_000_000, 60 * mb, 50 * mb);
+        assert_eq!(
+            offers.iter().map(|(br, _)| *br).collect::<Vec<_>>(),
+            vec![320_000, 192_000, 128_000]
+        );
+        assert!(offers[0].1 < 20 * mb);
+        let offers = smaller_bitrates(320_000, 120 * mb, 50 * mb);
+        assert_eq!(
+            offers.iter().map(|(br, _)| *br).collect::<Vec<_>>(),
+            vec![128_000]
+        );
+        assert!(smaller_bitrates(128_000, 60 * mb, 50 * mb).is_empty());
+    }
 }
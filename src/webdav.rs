@@ -0,0 +1,126 @@
+//! WebDAV upload target for `/fetch` (`[webdav]` config section)
+//!
+//! Nextcloud, Alist and most NAS shares speak plain WebDAV: folders are
+//! created with `MKCOL` one level at a time and files written with `PUT`.
+
+use anyhow::{Context, Result};
+use reqwest::{Method, StatusCode, Url};
+
+use crate::config::Config;
+use crate::http_client::{self, Profile};
+
+pub struct WebDav {
+    client: reqwest::Client,
+    base: Url,
+    username: String,
+    password: String,
+}
+
+impl WebDav {
+    pub fn new(config: &Config) -> Result<Self> {
+        let base = Url::parse(&config.webdav_url)
+            .with_context(|| format!("Invalid webdav.url: {}", config.webdav_url))?;
+        if base.cannot_be_a_base() {
+            anyhow::bail!("Invalid webdav.url: {}", config.webdav_url);
+        }
+        Ok(Self {
+            client: http_client::build(config, Profile::Upload)?,
+            base,
+            username: config.webdav_username.clone(),
+            password: config.webdav_password.clone(),
+        })
+    }
+
+    /// URL of `segments` below the share root, each segment percent-encoded
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    fn request(&self, method: Method, url: Url) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        if self.username.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.username, Some(&self.password))
+        }
+    }
+
+    /// Whether a file exists at `segments`
+    pub async fn exists(&self, segments: &[&str]) -> Result<bool> {
+        let response = self
+            .request(Method::HEAD, self.url(segments))
+            .send()
+            .await
+            .context("WebDAV request failed")?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => anyhow::bail!("WebDAV HEAD returned HTTP {status}"),
+        }
+    }
+
+    /// Create every folder of `dirs` that does not exist yet
+    pub async fn create_dirs(&self, dirs: &[&str]) -> Result<()> {
+        let mkcol = Method::from_bytes(b"MKCOL").expect("valid method");
+        for depth in 1..=dirs.len() {
+            let response = self
+                .request(mkcol.clone(), self.url(&dirs[..depth]))
+                .send()
+                .await
+                .context("WebDAV request failed")?;
+            // 405: the folder is already there
+            let status = response.status();
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                anyhow::bail!("WebDAV MKCOL returned HTTP {status}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a file of `len` bytes to `segments`, replacing any existing one
+    pub async fn put(&self, segments: &[&str], body: reqwest::Body, len: u64) -> Result<()> {
+        let response = self
+            .request(Method::PUT, self.url(segments))
+            .header("content-length", len)
+            .body(body)
+            .send()
+            .await
+            .context("WebDAV upload failed")?;
+        if !response.status().is_success() {
+            anyhow::bail!("WebDAV upload returned HTTP {}", response.status());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WebDav;
+    use crate::config::Config;
+
+    #[test]
+    fn encodes_segments_below_the_share() {
+        let mut config = Config {
+            webdav_url: "https://dav.example.com/remote.php/dav/files/me/Music/".to_string(),
+            ..Config::default()
+        };
+        let webdav = WebDav::new(&config).unwrap();
+        assert_eq!(
+            webdav
+                .url(&["AC DC", "Back in Black", "AC DC - Hells Bells.flac"])
+                .as_str(),
+            "https://dav.example.com/remote.php/dav/files/me/Music/AC%20DC/Back%20in%20Black/AC%20DC%20-%20Hells%20Bells.flac"
+        );
+
+        config.webdav_url = "https://dav.example.com".to_string();
+        let webdav = WebDav::new(&config).unwrap();
+        assert_eq!(
+            webdav.url(&["a#b"]).as_str(),
+            "https://dav.example.com/a%23b"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Admission limit for incoming message handlers (`[dispatcher]`)
+//!
+//! Every text message used to get its own task, so a flood of messages could
+//! spawn work without limit. Each handler still runs as its own task, so a
+//! multi-minute album download never holds up a quick command, but it must
+//! first take one of `dispatcher.max_handlers` permits; when none is left the
+//! message is rejected instead of piling up. The "busy" reply goes out at most
+//! once per chat per `BUSY_REPLY_INTERVAL`, so a flood does not turn into a
+//! flood of replies. A handler that panics is logged.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Semaphore;
+
+/// Minimum time between two "busy" replies to the same chat
+const BUSY_REPLY_INTERVAL: Duration = Duration::from_mins(1);
+
+pub struct HandlerLimit {
+    permits: Arc<Semaphore>,
+    max_handlers: usize,
+    /// When each chat was last told the bot is busy
+    busy_replies: Mutex<HashMap<i64, Instant>>,
+}
+
+impl HandlerLimit {
+    /// Allow up to `max_handlers` message handlers at once
+    #[must_use]
+    pub fn new(max_handlers: usize) -> Self {
+        let max_handlers = max_handlers.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max_handlers)),
+            max_handlers,
+            busy_replies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spawn `job`, or return `false` when `max_handlers` handlers are running
+    pub fn try_spawn<F>(&self, job: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            return false;
+        };
+        spawn_job("Message handler", async move {
+            let _permit = permit;
+            job.await;
+        });
+        true
+    }
+
+    /// Handlers currently running
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.max_handlers - self.permits.available_permits()
+    }
+
+    /// Whether a rejected message in `chat_id` should get a "busy" reply
+    pub fn should_reply_busy(&self, chat_id: i64) -> bool {
+        self.should_reply_busy_at(chat_id, Instant::now())
+    }
+
+    fn should_reply_busy_at(&self, chat_id: i64, now: Instant) -> bool {
+        let mut replies = self
+            .busy_replies
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        replies.retain(|_, at| now.duration_since(*at) < BUSY_REPLY_INTERVAL);
+        if replies.contains_key(&chat_id) {
+            return false;
+        }
+        replies.insert(chat_id, now);
+        true
+    }
+}
+
+/// Spawn a long-running background job, logging it if it panics
+pub fn spawn_job<F>(name: &'static str, job: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tokio::spawn(job);
+    tokio::spawn(async move {
+        if let Err(e) = handle.await
+            && e.is_panic()
+        {
+            tracing::error!("{} panicked: {}", name, panic_message(&*e.into_panic()));
+        }
+    });
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{BUSY_REPLY_INTERVAL, HandlerLimit};
+
+    #[tokio::test]
+    async fn rejects_handlers_over_the_limit() {
+        let limit = HandlerLimit::new(1);
+        let (release, blocked) = tokio::sync::oneshot::channel::<()>();
+        assert!(limit.try_spawn(async move {
+            blocked.await.ok();
+        }));
+        assert_eq!(limit.running(), 1);
+        assert!(!limit.try_spawn(async {}));
+
+        release.send(()).unwrap();
+        while limit.running() > 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(limit.try_spawn(async {}));
+    }
+
+    #[tokio::test]
+    async fn frees_the_permit_of_panicking_handlers() {
+        let limit = HandlerLimit::new(1);
+        assert!(limit.try_spawn(async { panic!("handler bug") }));
+        while limit.running() > 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let (done_tx, done) = tokio::sync::oneshot::channel::<()>();
+        assert!(limit.try_spawn(async move {
+            done_tx.send(()).ok();
+        }));
+        done.await.unwrap();
+    }
+
+    #[test]
+    fn replies_busy_once_per_chat_per_interval() {
+        let limit = HandlerLimit::new(1);
+        let now = Instant::now();
+        assert!(limit.should_reply_busy_at(1, now));
+        assert!(!limit.should_reply_busy_at(1, now + Duration::from_secs(5)));
+        assert!(limit.should_reply_busy_at(2, now + Duration::from_secs(5)));
+        assert!(limit.should_reply_busy_at(1, now + BUSY_REPLY_INTERVAL));
+    }
+}